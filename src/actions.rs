@@ -1,5 +1,8 @@
 pub mod create_file;
+pub mod create_file_from_template;
 pub mod read_file;
+pub mod read_files;
+pub mod tail_file;
 pub mod search_web;
 pub mod read_web_page;
 pub mod run_command;
@@ -7,13 +10,112 @@ pub mod ask_user;
 pub mod overwrite_file;
 pub mod replace_file_lines;
 pub mod confirm_action;
+pub mod target;
 pub mod delete_file;
 pub mod append_to_file;
 pub mod move_file;
 pub mod copy_file;
 pub mod list_directory;
+pub mod show_tree;
 pub mod check_path_exists;
+pub mod list_processes;
+pub mod get_port_usage;
+pub mod system_info;
+#[cfg(feature = "devops")]
+pub mod docker_ps;
+#[cfg(feature = "devops")]
+pub mod docker_logs;
+#[cfg(feature = "devops")]
+pub mod kubectl_get;
 pub mod find_files;
+pub mod find_symbol;
+pub mod hash_file;
+pub mod compare_files;
 pub mod ask_llm_to_create_file;
 pub mod ask_llm_to_overwrite_file;
-pub mod ask_llm_to_replace_file_lines; 
\ No newline at end of file
+pub mod ask_llm_to_replace_file_lines; 
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Output of an executed action, compact form is sent to the LLM, Display form is shown to the user
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionOutput {
+    None,
+    Text { text: String },
+    FileWritten { path: String, bytes: usize },
+    Command { exit_code: Option<i32>, stdout: String, stderr: String },
+    Listing { entries: Vec<String> },
+    Boolean { value: bool },
+    Error { kind: String, message: String },
+    Skipped,
+}
+
+pub const SKIPPED_OUTPUT: &str = "SKIPPED by user, the action was NOT executed";
+
+impl ActionOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        ActionOutput::Text { text: text.into() }
+    }
+
+    pub fn compact(&self) -> String {
+        match self {
+            ActionOutput::None => String::new(),
+            ActionOutput::Text { text } => text.clone(),
+            ActionOutput::FileWritten { path, bytes } => format!("wrote {} bytes to {}", bytes, path),
+            ActionOutput::Command { exit_code, stdout, stderr } => {
+                let mut compact = stdout.clone();
+                if !stderr.is_empty() {
+                    compact.push_str(&format!("\nstderr: {}", stderr));
+                }
+                if *exit_code != Some(0) {
+                    compact.push_str(&format!("\nexit code: {}", exit_code.map_or("none".to_string(), |code| code.to_string())));
+                }
+                compact
+            }
+            ActionOutput::Listing { entries } => entries.join("\n"),
+            ActionOutput::Boolean { value } => value.to_string(),
+            ActionOutput::Error { kind, message } => format!("ERROR: {} [{}]", message, kind),
+            ActionOutput::Skipped => SKIPPED_OUTPUT.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ActionOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionOutput::None => write!(f, "Done."),
+            ActionOutput::Text { text } => write!(f, "{}", text),
+            ActionOutput::FileWritten { path, bytes } => write!(f, "Wrote {} bytes to '{}'.", bytes, path),
+            ActionOutput::Command { exit_code, stdout, stderr } => {
+                write!(f, "Exit code: {}", exit_code.map_or("none".to_string(), |code| code.to_string()))?;
+                if !stdout.is_empty() {
+                    write!(f, "\n--- stdout ---\n{}", stdout)?;
+                }
+                if !stderr.is_empty() {
+                    write!(f, "\n--- stderr ---\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            ActionOutput::Listing { entries } => {
+                for entry in entries {
+                    writeln!(f, "  {}", entry)?;
+                }
+                write!(f, "{} entries", entries.len())
+            }
+            ActionOutput::Boolean { value } => write!(f, "{}", if *value { "Yes" } else { "No" }),
+            ActionOutput::Error { message, .. } => write!(f, "Error: {}", message),
+            ActionOutput::Skipped => write!(f, "Skipped."),
+        }
+    }
+}
+
+/// Fails when the file at `path` exists and may not be overwritten, so that a re-run plan does not clobber the files
+/// created by an earlier run
+pub(crate) async fn ensure_overwritable(path: &str, overwrite: bool, target: &dyn target::ExecutionTarget) -> anyhow::Result<()> {
+    if !overwrite && target.exists(path).await? {
+        return Err(crate::error::ActionError::AlreadyExists(format!("'{}' already exists and the action may not overwrite it", path)).into());
+    }
+    Ok(())
+}
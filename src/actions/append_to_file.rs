@@ -1,16 +1,9 @@
-use anyhow::{Context, Result};
-use std::fs;
-use std::io::Write;
-use crate::fs::expand_home;
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String, content: &String) -> Result<Option<String>> {
-    let expanded_path = expand_home(path)?;
-    let mut file = fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(&expanded_path)
-        .with_context(|| format!("Failed to open file for appending: {}", expanded_path.display()))?;
-    writeln!(file, "{}", content)
-        .with_context(|| format!("Failed to append content to file: {}", expanded_path.display()))?;
-    Ok(None)
-} 
\ No newline at end of file
+pub(crate) async fn execute(path: &str, content: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let line = format!("{}\n", content);
+    target.append_file(path, &line).await?;
+    Ok(ActionOutput::FileWritten { path: path.to_string(), bytes: line.len() })
+}
@@ -1,27 +1,37 @@
 use anyhow::{Context, Result};
+use crate::error::ActionError;
+use crate::actions::ActionOutput;
 use crate::config::Model;
 use crate::llm::ask_llm_with_history;
 use crate::json;
-use crate::actions::overwrite_file;
-use crate::executor::Action;
+use crate::actions::{overwrite_file, confirm_action};
+use crate::actions::confirm_action::REJECTED_OUTPUT;
+use crate::executor::{Action, ExecutedAction};
+use crate::classification::AutoConfirm;
+use crate::actions::target::ExecutionTarget;
 use reqwest::Client;
 
 pub(crate) async fn execute(
-    path: &String,
+    path: &str,
     model_config: &Model,
-    execution_history: &Vec<(Action, Option<String>)>,
+    execution_history: &[ExecutedAction],
     client: &Client,
-) -> Result<Option<String>> {
+    current_auto_confirm: AutoConfirm,
+    target: &dyn ExecutionTarget,
+) -> Result<ActionOutput> {
     let prompt = format!("Generate a JSON object for an OverwriteFileContents action with path: '{}'. The JSON object should have 'action' = \"overwrite_file_contents\", 'action_idx', 'path', and 'content' fields. Generated `content` will be used LITERALLY and will not be parsed further.", path);
     let response = ask_llm_with_history(model_config, &prompt, execution_history, client)
         .await
         .context("Failed to get response from LLM")?;
     let action: Action = serde_json::from_str(json::strip_json_fence(&response))
-        .context("Failed to parse LLM response as OverwriteFileContents action")?;
+        .map_err(|e| ActionError::LlmMalformedResponse(format!("Failed to parse LLM response as OverwriteFileContents action: {}", e)))?;
 
-    if let Action::OverwriteFileContents { path, content, .. } = action {
-        overwrite_file::execute(&path, &content).await
+    if let Action::OverwriteFileContents { path, content, .. } = &action {
+        if !confirm_action::confirm_generated_write(current_auto_confirm, &action, path, content, target).await? {
+            return Ok(ActionOutput::text(REJECTED_OUTPUT));
+        }
+        overwrite_file::execute(path, content, target).await
     } else {
-        anyhow::bail!("LLM did not return an OverwriteFileContents action, but instead: {:?}", action);
+        Err(ActionError::LlmMalformedResponse(format!("LLM did not return an OverwriteFileContents action, but instead: {:?}", action)).into())
     }
 } 
\ No newline at end of file
@@ -1,27 +1,39 @@
 use anyhow::{Context, Result};
+use crate::error::ActionError;
+use crate::actions::ActionOutput;
 use crate::config::Model;
 use crate::llm::ask_llm_with_history;
 use crate::json;
-use crate::actions::replace_file_lines;
-use crate::executor::Action;
+use crate::actions::{replace_file_lines, confirm_action};
+use crate::actions::confirm_action::REJECTED_OUTPUT;
+use crate::executor::{Action, ExecutedAction};
+use crate::classification::AutoConfirm;
+use crate::actions::target::ExecutionTarget;
 use reqwest::Client;
 
 pub(crate) async fn execute(
-    path: &String,
+    path: &str,
     model_config: &Model,
-    execution_history: &Vec<(Action, Option<String>)>,
+    execution_history: &[ExecutedAction],
     client: &Client,
-) -> Result<Option<String>> {
+    current_auto_confirm: AutoConfirm,
+    target: &dyn ExecutionTarget,
+) -> Result<ActionOutput> {
     let prompt = format!("Generate a JSON object for a ReplaceFileLines action with path: '{}'. The JSON object should have 'action' = \"replace_file_lines\", 'action_idx', 'path', 'from_line_idx', 'until_line_idx', and 'replacement_lines' fields. Generated `replacement_lines` will be used LITERALLY and will not be parsed further.", path);
     let response = ask_llm_with_history(model_config, &prompt, execution_history, client)
         .await
         .context("Failed to get response from LLM")?;
     let replace_file_lines_action: Action = serde_json::from_str(json::strip_json_fence(&response))
-        .context("Failed to parse LLM response as ReplaceFileLines action")?;
+        .map_err(|e| ActionError::LlmMalformedResponse(format!("Failed to parse LLM response as ReplaceFileLines action: {}", e)))?;
 
-    if let Action::ReplaceFileLines { path, from_line_idx, until_line_idx, replacement_lines, .. } = replace_file_lines_action {
-        replace_file_lines::execute(&path, from_line_idx, until_line_idx, &replacement_lines).await
+    if let Action::ReplaceFileLines { path, from_line_idx, until_line_idx, replacement_lines, .. } = &replace_file_lines_action {
+        let old_content = target.read_to_string(path).await?;
+        let new_content = replace_file_lines::replace_lines(&old_content, *from_line_idx, *until_line_idx, replacement_lines);
+        if !confirm_action::confirm_generated_write(current_auto_confirm, &replace_file_lines_action, path, &new_content, target).await? {
+            return Ok(ActionOutput::text(REJECTED_OUTPUT));
+        }
+        replace_file_lines::execute(path, *from_line_idx, *until_line_idx, replacement_lines, target).await
     } else {
-        anyhow::bail!("LLM did not return a ReplaceFileLines action, but instead: {:?}", replace_file_lines_action);
+        Err(ActionError::LlmMalformedResponse(format!("LLM did not return a ReplaceFileLines action, but instead: {:?}", replace_file_lines_action)).into())
     }
 } 
\ No newline at end of file
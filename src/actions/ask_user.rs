@@ -1,15 +1,122 @@
 use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::confirm_action::ensure_prompts_enabled;
+use crate::actions::run_command::provide_secret;
+use crate::error::ActionError;
+use crate::input;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
 use colored::*;
 
-pub(crate) async fn execute(question: &String) -> Result<Option<String>> {
+pub(crate) async fn execute(question: &str, choices: &[String], validation_regex: Option<&str>) -> Result<ActionOutput> {
+    // The whole answer has to match, not only a part of it
+    let validation = validation_regex
+        .map(|pattern| Regex::new(&format!("^(?:{})$", pattern))
+            .map(|regex| (regex, pattern))
+            .map_err(|e| ActionError::InvalidInput(format!("Invalid validation regex: {}: {}", pattern, e))))
+        .transpose()?;
+    ensure_prompts_enabled(&format!("The question '{}'", question))?;
     println!("Action: Ask user");
-    print!("{} ", question.green());
-    io::stdout().flush()?;
+    for (i, choice) in choices.iter().enumerate() {
+        println!("  {}. {}", i + 1, choice);
+    }
+    loop {
+        print!("{} ", question.green());
+        io::stdout().flush()?;
+        let Some(answer) = input::read_line()? else {
+            if choices.is_empty() && validation.is_none() {
+                return Ok(ActionOutput::text(""));
+            }
+            return Err(ActionError::InvalidInput(format!("No valid answer was given to the question '{}'", question)).into());
+        };
+        match check_answer(answer.trim(), choices, validation.as_ref().map(|(regex, pattern)| (regex, *pattern))) {
+            Ok(answer) => return Ok(ActionOutput::text(answer)),
+            Err(problem) => println!("{}", problem.yellow()),
+        }
+    }
+}
+
+/// Question of a form asked with AskUserForm
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct FormQuestion {
+    pub label: String,
+    pub question: String,
+    // Answer used when the user answers with an empty line
+    #[serde(default)]
+    pub default: Option<String>,
+    // The answer is not echoed nor sent to the LLM, the commands get it as an environment variable
+    #[serde(default)]
+    pub secret: bool,
+}
+
+// Name of the environment variable with the secret answer, e.g. `CLIFF_SECRET_DB_PASSWORD` for the label "db password"
+pub(crate) fn secret_env_var(label: &str) -> String {
+    let name: String = label.trim().chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    format!("CLIFF_SECRET_{}", name)
+}
+
+pub(crate) async fn execute_form(questions: &[FormQuestion]) -> Result<ActionOutput> {
+    let labels: Vec<&str> = questions.iter().map(|question| question.label.as_str()).collect();
+    ensure_prompts_enabled(&format!("The form asking for {}", labels.join(", ")))?;
+    println!("Action: Ask user to fill in a form");
+    let mut answers = Vec::new();
+    for question in questions {
+        match &question.default {
+            Some(default) if !question.secret => print!("{} [{}] ", question.question.green(), default),
+            _ => print!("{} ", question.question.green()),
+        }
+        io::stdout().flush()?;
+        let answer = if question.secret { input::read_secret()? } else { input::read_line()? };
+        let answer = answer.map(|answer| answer.trim().to_string()).filter(|answer| !answer.is_empty());
+        let answer = answer.or_else(|| question.default.clone()).unwrap_or_default();
+        if question.secret {
+            let name = secret_env_var(&question.label);
+            provide_secret(name.clone(), answer);
+            answers.push(format!("{}: [secret, available to the commands as ${}]", question.label, name));
+        } else {
+            answers.push(format!("{}: {}", question.label, answer));
+        }
+    }
+    Ok(ActionOutput::text(answers.join("\n")))
+}
+
+// The answer or the choice selected by its number or its text, the problem with the answer otherwise
+fn check_answer(answer: &str, choices: &[String], validation: Option<(&Regex, &str)>) -> Result<String, String> {
+    let answer = if choices.is_empty() {
+        answer.to_string()
+    } else {
+        let by_number = answer.parse::<usize>().ok().and_then(|number| choices.get(number.checked_sub(1)?));
+        let by_text = choices.iter().find(|choice| choice.eq_ignore_ascii_case(answer));
+        by_number.or(by_text).ok_or_else(|| format!("Please answer with a number from 1 to {} or one of the choices.", choices.len()))?.clone()
+    };
+    match validation {
+        Some((regex, pattern)) if !regex.is_match(&answer) => Err(format!("The answer should match `{}`, please try again.", pattern)),
+        _ => Ok(answer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_answer() {
+        let choices = ["staging".to_string(), "production".to_string()];
+        assert_eq!(check_answer("2", &choices, None), Ok("production".to_string()));
+        assert_eq!(check_answer("Staging", &choices, None), Ok("staging".to_string()));
+        assert!(check_answer("3", &choices, None).is_err());
+        assert!(check_answer("0", &choices, None).is_err());
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+        let version = Regex::new(r"^(?:\d+\.\d+\.\d+)$").unwrap();
+        assert_eq!(check_answer("1.2.3", &[], Some((&version, r"\d+\.\d+\.\d+"))), Ok("1.2.3".to_string()));
+        assert_eq!(check_answer("v1.2.3", &[], Some((&version, r"\d+\.\d+\.\d+"))), Err("The answer should match `\\d+\\.\\d+\\.\\d+`, please try again.".to_string()));
+    }
 
-    let response = input.trim().to_string();
-    Ok(Some(response))
+    #[test]
+    fn test_secret_env_var() {
+        assert_eq!(secret_env_var("db password"), "CLIFF_SECRET_DB_PASSWORD");
+        assert_eq!(secret_env_var(" api-key "), "CLIFF_SECRET_API_KEY");
+    }
 }
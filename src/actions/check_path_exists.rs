@@ -1,8 +1,8 @@
 use anyhow::Result;
-use crate::fs::expand_home;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String) -> Result<Option<String>> {
-    let expanded_path = expand_home(path)?;
-    let exists = expanded_path.exists();
-    Ok(Some(exists.to_string()))
-} 
\ No newline at end of file
+pub(crate) async fn execute(path: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let exists = target.exists(path).await?;
+    Ok(ActionOutput::Boolean { value: exists })
+}
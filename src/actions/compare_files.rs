@@ -0,0 +1,18 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::diff::unified_diff;
+use crate::actions::target::ExecutionTarget;
+
+const MAX_DIFF_LINES: usize = 200;
+
+pub(crate) async fn execute(path_a: &str, path_b: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let content_a = target.read(path_a).await?;
+    let content_b = target.read(path_b).await?;
+    if content_a == content_b {
+        return Ok(ActionOutput::text("identical"));
+    }
+    let (Ok(text_a), Ok(text_b)) = (String::from_utf8(content_a), String::from_utf8(content_b)) else {
+        return Ok(ActionOutput::text(format!("Binary files {} and {} differ", path_a, path_b)));
+    };
+    Ok(ActionOutput::text(unified_diff(&text_a, &text_b, path_a, path_b, MAX_DIFF_LINES)))
+}
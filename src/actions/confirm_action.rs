@@ -1,21 +1,180 @@
 use anyhow::Result;
+use colored::*;
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::classification::AutoConfirm;
+use crate::diff::unified_diff;
+use crate::executor::Action;
+use crate::actions::target::ExecutionTarget;
+use crate::error::ActionError;
+use crate::input;
+use crate::policy::ProjectPolicy;
 
-pub(crate) async fn execute(current_auto_confirm: bool) -> Result<(bool, bool)> {
+const MAX_PREVIEW_DIFF_LINES: usize = 200;
+pub(crate) const REJECTED_OUTPUT: &str = "The user rejected the generated changes, the file was not changed";
+
+// Set when nobody can answer the prompts, e.g. in the daemon, then the actions needing a confirmation are denied
+static PROMPTS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn disable_prompts() {
+    PROMPTS_DISABLED.store(true, Ordering::Relaxed);
+}
+
+// Fails when the prompts are disabled, `what` describes what needed the answer of the user
+pub(crate) fn ensure_prompts_enabled(what: &str) -> Result<()> {
+    if PROMPTS_DISABLED.load(Ordering::Relaxed) {
+        return Err(ActionError::PolicyDenied(format!("{} needs an answer of the user, which is not possible in the non-interactive mode", what)).into());
+    }
+    Ok(())
+}
+
+pub(crate) async fn execute(current_auto_confirm: AutoConfirm, action: &Action) -> Result<(AutoConfirm, bool)> {
     let mut current_auto_confirm = current_auto_confirm;
-    let mut confirmed = current_auto_confirm;
-    if !current_auto_confirm {
-        print!("Execute this step? (y/N/all): ");
+    let mut confirmed = current_auto_confirm.allows(action);
+    // The exact commands the user chose to always allow in the project
+    let command = match action {
+        Action::RunCommand { command, .. } => Some(command),
+        _ => None,
+    };
+    if !confirmed {
+        if let Some(command) = command {
+            if ProjectPolicy::load(&std::env::current_dir()?)?.allows_command(command) {
+                println!("The command `{}` is always allowed in this project ({}).", command.trim(), ProjectPolicy::POLICY_FILENAME);
+                return Ok((current_auto_confirm, true));
+            }
+        }
+        ensure_prompts_enabled(&format!("The action {:?} is not confirmed automatically and", action))?;
+        if command.is_some() {
+            print!("Execute this step? (y/N/all/always allow this command in the project): ");
+        } else {
+            print!("Execute this step? (y/N/all): ");
+        }
         io::stdout().flush()?;
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let choice = input.trim().to_lowercase();
+        let choice = input::read_line()?.unwrap_or_default().trim().to_lowercase();
         if choice == "y" || choice == "yes" {
             confirmed = true;
         } else if choice == "a" || choice == "all" {
             confirmed = true;
-            current_auto_confirm = true;
+            current_auto_confirm = AutoConfirm::All;
+        } else if let (Some(command), "always") = (command, choice.as_str()) {
+            confirmed = true;
+            ProjectPolicy::allow_command(&std::env::current_dir()?, command)?;
+            println!("The command `{}` will be allowed without asking in this project ({}).", command.trim(), ProjectPolicy::POLICY_FILENAME);
         }
     }
     Ok((current_auto_confirm, confirmed))
-} 
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FailureDecision {
+    Retry,
+    Replan,
+    Skip,
+    Abort,
+}
+
+pub(crate) async fn ask_failure_decision() -> Result<FailureDecision> {
+    if PROMPTS_DISABLED.load(Ordering::Relaxed) {
+        return Ok(FailureDecision::Abort);
+    }
+    loop {
+        print!("The step failed. (r)etry, re(p)lan, (s)kip or (a)bort? ");
+        io::stdout().flush()?;
+        match input::read_line()?.unwrap_or_default().trim().to_lowercase().as_str() {
+            "r" | "retry" => return Ok(FailureDecision::Retry),
+            "p" | "replan" => return Ok(FailureDecision::Replan),
+            "s" | "skip" => return Ok(FailureDecision::Skip),
+            "a" | "abort" | "" => return Ok(FailureDecision::Abort),
+            other => println!("Unknown choice '{}'.", other),
+        }
+    }
+}
+
+// Shows the changes the LLM generated for `path` and asks to confirm writing them unless the auto-confirm tier allows the `write_action`
+pub(crate) async fn confirm_generated_write(current_auto_confirm: AutoConfirm, write_action: &Action, path: &str, new_content: &str, target: &dyn ExecutionTarget) -> Result<bool> {
+    if current_auto_confirm.allows(write_action) {
+        return Ok(true);
+    }
+    ensure_prompts_enabled(&format!("Writing the generated changes to '{}' is not confirmed automatically and", path))?;
+    let old_content = target.read_to_string(path).await.unwrap_or_default();
+    println!("{}", "--- Generated Changes ---".yellow());
+    println!("{}", unified_diff(&old_content, new_content, path, path, MAX_PREVIEW_DIFF_LINES));
+    println!("{}", "-------------------------".yellow());
+    print!("Write these changes to '{}'? (y/N): ", path);
+    io::stdout().flush()?;
+    let choice = input::read_line()?.unwrap_or_default().trim().to_lowercase();
+    Ok(choice == "y" || choice == "yes")
+}
+
+// The target of the project config is chosen by whoever wrote the project, e.g. a cloned repository, it is used only
+// when the user confirms it and never when the actions are confirmed automatically
+pub(crate) fn confirm_project_target(target: &dyn ExecutionTarget, source: &str, auto_confirm: AutoConfirm) -> Result<()> {
+    let denied = || ActionError::PolicyDenied(format!("The target '{}' set in {} is not confirmed, pass `--target {}` to execute the plans on it", target, source, target));
+    if auto_confirm != AutoConfirm::None || PROMPTS_DISABLED.load(Ordering::Relaxed) || !input::is_interactive() {
+        return Err(denied().into());
+    }
+    print!("{} executes the plans on '{}'. Execute the commands and file actions there? (y/N): ", source, target);
+    io::stdout().flush()?;
+    match input::read_line()?.unwrap_or_default().trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(denied().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::ActionOutput;
+    use crate::actions::ask_llm_to_overwrite_file;
+    use crate::actions::target::LocalTarget;
+    use crate::config::Model;
+    use httpmock::prelude::*;
+    use reqwest::Client;
+
+    // Declines every write prompt of the tests, a prompt shown when the tier should skip it fails the test
+    fn decline_prompts() -> Result<()> {
+        let script = tempfile::NamedTempFile::new()?;
+        std::fs::write(script.path(), "n\n".repeat(10))?;
+        input::use_script(script.path())
+    }
+
+    #[tokio::test]
+    async fn test_declined_generated_write() -> Result<()> {
+        decline_prompts()?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("notes.txt").display().to_string();
+        std::fs::write(&path, "buy milk\n")?;
+        let server = MockServer::start();
+        let generated = serde_json::json!({ "action": "overwrite_file_contents", "action_idx": 1, "path": path, "content": "buy bread\n" });
+        server.mock(|when, then| {
+            when.method(POST).path("/chat");
+            then.status(200).json_body(serde_json::json!({ "answer": generated.to_string() }));
+        });
+        let model = Model {
+            name: "Test Model".to_string(),
+            api_url: server.url("/chat"),
+            request_format: r#"{"messages": {{messages}}}"#.to_string(),
+            response_json_path: "$.answer".to_string(),
+            ..Model::default()
+        };
+
+        let output = ask_llm_to_overwrite_file::execute(&path, &model, &[], &Client::new(), AutoConfirm::None, &LocalTarget).await?;
+
+        assert_eq!(output, ActionOutput::text(REJECTED_OUTPUT));
+        assert_eq!(std::fs::read_to_string(&path)?, "buy milk\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generated_write_confirmed_by_tier() -> Result<()> {
+        decline_prompts()?;
+        let dir = tempfile::tempdir()?;
+        let outside_path = dir.path().join("notes.txt").display().to_string();
+        let write = |path: &str| Action::OverwriteFileContents { action_idx: 1, path: path.to_string(), content: "buy bread\n".to_string() };
+
+        assert!(confirm_generated_write(AutoConfirm::Safe, &write("notes.txt"), "notes.txt", "buy bread\n", &LocalTarget).await?);
+        assert!(confirm_generated_write(AutoConfirm::All, &write(&outside_path), &outside_path, "buy bread\n", &LocalTarget).await?);
+        assert!(!confirm_generated_write(AutoConfirm::Safe, &write(&outside_path), &outside_path, "buy bread\n", &LocalTarget).await?);
+        Ok(())
+    }
+}
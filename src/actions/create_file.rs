@@ -1,12 +1,13 @@
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::Result;
+use crate::actions::{ensure_overwritable, ActionOutput};
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String, content: &String) -> Result<Option<String>> {
-    if let Some(parent_dir) = std::path::Path::new(path).parent() {
-        fs::create_dir_all(parent_dir)
-            .with_context(|| format!("Failed to create parent directories for '{}'", path))?;
+// With `if_not_exists` an existing file is kept as it is, without `overwrite` it is an error
+pub(crate) async fn execute(path: &str, content: &str, if_not_exists: bool, overwrite: bool, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    if if_not_exists && target.exists(path).await? {
+        return Ok(ActionOutput::text(format!("'{}' already exists, it was not changed", path)));
     }
-    fs::write(path, content)
-        .with_context(|| format!("Failed to write file: {}", path))?;
-    Ok(None)
-}
\ No newline at end of file
+    ensure_overwritable(path, overwrite, target).await?;
+    target.write_file(path, content).await?;
+    Ok(ActionOutput::FileWritten { path: path.to_string(), bytes: content.len() })
+}
@@ -0,0 +1,12 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use std::collections::HashMap;
+use crate::actions::create_file;
+use crate::actions::target::ExecutionTarget;
+use crate::templates;
+
+pub(crate) async fn execute(template: &str, path: &str, variables: &HashMap<String, String>, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let template_content = templates::load_template(&templates::templates_dir()?, template)?;
+    let content = templates::render(&template_content, variables)?;
+    create_file::execute(path, &content, false, true, target).await
+}
@@ -1,10 +1,8 @@
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String) -> Result<Option<String>> {
-    if std::path::Path::new(path).exists() {
-        fs::remove_file(path)
-            .with_context(|| format!("Failed to delete file: {}", path))?;
-    }
-    Ok(None)
-} 
\ No newline at end of file
+pub(crate) async fn execute(path: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    target.remove_file(path).await?;
+    Ok(ActionOutput::None)
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::error::{ActionError, IoResultExt};
+use tokio::process::Command;
+
+pub(crate) async fn execute(container: &str, lines: usize) -> Result<ActionOutput> {
+    let lines = lines.to_string();
+    let output = Command::new("docker")
+        .args(["logs", "--tail", lines.as_str(), container])
+        .output()
+        .await
+        .with_action_context(|| "Failed to execute 'docker', is it installed?".to_string())?;
+    if !output.status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("Failed to read logs of container '{}': {}", container, String::from_utf8_lossy(&output.stderr).trim()),
+            exit_code: output.status.code(),
+        }.into());
+    }
+    // `docker logs` forwards the stdout and the stderr of the container separately
+    Ok(ActionOutput::Command {
+        exit_code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::error::{ActionError, IoResultExt};
+use serde_json::Value;
+use tokio::process::Command;
+
+pub(crate) async fn execute() -> Result<ActionOutput> {
+    let output = run_cli("docker", &["ps", "--all", "--format", "{{json .}}"]).await?;
+    let entries = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .map(|container| {
+            let field = |name: &str| container[name].as_str().unwrap_or_default().to_string();
+            let ports = field("Ports");
+            let ports = if ports.is_empty() { String::new() } else { format!(", ports: {}", ports) };
+            format!("{} (image: {}, id: {}): {}{}", field("Names"), field("Image"), field("ID"), field("Status"), ports)
+        })
+        .collect();
+    Ok(ActionOutput::Listing { entries })
+}
+
+// Runs the CLI `program` directly without a shell and returns its stdout, the stderr is included into the error
+pub(crate) async fn run_cli(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_action_context(|| format!("Failed to execute '{}', is it installed?", program))?;
+    if !output.status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("'{} {}' failed with status {}: {}", program, args.join(" "), output.status, String::from_utf8_lossy(&output.stderr).trim()),
+            exit_code: output.status.code(),
+        }.into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
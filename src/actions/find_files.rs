@@ -1,15 +1,66 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use crate::error::ActionError;
+use crate::actions::ActionOutput;
+use std::fs;
+use std::path::{Path, PathBuf};
+use glob::MatchOptions;
+use regex::Regex;
+use crate::fs::{expand_home, walk_dir};
 
-pub(crate) async fn execute(pattern: &String) -> Result<Option<String>> {
-    let mut paths: Vec<String> = Vec::new();
-    for entry in glob::glob(pattern).with_context(|| format!("Failed to glob with pattern: {}", pattern))? {
+pub(crate) async fn execute(pattern: &str, path: Option<&str>, containing: Option<&str>) -> Result<ActionOutput> {
+    let (pattern, path, containing) = (pattern.to_string(), path.map(str::to_string), containing.map(str::to_string));
+    // glob and the directory walker only offer blocking APIs
+    tokio::task::spawn_blocking(move || find(&pattern, path.as_deref(), containing.as_deref())).await?
+}
+
+fn find(pattern: &str, path: Option<&str>, containing: Option<&str>) -> Result<ActionOutput> {
+    let content_regex = containing
+        .map(|containing| Regex::new(containing).map_err(|e| ActionError::InvalidInput(format!("Invalid regex: {}: {}", containing, e))))
+        .transpose()?;
+    let paths = if pattern.starts_with('/') || pattern.starts_with('~') {
+        glob_paths(&expand_home(pattern)?.to_string_lossy())?
+    } else {
+        walk_paths(pattern, path.unwrap_or("."))?
+    };
+    let entries = paths
+        .into_iter()
+        .filter(|path| content_regex.as_ref().is_none_or(|regex| file_contains(path, regex)))
+        .map(|path| path.display().to_string())
+        .collect();
+    Ok(ActionOutput::Listing { entries })
+}
+
+fn glob_paths(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for entry in glob::glob(pattern).map_err(|e| ActionError::InvalidInput(format!("Failed to glob with pattern: {}: {}", pattern, e)))? {
         match entry {
             Ok(path) => {
-                paths.push(path.display().to_string());
+                paths.push(path);
             }
             Err(e) => println!("glob error: {:?}", e),
         }
     }
-    let result = paths.join("\n");
-    Ok(Some(result))
-} 
\ No newline at end of file
+    Ok(paths)
+}
+
+fn walk_paths(pattern: &str, base_path: &str) -> Result<Vec<PathBuf>> {
+    let pattern = glob::Pattern::new(pattern).map_err(|e| ActionError::InvalidInput(format!("Failed to glob with pattern: {}: {}", pattern, e)))?;
+    let match_options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+    let expanded_base_path = expand_home(base_path)?;
+    let paths = walk_dir(&expanded_base_path, None)
+        .filter_map(|entry| {
+            let relative_path = entry.path().strip_prefix(&expanded_base_path).ok()?.to_path_buf();
+            pattern.matches_path_with(&relative_path, match_options).then(|| {
+                if base_path == "." { relative_path } else { Path::new(base_path).join(relative_path) }
+            })
+        })
+        .collect();
+    Ok(paths)
+}
+
+fn file_contains(path: &Path, regex: &Regex) -> bool {
+    expand_home(&path.to_string_lossy())
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|content| regex.is_match(&content))
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::error::{ActionError, IoResultExt};
+use serde_json::Value;
+use std::path::Path;
+use tokio::process::Command;
+#[cfg(feature = "syntax")]
+use crate::fs::{is_dir, walk_dir};
+
+// Finds the definitions of `name` in the files under the current directory, e.g. "src/config.rs:40-95 impl Config > pub fn save(&self)"
+pub(crate) async fn execute(name: &str, language: Option<&str>) -> Result<ActionOutput> {
+    #[cfg(feature = "syntax")]
+    if language.is_none_or(crate::syntax::supports_language) {
+        let (name, language) = (name.to_string(), language.map(str::to_lowercase));
+        // the directory walker only offers a blocking API
+        return tokio::task::spawn_blocking(move || find_with_tree_sitter(&name, language.as_deref())).await?;
+    }
+    find_with_ctags(name, language).await
+}
+
+#[cfg(feature = "syntax")]
+fn find_with_tree_sitter(name: &str, language: Option<&str>) -> Result<ActionOutput> {
+    let mut entries = Vec::new();
+    for entry in walk_dir(Path::new("."), None).filter(|entry| !is_dir(entry)) {
+        let path = entry.path().strip_prefix(".").unwrap_or(entry.path());
+        let Some(file_language) = crate::syntax::language_name(path) else {
+            continue;
+        };
+        if language.is_some_and(|language| language != file_language) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        for definition in crate::syntax::find_definitions(path, &content, name) {
+            entries.push(format!("{}:{}-{} {}", path.display(), definition.start_line, definition.end_line, definition.signature));
+        }
+    }
+    Ok(ActionOutput::Listing { entries })
+}
+
+// Universal Ctags covers the languages tree-sitter is not built for, the ignored files are not skipped
+async fn find_with_ctags(name: &str, language: Option<&str>) -> Result<ActionOutput> {
+    let mut command = Command::new("ctags");
+    command.args(["--recurse", "--output-format=json", "--fields=+ne", "--exclude=.git", "-f", "-"]);
+    if let Some(language) = language {
+        command.arg(format!("--languages={}", language));
+    }
+    let output = command.arg(".").output().await
+        .with_action_context(|| "Failed to execute 'ctags', install Universal Ctags or build cliff with the `syntax` feature".to_string())?;
+    if !output.status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("'ctags' failed with status {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()),
+            exit_code: output.status.code(),
+        }.into());
+    }
+    let entries = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|tag| tag["_type"] == "tag" && tag["name"] == name)
+        .map(|tag| describe_tag(&tag))
+        .collect();
+    Ok(ActionOutput::Listing { entries })
+}
+
+// e.g. "src/config.rs:40-95 function: pub fn save(&self) -> Result<()> {"
+fn describe_tag(tag: &Value) -> String {
+    let path = tag["path"].as_str().unwrap_or_default();
+    let path = Path::new(path).strip_prefix(".").unwrap_or(Path::new(path)).display();
+    let line = tag["line"].as_u64().unwrap_or_default();
+    let lines = match tag["end"].as_u64() {
+        Some(end) if end > line => format!("{}-{}", line, end),
+        _ => line.to_string(),
+    };
+    // The pattern is the searched line, e.g. "/^fn main() {$/"
+    let pattern = tag["pattern"].as_str().unwrap_or_default();
+    let source_line = pattern.trim_start_matches("/^").trim_end_matches("$/").trim();
+    format!("{}:{} {}: {}", path, lines, tag["kind"].as_str().unwrap_or("symbol"), source_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_tag() {
+        let tag = serde_json::json!({
+            "_type": "tag", "name": "save", "path": "./src/config.rs", "pattern": "/^    pub fn save(&self) -> Result<()> {$/",
+            "line": 40, "end": 95, "kind": "method"
+        });
+        assert_eq!(describe_tag(&tag), "src/config.rs:40-95 method: pub fn save(&self) -> Result<()> {");
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::list_processes::processes;
+use crate::error::ActionError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const SOCKET_TABLES: [(&str, &str); 4] = [
+    ("tcp", "/proc/net/tcp"),
+    ("tcp6", "/proc/net/tcp6"),
+    ("udp", "/proc/net/udp"),
+    ("udp6", "/proc/net/udp6"),
+];
+
+struct Socket {
+    protocol: &'static str,
+    local_address: String,
+    state: &'static str,
+    inode: u64,
+}
+
+pub(crate) async fn execute(port: u16) -> Result<ActionOutput> {
+    // The sockets are only found in the /proc tables of Linux, on the other systems the plans use `lsof` or `netstat`
+    if !Path::new("/proc/net").is_dir() {
+        return Err(ActionError::NotFound("Port usage is only supported on Linux, /proc/net is not available, use `lsof` or `netstat` instead".to_string()).into());
+    }
+    // The socket tables and file descriptors are read from blocking /proc files
+    tokio::task::spawn_blocking(move || {
+        let sockets = sockets_on_port(port);
+        if sockets.is_empty() {
+            return Ok(ActionOutput::Listing { entries: Vec::new() });
+        }
+        let owners = socket_owners(&sockets)?;
+        let entries = sockets
+            .iter()
+            .map(|socket| {
+                let owner = owners.get(&socket.inode).map_or("unknown process (no permission to inspect it)".to_string(), |(pid, name)| format!("pid {} ({})", pid, name));
+                format!("{} {} {} {}", socket.protocol, socket.local_address, socket.state, owner)
+            })
+            .collect();
+        Ok(ActionOutput::Listing { entries })
+    }).await?
+}
+
+fn sockets_on_port(port: u16) -> Vec<Socket> {
+    let mut sockets = Vec::new();
+    for (protocol, table_path) in SOCKET_TABLES {
+        let Ok(table) = fs::read_to_string(table_path) else {
+            continue;
+        };
+        for line in table.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(local_address), Some(state), Some(inode)) = (fields.get(1), fields.get(3), fields.get(9)) else {
+                continue;
+            };
+            let Some((address, socket_port)) = local_address.split_once(':') else {
+                continue;
+            };
+            if u16::from_str_radix(socket_port, 16) != Ok(port) {
+                continue;
+            }
+            sockets.push(Socket {
+                protocol,
+                local_address: format!("{}:{}", format_address(address), port),
+                state: socket_state(protocol, state),
+                inode: inode.parse().unwrap_or_default(),
+            });
+        }
+    }
+    sockets
+}
+
+// Pid and name of the processes holding the sockets, found by the `socket:[inode]` links of their file descriptors
+fn socket_owners(sockets: &[Socket]) -> Result<HashMap<u64, (u32, String)>> {
+    let mut owners = HashMap::new();
+    for process in processes()? {
+        let Ok(descriptors) = fs::read_dir(format!("/proc/{}/fd", process.pid)) else {
+            continue;
+        };
+        for descriptor in descriptors.filter_map(|descriptor| descriptor.ok()) {
+            let Ok(target) = fs::read_link(descriptor.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            let inode = target.strip_prefix("socket:[").and_then(|rest| rest.strip_suffix(']')).and_then(|inode| inode.parse::<u64>().ok());
+            if let Some(inode) = inode.filter(|inode| sockets.iter().any(|socket| socket.inode == *inode)) {
+                owners.insert(inode, (process.pid, process.name.clone()));
+            }
+        }
+    }
+    Ok(owners)
+}
+
+// Addresses in /proc/net are hex encoded 32 bit words in the host byte order
+fn format_address(address: &str) -> String {
+    let words: Vec<u32> = (0..address.len() / 8)
+        .filter_map(|word_idx| u32::from_str_radix(&address[word_idx * 8..word_idx * 8 + 8], 16).ok())
+        .collect();
+    match words.as_slice() {
+        [word] => Ipv4Addr::from(word.to_ne_bytes()).to_string(),
+        [_, _, _, _] => {
+            let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+            let bytes: [u8; 16] = bytes.try_into().unwrap_or_default();
+            format!("[{}]", Ipv6Addr::from(bytes))
+        }
+        _ => address.to_string(),
+    }
+}
+
+fn socket_state(protocol: &str, state: &str) -> &'static str {
+    if protocol.starts_with("udp") {
+        return "UDP";
+    }
+    match state {
+        "01" => "ESTABLISHED",
+        "02" => "SYN_SENT",
+        "03" => "SYN_RECV",
+        "04" => "FIN_WAIT1",
+        "05" => "FIN_WAIT2",
+        "06" => "TIME_WAIT",
+        "07" => "CLOSE",
+        "08" => "CLOSE_WAIT",
+        "09" => "LAST_ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use ring::digest;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::actions::target::ExecutionTarget;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn digest_algorithm(&self) -> &'static digest::Algorithm {
+        match self {
+            HashAlgorithm::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            HashAlgorithm::Sha256 => &digest::SHA256,
+            HashAlgorithm::Sha384 => &digest::SHA384,
+            HashAlgorithm::Sha512 => &digest::SHA512,
+        }
+    }
+}
+
+pub(crate) async fn execute(path: &str, algorithm: HashAlgorithm, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let content = target.read(path).await?;
+    let hash = digest::digest(algorithm.digest_algorithm(), &content)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    Ok(ActionOutput::text(hash))
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::docker_ps::run_cli;
+use crate::error::ActionError;
+use serde_json::Value;
+
+pub(crate) async fn execute(resource: &str, namespace: Option<&str>) -> Result<ActionOutput> {
+    let mut args = vec!["get", resource, "--output", "json"];
+    if let Some(namespace) = namespace {
+        args.extend(["--namespace", namespace]);
+    }
+    let output = run_cli("kubectl", &args).await?;
+    let response: Value = serde_json::from_str(&output)
+        .map_err(|e| ActionError::InvalidInput(format!("Failed to parse the output of 'kubectl get {}': {}", resource, e)))?;
+    let items = match response["items"].as_array() {
+        Some(items) => items.iter().collect(),
+        None => vec![&response],
+    };
+    let entries = items.into_iter().map(describe_item).collect();
+    Ok(ActionOutput::Listing { entries })
+}
+
+// e.g. "Pod default/web-5d8f (phase: Running, ready: 1/1, restarts: 0)"
+fn describe_item(item: &Value) -> String {
+    let metadata = &item["metadata"];
+    let name = match metadata["namespace"].as_str() {
+        Some(namespace) => format!("{}/{}", namespace, metadata["name"].as_str().unwrap_or_default()),
+        None => metadata["name"].as_str().unwrap_or_default().to_string(),
+    };
+    let mut details = Vec::new();
+    if let Some(phase) = item["status"]["phase"].as_str() {
+        details.push(format!("phase: {}", phase));
+    }
+    if let Some(container_statuses) = item["status"]["containerStatuses"].as_array() {
+        let ready = container_statuses.iter().filter(|status| status["ready"].as_bool() == Some(true)).count();
+        let restarts: u64 = container_statuses.iter().filter_map(|status| status["restartCount"].as_u64()).sum();
+        details.push(format!("ready: {}/{}, restarts: {}", ready, container_statuses.len(), restarts));
+    }
+    if let (Some(ready), Some(replicas)) = (item["status"]["readyReplicas"].as_u64().or(Some(0)), item["spec"]["replicas"].as_u64()) {
+        details.push(format!("replicas ready: {}/{}", ready, replicas));
+    }
+    let kind = item["kind"].as_str().unwrap_or("Resource");
+    if details.is_empty() {
+        format!("{} {}", kind, name)
+    } else {
+        format!("{} {} ({})", kind, name, details.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_item() {
+        let pod = serde_json::json!({
+            "kind": "Pod",
+            "metadata": { "name": "web-5d8f", "namespace": "default" },
+            "status": { "phase": "Running", "containerStatuses": [{ "ready": true, "restartCount": 2 }] }
+        });
+        assert_eq!(describe_item(&pod), "Pod default/web-5d8f (phase: Running, ready: 1/1, restarts: 2)");
+    }
+}
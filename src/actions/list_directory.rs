@@ -1,15 +1,63 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use crate::error::{ActionError, IoResultExt};
+use crate::actions::ActionOutput;
 use std::fs;
-use crate::fs::expand_home;
+use std::path::Path;
+use crate::fs::{expand_home, is_dir, walk_dir};
 
-pub(crate) async fn execute(path: &String) -> Result<Option<String>> {
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+pub(crate) async fn execute(path: &str, recursive: bool, glob: Option<&str>, max_entries: Option<usize>) -> Result<ActionOutput> {
+    let (path, glob) = (path.to_string(), glob.map(str::to_string));
+    // the directory walker only offers a blocking API
+    tokio::task::spawn_blocking(move || list(&path, recursive, glob.as_deref(), max_entries)).await?
+}
+
+fn list(path: &str, recursive: bool, glob: Option<&str>, max_entries: Option<usize>) -> Result<ActionOutput> {
     let expanded_path = expand_home(path)?;
-    let mut entries = Vec::new();
-    for entry in fs::read_dir(&expanded_path)
-        .with_context(|| format!("Failed to read directory: {}", expanded_path.display()))? {
-        let entry = entry.with_context(|| format!("Failed to read directory entry in {}", expanded_path.display()))?;
-        entries.push(entry.file_name().to_string_lossy().to_string());
+    let pattern = glob
+        .map(|glob| glob::Pattern::new(glob).map_err(|e| ActionError::InvalidInput(format!("Invalid glob pattern: {}: {}", glob, e))))
+        .transpose()?;
+    let max_entries = max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
+
+    let mut entries: Vec<(String, bool, u64)> = Vec::new();
+    if recursive {
+        for entry in walk_dir(&expanded_path, None) {
+            let relative_path = entry.path().strip_prefix(&expanded_path).unwrap_or(entry.path());
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            entries.push((relative_path.display().to_string(), is_dir(&entry), size));
+        }
+    } else {
+        for entry in fs::read_dir(&expanded_path)
+            .with_action_context(|| format!("Failed to read directory: {}", expanded_path.display()))? {
+            let entry = entry.with_action_context(|| format!("Failed to read directory entry in {}", expanded_path.display()))?;
+            let metadata = entry.metadata()
+                .with_action_context(|| format!("Failed to read metadata of {}", entry.path().display()))?;
+            entries.push((entry.file_name().to_string_lossy().to_string(), metadata.is_dir(), metadata.len()));
+        }
+        entries.sort();
     }
-    let result = entries.join("\n");
-    Ok(Some(result))
-} 
\ No newline at end of file
+
+    let mut matching_entries = entries
+        .into_iter()
+        .filter(|(name, _, _)| pattern.as_ref().is_none_or(|pattern| matches_glob(pattern, name)));
+    let mut lines: Vec<String> = matching_entries
+        .by_ref()
+        .take(max_entries)
+        .map(|(name, is_dir, size)| if is_dir {
+            format!("{}/ (dir)", name)
+        } else {
+            format!("{} (file, {} bytes)", name, size)
+        })
+        .collect();
+    let remaining = matching_entries.count();
+    if remaining > 0 {
+        lines.push(format!("... ({} more entries not shown)", remaining));
+    }
+    Ok(ActionOutput::Listing { entries: lines })
+}
+
+fn matches_glob(pattern: &glob::Pattern, relative_path: &str) -> bool {
+    let file_name = Path::new(relative_path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    pattern.matches(relative_path) || pattern.matches(&file_name)
+}
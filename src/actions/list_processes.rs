@@ -0,0 +1,58 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+const MAX_PROCESSES: usize = 200;
+
+pub(crate) struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub command_line: String,
+    pub memory_kb: Option<u64>,
+}
+
+pub(crate) async fn execute(filter: Option<&str>) -> Result<ActionOutput> {
+    let filter = filter.map(str::to_lowercase);
+    // The process information is read from blocking system calls
+    tokio::task::spawn_blocking(move || {
+        let mut matching_processes = processes()?
+            .into_iter()
+            .filter(|process| filter.as_ref().is_none_or(|filter| {
+                process.name.to_lowercase().contains(filter) || process.command_line.to_lowercase().contains(filter)
+            }));
+        let mut entries: Vec<String> = matching_processes
+            .by_ref()
+            .take(MAX_PROCESSES)
+            .map(|process| {
+                let memory = process.memory_kb.map_or("unknown".to_string(), |memory_kb| format!("{} kB", memory_kb));
+                format!("pid {}: {} (memory {}) {}", process.pid, process.name, memory, process.command_line)
+            })
+            .collect();
+        let remaining = matching_processes.count();
+        if remaining > 0 {
+            entries.push(format!("... ({} more processes not shown)", remaining));
+        }
+        Ok(ActionOutput::Listing { entries })
+    }).await?
+}
+
+// Processes of the machine in pid order
+pub(crate) fn processes() -> Result<Vec<ProcessInfo>> {
+    let mut system = System::new();
+    let refresh_kind = ProcessRefreshKind::nothing().with_memory().with_cmd(UpdateKind::Always);
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+    let mut processes: Vec<ProcessInfo> = system.processes()
+        .iter()
+        // The threads of the processes are listed as processes on Linux
+        .filter(|(_, process)| process.thread_kind().is_none())
+        .map(|(pid, process)| ProcessInfo {
+            pid: pid.as_u32(),
+            name: process.name().to_string_lossy().to_string(),
+            command_line: process.cmd().iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "),
+            // Zero when the memory of the process can't be inspected
+            memory_kb: Some(process.memory() / 1024).filter(|memory_kb| *memory_kb > 0),
+        })
+        .collect();
+    processes.sort_by_key(|process| process.pid);
+    Ok(processes)
+}
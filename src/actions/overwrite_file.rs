@@ -1,14 +1,8 @@
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String, content: &String) -> Result<Option<String>> {
-    if !std::path::Path::new(path).exists() {
-        if let Some(parent_dir) = std::path::Path::new(path).parent() {
-            fs::create_dir_all(parent_dir)
-                .with_context(|| format!("Failed to create parent directories for '{}'", path))?;
-        }
-    }
-    fs::write(path, content)
-        .with_context(|| format!("Failed to write file: {}", path))?;
-    Ok(None)
-} 
\ No newline at end of file
+pub(crate) async fn execute(path: &str, content: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    target.write_file(path, content).await?;
+    Ok(ActionOutput::FileWritten { path: path.to_string(), bytes: content.len() })
+}
@@ -1,9 +1,8 @@
-use anyhow::{Context, Result};
-use std::fs;
-use crate::fs::expand_home;
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String) -> Result<Option<String>> {
-    let content = fs::read_to_string(expand_home(path)?)
-        .with_context(|| format!("Failed to read file: {}", path))?;
-    Ok(Some(content))
-}
\ No newline at end of file
+pub(crate) async fn execute(path: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let content = target.read_to_string(path).await?;
+    Ok(ActionOutput::text(content))
+}
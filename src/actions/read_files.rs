@@ -0,0 +1,32 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
+use std::sync::Arc;
+
+const MAX_FILE_SIZE: u64 = 64 * 1024;
+
+pub(crate) async fn execute(paths: &[String], target: Arc<dyn ExecutionTarget>) -> Result<ActionOutput> {
+    let reads: Vec<_> = paths
+        .iter()
+        .cloned()
+        .map(|path| {
+            let target = target.clone();
+            tokio::spawn(async move { read_labeled(&path, target.as_ref()).await })
+        })
+        .collect();
+    let mut sections = Vec::new();
+    for read in reads {
+        sections.push(read.await?);
+    }
+    Ok(ActionOutput::text(sections.join("\n")))
+}
+
+async fn read_labeled(path: &str, target: &dyn ExecutionTarget) -> String {
+    match target.read_prefix(path, MAX_FILE_SIZE).await {
+        Ok((bytes, size)) if size > MAX_FILE_SIZE => {
+            format!("--- File: {} ---\n{}\n... [truncated {} of {} bytes]", path, String::from_utf8_lossy(&bytes), size - MAX_FILE_SIZE, size)
+        }
+        Ok((bytes, _)) => format!("--- File: {} ---\n{}", path, String::from_utf8_lossy(&bytes)),
+        Err(e) => format!("--- File: {} ---\nERROR: {}", path, e),
+    }
+}
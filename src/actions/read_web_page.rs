@@ -1,9 +1,14 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crate::actions::ActionOutput;
+use crate::guardrails::guard;
+use crate::web_fixtures;
 use reqwest::Client;
 
-pub(crate) async fn execute(client: &Client, url: &String) -> Result<Option<String>> {
+pub(crate) async fn execute(client: &Client, url: &str) -> Result<ActionOutput> {
     println!("Action: Read web page at '{}'", url);
-    let response = client.get(url).send().await?.text().await?;
+    let response = web_fixtures::get(client, url).await
+        .with_context(|| format!("Failed to read web page: {}", url))?
+        .body;
     println!("Success: Web page read. {}", response);
-    Ok(Some(response))
+    Ok(ActionOutput::text(guard(url, response)))
 }
@@ -1,9 +1,16 @@
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
 
-pub(crate) async fn execute(path: &String, from_line_idx: usize, until_line_idx: usize, new_contents: &String) -> Result<Option<String>> {
-    let mut lines: Vec<String> = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read file for replacement: {}", path))?
+pub(crate) async fn execute(path: &str, from_line_idx: usize, until_line_idx: usize, new_contents: &str, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let content = target.read_to_string(path).await?;
+    let modified_content = replace_lines(&content, from_line_idx, until_line_idx, new_contents);
+    target.write_file(path, &modified_content).await?;
+    Ok(ActionOutput::FileWritten { path: path.to_string(), bytes: modified_content.len() })
+}
+
+pub(crate) fn replace_lines(content: &str, from_line_idx: usize, until_line_idx: usize, new_contents: &str) -> String {
+    let mut lines: Vec<String> = content
         .lines()
         .map(|s| s.to_string())
         .collect();
@@ -25,8 +32,5 @@ pub(crate) async fn execute(path: &String, from_line_idx: usize, until_line_idx:
         lines.insert(range_start, line);
     }
 
-    let modified_content = lines.join("\n");
-    fs::write(path, modified_content)
-        .with_context(|| format!("Failed to write modified file: {}", path))?;
-    Ok(None)
-} 
\ No newline at end of file
+    lines.join("\n")
+}
@@ -1,41 +1,160 @@
-use anyhow::{Context, Result};
-use std::process::{Command, Stdio};
+use anyhow::Result;
+use crate::error::{ActionError, IoResultExt};
+use crate::actions::ActionOutput;
+use crate::actions::target::ExecutionTarget;
+use crate::config::ShellConfig;
+use regex::Regex;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
 use colored::*;
 
-pub(crate) async fn execute(command: &String) -> Result<Option<String>> {
-    println!("Action: Run command `{}`", command);
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
-    let mut cmd = Command::new(shell);
-    cmd.arg("-c");
-    cmd.arg(command);
+// Secret answers of the forms as the environment variables of the commands, never in the plans or the history
+static SECRETS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Passes the secret to the commands run afterwards as the environment variable `name`, the secret is redacted from
+/// their outputs
+pub(crate) fn provide_secret(name: String, value: String) {
+    let mut secrets = SECRETS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    secrets.retain(|(existing, _)| *existing != name);
+    secrets.push((name, value));
+}
+
+fn secrets() -> Vec<(String, String)> {
+    SECRETS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+fn redact_secrets(output: &str, secrets: &[(String, String)]) -> String {
+    secrets
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .fold(output.to_string(), |output, (name, value)| output.replace(value.as_str(), &format!("[redacted ${}]", name)))
+}
+
+pub(crate) async fn execute(command: &str, shell_config: ShellConfig, target: &dyn ExecutionTarget) -> Result<ActionOutput> {
+    let shell = shell_config.resolve();
+    if target.is_local() {
+        println!("Action: Run command `{}` with {}", command, shell.program());
+    } else {
+        println!("Action: Run command `{}` with {} on {}", command, shell.program(), target);
+    }
+    let mut cmd = target.command(shell.program(), &shell.args(command, shell_config.login));
+    let secrets = secrets();
+    cmd.envs(secrets.iter().map(|(name, value)| (name, value)));
 
     cmd.stdin(Stdio::inherit());
     cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::inherit());
+    cmd.stderr(Stdio::piped());
 
-    let output = cmd.output() // Use output() to get status and streams
-        .with_context(|| format!("Failed to execute command: {}", command))?;
+    let mut child = cmd.spawn()
+        .with_action_context(|| format!("Failed to execute command: {}", command))?;
+    let stdout = child.stdout.take().expect("stdout of the command is piped");
+    let stderr = child.stderr.take().expect("stderr of the command is piped");
 
-    if !output.stdout.is_empty() {
-        println!("{}", "--- Command Output ---".green());
-        for line in String::from_utf8_lossy(&output.stdout).lines() {
-            println!("{}", line.green());
-        }
-        println!("{}", "----------------------".green());
-    }
-     if !output.stderr.is_empty() {
-        eprintln!("{}", "--- Command Error Output ---".red());
-        for line in String::from_utf8_lossy(&output.stderr).lines() {
-            eprintln!("{}", line.red());
-        }
-        eprintln!("{}", "--------------------------".red());
-    }
+    // Both streams are read concurrently so that the command does not block on a full pipe
+    let (stdout, stderr) = tokio::try_join!(
+        stream_lines(stdout, |line| println!("{}", line.green())),
+        stream_lines(stderr, |line| eprintln!("{}", line.red())),
+    ).with_action_context(|| format!("Failed to read the output of command: {}", command))?;
+    let status = child.wait().await
+        .with_action_context(|| format!("Failed to wait for command: {}", command))?;
 
-    if output.status.success() {
+    if status.success() {
         println!("Success: Command executed successfully.");
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        Ok(Some(output_str.trim().to_string()))
+        Ok(ActionOutput::Command {
+            exit_code: status.code(),
+            stdout: redact_secrets(stdout.trim(), &secrets),
+            stderr: redact_secrets(stderr.trim(), &secrets),
+        })
     } else {
-        anyhow::bail!("Command failed with status: {}", output.status);
+        Err(ActionError::CommandFailed {
+            message: format!("Command failed with status: {}", status),
+            exit_code: status.code(),
+        }.into())
+    }
+}
+
+// Runs the command with the shell and writes `input` to its standard input
+pub(crate) async fn pipe_to_command(input: &str, command: &str, shell_config: ShellConfig) -> Result<()> {
+    let shell = shell_config.resolve();
+    let mut child = tokio::process::Command::new(shell.program())
+        .args(shell.args(command, shell_config.login))
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .with_action_context(|| format!("Failed to execute command: {}", command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).await
+            .with_action_context(|| format!("Failed to pipe the input to: {}", command))?;
+    }
+    let status = child.wait().await
+        .with_action_context(|| format!("Failed to wait for command: {}", command))?;
+    if !status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("Command `{}` failed with {}", command, status),
+            exit_code: status.code(),
+        }.into());
+    }
+    Ok(())
+}
+
+// Prints every line of `stream` as soon as it is read and returns the whole captured output
+async fn stream_lines<R: AsyncRead + Unpin>(stream: R, print_line: fn(&str)) -> std::io::Result<String> {
+    let mut reader = BufReader::new(stream);
+    let mut captured = String::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        let text = String::from_utf8_lossy(&line);
+        print_line(text.trim_end_matches(['\n', '\r']));
+        captured.push_str(&sanitize_line(&text));
+        captured.push('\n');
+        line.clear();
+    }
+    Ok(captured)
+}
+
+// CSI sequences (colors, cursor movement), OSC sequences (titles, hyperlinks) and the other escapes of the terminals
+fn ansi_codes() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-Z\\-_]").expect("Invalid ANSI pattern"))
+}
+
+pub(crate) fn strip_ansi(text: &str) -> String {
+    ansi_codes().replace_all(text, "").into_owned()
+}
+
+// The line as it would be seen in a terminal, safe to embed in the prompts and the JSON: without the ANSI codes, only
+// the last state of a line redrawn with carriage returns, e.g. of a progress bar, and without the other control characters
+pub(crate) fn sanitize_line(line: &str) -> String {
+    let line = strip_ansi(line.trim_end_matches(['\n', '\r']));
+    let visible = line.rsplit('\r').find(|segment| !segment.is_empty()).unwrap_or_default();
+    visible.chars().filter(|c| *c == '\t' || !c.is_control()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::target::LocalTarget;
+    use crate::config::Shell;
+
+    #[tokio::test]
+    async fn test_captured_output_is_sanitized() -> Result<()> {
+        let output = b"\x1b[1;32mok\x1b[0m\r\n 10%\r 50%\r100%\r\nbell\x07 \xff\n\x1b]0;title\x07done";
+        assert_eq!(stream_lines(&output[..], |_| {}).await?, "ok\n100%\nbell \u{fffd}\ndone\n");
+
+        let secrets = [("CLIFF_SECRET_TOKEN".to_string(), "s3cr3t".to_string()), ("CLIFF_SECRET_EMPTY".to_string(), String::new())];
+        assert_eq!(redact_secrets("token=s3cr3t", &secrets), "token=[redacted $CLIFF_SECRET_TOKEN]");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_output() -> Result<()> {
+        let shell = ShellConfig { name: Some(Shell::Sh), login: false };
+
+        let output = execute("echo built; echo 'warning: unused' >&2; echo done", shell, &LocalTarget).await?;
+        assert_eq!(output, ActionOutput::Command { exit_code: Some(0), stdout: "built\ndone".to_string(), stderr: "warning: unused".to_string() });
+
+        let error = execute("echo partial; echo 'error: missing' >&2; exit 3", shell, &LocalTarget).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ActionError>(), Some(ActionError::CommandFailed { exit_code: Some(3), .. })));
+        Ok(())
     }
 }
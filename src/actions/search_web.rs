@@ -1,11 +1,17 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crate::actions::ActionOutput;
+use crate::guardrails::guard;
+use crate::web_fixtures;
+use reqwest::Client;
 use urlencoding::encode;
 
-pub(crate) async fn execute(query: &String) -> Result<Option<String>> {
+pub(crate) async fn execute(client: &Client, query: &str) -> Result<ActionOutput> {
     println!("Action: Search web for '{}'", query);
-    let url_encoded_query = encode(&query);
+    let url_encoded_query = encode(query);
     let url = format!("https://api.duckduckgo.com/?q={}&format=json&pretty=1", url_encoded_query);
-    let response = reqwest::get(&url).await?.text().await?;
+    let response = web_fixtures::get(client, &url).await
+        .with_context(|| format!("Failed to search web for '{}'", query))?
+        .body;
     println!("Success: Web search completed. {}", response);
-    Ok(Some(response))
+    Ok(ActionOutput::text(guard(&format!("the web search for '{}'", query), response)))
 }
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::error::ActionError;
+use crate::fs::{expand_home, is_dir, walk_dir};
+
+const MAX_ENTRIES: usize = 500;
+
+pub(crate) async fn execute(path: &str, max_depth: Option<usize>, include_sizes: bool) -> Result<ActionOutput> {
+    let path = path.to_string();
+    // the directory walker only offers a blocking API
+    tokio::task::spawn_blocking(move || tree(&path, max_depth, include_sizes)).await?
+}
+
+fn tree(path: &str, max_depth: Option<usize>, include_sizes: bool) -> Result<ActionOutput> {
+    let expanded_path = expand_home(path)?;
+    if !expanded_path.is_dir() {
+        return Err(ActionError::NotFound(format!("Not a directory: {}", expanded_path.display())).into());
+    }
+    let mut lines = vec![format!("{}/", path.trim_end_matches('/'))];
+    for (entry_idx, entry) in walk_dir(&expanded_path, max_depth).enumerate() {
+        if entry_idx == MAX_ENTRIES {
+            lines.push(format!("... (truncated after {} entries)", MAX_ENTRIES));
+            break;
+        }
+        let indent = "  ".repeat(entry.depth());
+        let name = entry.file_name().to_string_lossy();
+        if is_dir(&entry) {
+            lines.push(format!("{}{}/", indent, name));
+        } else if include_sizes {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            lines.push(format!("{}{} ({} bytes)", indent, name, size));
+        } else {
+            lines.push(format!("{}{}", indent, name));
+        }
+    }
+    Ok(ActionOutput::text(lines.join("\n")))
+}
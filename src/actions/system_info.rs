@@ -0,0 +1,73 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use std::fs;
+use std::path::Path;
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+pub(crate) async fn execute() -> Result<ActionOutput> {
+    let workdir = std::env::current_dir()?;
+    // /proc files and statvfs are blocking
+    let snapshot = tokio::task::spawn_blocking(move || {
+        let mut lines = vec![environment_summary()];
+        if let Some(load_average) = load_average() {
+            lines.push(format!("load average: {}", load_average));
+        }
+        if let Some((total, available)) = memory() {
+            lines.push(format!("memory: {:.1} GiB used of {:.1} GiB", (total - available) as f64 / GIB, total as f64 / GIB));
+        }
+        if let Some((total, available)) = disk_space(&workdir) {
+            lines.push(format!("disk ({}): {:.1} GiB free of {:.1} GiB", workdir.display(), available as f64 / GIB, total as f64 / GIB));
+        }
+        lines.join("\n")
+    }).await?;
+    Ok(ActionOutput::text(snapshot))
+}
+
+// One line description of the OS and the hardware of the machine of the user, e.g. for the planning prompt
+pub(crate) fn environment_summary() -> String {
+    let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+    let os_name = fs::read_to_string("/etc/os-release").ok().and_then(|os_release| {
+        os_release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    });
+    match os_name {
+        Some(os_name) => format!("os: {} {} ({}), cpus: {}", std::env::consts::OS, std::env::consts::ARCH, os_name, cpus),
+        None => format!("os: {} {}, cpus: {}", std::env::consts::OS, std::env::consts::ARCH, cpus),
+    }
+}
+
+fn load_average() -> Option<String> {
+    let load_average = fs::read_to_string("/proc/loadavg").ok()?;
+    Some(load_average.split_whitespace().take(3).collect::<Vec<_>>().join(" "))
+}
+
+// Total and available memory in bytes
+fn memory() -> Option<(u64, u64)> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let value = meminfo.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?;
+        value.trim().trim_end_matches("kB").trim().parse::<u64>().ok().map(|kb| kb * 1024)
+    };
+    Some((field("MemTotal")?, field("MemAvailable")?))
+}
+
+// Total and available space in bytes of the file system containing `path`
+#[cfg(unix)]
+fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block_size = stat.f_frsize as u64;
+    Some((stat.f_blocks as u64 * block_size, stat.f_bavail as u64 * block_size))
+}
+
+#[cfg(not(unix))]
+fn disk_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use crate::error::IoResultExt;
+use crate::actions::ActionOutput;
+use crate::fs::expand_home;
+use std::io::SeekFrom;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const CHUNK_SIZE: u64 = 8 * 1024;
+const MAX_TAIL_SIZE: u64 = 1024 * 1024;
+
+// Reads the file backwards chunk by chunk until enough lines are found, so that large logs are never loaded entirely
+pub(crate) async fn execute(path: &str, lines: usize) -> Result<ActionOutput> {
+    let expanded_path = expand_home(path)?;
+    let mut file = File::open(&expanded_path).await
+        .with_action_context(|| format!("Failed to read file: {}", path))?;
+    let file_size = file.metadata().await
+        .with_action_context(|| format!("Failed to read metadata of {}", path))?
+        .len();
+
+    let mut position = file_size;
+    let mut tail: Vec<u8> = Vec::new();
+    // The trailing newline of the file does not start a new line
+    while position > 0 && count_newlines(tail.strip_suffix(b"\n").unwrap_or(&tail)) < lines && file_size - position < MAX_TAIL_SIZE {
+        let chunk_size = CHUNK_SIZE.min(position);
+        position -= chunk_size;
+        let mut chunk = vec![0; chunk_size as usize];
+        file.seek(SeekFrom::Start(position)).await
+            .with_action_context(|| format!("Failed to seek in file: {}", path))?;
+        file.read_exact(&mut chunk).await
+            .with_action_context(|| format!("Failed to read file: {}", path))?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&tail);
+    let all_lines: Vec<&str> = text.lines().collect();
+    let first_line = all_lines.len().saturating_sub(lines);
+    Ok(ActionOutput::text(all_lines[first_line..].join("\n")))
+}
+
+fn count_newlines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&byte| byte == b'\n').count()
+}
@@ -0,0 +1,223 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::error::{ActionError, IoResultExt};
+use std::fmt;
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+pub mod directory;
+pub mod docker;
+pub mod local;
+pub mod protected;
+pub mod quota;
+pub mod ssh;
+
+pub use directory::DirectoryTarget;
+pub use docker::DockerTarget;
+pub use local::LocalTarget;
+pub use protected::ProtectedTarget;
+pub use quota::QuotaTarget;
+pub use ssh::SshTarget;
+
+// Environment the commands and the file actions of a plan are executed in, selected per run
+#[async_trait]
+pub trait ExecutionTarget: fmt::Debug + fmt::Display + Send + Sync {
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    // Command running `program` with `args` in the target
+    fn command(&self, program: &str, args: &[String]) -> Command;
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>>;
+
+    // The first `max_bytes` bytes of the file and its size, without reading the rest of a large file
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)>;
+
+    async fn read_to_string(&self, path: &str) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.read(path).await?).to_string())
+    }
+
+    // Writes the file creating its parent directories
+    async fn write_file(&self, path: &str, content: &str) -> Result<()>;
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()>;
+
+    // Removes the file, a missing file is not an error
+    async fn remove_file(&self, path: &str) -> Result<()>;
+
+    // Moves the file creating the parent directories of the destination
+    async fn rename(&self, source: &str, destination: &str) -> Result<()>;
+
+    // Copies the file creating the parent directories of the destination, returns the number of copied bytes
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64>;
+
+    async fn exists(&self, path: &str) -> Result<bool>;
+
+    // Called when the target is reused for another run, e.g. by the daemon, to reset what is counted per run
+    fn start_run(&self) {}
+}
+
+// Parses "local", "ssh://[user@]host[:port]" or "docker://container"
+pub fn parse_target(target: &str) -> Result<Arc<dyn ExecutionTarget>> {
+    if target == "local" {
+        Ok(Arc::new(LocalTarget))
+    } else if let Some(address) = target.strip_prefix("ssh://") {
+        Ok(Arc::new(SshTarget::parse(address).ok_or_else(|| anyhow::anyhow!("Invalid target '{}', expected 'ssh://[user@]host[:port]'", target))?))
+    } else if let Some(container) = target.strip_prefix("docker://").filter(|container| !container.is_empty() && !container.starts_with('-')) {
+        Ok(Arc::new(DockerTarget { container: container.to_string() }))
+    } else {
+        anyhow::bail!("Unsupported target '{}', expected 'local', 'ssh://[user@]host[:port]' or 'docker://container'", target)
+    }
+}
+
+// Target running POSIX shell scripts in another environment, the file actions are implemented with standard utilities
+pub(crate) trait RemoteShell: fmt::Debug + fmt::Display + Send + Sync {
+    fn command(&self, program: &str, args: &[String]) -> Command;
+
+    // Whether the exit code comes from the failure to reach the environment rather than from the script
+    fn is_connection_failure(&self, exit_code: Option<i32>) -> bool;
+}
+
+async fn run_script<T: RemoteShell + ?Sized>(target: &T, script: &str, input: Option<&[u8]>) -> Result<Output> {
+    let mut command = target.command("sh", &["-c".to_string(), script.to_string()]);
+    command.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() });
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+    let mut child = command.spawn()
+        .with_action_context(|| format!("Failed to start a command on {}", target))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input).await
+            .with_action_context(|| format!("Failed to send data to {}", target))?;
+    }
+    Ok(child.wait_with_output().await
+        .with_action_context(|| format!("Failed to wait for a command on {}", target))?)
+}
+
+// Runs the script, failing with an error classified by the output of the script
+async fn run_checked<T: RemoteShell + ?Sized>(target: &T, script: &str, input: Option<&[u8]>, context: impl FnOnce() -> String) -> Result<Vec<u8>> {
+    let output = run_script(target, script, input).await?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let message = format!("{}: {}", context(), stderr);
+    Err(match output.status.code() {
+        exit_code if target.is_connection_failure(exit_code) => ActionError::Network(message),
+        _ if stderr.contains("No such file or directory") => ActionError::NotFound(message),
+        _ if stderr.contains("Permission denied") => ActionError::PermissionDenied(message),
+        exit_code => ActionError::CommandFailed { message, exit_code },
+    }.into())
+}
+
+#[async_trait]
+impl<T: RemoteShell> ExecutionTarget for T {
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        RemoteShell::command(self, program, args)
+    }
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        run_checked(self, &format!("cat -- {}", shell_path(path)), None, || format!("Failed to read file: {}", path)).await
+    }
+
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)> {
+        let quoted_path = shell_path(path);
+        let script = format!("wc -c < {} && head -c {} -- {}", quoted_path, max_bytes, quoted_path);
+        let output = run_checked(self, &script, None, || format!("Failed to read file: {}", path)).await?;
+        let size_end = output.iter().position(|byte| *byte == b'\n').unwrap_or(output.len());
+        let size = String::from_utf8_lossy(&output[..size_end]).trim().parse().unwrap_or_default();
+        Ok((output.get(size_end + 1..).unwrap_or_default().to_vec(), size))
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        let quoted_path = shell_path(path);
+        let script = format!("mkdir -p -- \"$(dirname -- {})\" && cat > {}", quoted_path, quoted_path);
+        run_checked(self, &script, Some(content.as_bytes()), || format!("Failed to write file: {}", path)).await?;
+        Ok(())
+    }
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()> {
+        let script = format!("cat >> {}", shell_path(path));
+        run_checked(self, &script, Some(content.as_bytes()), || format!("Failed to append content to file: {}", path)).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        run_checked(self, &format!("rm -f -- {}", shell_path(path)), None, || format!("Failed to delete file: {}", path)).await?;
+        Ok(())
+    }
+
+    async fn rename(&self, source: &str, destination: &str) -> Result<()> {
+        let (quoted_source, quoted_destination) = (shell_path(source), shell_path(destination));
+        let script = format!("mkdir -p -- \"$(dirname -- {})\" && mv -- {} {}", quoted_destination, quoted_source, quoted_destination);
+        run_checked(self, &script, None, || format!("Failed to move file from '{}' to '{}'", source, destination)).await?;
+        Ok(())
+    }
+
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64> {
+        let (quoted_source, quoted_destination) = (shell_path(source), shell_path(destination));
+        let script = format!(
+            "mkdir -p -- \"$(dirname -- {})\" && cp -- {} {} && wc -c < {}",
+            quoted_destination, quoted_source, quoted_destination, quoted_destination
+        );
+        let output = run_checked(self, &script, None, || format!("Failed to copy file from '{}' to '{}'", source, destination)).await?;
+        Ok(String::from_utf8_lossy(&output).trim().parse().unwrap_or_default())
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        let output = run_script(self, &format!("test -e {}", shell_path(path)), None).await?;
+        if self.is_connection_failure(output.status.code()) {
+            return Err(ActionError::Network(format!("Failed to reach {}: {}", self, String::from_utf8_lossy(&output.stderr).trim())).into());
+        }
+        Ok(output.status.success())
+    }
+}
+
+// Quotes the argument for a POSIX shell
+pub(crate) fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+// Quotes the path keeping `~/` expandable by the shell of the target
+fn shell_path(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(relative_path) => format!("\"$HOME\"/{}", quote(relative_path)),
+        None if path == "~" => "\"$HOME\"".to_string(),
+        None => quote(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() -> Result<()> {
+        assert!(parse_target("local")?.is_local());
+        assert_eq!(parse_target("ssh://deploy@example.com:2222")?.to_string(), "ssh://deploy@example.com:2222");
+        assert_eq!(parse_target("docker://web")?.to_string(), "docker://web");
+        assert!(parse_target("ftp://example.com").is_err());
+        assert!(parse_target("ssh://example.com:port").is_err());
+        assert!(parse_target("docker://").is_err());
+        assert!(parse_target("ssh://-oProxyCommand=sh").is_err());
+        assert!(parse_target("ssh://-l@example.com").is_err());
+        assert!(parse_target("docker://--privileged").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ssh_destination_after_options() {
+        let target = SshTarget::parse("deploy@example.com:2222").unwrap();
+        let command = RemoteShell::command(&target, "ls", &[]);
+        let args: Vec<_> = command.as_std().get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args, ["-p", "2222", "--", "deploy@example.com", "'ls'"]);
+    }
+
+    #[test]
+    fn test_shell_path_quoting() {
+        assert_eq!(shell_path("~/it's here.txt"), "\"$HOME\"/'it'\\''s here.txt'");
+        assert_eq!(shell_path("/etc/hosts"), "'/etc/hosts'");
+    }
+}
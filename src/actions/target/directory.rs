@@ -0,0 +1,96 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::error::ActionError;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use tokio::process::Command;
+use super::{ExecutionTarget, LocalTarget};
+
+// Executes the actions in a directory of the local machine, e.g. the directory of an eval case. The file actions may
+// only use the relative paths inside it, the commands start in it but are not confined to it
+#[derive(Debug, Clone)]
+pub struct DirectoryTarget {
+    root: PathBuf,
+}
+
+impl DirectoryTarget {
+    pub fn new(root: &Path) -> DirectoryTarget {
+        DirectoryTarget { root: root.to_path_buf() }
+    }
+
+    fn resolve(&self, path: &str) -> Result<String> {
+        let relative_path = Path::new(path);
+        let inside = !path.starts_with('~') && relative_path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            return Err(ActionError::PolicyDenied(format!("The path '{}' is not a relative path inside the directory {}", path, self.root.display())).into());
+        }
+        Ok(self.root.join(relative_path).to_string_lossy().to_string())
+    }
+}
+
+impl fmt::Display for DirectoryTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root.display())
+    }
+}
+
+#[async_trait]
+impl ExecutionTarget for DirectoryTarget {
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = LocalTarget.command(program, args);
+        command.current_dir(&self.root);
+        command
+    }
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        LocalTarget.read(&self.resolve(path)?).await
+    }
+
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)> {
+        LocalTarget.read_prefix(&self.resolve(path)?, max_bytes).await
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        LocalTarget.write_file(&self.resolve(path)?, content).await
+    }
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()> {
+        LocalTarget.append_file(&self.resolve(path)?, content).await
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        LocalTarget.remove_file(&self.resolve(path)?).await
+    }
+
+    async fn rename(&self, source: &str, destination: &str) -> Result<()> {
+        LocalTarget.rename(&self.resolve(source)?, &self.resolve(destination)?).await
+    }
+
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64> {
+        LocalTarget.copy(&self.resolve(source)?, &self.resolve(destination)?).await
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        LocalTarget.exists(&self.resolve(path)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::error_kind;
+
+    #[tokio::test]
+    async fn test_paths_inside_the_directory() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = DirectoryTarget::new(dir.path());
+
+        target.write_file("notes/hello.txt", "hello").await?;
+        assert_eq!(std::fs::read_to_string(dir.path().join("notes").join("hello.txt"))?, "hello");
+        assert_eq!(target.read_to_string("./notes/hello.txt").await?, "hello");
+        for path in ["/etc/hosts", "../outside.txt", "notes/../../outside.txt", "~/.bashrc"] {
+            assert_eq!(error_kind(&target.write_file(path, "x").await.unwrap_err()), "policy_denied");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use std::fmt;
+use tokio::process::Command;
+use super::RemoteShell;
+
+// Executes the actions in a running docker container
+#[derive(Debug, Clone)]
+pub struct DockerTarget {
+    pub container: String,
+}
+
+impl fmt::Display for DockerTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "docker://{}", self.container)
+    }
+}
+
+impl RemoteShell for DockerTarget {
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = Command::new("docker");
+        command.arg("exec").arg("-i").arg(&self.container).arg(program).args(args);
+        command
+    }
+
+    fn is_connection_failure(&self, exit_code: Option<i32>) -> bool {
+        matches!(exit_code, Some(125) | Some(126))
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::error::IoResultExt;
+use crate::fs::expand_home;
+use std::fmt;
+use std::path::Path;
+use tokio::process::Command;
+use super::ExecutionTarget;
+
+// Executes the actions on the machine cliff runs on
+#[derive(Debug, Clone, Copy)]
+pub struct LocalTarget;
+
+impl fmt::Display for LocalTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "local")
+    }
+}
+
+async fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await
+            .with_action_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ExecutionTarget for LocalTarget {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    }
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(tokio::fs::read(expand_home(path)?).await
+            .with_action_context(|| format!("Failed to read file: {}", path))?)
+    }
+
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)> {
+        use tokio::io::AsyncReadExt;
+        let expanded = expand_home(path)?;
+        let read = async {
+            let file = tokio::fs::File::open(&expanded).await?;
+            let size = file.metadata().await?.len();
+            let mut prefix = Vec::new();
+            file.take(max_bytes).read_to_end(&mut prefix).await?;
+            Ok((prefix, size))
+        };
+        Ok(read.await.with_action_context(|| format!("Failed to read file: {}", path))?)
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        let expanded = expand_home(path)?;
+        create_parent(&expanded).await?;
+        tokio::fs::write(&expanded, content).await
+            .with_action_context(|| format!("Failed to write file: {}", path))?;
+        Ok(())
+    }
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+        let mut file = tokio::fs::OpenOptions::new().append(true).open(expand_home(path)?).await
+            .with_action_context(|| format!("Failed to append content to file: {}", path))?;
+        file.write_all(content.as_bytes()).await
+            .with_action_context(|| format!("Failed to append content to file: {}", path))?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        match tokio::fs::remove_file(expand_home(path)?).await {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result.with_action_context(|| format!("Failed to delete file: {}", path))?),
+        }
+    }
+
+    async fn rename(&self, source: &str, destination: &str) -> Result<()> {
+        let expanded = expand_home(destination)?;
+        create_parent(&expanded).await?;
+        tokio::fs::rename(expand_home(source)?, &expanded).await
+            .with_action_context(|| format!("Failed to move file from '{}' to '{}'", source, destination))?;
+        Ok(())
+    }
+
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64> {
+        let expanded = expand_home(destination)?;
+        create_parent(&expanded).await?;
+        Ok(tokio::fs::copy(expand_home(source)?, &expanded).await
+            .with_action_context(|| format!("Failed to copy file from '{}' to '{}'", source, destination))?)
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(expand_home(path)?.exists())
+    }
+}
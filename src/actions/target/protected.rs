@@ -0,0 +1,113 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::fs::{ensure_writable, protected_paths};
+use std::fmt;
+use std::sync::Arc;
+use tokio::process::Command;
+use super::ExecutionTarget;
+
+// Wraps a target failing the writes, moves and deletions of the protected paths, whichever target executes them
+#[derive(Debug)]
+pub struct ProtectedTarget {
+    inner: Arc<dyn ExecutionTarget>,
+    patterns: Vec<String>,
+}
+
+impl ProtectedTarget {
+    // Protects the paths of the `protected_paths` of the config
+    pub fn new(inner: Arc<dyn ExecutionTarget>) -> ProtectedTarget {
+        ProtectedTarget::with_patterns(inner, protected_paths().to_vec())
+    }
+
+    pub fn with_patterns(inner: Arc<dyn ExecutionTarget>, patterns: Vec<String>) -> ProtectedTarget {
+        ProtectedTarget { inner, patterns }
+    }
+
+    fn check(&self, path: &str) -> Result<()> {
+        ensure_writable(path, self.inner.is_local(), &self.patterns)
+    }
+}
+
+impl fmt::Display for ProtectedTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inner)
+    }
+}
+
+#[async_trait]
+impl ExecutionTarget for ProtectedTarget {
+    fn is_local(&self) -> bool {
+        self.inner.is_local()
+    }
+
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        self.inner.command(program, args)
+    }
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.inner.read(path).await
+    }
+
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)> {
+        self.inner.read_prefix(path, max_bytes).await
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        self.check(path)?;
+        self.inner.write_file(path, content).await
+    }
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()> {
+        self.check(path)?;
+        self.inner.append_file(path, content).await
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        self.check(path)?;
+        self.inner.remove_file(path).await
+    }
+
+    async fn rename(&self, source: &str, destination: &str) -> Result<()> {
+        self.check(source)?;
+        self.check(destination)?;
+        self.inner.rename(source, destination).await
+    }
+
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64> {
+        self.check(destination)?;
+        self.inner.copy(source, destination).await
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    fn start_run(&self) {
+        self.inner.start_run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::target::parse_target;
+    use crate::error::error_kind;
+
+    #[tokio::test]
+    async fn test_protected_paths_on_remote_target() -> Result<()> {
+        let target = ProtectedTarget::with_patterns(parse_target("ssh://deploy@example.com")?, vec!["~/.ssh/**".to_string()]);
+
+        // Denied before ssh is started
+        let errors = [
+            target.write_file("~/.ssh/authorized_keys", "ssh-ed25519 AAAA").await.unwrap_err(),
+            target.append_file("~/.ssh/config", "Host *").await.unwrap_err(),
+            target.remove_file("~/.ssh/../.ssh/id_ed25519").await.unwrap_err(),
+            target.rename("~/notes.txt", "~/.ssh/config").await.unwrap_err(),
+            target.copy("~/notes.txt", "~/.ssh/known_hosts").await.unwrap_err(),
+        ];
+        for error in &errors {
+            assert_eq!(error_kind(error), "policy_denied");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use crate::config::QuotaConfig;
+use crate::error::ActionError;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::process::Command;
+use super::ExecutionTarget;
+
+// Wraps a target failing the writes and the deletions exceeding the quotas of a run
+#[derive(Debug)]
+pub struct QuotaTarget {
+    inner: Arc<dyn ExecutionTarget>,
+    quotas: QuotaConfig,
+    bytes_written: AtomicU64,
+    files_created: AtomicU64,
+    files_deleted: AtomicU64,
+}
+
+impl QuotaTarget {
+    pub fn new(inner: Arc<dyn ExecutionTarget>, quotas: QuotaConfig) -> QuotaTarget {
+        QuotaTarget { inner, quotas, bytes_written: AtomicU64::new(0), files_created: AtomicU64::new(0), files_deleted: AtomicU64::new(0) }
+    }
+
+    // Charges the amount if the total stays within the limit, the concurrent changes never exceed the limit together
+    fn charge(counter: &AtomicU64, amount: u64, limit: Option<u64>, what: &str) -> Result<()> {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used + amount).filter(|total| limit.is_none_or(|limit| *total <= limit)))
+            .map(|_| ())
+            .map_err(|_| ActionError::PolicyDenied(format!("The quota of {} {} per run would be exceeded", limit.unwrap_or_default(), what)).into())
+    }
+
+    fn refund(counter: &AtomicU64, amount: u64) {
+        let _ = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used.saturating_sub(amount)));
+    }
+
+    // Charges the file created at `path` when it does not exist yet and the bytes written to it, the charges are refunded
+    // when the change fails
+    async fn charge_write<T>(&self, path: &str, bytes: u64, change: impl Future<Output = Result<T>>) -> Result<T> {
+        let created = u64::from(!self.inner.exists(path).await?);
+        Self::charge(&self.files_created, created, self.quotas.max_files_created, "files created")?;
+        if let Err(e) = Self::charge(&self.bytes_written, bytes, self.quotas.max_bytes_written, "bytes written") {
+            Self::refund(&self.files_created, created);
+            return Err(e);
+        }
+        let result = change.await;
+        if result.is_err() {
+            Self::refund(&self.files_created, created);
+            Self::refund(&self.bytes_written, bytes);
+        }
+        result
+    }
+}
+
+impl fmt::Display for QuotaTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inner)
+    }
+}
+
+#[async_trait]
+impl ExecutionTarget for QuotaTarget {
+    fn is_local(&self) -> bool {
+        self.inner.is_local()
+    }
+
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        self.inner.command(program, args)
+    }
+
+    async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.inner.read(path).await
+    }
+
+    async fn read_prefix(&self, path: &str, max_bytes: u64) -> Result<(Vec<u8>, u64)> {
+        self.inner.read_prefix(path, max_bytes).await
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        self.charge_write(path, content.len() as u64, self.inner.write_file(path, content)).await
+    }
+
+    async fn append_file(&self, path: &str, content: &str) -> Result<()> {
+        self.charge_write(path, content.len() as u64, self.inner.append_file(path, content)).await
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        Self::charge(&self.files_deleted, 1, self.quotas.max_files_deleted, "files deleted")?;
+        let result = self.inner.remove_file(path).await;
+        if result.is_err() {
+            Self::refund(&self.files_deleted, 1);
+        }
+        result
+    }
+
+    async fn rename(&self, source: &str, destination: &str) -> Result<()> {
+        self.inner.rename(source, destination).await
+    }
+
+    async fn copy(&self, source: &str, destination: &str) -> Result<u64> {
+        // The size of the source is charged before the copy, only the size is read
+        let (_, size) = self.inner.read_prefix(source, 0).await?;
+        self.charge_write(destination, size, self.inner.copy(source, destination)).await
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    fn start_run(&self) {
+        self.bytes_written.store(0, Ordering::SeqCst);
+        self.files_created.store(0, Ordering::SeqCst);
+        self.files_deleted.store(0, Ordering::SeqCst);
+        self.inner.start_run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::target::LocalTarget;
+    use crate::error::error_kind;
+
+    fn quota_target(quotas: QuotaConfig) -> QuotaTarget {
+        QuotaTarget::new(Arc::new(LocalTarget), quotas)
+    }
+
+    #[tokio::test]
+    async fn test_bytes_written_quota() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let target = quota_target(QuotaConfig { max_bytes_written: Some(10), ..QuotaConfig::default() });
+
+        target.write_file(&path("a.txt"), "12345").await?;
+        target.append_file(&path("a.txt"), "678").await?;
+        assert_eq!(error_kind(&target.write_file(&path("b.txt"), "123").await.unwrap_err()), "policy_denied");
+        assert!(!dir.path().join("b.txt").exists());
+
+        // The copied bytes are charged too
+        std::fs::write(dir.path().join("large.txt"), "x".repeat(100))?;
+        assert_eq!(error_kind(&target.copy(&path("large.txt"), &path("copy.txt")).await.unwrap_err()), "policy_denied");
+        assert!(!dir.path().join("copy.txt").exists());
+
+        // A failed write is not charged
+        assert!(target.write_file(&dir.path().join("large.txt").join("c.txt").to_string_lossy(), "12").await.is_err());
+        target.write_file(&path("c.txt"), "12").await?;
+
+        target.start_run();
+        target.write_file(&path("d.txt"), "1234567890").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_files_created_quota() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let target = quota_target(QuotaConfig { max_files_created: Some(2), ..QuotaConfig::default() });
+
+        target.write_file(&path("a.txt"), "a").await?;
+        target.copy(&path("a.txt"), &path("b.txt")).await?;
+        // Overwriting an existing file creates none
+        target.write_file(&path("a.txt"), "changed").await?;
+        assert_eq!(error_kind(&target.write_file(&path("c.txt"), "c").await.unwrap_err()), "policy_denied");
+        assert_eq!(error_kind(&target.copy(&path("a.txt"), &path("d.txt")).await.unwrap_err()), "policy_denied");
+        assert!(!dir.path().join("c.txt").exists() && !dir.path().join("d.txt").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_files_deleted_quota() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        for name in ["a.txt", "b.txt"] {
+            std::fs::write(dir.path().join(name), name)?;
+        }
+        let target = quota_target(QuotaConfig { max_files_deleted: Some(1), ..QuotaConfig::default() });
+
+        // A failed deletion is not charged
+        std::fs::create_dir(dir.path().join("dir"))?;
+        assert!(target.remove_file(&path("dir")).await.is_err());
+        target.remove_file(&path("a.txt")).await?;
+        assert_eq!(error_kind(&target.remove_file(&path("b.txt")).await.unwrap_err()), "policy_denied");
+        assert!(dir.path().join("b.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_charges_stay_within_the_limit() {
+        let counter = AtomicU64::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let _ = QuotaTarget::charge(&counter, 1, Some(500), "bytes written");
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.load(Ordering::SeqCst), 500);
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use tokio::process::Command;
+use super::{quote, RemoteShell};
+
+// Executes the actions on a remote host over ssh
+#[derive(Debug, Clone)]
+pub struct SshTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SshTarget {
+    pub fn parse(address: &str) -> Option<SshTarget> {
+        let (user, host_port) = match address.split_once('@') {
+            Some((user, rest)) => (Some(user.to_string()), rest),
+            None => (None, address),
+        };
+        let (host, port) = match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port.parse().ok()?)),
+            None => (host_port, None),
+        };
+        // A leading `-` would be read by ssh as an option, e.g. `-oProxyCommand=...` running a local command
+        if host.is_empty() || host.starts_with('-') || user.as_deref().is_some_and(|user| user.is_empty() || user.starts_with('-')) {
+            return None;
+        }
+        Some(SshTarget { user, host: host.to_string(), port })
+    }
+}
+
+impl fmt::Display for SshTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ssh://")?;
+        if let Some(user) = &self.user {
+            write!(f, "{}@", user)?;
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteShell for SshTarget {
+    fn command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = Command::new("ssh");
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        let destination = match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        };
+        let remote_command = std::iter::once(quote(program)).chain(args.iter().map(|arg| quote(arg))).collect::<Vec<_>>().join(" ");
+        command.arg("--").arg(destination).arg(remote_command);
+        command
+    }
+
+    fn is_connection_failure(&self, exit_code: Option<i32>) -> bool {
+        exit_code == Some(255)
+    }
+}
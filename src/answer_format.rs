@@ -0,0 +1,104 @@
+use clap::ValueEnum;
+use crate::json;
+use std::fmt;
+
+// Markers of Markdown formatting at the start of a line, not expected in plain text answers
+const MARKDOWN_LINE_STARTS: [&str; 5] = ["#", "```", "* ", "- ", "> "];
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum AnswerFormat {
+    /// Markdown formatted text
+    Markdown,
+    /// Text without any Markdown formatting
+    Plain,
+    /// Valid JSON only
+    Json,
+    /// Only the code, the contents of the fenced code blocks are output
+    Code,
+}
+
+impl fmt::Display for AnswerFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AnswerFormat::Markdown => "Markdown",
+            AnswerFormat::Plain => "plain text",
+            AnswerFormat::Json => "valid JSON",
+            AnswerFormat::Code => "code in a fenced code block",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl AnswerFormat {
+    /// Instruction appended to the question
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            AnswerFormat::Markdown => "Format the answer as Markdown.",
+            AnswerFormat::Plain => "Answer in plain text WITHOUT any Markdown: no headings, bold or italic text, bullet lists, quotes or code fences.",
+            AnswerFormat::Json => "Answer ONLY with valid JSON, without any explanations or code fences.",
+            AnswerFormat::Code => "Answer ONLY with the code in a single fenced code block, without any explanations.",
+        }
+    }
+
+    /// The answer in the format, e.g. only the JSON or the code, or the reason why the answer does not conform to it
+    pub fn conform(&self, answer: &str) -> Result<String, String> {
+        let answer = trim_boilerplate(answer);
+        match self {
+            AnswerFormat::Markdown => Ok(answer),
+            AnswerFormat::Plain => match answer.lines().find(|line| MARKDOWN_LINE_STARTS.iter().any(|start| line.trim_start().starts_with(start)) || line.contains("**")) {
+                Some(line) => Err(format!("the line \"{}\" is formatted with Markdown", line.trim())),
+                None => Ok(answer),
+            },
+            AnswerFormat::Json => {
+                let json = json::strip_json_fence(&answer).trim();
+                serde_json::from_str::<serde_json::Value>(json)
+                    .map(|_| json.to_string())
+                    .map_err(|e| format!("the answer is not valid JSON: {}", e))
+            }
+            AnswerFormat::Code => extract_code(&answer).ok_or_else(|| "the answer contains no fenced code block".to_string()),
+        }
+    }
+}
+
+/// Removes the reasoning of the thinking models and the surrounding whitespace
+pub fn trim_boilerplate(answer: &str) -> String {
+    let mut answer = answer.to_string();
+    while let Some(start) = answer.find("<think>") {
+        match answer[start..].find("</think>") {
+            Some(end) => answer.replace_range(start..start + end + "</think>".len(), ""),
+            None => break,
+        }
+    }
+    answer.trim().to_string()
+}
+
+/// Contents of all the fenced code blocks of the answer, None when there are none
+pub fn extract_code(answer: &str) -> Option<String> {
+    let mut blocks = Vec::new();
+    let mut current_block: Option<Vec<&str>> = None;
+    for line in answer.lines() {
+        let is_fence = line.trim_start().starts_with("```");
+        match (current_block.as_mut(), is_fence) {
+            (None, true) => current_block = Some(Vec::new()),
+            (Some(_), true) => blocks.push(current_block.take().unwrap_or_default().join("\n")),
+            (Some(block), false) => block.push(line),
+            (None, false) => {}
+        }
+    }
+    if blocks.is_empty() { None } else { Some(blocks.join("\n\n")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conform() {
+        assert_eq!(AnswerFormat::Json.conform("<think>easy</think>```json\n{\"a\": 1}\n```"), Ok("{\"a\": 1}".to_string()));
+        assert!(AnswerFormat::Json.conform("Here it is: {\"a\": 1}").is_err());
+        assert_eq!(AnswerFormat::Code.conform("Sure:\n```sh\nls -la\n```"), Ok("ls -la".to_string()));
+        assert!(AnswerFormat::Code.conform("Use ls -la").is_err());
+        assert_eq!(AnswerFormat::Plain.conform("C# is a language.\nIt has 2 * 3 = 6 operators."), Ok("C# is a language.\nIt has 2 * 3 = 6 operators.".to_string()));
+        assert_eq!(AnswerFormat::Plain.conform("## Summary\nC# is a language."), Err("the line \"## Summary\" is formatted with Markdown".to_string()));
+    }
+}
@@ -0,0 +1,1158 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use crate::config::{Model, ModelKind, StreamFormat};
+use crate::conversation::{ChatMessage, Role};
+use crate::error::{ActionError, LlmError};
+use crate::llm::{apply_capabilities, render_request, response_excerpt, validate_request_format};
+use crate::proxy;
+use crate::unix_socket;
+use jsonpath_lib::select as jsonpath_select;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+// Version of the Anthropic messages API the requests are made in
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// The Anthropic API requires a limit of the answer
+const ANTHROPIC_DEFAULT_MAX_TOKENS: usize = 4096;
+// Line separating the answers in the answers file of a mock model
+const MOCK_ANSWER_SEPARATOR: &str = "---";
+// Paths of the endpoints appended to the base URL of an OpenAI-compatible API
+const OPENAI_CHAT_PATH: &str = "/chat/completions";
+pub(crate) const OPENAI_EMBEDDINGS_PATH: &str = "/embeddings";
+// Tool the OpenAI-compatible models without the JSON mode are made to call with a JSON answer
+const ANSWER_TOOL: &str = "answer";
+
+// Tokens reported by the APIs in this process
+static USAGE: Mutex<TokenUsage> = Mutex::new(TokenUsage { prompt_tokens: 0, cached_prompt_tokens: 0, completion_tokens: 0 });
+
+/// Numbers of the tokens of the requests and the answers as reported by the APIs
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    // Tokens of the prompts read from the prompt caches of the APIs, included in the prompt tokens
+    #[serde(default)]
+    pub cached_prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Tokens reported since the `earlier` usage
+    pub fn since(&self, earlier: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            cached_prompt_tokens: self.cached_prompt_tokens.saturating_sub(earlier.cached_prompt_tokens),
+            completion_tokens: self.completion_tokens.saturating_sub(earlier.completion_tokens),
+        }
+    }
+}
+
+/// Tokens reported by the APIs in this process so far
+pub fn usage() -> TokenUsage {
+    *USAGE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn record_usage(usage: Option<TokenUsage>) {
+    let Some(usage) = usage else {
+        return;
+    };
+    let mut total = USAGE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    total.prompt_tokens += usage.prompt_tokens;
+    total.cached_prompt_tokens += usage.cached_prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+}
+
+// Usage of an OpenAI-compatible response, e.g. `{"prompt_tokens": 12, "completion_tokens": 3}`, None when the API does
+// not report it
+fn openai_usage(usage: &Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        prompt_tokens: usage["prompt_tokens"].as_u64()?,
+        cached_prompt_tokens: usage["prompt_tokens_details"]["cached_tokens"].as_u64().unwrap_or_default(),
+        completion_tokens: usage["completion_tokens"].as_u64()?,
+    })
+}
+
+// Usage of an Anthropic response, its input tokens do not include the ones read from or written to the cache
+fn anthropic_usage(usage: &Value) -> Option<TokenUsage> {
+    let cache_read = usage["cache_read_input_tokens"].as_u64().unwrap_or_default();
+    let cache_creation = usage["cache_creation_input_tokens"].as_u64().unwrap_or_default();
+    Some(TokenUsage {
+        prompt_tokens: usage["input_tokens"].as_u64()? + cache_read + cache_creation,
+        cached_prompt_tokens: cache_read,
+        completion_tokens: usage["output_tokens"].as_u64()?,
+    })
+}
+
+// Usage of a Gemini response, its prompt tokens include the cached ones
+fn gemini_usage(usage: &Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        prompt_tokens: usage["promptTokenCount"].as_u64()?,
+        cached_prompt_tokens: usage["cachedContentTokenCount"].as_u64().unwrap_or_default(),
+        completion_tokens: usage["candidatesTokenCount"].as_u64().unwrap_or_default(),
+    })
+}
+
+/// Tool the model is made to call, its arguments are the answer, e.g. the plan matching the JSON schema of the plans
+pub struct Tool {
+    pub name: &'static str,
+    pub description: &'static str,
+    // JSON schema of the arguments
+    pub parameters: Value,
+}
+
+// Tool of the JSON answers of the models without the JSON mode, any JSON object is accepted
+fn answer_tool() -> Tool {
+    Tool {
+        name: ANSWER_TOOL,
+        description: "Give the answer, a JSON object in the format requested by the user",
+        parameters: json!({ "type": "object", "additionalProperties": true }),
+    }
+}
+
+/// Whether the answers of the model can be the arguments of a tool call, the other models are asked for JSON answers
+pub fn calls_tools(model: &Model) -> bool {
+    matches!(model.kind, ModelKind::OpenAi | ModelKind::Anthropic) && model.capabilities.supports_tools
+}
+
+/// API of a provider of LLMs, the requests to the model of a configured kind are made by its backend
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Answer of the model to the messages, with `json_answer` the API is asked for a JSON answer when it can be
+    async fn chat(&self, messages: &[ChatMessage], json_answer: bool, client: &Client) -> Result<String>;
+
+    /// Answer of the model to the messages, its parts are passed to `on_text` as they arrive when the API streams them,
+    /// otherwise the whole answer at once
+    async fn chat_streaming(&self, messages: &[ChatMessage], on_text: &mut (dyn for<'t> FnMut(&'t str) + Send), client: &Client) -> Result<String> {
+        let answer = self.chat(messages, false, client).await?;
+        on_text(&answer);
+        Ok(answer)
+    }
+
+    /// Arguments of the call of the tool the model is made to make, as a JSON object. The models which cannot call tools
+    /// give a JSON answer as `chat` does
+    async fn chat_with_tool(&self, messages: &[ChatMessage], _tool: &Tool, client: &Client) -> Result<String> {
+        self.chat(messages, true, client).await
+    }
+
+    /// Answer of the model to the messages continuing the server-side conversation with the id, which is replaced by the
+    /// id in the response. The models without a conversation param are sent the messages alone and keep no id
+    async fn chat_in_conversation(&self, messages: &[ChatMessage], conversation_id: &mut Option<String>, client: &Client) -> Result<String> {
+        *conversation_id = None;
+        self.chat(messages, false, client).await
+    }
+}
+
+/// Backend of the kind of the model
+pub fn of(model: &Model) -> Box<dyn LlmBackend + '_> {
+    match model.kind {
+        ModelKind::Template => Box::new(TemplateBackend { model }),
+        ModelKind::OpenAi => Box::new(OpenAiBackend { model }),
+        ModelKind::Anthropic => Box::new(AnthropicBackend { model }),
+        ModelKind::Gemini => Box::new(GeminiBackend { model }),
+        ModelKind::Mock => Box::new(MockBackend { model }),
+    }
+}
+
+// The request rendered from the request format, the answer selected with the response JSON path
+struct TemplateBackend<'a> {
+    model: &'a Model,
+}
+
+impl TemplateBackend<'_> {
+    // Answer and response of the API, the request continues the server-side conversation with the id when there is one
+    async fn exchange(&self, messages: &[ChatMessage], json_answer: bool, conversation_id: Option<&str>, client: &Client) -> Result<(String, Value)> {
+        validate_request_format(self.model)?;
+        let request_body = apply_capabilities(render_request(messages, self.model)?, self.model, json_answer);
+        let response = send(self.model, continue_conversation(request_body, self.model, conversation_id)?, client).await?;
+        Ok((self.answer(&response)?, response))
+    }
+
+    fn answer(&self, response: &Value) -> Result<String> {
+        match select(response, &self.model.response_json_path, self.model)? {
+            Value::String(answer) => Ok(answer),
+            other => anyhow::bail!(
+                "Expected a string at JSONPath '{}', but found: {:?}",
+                &self.model.response_json_path,
+                other
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for TemplateBackend<'_> {
+    async fn chat(&self, messages: &[ChatMessage], json_answer: bool, client: &Client) -> Result<String> {
+        Ok(self.exchange(messages, json_answer, None, client).await?.0)
+    }
+
+    async fn chat_in_conversation(&self, messages: &[ChatMessage], conversation_id: &mut Option<String>, client: &Client) -> Result<String> {
+        let (answer, response) = self.exchange(messages, false, conversation_id.as_deref(), client).await?;
+        *conversation_id = conversation_id_in(&response, self.model);
+        Ok(answer)
+    }
+
+    // Streamed only when the model is configured with the stream format and the JSONPath of the parts of the answer
+    async fn chat_streaming(&self, messages: &[ChatMessage], on_text: &mut (dyn for<'t> FnMut(&'t str) + Send), client: &Client) -> Result<String> {
+        let (Some(format), Some(path)) = (self.model.stream_format, &self.model.stream_json_path) else {
+            let answer = self.chat(messages, false, client).await?;
+            on_text(&answer);
+            return Ok(answer);
+        };
+        validate_request_format(self.model)?;
+        let request_body = apply_capabilities(render_request(messages, self.model)?, self.model, false);
+        let response = post(self.model, &self.model.api_url, stream_request(request_body, self.model)?, client).await?;
+        // The servers ignoring the streaming answer at once
+        if format == StreamFormat::Sse && !is_event_stream(&response) {
+            let answer = self.answer(&parse_response(response).await?)?;
+            on_text(&answer);
+            return Ok(answer);
+        }
+        let mut answer = String::new();
+        let mut on_event = |event: &Value| {
+            match jsonpath_select(event, path).ok().and_then(|values| values.first().cloned()) {
+                Some(Value::String(text)) if !text.is_empty() => {
+                    on_text(text);
+                    answer.push_str(text);
+                }
+                _ => {}
+            }
+        };
+        match format {
+            StreamFormat::Sse => read_events(response, self.model, &mut on_event).await?,
+            StreamFormat::Ndjson => read_json_lines(response, self.model, &mut on_event).await?,
+        }
+        Ok(answer)
+    }
+}
+
+// The chat completions API, the API URL is either the URL of the endpoint or the base URL of the API, e.g.
+// `http://localhost:8000/v1`
+struct OpenAiBackend<'a> {
+    model: &'a Model,
+}
+
+impl OpenAiBackend<'_> {
+    fn url(&self) -> String {
+        openai_url(&self.model.api_url, OPENAI_CHAT_PATH)
+    }
+
+    // JSON answers use the JSON mode, or a forced call of the answer tool when only tools are supported
+    fn request(&self, messages: &[ChatMessage], json_answer: bool) -> Value {
+        let capabilities = &self.model.capabilities;
+        let mut request = json!({ "model": self.model.model_identifier, "messages": messages });
+        if json_answer && capabilities.supports_json_mode {
+            request["response_format"] = json!({ "type": "json_object" });
+        } else if json_answer && capabilities.supports_tools {
+            Self::force_tool(&mut request, &answer_tool());
+        }
+        if let Some(max_output_tokens) = capabilities.max_output_tokens {
+            request["max_tokens"] = json!(max_output_tokens);
+        }
+        request
+    }
+
+    fn force_tool(request: &mut Value, tool: &Tool) {
+        request["tools"] = json!([{
+            "type": "function",
+            "function": { "name": tool.name, "description": tool.description, "parameters": tool.parameters },
+        }]);
+        request["tool_choice"] = json!({ "type": "function", "function": { "name": tool.name } });
+    }
+
+    fn answer(&self, response: &Value) -> Result<String> {
+        record_usage(openai_usage(&response["usage"]));
+        let message = &response["choices"][0]["message"];
+        if let Some(arguments) = message["tool_calls"][0]["function"]["arguments"].as_str() {
+            return Ok(arguments.to_string());
+        }
+        text_at(response, "$.choices[0].message.content", self.model)
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiBackend<'_> {
+    async fn chat(&self, messages: &[ChatMessage], json_answer: bool, client: &Client) -> Result<String> {
+        let response = send_to(self.model, &self.url(), self.request(messages, json_answer).to_string(), client).await?;
+        self.answer(&response)
+    }
+
+    async fn chat_with_tool(&self, messages: &[ChatMessage], tool: &Tool, client: &Client) -> Result<String> {
+        if !self.model.capabilities.supports_tools {
+            return self.chat(messages, true, client).await;
+        }
+        let mut request = self.request(messages, false);
+        Self::force_tool(&mut request, tool);
+        let response = send_to(self.model, &self.url(), request.to_string(), client).await?;
+        self.answer(&response)
+    }
+
+    async fn chat_in_conversation(&self, messages: &[ChatMessage], conversation_id: &mut Option<String>, client: &Client) -> Result<String> {
+        let request_body = continue_conversation(self.request(messages, false).to_string(), self.model, conversation_id.as_deref())?;
+        let response = send_to(self.model, &self.url(), request_body, client).await?;
+        *conversation_id = conversation_id_in(&response, self.model);
+        self.answer(&response)
+    }
+
+    async fn chat_streaming(&self, messages: &[ChatMessage], on_text: &mut (dyn for<'t> FnMut(&'t str) + Send), client: &Client) -> Result<String> {
+        let mut request = self.request(messages, false);
+        request["stream"] = json!(true);
+        request["stream_options"] = json!({ "include_usage": true });
+        let response = post(self.model, &self.url(), request.to_string(), client).await?;
+        // The servers ignoring the streaming answer at once
+        if !is_event_stream(&response) {
+            let answer = self.answer(&parse_response(response).await?)?;
+            on_text(&answer);
+            return Ok(answer);
+        }
+        let mut answer = String::new();
+        read_events(response, self.model, &mut |event| {
+            record_usage(openai_usage(&event["usage"]));
+            if let Some(text) = event["choices"][0]["delta"]["content"].as_str() {
+                on_text(text);
+                answer.push_str(text);
+            }
+        }).await?;
+        Ok(answer)
+    }
+}
+
+// The system messages are sent apart from the conversation, the images as base64 content parts
+struct AnthropicBackend<'a> {
+    model: &'a Model,
+}
+
+impl AnthropicBackend<'_> {
+    // The cacheable messages end with a cache breakpoint, the prefix of the request up to it is cached by the API
+    fn content(message: &ChatMessage) -> Value {
+        if message.images.is_empty() && !message.cacheable {
+            return json!(message.content);
+        }
+        let images = message.images.iter().filter_map(|url| {
+            let (media_type, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+            Some(json!({ "type": "image", "source": { "type": "base64", "media_type": media_type, "data": data } }))
+        });
+        Value::Array(images.chain(std::iter::once(Self::text_block(message))).collect())
+    }
+
+    fn text_block(message: &ChatMessage) -> Value {
+        let mut text = json!({ "type": "text", "text": message.content });
+        if message.cacheable {
+            text["cache_control"] = json!({ "type": "ephemeral" });
+        }
+        text
+    }
+
+    // The API has no JSON mode, JSON answers are a forced use of the answer tool when the model supports tools
+    fn request(&self, messages: &[ChatMessage], json_answer: bool) -> Value {
+        let system: Vec<&ChatMessage> = messages.iter().filter(|message| message.role == Role::System).collect();
+        let conversation: Vec<Value> = messages
+            .iter()
+            .filter(|message| message.role != Role::System)
+            .map(|message| json!({ "role": message.role, "content": Self::content(message) }))
+            .collect();
+        let mut request = json!({
+            "model": self.model.model_identifier,
+            "max_tokens": self.model.capabilities.max_output_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+            "messages": conversation,
+        });
+        if system.iter().any(|message| message.cacheable) {
+            request["system"] = system.iter().map(|message| Self::text_block(message)).collect();
+        } else if !system.is_empty() {
+            request["system"] = json!(system.iter().map(|message| message.content.as_str()).collect::<Vec<_>>().join("\n\n"));
+        }
+        if json_answer && self.model.capabilities.supports_tools {
+            Self::force_tool(&mut request, &answer_tool());
+        }
+        request
+    }
+
+    fn force_tool(request: &mut Value, tool: &Tool) {
+        request["tools"] = json!([{ "name": tool.name, "description": tool.description, "input_schema": tool.parameters }]);
+        request["tool_choice"] = json!({ "type": "tool", "name": tool.name });
+    }
+
+    // The input of the tool when the model used it, otherwise the text blocks of the content
+    fn answer(&self, response: &Value, tool: &str) -> Result<String> {
+        record_usage(anthropic_usage(&response["usage"]));
+        let blocks = response["content"].as_array().map(Vec::as_slice).unwrap_or_default();
+        if let Some(tool_use) = blocks.iter().find(|block| block["type"] == "tool_use" && block["name"] == tool) {
+            return Ok(tool_use["input"].to_string());
+        }
+        let texts: Vec<&str> = blocks.iter().filter(|block| block["type"] == "text").filter_map(|block| block["text"].as_str()).collect();
+        if texts.is_empty() {
+            return Err(LlmError::ValueNotFound {
+                model: self.model.name.clone(),
+                path: "$.content[*].text".to_string(),
+                detail: response_excerpt(&response.to_string()),
+            }.into());
+        }
+        Ok(texts.concat())
+    }
+}
+
+#[async_trait]
+impl LlmBackend for AnthropicBackend<'_> {
+    async fn chat(&self, messages: &[ChatMessage], json_answer: bool, client: &Client) -> Result<String> {
+        let response = send(self.model, self.request(messages, json_answer).to_string(), client).await?;
+        self.answer(&response, ANSWER_TOOL)
+    }
+
+    async fn chat_with_tool(&self, messages: &[ChatMessage], tool: &Tool, client: &Client) -> Result<String> {
+        if !self.model.capabilities.supports_tools {
+            return self.chat(messages, true, client).await;
+        }
+        let mut request = self.request(messages, false);
+        Self::force_tool(&mut request, tool);
+        let response = send(self.model, request.to_string(), client).await?;
+        self.answer(&response, tool.name)
+    }
+
+    // The tokens of the request arrive in the `message_start` event, the ones of the answer in the `message_delta` events
+    async fn chat_streaming(&self, messages: &[ChatMessage], on_text: &mut (dyn for<'t> FnMut(&'t str) + Send), client: &Client) -> Result<String> {
+        let mut request = self.request(messages, false);
+        request["stream"] = json!(true);
+        let response = post(self.model, &self.model.api_url, request.to_string(), client).await?;
+        if !is_event_stream(&response) {
+            let answer = self.answer(&parse_response(response).await?, ANSWER_TOOL)?;
+            on_text(&answer);
+            return Ok(answer);
+        }
+        let mut answer = String::new();
+        let mut usage = json!({});
+        read_events(response, self.model, &mut |event| match event["type"].as_str() {
+            Some("message_start") => usage = event["message"]["usage"].clone(),
+            Some("message_delta") => usage["output_tokens"] = event["usage"]["output_tokens"].clone(),
+            Some("content_block_delta") => if let Some(text) = event["delta"]["text"].as_str() {
+                on_text(text);
+                answer.push_str(text);
+            },
+            _ => {}
+        }).await?;
+        record_usage(anthropic_usage(&usage));
+        Ok(answer)
+    }
+}
+
+// The generateContent API of Gemini, the API URL is the URL of the model, e.g.
+// `https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash`, the system messages are sent as the
+// system instruction and the images as inline data parts
+struct GeminiBackend<'a> {
+    model: &'a Model,
+}
+
+impl GeminiBackend<'_> {
+    fn url(&self, method: &str) -> String {
+        gemini_url(&self.model.api_url, method)
+    }
+
+    fn parts(message: &ChatMessage) -> Vec<Value> {
+        let images = message.images.iter().filter_map(|url| {
+            let (mime_type, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+            Some(json!({ "inline_data": { "mime_type": mime_type, "data": data } }))
+        });
+        images.chain(std::iter::once(json!({ "text": message.content }))).collect()
+    }
+
+    fn request(&self, messages: &[ChatMessage], json_answer: bool) -> Value {
+        let system: Vec<&str> = messages.iter().filter(|message| message.role == Role::System).map(|message| message.content.as_str()).collect();
+        let contents: Vec<Value> = messages
+            .iter()
+            .filter(|message| message.role != Role::System)
+            .map(|message| json!({
+                "role": if message.role == Role::Assistant { "model" } else { "user" },
+                "parts": Self::parts(message),
+            }))
+            .collect();
+        let mut request = json!({ "contents": contents });
+        if !system.is_empty() {
+            request["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+        }
+        if json_answer && self.model.capabilities.supports_json_mode {
+            request["generationConfig"]["responseMimeType"] = json!("application/json");
+        }
+        if let Some(max_output_tokens) = self.model.capabilities.max_output_tokens {
+            request["generationConfig"]["maxOutputTokens"] = json!(max_output_tokens);
+        }
+        request
+    }
+
+    // The text parts of the first candidate, an empty text in the streamed chunks without one
+    fn text(response: &Value) -> String {
+        let parts = response["candidates"][0]["content"]["parts"].as_array().map(Vec::as_slice).unwrap_or_default();
+        parts.iter().filter_map(|part| part["text"].as_str()).collect()
+    }
+
+    fn answer(&self, response: &Value) -> Result<String> {
+        record_usage(gemini_usage(&response["usageMetadata"]));
+        if response["candidates"][0]["content"]["parts"].is_array() {
+            return Ok(Self::text(response));
+        }
+        Err(LlmError::ValueNotFound {
+            model: self.model.name.clone(),
+            path: "$.candidates[0].content.parts[*].text".to_string(),
+            detail: response_excerpt(&response.to_string()),
+        }.into())
+    }
+}
+
+#[async_trait]
+impl LlmBackend for GeminiBackend<'_> {
+    async fn chat(&self, messages: &[ChatMessage], json_answer: bool, client: &Client) -> Result<String> {
+        let response = send_to(self.model, &self.url("generateContent"), self.request(messages, json_answer).to_string(), client).await?;
+        self.answer(&response)
+    }
+
+    // Every streamed chunk has the usage so far, the last one the usage of the whole answer
+    async fn chat_streaming(&self, messages: &[ChatMessage], on_text: &mut (dyn for<'t> FnMut(&'t str) + Send), client: &Client) -> Result<String> {
+        let url = self.url("streamGenerateContent?alt=sse");
+        let response = post(self.model, &url, self.request(messages, false).to_string(), client).await?;
+        if !is_event_stream(&response) {
+            let answer = self.answer(&parse_response(response).await?)?;
+            on_text(&answer);
+            return Ok(answer);
+        }
+        let mut answer = String::new();
+        let mut usage = Value::Null;
+        read_events(response, self.model, &mut |event| {
+            usage = event["usageMetadata"].clone();
+            let text = Self::text(event);
+            if !text.is_empty() {
+                on_text(&text);
+                answer.push_str(&text);
+            }
+        }).await?;
+        record_usage(gemini_usage(&usage));
+        Ok(answer)
+    }
+}
+
+/// URL of the method of the Gemini model, e.g. `generateContent`, the API URL may already end with a method
+pub(crate) fn gemini_url(api_url: &str, method: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    let model_url = api_url.rsplit_once(':').filter(|(_, api_method)| !api_method.contains('/')).map_or(api_url, |(model_url, _)| model_url);
+    format!("{}:{}", model_url, method)
+}
+
+// Answers with the answers of the file at the API URL in turn, repeating the last one, or without a file echoes the
+// last message
+struct MockBackend<'a> {
+    model: &'a Model,
+}
+
+#[async_trait]
+impl LlmBackend for MockBackend<'_> {
+    async fn chat(&self, messages: &[ChatMessage], _json_answer: bool, _client: &Client) -> Result<String> {
+        static TURNS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+        if self.model.api_url.is_empty() {
+            return Ok(messages.last().map(|message| message.content.clone()).unwrap_or_default());
+        }
+        let content = tokio::fs::read_to_string(&self.model.api_url).await
+            .with_context(|| format!("Failed to read the answers of the mock model '{}': {}", self.model.name, self.model.api_url))?;
+        let answers: Vec<String> = content
+            .split(&format!("\n{}\n", MOCK_ANSWER_SEPARATOR))
+            .map(|answer| answer.trim().to_string())
+            .collect();
+        let mut turns = TURNS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let turn = turns.get_or_insert_with(HashMap::new).entry(self.model.name.clone()).or_default();
+        let answer = answers[(*turn).min(answers.len() - 1)].clone();
+        *turn += 1;
+        Ok(answer)
+    }
+}
+
+// Authenticates with the API key in the configured header, by default in the header of the provider
+fn authorize(request_builder: RequestBuilder, model: &Model) -> RequestBuilder {
+    let Some(api_key) = &model.api_key else {
+        return request_builder;
+    };
+    match (&model.api_key_header, model.kind) {
+        (Some(api_key_header), _) => match api_key_header.split_once(":") {
+            Some((header_name, header_value)) => request_builder.header(header_name.trim(), header_value.replace("{{api_key}}", api_key)),
+            None => {
+                eprintln!("Warning: Invalid api_key_header format. Expected 'Header-Name: Header-Value': '{}'", api_key_header);
+                request_builder.bearer_auth(api_key)
+            }
+        },
+        (None, ModelKind::Anthropic) => request_builder.header("x-api-key", api_key),
+        (None, ModelKind::Gemini) => request_builder.query(&[("key", api_key)]),
+        (None, _) => request_builder.bearer_auth(api_key),
+    }
+}
+
+// Request body with the id of the server-side conversation in the conversation param of the model, the body itself
+// without an id or a param
+fn continue_conversation(request_body: String, model: &Model, conversation_id: Option<&str>) -> Result<String> {
+    let (Some(param), Some(conversation_id)) = (&model.conversation_param, conversation_id) else {
+        return Ok(request_body);
+    };
+    match serde_json::from_str::<Value>(&request_body) {
+        Ok(Value::Object(mut request)) => {
+            request.insert(param.clone(), json!(conversation_id));
+            Ok(Value::Object(request).to_string())
+        }
+        _ => Err(ActionError::InvalidInput(format!(
+            "The request of the model '{}' is not a JSON object, the conversation id cannot be sent in '{}'", model.name, param
+        )).into()),
+    }
+}
+
+// Request body asking the API to stream the answer
+fn stream_request(request_body: String, model: &Model) -> Result<String> {
+    match serde_json::from_str::<Value>(&request_body) {
+        Ok(Value::Object(mut request)) => {
+            request.insert("stream".to_string(), json!(true));
+            Ok(Value::Object(request).to_string())
+        }
+        _ => Err(ActionError::InvalidInput(format!(
+            "The request of the model '{}' is not a JSON object, the answer cannot be asked to be streamed", model.name
+        )).into()),
+    }
+}
+
+// Id of the server-side conversation in the response, None without a conversation JSON path or when the response has
+// no id, the whole transcript is then sent again
+fn conversation_id_in(response: &Value, model: &Model) -> Option<String> {
+    let path = model.conversation_json_path.as_ref()?;
+    match jsonpath_select(response, path).ok()?.first()? {
+        Value::String(conversation_id) => Some(conversation_id.clone()),
+        Value::Number(conversation_id) => Some(conversation_id.to_string()),
+        _ => None,
+    }
+}
+
+/// URL of the endpoint of an OpenAI-compatible API, the API URL is either the URL of the endpoint or the base URL
+pub(crate) fn openai_url(api_url: &str, endpoint_path: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    if api_url.ends_with(endpoint_path) {
+        api_url.to_string()
+    } else if unix_socket::is_unix_socket(api_url) && !api_url[unix_socket::SCHEME.len()..].contains(":/") {
+        format!("{}:{}", api_url, endpoint_path)
+    } else {
+        format!("{}{}", api_url, endpoint_path)
+    }
+}
+
+/// Sends the request body to the API of the model, returns the JSON of the response
+pub(crate) async fn send(model: &Model, request_body: String, client: &Client) -> Result<Value> {
+    send_to(model, &model.api_url, request_body, client).await
+}
+
+pub(crate) async fn send_to(model: &Model, url: &str, request_body: String, client: &Client) -> Result<Value> {
+    parse_response(post(model, url, request_body, client).await?).await
+}
+
+// Body printed by the request transform command of the model for the request body, the body itself without a command
+async fn transform_request(model: &Model, request_body: String) -> Result<String> {
+    let Some(transform) = &model.request_transform else {
+        return Ok(request_body);
+    };
+    let mut child = Command::new("sh")
+        .args(["-c", transform])
+        .env("CLIFF_MODEL", &model.name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| ActionError::from_io(format!("Failed to start the request transform of the model '{}'", model.name), e))?;
+    // Written while the output is read, the command may print before it has read the whole body
+    let mut stdin = child.stdin.take();
+    let writing = tokio::spawn(async move {
+        match &mut stdin {
+            Some(stdin) => stdin.write_all(request_body.as_bytes()).await,
+            None => Ok(()),
+        }
+    });
+    let output = child.wait_with_output().await
+        .map_err(|e| ActionError::from_io(format!("Failed to run the request transform of the model '{}'", model.name), e))?;
+    // The commands ignoring the body close their input early
+    if let Ok(Err(e)) = writing.await {
+        if e.kind() != std::io::ErrorKind::BrokenPipe {
+            return Err(ActionError::from_io(format!("Failed to pass the request to the request transform of the model '{}'", model.name), e).into());
+        }
+    }
+    if !output.status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("The request transform of the model '{}' failed: {}", model.name, String::from_utf8_lossy(&output.stderr).trim()),
+            exit_code: output.status.code(),
+        }.into());
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| ActionError::InvalidInput(format!("The request transform of the model '{}' printed a body that is not UTF-8", model.name)).into())
+}
+
+// Posts the request body to the URL of the API of the model, fails unless the response is successful
+// The URL with the model identifier in place of `{{model}}` and the query params of the model appended
+fn request_url(model: &Model, url: &str) -> String {
+    let url = url.replace("{{model}}", model.model_identifier.as_deref().unwrap_or_default());
+    let query: Vec<String> = model.query_params
+        .iter()
+        .map(|(name, value)| format!("{}={}", urlencoding::encode(name), urlencoding::encode(value)))
+        .collect();
+    match (query.is_empty(), url.contains('?')) {
+        (true, _) => url,
+        (false, true) => format!("{}&{}", url, query.join("&")),
+        (false, false) => format!("{}?{}", url, query.join("&")),
+    }
+}
+
+async fn post(model: &Model, url: &str, request_body: String, client: &Client) -> Result<Response> {
+    let request_body = transform_request(model, request_body).await?;
+    let client = proxy::model_client(model, client)?;
+    let url = &request_url(model, url);
+    let mut request_builder = client.post(unix_socket::request_url(url))
+        .header("Content-Type", "application/json")
+        .body(request_body);
+    if model.kind == ModelKind::Anthropic {
+        request_builder = request_builder.header("anthropic-version", ANTHROPIC_VERSION);
+    }
+    let request_builder = authorize(request_builder, model);
+    let response = if unix_socket::is_unix_socket(url) {
+        let request = request_builder.build().map_err(|e| ActionError::from_reqwest(format!("Failed to build request to {}", url), e))?;
+        unix_socket::send(url, request).await?
+    } else {
+        request_builder.send().await.map_err(|e| ActionError::from_reqwest(format!("Failed to send request to {}", url), e))?
+    };
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_body = response.text().await.unwrap_or_else(|_| "Could not read error body".to_string());
+        let (model, detail) = (model.name.clone(), response_excerpt(&error_body));
+        // Rejected credentials are distinguished so that they can be reported as such
+        return Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => LlmError::Unauthorized { model, status, detail },
+            _ => LlmError::RequestFailed { model, status, detail },
+        }.into());
+    }
+    Ok(response)
+}
+
+fn is_event_stream(response: &Response) -> bool {
+    response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+// Passes the JSON data of the server-sent events of the response to `on_event` until the stream or the `[DONE]` event
+// ends it, fails on the error events
+async fn read_events(response: Response, model: &Model, on_event: &mut (dyn FnMut(&Value) + Send)) -> Result<()> {
+    read_lines(response, model, &mut |line| {
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return Ok(true);
+        };
+        if data == "[DONE]" {
+            return Ok(false);
+        }
+        on_event(&parse_streamed(data)?);
+        Ok(true)
+    }).await
+}
+
+// Passes the JSON objects of the lines of the response to `on_event` until the stream ends or an object is marked
+// `"done": true` as by Ollama, fails on the error objects
+async fn read_json_lines(response: Response, model: &Model, on_event: &mut (dyn FnMut(&Value) + Send)) -> Result<()> {
+    read_lines(response, model, &mut |line| {
+        if line.is_empty() {
+            return Ok(true);
+        }
+        let event = parse_streamed(line)?;
+        on_event(&event);
+        Ok(event["done"] != json!(true))
+    }).await
+}
+
+// Passes the trimmed lines of the response to `on_line` as they arrive until it returns false or the stream ends
+async fn read_lines(mut response: Response, model: &Model, on_line: &mut (dyn FnMut(&str) -> Result<bool> + Send)) -> Result<()> {
+    let mut pending = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| ActionError::from_reqwest(format!("Failed to read the answer from {}", model.api_url), e))? {
+        pending.extend_from_slice(&chunk);
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if !on_line(String::from_utf8_lossy(&line).trim())? {
+                return Ok(());
+            }
+        }
+    }
+    // The last line may end without a newline
+    on_line(String::from_utf8_lossy(&pending).trim())?;
+    Ok(())
+}
+
+fn parse_streamed(data: &str) -> Result<Value> {
+    let event: Value = serde_json::from_str(data)
+        .map_err(|e| ActionError::LlmMalformedResponse(format!("Failed to parse the streamed answer: {}, event: {}", e, response_excerpt(data))))?;
+    if let Some(error) = event.get("error") {
+        return Err(ActionError::LlmMalformedResponse(format!("The streamed answer failed: {}", response_excerpt(&error.to_string()))).into());
+    }
+    Ok(event)
+}
+
+async fn parse_response(response: Response) -> Result<Value> {
+    let response_text = response.text().await
+        .with_context(|| "Failed to read LLM response text")?;
+    Ok(serde_json::from_str(&response_text)
+        .map_err(|e| ActionError::LlmMalformedResponse(format!("Failed to parse LLM response as JSON: {}, response: {}", e, response_excerpt(&response_text))))?)
+}
+
+/// The first value at the JSON path of the response
+pub(crate) fn select(response: &Value, path: &str, model: &Model) -> Result<Value> {
+    let selected_values = jsonpath_select(response, path)
+        .map_err(|e| LlmError::InvalidJsonPath { model: model.name.clone(), path: path.to_string(), detail: e.to_string() })?;
+    match selected_values.first() {
+        Some(value) => Ok((*value).clone()),
+        None => Err(LlmError::ValueNotFound {
+            model: model.name.clone(),
+            path: path.to_string(),
+            detail: response_excerpt(&response.to_string()),
+        }.into()),
+    }
+}
+
+// The string at the JSON path of the response
+fn text_at(response: &Value, path: &str, model: &Model) -> Result<String> {
+    match select(response, path, model)? {
+        Value::String(text) => Ok(text),
+        other => Err(ActionError::LlmMalformedResponse(format!("Expected a string at JSONPath '{}', but found: {}", path, other)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelCapabilities;
+    use std::collections::BTreeMap;
+    use httpmock::prelude::*;
+
+    fn model(kind: ModelKind, api_url: String) -> Model {
+        Model {
+            name: "test".to_string(),
+            api_url,
+            api_key: Some("secret".to_string()),
+            model_identifier: Some("test-model".to_string()),
+            kind,
+            capabilities: ModelCapabilities { supports_json_mode: true, ..Default::default() },
+            ..Model::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gemini_backend() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let generate = server.mock(|when, then| {
+            when.method(POST).path("/v1beta/models/gemini-test:generateContent").query_param("key", "secret").json_body(json!({
+                "contents": [
+                    { "role": "user", "parts": [{ "text": "Hi" }] },
+                    { "role": "model", "parts": [{ "text": "Hello" }] },
+                    { "role": "user", "parts": [{ "inline_data": { "mime_type": "image/png", "data": "iVBORw0KGgo=" } }, { "text": "What is it?" }] },
+                ],
+                "systemInstruction": { "parts": [{ "text": "Be brief" }] },
+                "generationConfig": { "responseMimeType": "application/json" },
+            }));
+            then.status(200).json_body(json!({
+                "candidates": [{ "content": { "role": "model", "parts": [{ "text": "{\"answer\": " }, { "text": "\"a logo\"}" }] } }],
+                "usageMetadata": { "promptTokenCount": 12, "candidatesTokenCount": 5 },
+            }));
+        });
+        let mut question = ChatMessage::user("What is it?");
+        question.images.push("data:image/png;base64,iVBORw0KGgo=".to_string());
+        let messages = [ChatMessage::new(Role::System, "Be brief"), ChatMessage::user("Hi"), ChatMessage::new(Role::Assistant, "Hello"), question];
+        let gemini_model = model(ModelKind::Gemini, server.url("/v1beta/models/gemini-test"));
+
+        let answer = of(&gemini_model).chat(&messages, true, &client).await?;
+
+        generate.assert();
+        assert_eq!(answer, r#"{"answer": "a logo"}"#);
+        assert_eq!(gemini_url("https://example.com/v1beta/models/gemini-test:generateContent", "embedContent"), "https://example.com/v1beta/models/gemini-test:embedContent");
+        assert_eq!(gemini_url("http://localhost:8080/models/gemini-test/", "generateContent"), "http://localhost:8080/models/gemini-test:generateContent");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_azure_openai_deployment() -> Result<()> {
+        let server = MockServer::start();
+        let azure = server.mock(|when, then| {
+            when.method(POST)
+                .path("/openai/deployments/gpt-4o-prod/chat/completions")
+                .query_param("api-version", "2024-10-21")
+                .header("api-key", "secret");
+            then.status(200).json_body(json!({ "choices": [{ "message": { "role": "assistant", "content": "Hello" } }] }));
+        });
+        let azure_model = Model {
+            api_key_header: Some("api-key: {{api_key}}".to_string()),
+            model_identifier: Some("gpt-4o-prod".to_string()),
+            query_params: BTreeMap::from([("api-version".to_string(), "2024-10-21".to_string())]),
+            ..model(ModelKind::OpenAi, server.url("/openai/deployments/{{model}}"))
+        };
+
+        let answer = of(&azure_model).chat(&[ChatMessage::user("Hi")], false, &Client::new()).await?;
+
+        azure.assert();
+        assert_eq!(answer, "Hello");
+        assert_eq!(request_url(&azure_model, "https://example.com/v1?tenant=a b"), "https://example.com/v1?tenant=a b&api-version=2024-10-21");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_native_backends() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let messages = [ChatMessage::new(Role::System, "Be brief"), ChatMessage::user("Hi")];
+
+        let openai = server.mock(|when, then| {
+            when.method(POST).path("/v1/chat/completions").header("authorization", "Bearer secret").json_body(json!({
+                "model": "test-model",
+                "messages": [{ "role": "system", "content": "Be brief" }, { "role": "user", "content": "Hi" }],
+                "response_format": { "type": "json_object" },
+            }));
+            then.status(200).json_body(json!({ "choices": [{ "message": { "role": "assistant", "content": "{}" } }] }));
+        });
+        let openai_model = model(ModelKind::OpenAi, server.url("/v1/chat/completions"));
+        assert_eq!(of(&openai_model).chat(&messages, true, &client).await?, "{}");
+        openai.assert();
+
+        let anthropic = server.mock(|when, then| {
+            when.method(POST).path("/v1/messages").header("x-api-key", "secret").header("anthropic-version", ANTHROPIC_VERSION).json_body(json!({
+                "model": "test-model",
+                "max_tokens": ANTHROPIC_DEFAULT_MAX_TOKENS,
+                "system": "Be brief",
+                "messages": [{ "role": "user", "content": "Hi" }],
+            }));
+            then.status(200).json_body(json!({ "content": [{ "type": "text", "text": "Hello" }, { "type": "text", "text": "!" }] }));
+        });
+        let anthropic_model = model(ModelKind::Anthropic, server.url("/v1/messages"));
+        assert_eq!(of(&anthropic_model).chat(&messages, false, &client).await?, "Hello!");
+        anthropic.assert();
+
+        let answers = tempfile::NamedTempFile::new()?;
+        std::fs::write(answers.path(), "First\n---\nSecond\n")?;
+        let mock_model = model(ModelKind::Mock, answers.path().to_string_lossy().to_string());
+        let answers: Vec<String> = [
+            of(&mock_model).chat(&messages, false, &client).await?,
+            of(&mock_model).chat(&messages, false, &client).await?,
+            of(&mock_model).chat(&messages, false, &client).await?,
+        ].to_vec();
+        assert_eq!(answers, ["First", "Second", "Second"]);
+        assert_eq!(of(&model(ModelKind::Mock, String::new())).chat(&messages, false, &client).await?, "Hi");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_openai_backend() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let messages = [ChatMessage::user("Hi")];
+        let mut tools_model = model(ModelKind::OpenAi, server.url("/v1/"));
+        tools_model.capabilities = ModelCapabilities { supports_tools: true, ..Default::default() };
+
+        let tools = server.mock(|when, then| {
+            when.method(POST).path("/v1/chat/completions").json_body_partial(r#"{"tool_choice": {"type": "function", "function": {"name": "answer"}}}"#);
+            then.status(200).json_body(json!({
+                "choices": [{ "message": { "tool_calls": [{ "function": { "name": "answer", "arguments": "{\"done\": true}" } }] } }],
+                "usage": { "prompt_tokens": 7, "completion_tokens": 2 },
+            }));
+        });
+        let before = usage();
+        assert_eq!(of(&tools_model).chat(&messages, true, &client).await?, r#"{"done": true}"#);
+        tools.assert();
+        assert!(usage().since(before).completion_tokens >= 2);
+
+        let stream = server.mock(|when, then| {
+            when.method(POST).path("/v1/chat/completions").json_body_partial(r#"{"stream": true}"#);
+            then.status(200).header("content-type", "text/event-stream").body(concat!(
+                "data: {\"choices\": [{\"delta\": {\"role\": \"assistant\"}}]}\n\n",
+                "data: {\"choices\": [{\"delta\": {\"content\": \"Hel\"}}]}\n\n",
+                "data: {\"choices\": [{\"delta\": {\"content\": \"lo\"}}]}\n\n",
+                "data: [DONE]\n\n",
+            ));
+        });
+        let mut parts = Vec::new();
+        let answer = of(&tools_model).chat_streaming(&messages, &mut |text| parts.push(text.to_string()), &client).await?;
+        stream.assert();
+        assert_eq!(answer, "Hello");
+        assert_eq!(parts, ["Hel", "lo"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_tool() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let messages = [ChatMessage::user("Plan it")];
+        let tool = Tool { name: "plan", description: "Give the plan", parameters: json!({ "type": "object", "required": ["steps"] }) };
+
+        let openai = server.mock(|when, then| {
+            when.method(POST).path("/v1/chat/completions").json_body_partial(r#"{
+                "tools": [{"type": "function", "function": {"name": "plan", "parameters": {"type": "object", "required": ["steps"]}}}],
+                "tool_choice": {"type": "function", "function": {"name": "plan"}}
+            }"#);
+            then.status(200).json_body(json!({ "choices": [{ "message": { "tool_calls": [{ "function": { "name": "plan", "arguments": "{\"steps\": []}" } }] } }] }));
+        });
+        let mut openai_model = model(ModelKind::OpenAi, server.url("/v1"));
+        openai_model.capabilities = ModelCapabilities { supports_tools: true, supports_json_mode: true, ..Default::default() };
+        assert!(calls_tools(&openai_model));
+        assert_eq!(of(&openai_model).chat_with_tool(&messages, &tool, &client).await?, r#"{"steps": []}"#);
+        openai.assert();
+
+        let anthropic = server.mock(|when, then| {
+            when.method(POST).path("/v1/messages").json_body_partial(r#"{
+                "tools": [{"name": "plan", "input_schema": {"type": "object", "required": ["steps"]}}],
+                "tool_choice": {"type": "tool", "name": "plan"}
+            }"#);
+            then.status(200).json_body(json!({ "content": [{ "type": "tool_use", "id": "toolu_1", "name": "plan", "input": { "steps": [] } }] }));
+        });
+        let mut anthropic_model = model(ModelKind::Anthropic, server.url("/v1/messages"));
+        anthropic_model.capabilities = ModelCapabilities { supports_tools: true, ..Default::default() };
+        assert_eq!(of(&anthropic_model).chat_with_tool(&messages, &tool, &client).await?, r#"{"steps":[]}"#);
+        anthropic.assert();
+
+        // Without tools the model is asked for a JSON answer
+        let json_mode = server.mock(|when, then| {
+            when.method(POST).path("/v2/chat/completions").json_body_partial(r#"{"response_format": {"type": "json_object"}}"#);
+            then.status(200).json_body(json!({ "choices": [{ "message": { "content": "{\"steps\": []}" } }] }));
+        });
+        openai_model.api_url = server.url("/v2");
+        openai_model.capabilities.supports_tools = false;
+        assert!(!calls_tools(&openai_model));
+        assert_eq!(of(&openai_model).chat_with_tool(&messages, &tool, &client).await?, r#"{"steps": []}"#);
+        json_mode.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_transform() -> Result<()> {
+        let server = MockServer::start();
+        let wrapped = server.mock(|when, then| {
+            when.method(POST).path("/v1/chat/completions").json_body_partial(r#"{"tenant": "test", "payload": {"model": "test-model"}}"#);
+            then.status(200).json_body(json!({ "choices": [{ "message": { "content": "Hello" } }] }));
+        });
+        let mut openai_model = model(ModelKind::OpenAi, server.url("/v1"));
+        openai_model.request_transform = Some(r#"printf '{"tenant": "%s", "payload": %s}' "$CLIFF_MODEL" "$(cat)""#.to_string());
+        assert_eq!(of(&openai_model).chat(&[ChatMessage::user("Hi")], false, &Client::new()).await?, "Hello");
+        wrapped.assert();
+
+        openai_model.request_transform = Some("echo 'no gateway' >&2; exit 3".to_string());
+        let error = of(&openai_model).chat(&[ChatMessage::user("Hi")], false, &Client::new()).await.unwrap_err();
+        assert!(error.to_string().contains("no gateway"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_conversation() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let mut template_model = model(ModelKind::Template, server.url("/v1/responses"));
+        template_model.request_format = r#"{"model": "{{model}}", "input": {{messages}}}"#.to_string();
+        template_model.response_json_path = "$.text".to_string();
+        template_model.conversation_json_path = Some("$.id".to_string());
+        template_model.conversation_param = Some("previous_response_id".to_string());
+
+        let continued = server.mock(|when, then| {
+            when.method(POST).path("/v1/responses").json_body_partial(r#"{"previous_response_id": "resp_1"}"#);
+            then.status(200).json_body(json!({ "text": "Second" }));
+        });
+        let started = server.mock(|when, then| {
+            when.method(POST).path("/v1/responses");
+            then.status(200).json_body(json!({ "id": "resp_1", "text": "First" }));
+        });
+        let mut conversation_id = None;
+        let backend = of(&template_model);
+        assert_eq!(backend.chat_in_conversation(&[ChatMessage::user("Hi")], &mut conversation_id, &client).await?, "First");
+        assert_eq!(conversation_id.as_deref(), Some("resp_1"));
+        assert_eq!(backend.chat_in_conversation(&[ChatMessage::user("Again")], &mut conversation_id, &client).await?, "Second");
+        assert_eq!(conversation_id, None);
+        started.assert();
+        continued.assert();
+        Ok(())
+    }
+
+    #[test]
+    fn test_anthropic_request() {
+        let anthropic_model = model(ModelKind::Anthropic, "http://localhost/v1/messages".to_string());
+        let backend = AnthropicBackend { model: &anthropic_model };
+
+        let request = backend.request(&[ChatMessage::new(Role::System, "Schema").cacheable(), ChatMessage::user("Hi").cacheable()], false);
+        assert_eq!(request["system"], json!([{ "type": "text", "text": "Schema", "cache_control": { "type": "ephemeral" } }]));
+        assert_eq!(request["messages"], json!([{ "role": "user", "content": [{ "type": "text", "text": "Hi", "cache_control": { "type": "ephemeral" } }] }]));
+
+        let request = backend.request(&[ChatMessage::new(Role::System, "Rules"), ChatMessage::new(Role::System, "Schema"), ChatMessage::user("Hi")], false);
+        assert_eq!(request["system"], json!("Rules\n\nSchema"));
+        assert_eq!(request["messages"], json!([{ "role": "user", "content": "Hi" }]));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_backend() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let messages = [ChatMessage::user("Hi")];
+        let mut anthropic_model = model(ModelKind::Anthropic, server.url("/v1/messages"));
+        anthropic_model.capabilities = ModelCapabilities { supports_tools: true, ..Default::default() };
+
+        let tool_use = server.mock(|when, then| {
+            when.method(POST).path("/v1/messages").json_body_partial(r#"{
+                "system": [{"type": "text", "text": "Schema", "cache_control": {"type": "ephemeral"}}],
+                "tool_choice": {"type": "tool", "name": "answer"}
+            }"#);
+            then.status(200).json_body(json!({
+                "content": [{ "type": "tool_use", "id": "toolu_1", "name": "answer", "input": { "done": true } }],
+                "usage": { "input_tokens": 9, "cache_read_input_tokens": 100, "output_tokens": 4 },
+            }));
+        });
+        let before = usage();
+        let cached_messages = [ChatMessage::new(Role::System, "Schema").cacheable(), ChatMessage::user("Hi")];
+        assert_eq!(of(&anthropic_model).chat(&cached_messages, true, &client).await?, r#"{"done":true}"#);
+        tool_use.assert();
+        let tokens = usage().since(before);
+        assert!(tokens.prompt_tokens >= 109 && tokens.cached_prompt_tokens >= 100);
+
+        let stream = server.mock(|when, then| {
+            when.method(POST).path("/v1/messages").json_body_partial(r#"{"stream": true}"#);
+            then.status(200).header("content-type", "text/event-stream").body(concat!(
+                "event: message_start\ndata: {\"type\": \"message_start\", \"message\": {\"usage\": {\"input_tokens\": 9, \"output_tokens\": 1}}}\n\n",
+                "event: content_block_start\ndata: {\"type\": \"content_block_start\", \"index\": 0, \"content_block\": {\"type\": \"text\", \"text\": \"\"}}\n\n",
+                "event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"text_delta\", \"text\": \"Hel\"}}\n\n",
+                "event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"text_delta\", \"text\": \"lo\"}}\n\n",
+                "event: message_delta\ndata: {\"type\": \"message_delta\", \"usage\": {\"output_tokens\": 2}}\n\n",
+                "event: message_stop\ndata: {\"type\": \"message_stop\"}\n\n",
+            ));
+        });
+        let mut parts = Vec::new();
+        let answer = of(&anthropic_model).chat_streaming(&messages, &mut |text| parts.push(text.to_string()), &client).await?;
+        stream.assert();
+        assert_eq!((answer.as_str(), parts.as_slice()), ("Hello", ["Hel".to_string(), "lo".to_string()].as_slice()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_backend_streams_json_lines() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let stream = server.mock(|when, then| {
+            when.method(POST).path("/api/chat").json_body(json!({ "model": "test-model", "prompt": "Hi", "stream": true }));
+            then.status(200).header("content-type", "application/x-ndjson").body(concat!(
+                "{\"message\": {\"content\": \"Hel\"}, \"done\": false}\n",
+                "{\"message\": {\"content\": \"lo\"}, \"done\": false}\n",
+                "{\"message\": {\"content\": \"\"}, \"done\": true}\n",
+                "{\"message\": {\"content\": \" after the end\"}, \"done\": false}",
+            ));
+        });
+        let template_model = Model {
+            request_format: r#"{"model": "{{model}}", "prompt": "{{prompt}}"}"#.to_string(),
+            response_json_path: "$.message.content".to_string(),
+            stream_format: Some(StreamFormat::Ndjson),
+            stream_json_path: Some("$.message.content".to_string()),
+            ..model(ModelKind::Template, server.url("/api/chat"))
+        };
+        let mut parts = Vec::new();
+
+        let answer = of(&template_model).chat_streaming(&[ChatMessage::user("Hi")], &mut |text| parts.push(text.to_string()), &client).await?;
+
+        stream.assert();
+        assert_eq!(answer, "Hello");
+        assert_eq!(parts, vec!["Hel", "lo"]);
+        Ok(())
+    }
+}
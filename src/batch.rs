@@ -0,0 +1,197 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::answer_format::AnswerFormat;
+use crate::config::Model;
+use crate::error::error_kind;
+use crate::llm::ask_llm_in_format;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Prompts and answers as plain text
+    #[default]
+    Text,
+    /// One JSON object with the prompt and the answer or the error per line
+    Jsonl,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    // Instruction preceding every prompt
+    pub instruction: Option<String>,
+    // Format the answers are asked in and repaired to conform to
+    pub answer_format: Option<AnswerFormat>,
+    // Maximum number of prompts sent to the LLM at the same time
+    pub concurrency: usize,
+    // Maximum number of prompts sent per minute
+    pub rate_limit: Option<u32>,
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct BatchRecord {
+    index: usize,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'static str>,
+}
+
+// Prompts of the batch file: every non-empty line is a prompt, either plain text or a JSON object with a "prompt" field
+pub fn read_prompts(content: &str) -> Result<Vec<String>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_idx, line)| {
+            if !line.trim_start().starts_with('{') {
+                return Ok(line.to_string());
+            }
+            let record: serde_json::Value = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("Invalid JSON record on line {}: {}", line_idx + 1, e))?;
+            match record.get("prompt") {
+                Some(serde_json::Value::String(prompt)) => Ok(prompt.clone()),
+                _ => anyhow::bail!("The JSON record on line {} has no string field \"prompt\"", line_idx + 1),
+            }
+        })
+        .collect()
+}
+
+/// Asks every prompt independently and writes the results in the order of the prompts, returns the number of failed prompts
+pub async fn run(
+    prompts: Vec<String>,
+    model_config: &Model,
+    context_sources: &[String],
+    client: &Client,
+    options: BatchOptions,
+    post_process: impl Fn(&str) -> Result<String>,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let model_config = Arc::new(model_config.clone());
+    let context_sources = Arc::new(context_sources.to_vec());
+    let permits = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let pacing: Option<Arc<Mutex<Interval>>> = options.rate_limit.filter(|limit| *limit > 0).map(|limit| {
+        let mut pacing = interval(Duration::from_secs(60) / limit);
+        pacing.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Arc::new(Mutex::new(pacing))
+    });
+
+    let mut requests = JoinSet::new();
+    for (index, prompt) in prompts.iter().enumerate() {
+        let (model_config, context_sources, client) = (model_config.clone(), context_sources.clone(), client.clone());
+        let (permits, pacing) = (permits.clone(), pacing.clone());
+        let answer_format = options.answer_format;
+        let prompt = match &options.instruction {
+            Some(instruction) => format!("{}\n\n{}", instruction, prompt),
+            None => prompt.clone(),
+        };
+        requests.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            if let Some(pacing) = pacing {
+                pacing.lock().await.tick().await;
+            }
+            let answer = ask_llm_in_format(&model_config, &prompt, &context_sources, answer_format, &client).await;
+            Ok::<_, anyhow::Error>((index, answer))
+        });
+    }
+
+    // Results arriving out of order wait until all the previous prompts are written
+    let mut pending = BTreeMap::new();
+    let mut next_index = 0;
+    let mut failed = 0;
+    while let Some(joined) = requests.join_next().await {
+        let (index, answer) = joined??;
+        pending.insert(index, answer);
+        while let Some(answer) = pending.remove(&next_index) {
+            let record = match answer.and_then(|answer| post_process(&answer)) {
+                Ok(answer) => BatchRecord { index: next_index, prompt: prompts[next_index].clone(), answer: Some(answer), error: None, error_kind: None },
+                Err(e) => {
+                    failed += 1;
+                    BatchRecord { index: next_index, prompt: prompts[next_index].clone(), answer: None, error: Some(format!("{:#}", e)), error_kind: Some(error_kind(&e)) }
+                }
+            };
+            write_record(&record, options.format, out)?;
+            next_index += 1;
+        }
+    }
+    Ok(failed)
+}
+
+fn write_record(record: &BatchRecord, format: OutputFormat, out: &mut impl Write) -> Result<()> {
+    match format {
+        OutputFormat::Jsonl => writeln!(out, "{}", serde_json::to_string(record)?)?,
+        OutputFormat::Text => {
+            writeln!(out, "--- Prompt {}: {}", record.index + 1, record.prompt)?;
+            match (&record.answer, &record.error) {
+                (Some(answer), _) => writeln!(out, "{}\n", answer)?,
+                (None, error) => writeln!(out, "ERROR: {}\n", error.as_deref().unwrap_or_default())?,
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_read_prompts() -> Result<()> {
+        let content = "Translate 'cat'\n\n{\"prompt\": \"Translate 'dog'\", \"id\": 7}\n";
+        assert_eq!(read_prompts(content)?, vec!["Translate 'cat'".to_string(), "Translate 'dog'".to_string()]);
+        assert!(read_prompts("{\"text\": \"no prompt\"}").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_writes_results_in_order() -> Result<()> {
+        let server = MockServer::start();
+        let client = Client::new();
+        let failing = server.mock(|when, then| {
+            when.method(POST).path("/batch").body_contains("Answer briefly\\n\\nsecond");
+            then.status(500).body("Internal error");
+        });
+        let succeeding = server.mock(|when, then| {
+            when.method(POST).path("/batch").body_contains("Answer briefly\\n\\nfirst");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"answer": "  first answer  "}"#);
+        });
+        let model_config = Model {
+            name: "Test Model".to_string(),
+            api_url: server.url("/batch"),
+            request_format: r#"{"input": "{{prompt}}"}"#.to_string(),
+            response_json_path: "$.answer".to_string(),
+            ..Model::default()
+        };
+        let options = BatchOptions { instruction: Some("Answer briefly".to_string()), answer_format: None, concurrency: 2, rate_limit: None, format: OutputFormat::Jsonl };
+        let mut out = Vec::new();
+
+        let failed = run(
+            vec!["first".to_string(), "second".to_string()], &model_config, &[], &client, options,
+            |answer| Ok(answer.trim().to_string()), &mut out,
+        ).await?;
+
+        succeeding.assert();
+        failing.assert();
+        assert_eq!(failed, 1);
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)?.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+        assert_eq!(lines[0], serde_json::json!({ "index": 0, "prompt": "first", "answer": "first answer" }));
+        assert_eq!(lines[1]["index"], 1);
+        assert_eq!(lines[1]["error_kind"], "other");
+        Ok(())
+    }
+}
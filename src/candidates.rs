@@ -0,0 +1,255 @@
+use anyhow::Result;
+use crate::actions::confirm_action::ensure_prompts_enabled;
+use crate::answer_format::AnswerFormat;
+use crate::config::Model;
+use crate::error::ActionError;
+use crate::input;
+use crate::llm::ask_llm_in_format;
+use colored::*;
+use reqwest::Client;
+use serde::Deserialize;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+// Width of the terminal when `$COLUMNS` is not set
+const DEFAULT_TERMINAL_WIDTH: usize = 120;
+// Narrower columns are hard to read, the candidates are shown one after another instead
+const MIN_COLUMN_WIDTH: usize = 30;
+const COLUMN_SEPARATOR: &str = " │ ";
+
+// Choice of the judge among the candidate answers, `best` is 1-based
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Judgement {
+    pub best: usize,
+    pub reason: String,
+}
+
+/// Asks the same question `n` times in parallel, the failed requests are reported and skipped unless all of them fail
+pub async fn ask_candidates(
+    model_config: &Model,
+    prompt: &str,
+    context_sources: &[String],
+    format: Option<AnswerFormat>,
+    n: usize,
+    client: &Client,
+) -> Result<Vec<String>> {
+    let (model_config, prompt, context_sources) = (Arc::new(model_config.clone()), Arc::new(prompt.to_string()), Arc::new(context_sources.to_vec()));
+    let mut requests = JoinSet::new();
+    for index in 0..n {
+        let (model_config, prompt, context_sources, client) = (model_config.clone(), prompt.clone(), context_sources.clone(), client.clone());
+        requests.spawn(async move { (index, ask_llm_in_format(&model_config, &prompt, &context_sources, format, &client).await) });
+    }
+    let mut answers = Vec::new();
+    let mut last_error = None;
+    while let Some(joined) = requests.join_next().await {
+        match joined? {
+            (index, Ok(answer)) => answers.push((index, answer)),
+            (index, Err(e)) => {
+                eprintln!("Warning: Candidate {} failed: {:#}", index + 1, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    if answers.is_empty() {
+        return Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No candidates were requested")));
+    }
+    answers.sort_by_key(|(index, _)| *index);
+    Ok(answers.into_iter().map(|(_, answer)| answer).collect())
+}
+
+/// Answer of one of the compared models, with the time it took
+pub struct ModelAnswer {
+    pub model: String,
+    pub latency: Duration,
+    pub answer: Result<String>,
+}
+
+/// Asks the same question to all the models at the same time, the answers are in the order of the models
+pub async fn ask_models(
+    models: &[&Model],
+    prompt: &str,
+    context_sources: &[String],
+    format: Option<AnswerFormat>,
+    client: &Client,
+) -> Result<Vec<ModelAnswer>> {
+    let (prompt, context_sources) = (Arc::new(prompt.to_string()), Arc::new(context_sources.to_vec()));
+    let mut requests = JoinSet::new();
+    for (index, model_config) in models.iter().enumerate() {
+        let (model_config, prompt, context_sources, client) = ((*model_config).clone(), prompt.clone(), context_sources.clone(), client.clone());
+        requests.spawn(async move {
+            let started = Instant::now();
+            let answer = ask_llm_in_format(&model_config, &prompt, &context_sources, format, &client).await;
+            (index, ModelAnswer { model: model_config.name, latency: started.elapsed(), answer })
+        });
+    }
+    let mut answers = Vec::new();
+    while let Some(joined) = requests.join_next().await {
+        answers.push(joined?);
+    }
+    answers.sort_by_key(|(index, _)| *index);
+    Ok(answers.into_iter().map(|(_, answer)| answer).collect())
+}
+
+/// The answers of the models side by side, titled with the models and their latencies
+pub fn format_model_answers(answers: &[ModelAnswer], terminal_width: usize) -> String {
+    let titles: Vec<String> = answers
+        .iter()
+        .map(|answer| match &answer.answer {
+            Ok(_) => format!("--- {} ({:.1}s)", answer.model, answer.latency.as_secs_f64()),
+            Err(_) => format!("--- {} (failed after {:.1}s)", answer.model, answer.latency.as_secs_f64()),
+        })
+        .collect();
+    let texts: Vec<String> = answers
+        .iter()
+        .map(|answer| match &answer.answer {
+            Ok(text) => text.clone(),
+            Err(e) => format!("Error: {:#}", e),
+        })
+        .collect();
+    format_columns(&titles, &texts, terminal_width)
+}
+
+// Splits the text into lines of at most `width` characters, breaking at spaces where possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let mut word = word.to_string();
+            while word.chars().count() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                let rest = word.chars().skip(width).collect();
+                lines.push(word.chars().take(width).collect());
+                word = rest;
+            }
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// The candidates numbered in columns next to each other, or one after another when the columns would be too narrow
+pub fn format_candidates(candidates: &[String], terminal_width: usize) -> String {
+    let titles: Vec<String> = (1..=candidates.len()).map(|i| format!("--- Candidate {}", i)).collect();
+    format_columns(&titles, candidates, terminal_width)
+}
+
+// The titled texts in columns next to each other, or one after another when the columns would be too narrow
+fn format_columns(titles: &[String], texts: &[String], terminal_width: usize) -> String {
+    let columns = texts.len().max(1);
+    let column_width = terminal_width.saturating_sub(COLUMN_SEPARATOR.chars().count() * (columns - 1)) / columns;
+    if column_width < MIN_COLUMN_WIDTH {
+        return titles
+            .iter()
+            .zip(texts)
+            .map(|(title, text)| format!("{}\n{}", title, text.trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+    }
+    let wrapped: Vec<Vec<String>> = titles
+        .iter()
+        .zip(texts)
+        .map(|(title, text)| [wrap(title, column_width), wrap(text.trim_end(), column_width)].concat())
+        .collect();
+    let rows = wrapped.iter().map(Vec::len).max().unwrap_or_default();
+    (0..rows)
+        .map(|row| {
+            let cells: Vec<String> = wrapped
+                .iter()
+                .map(|lines| format!("{:<width$}", lines.get(row).map(String::as_str).unwrap_or_default(), width = column_width))
+                .collect();
+            cells.join(COLUMN_SEPARATOR).trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// Asks the user to choose one of the shown candidates, returns its 0-based index
+pub fn choose_candidate(count: usize) -> Result<usize> {
+    ensure_prompts_enabled("Choosing among the candidate answers")?;
+    if !input::is_interactive() {
+        return Err(ActionError::InvalidInput("Choosing among the candidate answers needs a terminal, use `--best-of` to let the LLM choose".to_string()).into());
+    }
+    loop {
+        eprint!("{}", format!("Choose the answer [1-{}]: ", count).yellow());
+        io::stderr().flush()?;
+        let Some(input) = input::read_line()? else {
+            return Err(ActionError::InvalidInput("No answer was chosen".to_string()).into());
+        };
+        match input.trim().parse::<usize>() {
+            Ok(choice) if (1..=count).contains(&choice) => return Ok(choice - 1),
+            _ => eprintln!("Enter a number from 1 to {}.", count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_format_candidates() {
+        let candidates = vec!["Paris is the capital of France.".to_string(), "Paris.\nIt is on the Seine.".to_string()];
+        assert_eq!(format_candidates(&candidates, 65), [
+            "--- Candidate 1                 │ --- Candidate 2",
+            "Paris is the capital of France. │ Paris.",
+            "                                │ It is on the Seine.",
+        ].join("\n"));
+        assert_eq!(format_candidates(&candidates, 40), "--- Candidate 1\nParis is the capital of France.\n\n--- Candidate 2\nParis.\nIt is on the Seine.");
+        assert_eq!(wrap("a verylongword", 4), vec!["a", "very", "long", "word"]);
+
+        let answers = [
+            ModelAnswer { model: "fast".to_string(), latency: Duration::from_millis(420), answer: Ok("Paris.".to_string()) },
+            ModelAnswer { model: "smart".to_string(), latency: Duration::from_millis(2000), answer: Err(anyhow::anyhow!("timed out")) },
+        ];
+        assert_eq!(format_model_answers(&answers, 40), "--- fast (0.4s)\nParis.\n\n--- smart (failed after 2.0s)\nError: timed out");
+    }
+
+    #[tokio::test]
+    async fn test_ask_models() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/fast");
+            then.status(200).json_body(serde_json::json!({ "answer": "Paris." }));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/broken");
+            then.status(400).body("unknown model");
+        });
+        let model = |name: &str, path: &str| Model {
+            name: name.to_string(),
+            api_url: server.url(path),
+            request_format: r#"{"messages": {{messages}}}"#.to_string(),
+            response_json_path: "$.answer".to_string(),
+            ..Model::default()
+        };
+        let (broken, fast) = (model("broken", "/broken"), model("fast", "/fast"));
+
+        let answers = ask_models(&[&broken, &fast], "What is the capital of France?", &[], None, &Client::new()).await?;
+
+        assert_eq!(answers.iter().map(|answer| answer.model.as_str()).collect::<Vec<_>>(), ["broken", "fast"]);
+        assert!(answers[0].answer.is_err());
+        assert_eq!(answers[1].answer.as_deref().ok(), Some("Paris."));
+        let formatted = format_model_answers(&answers, 40);
+        let (broken_answer, fast_answer) = formatted.split_once("\n\n--- fast (").expect("the answer of fast follows the one of broken");
+        assert!(broken_answer.starts_with("--- broken (failed after ") && broken_answer.contains("Error: "));
+        assert!(fast_answer.ends_with("s)\nParis."));
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use crate::actions::target::ExecutionTarget;
+use crate::classification::{classify, ActionClass};
+use crate::diff::unified_diff;
+use crate::error::ActionError;
+use crate::executor::Action;
+use crate::profiles;
+use crate::runs::RunMetadata;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const CHANGES_DIR_NAME: &str = "changes";
+// The changes of the older runs are deleted
+const MAX_CHANGES: usize = 100;
+// Larger files are recorded only by their checksums
+const MAX_SNAPSHOT_BYTES: usize = 1024 * 1024;
+
+// Files written since the last run finished
+static CHANGES: Mutex<Vec<FileChange>> = Mutex::new(Vec::new());
+
+/// Content of a file at a moment of the run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Snapshot {
+    Missing,
+    Text { content: String },
+    // Not UTF-8 or larger than MAX_SNAPSHOT_BYTES
+    Binary { sha256: String },
+}
+
+/// File written by a run: its content before the first write of the run and after the last one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub before: Snapshot,
+    pub after: Snapshot,
+}
+
+async fn snapshot(path: &str, target: &dyn ExecutionTarget) -> Snapshot {
+    let Ok(bytes) = target.read(path).await else {
+        return Snapshot::Missing;
+    };
+    match String::from_utf8(bytes) {
+        Ok(content) if content.len() <= MAX_SNAPSHOT_BYTES => Snapshot::Text { content },
+        Ok(content) => Snapshot::Binary { sha256: sha256(content.as_bytes()) },
+        Err(e) => Snapshot::Binary { sha256: sha256(e.as_bytes()) },
+    }
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    digest::digest(&digest::SHA256, bytes).as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn written_paths(action: &Action) -> Vec<String> {
+    match classify(action) {
+        ActionClass::Write { paths } => paths,
+        ActionClass::Read | ActionClass::Execute => Vec::new(),
+    }
+}
+
+/// Records the content of the files the action writes before it is executed, unless they were written earlier in the run
+pub async fn record_before(action: &Action, target: &dyn ExecutionTarget) {
+    for path in written_paths(action) {
+        let recorded = CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter().any(|change| change.path == path);
+        if !recorded {
+            let before = snapshot(&path, target).await;
+            CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(FileChange { path, after: before.clone(), before });
+        }
+    }
+}
+
+/// Records the content of the files the action wrote after it was executed
+pub async fn record_after(action: &Action, target: &dyn ExecutionTarget) {
+    for path in written_paths(action) {
+        let after = snapshot(&path, target).await;
+        let mut changes = CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(change) = changes.iter_mut().find(|change| change.path == path) {
+            change.after = after;
+        }
+    }
+}
+
+fn changes_dir() -> Result<PathBuf> {
+    Ok(profiles::data_dir()?.join(CHANGES_DIR_NAME))
+}
+
+/// Saves the files changed by the finished run, the changes of the older runs beyond the latest ones are deleted
+pub fn save(run: &RunMetadata) -> Result<()> {
+    let changes = std::mem::take(&mut *CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    let dir = changes_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create the changes directory: {:?}", dir))?;
+    let path = dir.join(format!("{}.json", run.id));
+    fs::write(&path, serde_json::to_string(&changes)?).with_context(|| format!("Failed to write the changes: {:?}", path))?;
+
+    let mut saved: Vec<_> = fs::read_dir(&dir)?.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    if saved.len() > MAX_CHANGES {
+        saved.sort_by_key(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok());
+        for old in &saved[..saved.len() - MAX_CHANGES] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(())
+}
+
+/// Files changed by the run
+pub fn load(run_id: &str) -> Result<Vec<FileChange>> {
+    let path = changes_dir()?.join(format!("{}.json", run_id));
+    let content = fs::read_to_string(&path)
+        .map_err(|e| ActionError::from_io(format!("No changes of the run '{}' were recorded", run_id), e))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse the changes: {:?}", path))
+}
+
+/// Combined diff of the changes, applicable with `git apply` or `patch -p1` in the working directory of the run
+pub fn patch(changes: &[FileChange]) -> String {
+    let mut patch = String::new();
+    for change in changes.iter().filter(|change| change.before != change.after) {
+        let label = |prefix: &str| format!("{}/{}", prefix, change.path.trim_start_matches('/'));
+        let (old_label, new_label) = match (&change.before, &change.after) {
+            (Snapshot::Missing, _) => ("/dev/null".to_string(), label("b")),
+            (_, Snapshot::Missing) => (label("a"), "/dev/null".to_string()),
+            _ => (label("a"), label("b")),
+        };
+        patch.push_str(&format!("diff --git {} {}\n", label("a"), label("b")));
+        match (&change.before, &change.after) {
+            (Snapshot::Missing, Snapshot::Text { .. }) => patch.push_str("new file mode 100644\n"),
+            (Snapshot::Text { .. }, Snapshot::Missing) => patch.push_str("deleted file mode 100644\n"),
+            _ => {}
+        }
+        let text = |snapshot: &Snapshot| match snapshot {
+            Snapshot::Missing => Some(String::new()),
+            Snapshot::Text { content } => Some(content.clone()),
+            Snapshot::Binary { .. } => None,
+        };
+        match (text(&change.before), text(&change.after)) {
+            (Some(before), Some(after)) => patch.push_str(&unified_diff(&before, &after, &old_label, &new_label, usize::MAX)),
+            _ => patch.push_str(&format!("Binary files {} and {} differ\n", old_label, new_label)),
+        }
+    }
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch() {
+        let text = |content: &str| Snapshot::Text { content: content.to_string() };
+        let changes = [
+            FileChange { path: "notes.txt".to_string(), before: text("a\nb\n"), after: text("a\nc\n") },
+            FileChange { path: "new.txt".to_string(), before: Snapshot::Missing, after: text("hello\n") },
+            FileChange { path: "old.txt".to_string(), before: text("bye\n"), after: Snapshot::Missing },
+            FileChange { path: "same.txt".to_string(), before: text("x\n"), after: text("x\n") },
+            FileChange { path: "logo.png".to_string(), before: Snapshot::Missing, after: Snapshot::Binary { sha256: "00".to_string() } },
+        ];
+        assert_eq!(patch(&changes), [
+            "diff --git a/notes.txt b/notes.txt",
+            "--- a/notes.txt",
+            "+++ b/notes.txt",
+            "@@ -1,2 +1,2 @@",
+            " a",
+            "-b",
+            "+c",
+            "diff --git a/new.txt b/new.txt",
+            "new file mode 100644",
+            "--- /dev/null",
+            "+++ b/new.txt",
+            "@@ -0,0 +1 @@",
+            "+hello",
+            "diff --git a/old.txt b/old.txt",
+            "deleted file mode 100644",
+            "--- a/old.txt",
+            "+++ /dev/null",
+            "@@ -1 +0,0 @@",
+            "-bye",
+            "diff --git a/logo.png b/logo.png",
+            "Binary files /dev/null and b/logo.png differ",
+            "",
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+use crate::transform::chunk_lines;
+
+// Part of a file for the context or an embedding index, located by its lines and the signatures of the definitions in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk<'a> {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub signatures: Vec<String>,
+    pub text: &'a str,
+}
+
+impl Chunk<'_> {
+    // E.g. `src/config.rs:40-95 (impl Config > pub fn save(&self) -> Result<()>)`
+    pub fn header(&self, path: &str) -> String {
+        let mut header = format!("{}:{}-{}", path, self.start_line, self.end_line);
+        if !self.signatures.is_empty() {
+            header.push_str(&format!(" ({})", self.signatures.join("; ")));
+        }
+        header
+    }
+}
+
+// Splits the file along its definitions when the language is supported, along the lines otherwise, into chunks of at most `max_bytes` bytes
+// where possible, a definition longer than that is split along its nested definitions or its lines
+pub fn chunk_source<'a>(path: &Path, content: &'a str, max_bytes: usize) -> Vec<Chunk<'a>> {
+    #[cfg(feature = "syntax")]
+    if let Some(chunks) = crate::syntax::chunk_definitions(path, content, max_bytes) {
+        return chunks;
+    }
+    #[cfg(not(feature = "syntax"))]
+    let _ = path;
+    line_chunks(content, 0, content.len(), &[], max_bytes)
+}
+
+pub(crate) fn line_chunks<'a>(content: &'a str, start: usize, end: usize, signatures: &[String], max_bytes: usize) -> Vec<Chunk<'a>> {
+    let mut chunks = Vec::new();
+    let mut offset = start;
+    for text in chunk_lines(&content[start..end], max_bytes) {
+        chunks.push(chunk(content, offset, offset + text.len(), signatures.to_vec()));
+        offset += text.len();
+    }
+    chunks
+}
+
+pub(crate) fn chunk(content: &str, start: usize, end: usize, signatures: Vec<String>) -> Chunk<'_> {
+    let text = &content[start..end];
+    let start_line = content[..start].matches('\n').count() + 1;
+    let end_line = start_line + text.trim_end_matches('\n').matches('\n').count();
+    Chunk { start_line, end_line, signatures, text }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_along_lines() {
+        let content = "first line\nsecond line\nthird line\n";
+        let chunks = chunk_source(Path::new("notes.txt"), content, 24);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line, chunks[0].text), (1, 2, "first line\nsecond line\n"));
+        assert_eq!((chunks[1].start_line, chunks[1].end_line, chunks[1].text), (3, 3, "third line\n"));
+        assert_eq!(chunks[1].header("notes.txt"), "notes.txt:3-3");
+    }
+
+    #[cfg(feature = "syntax")]
+    #[test]
+    fn test_chunk_along_definitions() {
+        let content = "use std::fmt;\n\n// Greets\nfn greet(name: &str) -> String {\n    format!(\"Hello {}\", name)\n}\n\nstruct Config {\n    name: String,\n}\n\nimpl Config {\n    fn new() -> Config {\n        Config { name: String::new() }\n    }\n\n    fn name(&self) -> &str {\n        &self.name\n    }\n}\n";
+        let chunks = chunk_source(Path::new("src/lib.rs"), content, 100);
+        let headers: Vec<String> = chunks.iter().map(|chunk| chunk.header("src/lib.rs")).collect();
+        assert_eq!(headers, vec![
+            "src/lib.rs:1-6 (fn greet(name: &str) -> String)",
+            "src/lib.rs:8-10 (struct Config)",
+            "src/lib.rs:12-15 (impl Config > fn new() -> Config)",
+            "src/lib.rs:17-20 (impl Config > fn name(&self) -> &str)",
+        ]);
+        assert_eq!(chunks.iter().map(|chunk| chunk.text).collect::<String>(), content);
+    }
+}
@@ -0,0 +1,127 @@
+use clap::ValueEnum;
+use std::path::Path;
+use crate::executor::Action;
+use crate::fs::is_within_dir;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionClass {
+    // Only reads files, web pages or asks the user/LLM, never changes anything on the machine of the user
+    Read,
+    // Writes, moves or deletes the files at `paths`
+    Write { paths: Vec<String> },
+    // Runs arbitrary commands, the effects cannot be determined upfront
+    Execute,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AutoConfirm {
+    /// Ask for confirmation before every action
+    None,
+    /// Automatically confirm only read-only actions
+    Reads,
+    /// Automatically confirm read-only actions and writes inside the working directory or the scratch directory of the run
+    Safe,
+    /// Automatically confirm all actions
+    All,
+}
+
+impl AutoConfirm {
+    pub fn allows(&self, action: &Action) -> bool {
+        match self {
+            AutoConfirm::None => false,
+            AutoConfirm::Reads => classify(action) == ActionClass::Read,
+            AutoConfirm::Safe => match classify(action) {
+                ActionClass::Read => true,
+                ActionClass::Write { paths } => match std::env::current_dir() {
+                    Ok(workdir) => paths.iter().all(|path| {
+                        is_within_dir(path, &workdir) || crate::scratch::path().is_some_and(|scratch_dir| is_within_dir(path, scratch_dir))
+                    }),
+                    Err(_) => false,
+                },
+                ActionClass::Execute => false,
+            },
+            AutoConfirm::All => true,
+        }
+    }
+}
+
+// Whether all the files written by the action are inside `dir`, a command may write anywhere so it is not
+pub fn writes_only_within(action: &Action, dir: &Path) -> bool {
+    match classify(action) {
+        ActionClass::Write { paths } => paths.iter().all(|path| is_within_dir(path, dir)),
+        ActionClass::Read => true,
+        ActionClass::Execute => false,
+    }
+}
+
+pub fn classify(action: &Action) -> ActionClass {
+    match action {
+        Action::SearchWeb { .. }
+        | Action::ReadWebPage { .. }
+        | Action::AskUser { .. }
+        | Action::AskUserForm { .. }
+        | Action::AskLlm { .. }
+        | Action::AskLlmForPlan { .. }
+        | Action::ReadFile { .. }
+        | Action::ReadFiles { .. }
+        | Action::TailFile { .. }
+        | Action::FindFiles { .. }
+        | Action::FindSymbol { .. }
+        | Action::ListDirectory { .. }
+        | Action::ShowTree { .. }
+        | Action::CheckPathExists { .. }
+        | Action::HashFile { .. }
+        | Action::CompareFiles { .. }
+        | Action::ListProcesses { .. }
+        | Action::GetPortUsage { .. }
+        | Action::SystemInfo { .. } => ActionClass::Read,
+        #[cfg(feature = "devops")]
+        Action::DockerPs { .. } | Action::DockerLogs { .. } | Action::KubectlGet { .. } => ActionClass::Read,
+        Action::CreateFile { path, .. }
+        | Action::CreateFileFromTemplate { path, .. }
+        | Action::AskLlmToCreateFile { path, .. }
+        | Action::DeleteFile { path, .. }
+        | Action::OverwriteFileContents { path, .. }
+        | Action::AskLlmToOverwriteFileContents { path, .. }
+        | Action::ReplaceFileLines { path, .. }
+        | Action::AskLlmToReplaceFileLines { path, .. }
+        | Action::AppendToFile { path, .. } => ActionClass::Write { paths: vec![path.clone()] },
+        Action::MoveFile { source, destination, .. } => ActionClass::Write { paths: vec![source.clone(), destination.clone()] },
+        Action::CopyFile { destination, .. } => ActionClass::Write { paths: vec![destination.clone()] },
+        Action::RunCommand { .. } => ActionClass::Execute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(&Action::ReadFile { action_idx: 0, path: "a.txt".to_string() }), ActionClass::Read);
+        assert_eq!(
+            classify(&Action::MoveFile { action_idx: 0, source: "a.txt".to_string(), destination: "b.txt".to_string(), overwrite: true }),
+            ActionClass::Write { paths: vec!["a.txt".to_string(), "b.txt".to_string()] }
+        );
+        assert_eq!(classify(&Action::RunCommand { action_idx: 0, command: "ls".to_string() }), ActionClass::Execute);
+    }
+
+    #[test]
+    fn test_auto_confirm_tiers() {
+        let read = Action::ListDirectory { action_idx: 0, path: ".".to_string(), recursive: false, glob: None, max_entries: None };
+        let write_inside = Action::CreateFile { action_idx: 1, path: "out/hello.txt".to_string(), content: "".to_string(), if_not_exists: false, overwrite: true };
+        let write_outside = Action::DeleteFile { action_idx: 2, path: "../outside.txt".to_string() };
+        let command = Action::RunCommand { action_idx: 3, command: "ls".to_string() };
+
+        let allowed = |tier: AutoConfirm| -> Vec<bool> {
+            [&read, &write_inside, &write_outside, &command].iter().map(|action| tier.allows(action)).collect()
+        };
+
+        assert_eq!(allowed(AutoConfirm::None), vec![false, false, false, false]);
+        assert_eq!(allowed(AutoConfirm::Reads), vec![true, false, false, false]);
+        assert_eq!(allowed(AutoConfirm::Safe), vec![true, true, false, false]);
+        assert_eq!(allowed(AutoConfirm::All), vec![true, true, true, true]);
+        let workdir = std::env::current_dir().unwrap();
+        assert_eq!([&read, &write_inside, &write_outside, &command].map(|action| writes_only_within(action, &workdir)), [true, true, false, false]);
+    }
+}
@@ -0,0 +1,831 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use crate::actions::target::{parse_target, ExecutionTarget};
+use crate::answer_format::AnswerFormat;
+use crate::batch::OutputFormat;
+use crate::classification::AutoConfirm;
+use crate::config::{FailurePolicy, ModelCapabilities, ModelKind, PreviewConfig, Shell, StreamFormat, TrimmingConfig};
+use crate::consistency::DivergencePolicy;
+use crate::cron::CronSchedule;
+use crate::onboarding;
+use crate::protocol::Protocol;
+use crate::trace::TraceFormat;
+use crate::validators::{self, Validator};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "CLIFF: Command Line Interface Friendly & Facilitator", long_about = "CLIFF: Command Line Interface Friendly & Facilitator")]
+pub(crate) struct Cli {
+    /// Command to execute
+    #[command(subcommand)]
+    pub(crate) command: Commands,
+    /// Configured LLM model to use to execute the command
+    #[arg(short, long, global = true)]
+    pub(crate) model: Option<String>,
+    /// Files or URLs to provide as context
+    #[arg(short, long, value_delimiter = ',', global = true)]
+    pub(crate) context: Vec<String>,
+    /// Profile with its own config and state (models, history, runs, indexes), see `cliff profile`
+    #[arg(long, global = true)]
+    pub(crate) profile: Option<String>,
+    /// File answering the prompts line by line instead of the standard input, e.g. for tests and demos (defaults to `$CLIFF_INPUT_SCRIPT`)
+    #[arg(long, global = true)]
+    pub(crate) input_script: Option<PathBuf>,
+    /// Print the long plans and answers at once instead of through `$PAGER` (`less` by default)
+    #[arg(long, global = true)]
+    pub(crate) no_pager: bool,
+    /// Show the whole contents of the file actions in the plans instead of their first lines
+    #[arg(long, global = true)]
+    pub(crate) show_full: bool,
+    /// Write the progress of the execution as JSON lines to the inherited file descriptor, e.g. `3`
+    #[arg(long, global = true, conflicts_with = "events_file")]
+    pub(crate) events_fd: Option<i32>,
+    /// Append the progress of the execution as JSON lines to the file, e.g. a named pipe
+    #[arg(long, global = true)]
+    pub(crate) events_file: Option<PathBuf>,
+    /// Record the responses of the web pages, web searches and context URLs in the JSON file, e.g. `web.json`, for `--replay-web`
+    #[arg(long, global = true, conflicts_with = "replay_web")]
+    pub(crate) record_web: Option<PathBuf>,
+    /// Answer the web requests with the responses recorded by `--record-web` instead of sending them
+    #[arg(long, global = true)]
+    pub(crate) replay_web: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Commands {
+    /// Start interactive session
+    #[command(name = "session")]
+    Session,
+    /// Ask a question to the configured LLM
+    Ask(AskArgs),
+    /// Ask the LLM to generate a plan and execute it
+    Act(ActArgs),
+    /// Run a task of the project defined in `.cliff.toml`, lists the tasks when no name is given
+    Run {
+        /// Name of the task
+        task: Option<String>,
+        /// Value of a `{{name}}` placeholder of the task instruction in the form name=value, can be repeated
+        #[arg(long = "param", value_parser = parse_variable, requires = "task")]
+        params: Vec<(String, String)>,
+        /// Commit the changes of every step writing files or running commands to the new branch `cliff/<run id>`, the working tree has to be clean
+        #[arg(long, requires = "task")]
+        git_commits: bool,
+        /// Keep the scratch directory the plan puts its intermediate files into after the run instead of removing it
+        #[arg(long, requires = "task")]
+        keep_scratch: bool,
+        /// Automatically confirm actions in the plan: `reads` (read-only actions), `safe` (reads and writes inside the working directory) or `all`
+        #[arg(long, value_enum, default_value = "none", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+        /// What to do when an action fails: `ask`, `replan` or `abort` (defaults to the configured policy)
+        #[arg(long, value_enum)]
+        on_failure: Option<FailurePolicy>,
+        /// Environment to execute the commands and file actions in: `local`, `ssh://[user@]host[:port]` or `docker://container` (defaults to `target` in `.cliff.toml`)
+        #[arg(long, value_parser = parse_target)]
+        target: Option<Arc<dyn ExecutionTarget>>
+    },
+    /// List, show and run the reusable plans: the built-in ones and the ones saved in the plans directory of the config
+    Plans(PlansArgs),
+    /// Run tasks of the project on a cron schedule without prompts, or once with `--once`
+    Daemon {
+        /// Cron schedule in the local time, e.g. "0 9 * * *" or "@hourly"
+        #[arg(long, value_parser = CronSchedule::parse, required_unless_present = "once")]
+        schedule: Option<CronSchedule>,
+        /// Name of the task in `.cliff.toml` to run, can be repeated
+        #[arg(long = "task", required = true)]
+        tasks: Vec<String>,
+        /// Value of a `{{name}}` placeholder of the task instructions in the form name=value, can be repeated
+        #[arg(long = "param", value_parser = parse_variable)]
+        params: Vec<(String, String)>,
+        /// Run the tasks once right away and exit
+        #[arg(long, conflicts_with = "schedule")]
+        once: bool,
+        /// Actions confirmed automatically: `none`, `reads`, `safe` or `all`, the other actions are denied
+        #[arg(long, value_enum, default_value = "reads", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+        /// What to do when an action fails: `replan` or `abort` (defaults to the configured policy, `ask` aborts)
+        #[arg(long, value_enum)]
+        on_failure: Option<FailurePolicy>,
+        /// Shell command receiving a summary of every finished task on its standard input, e.g. `notify-send cliff "$(cat)"`
+        #[arg(long)]
+        notify: Option<String>,
+        /// Environment to execute the commands and file actions in: `local`, `ssh://[user@]host[:port]` or `docker://container` (the `target` in `.cliff.toml` is not used without it)
+        #[arg(long, value_parser = parse_target)]
+        target: Option<Arc<dyn ExecutionTarget>>
+    },
+    /// Serve webhooks triggering the tasks of the project, authenticated with the secret in `$CLIFF_WEBHOOK_SECRET`, or the JSON requests of an editor extension with `--protocol stdio`
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        listen: SocketAddr,
+        /// Actions confirmed automatically: `none`, `reads`, `safe` or `all`, the other actions are denied for the webhooks and asked for with `--protocol stdio`
+        #[arg(long, value_enum, default_value = "reads", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+        /// `http` for the webhooks or `stdio` for the requests read as JSON lines from the standard input, answered with the events on the standard output
+        #[arg(long, value_enum, default_value = "http")]
+        protocol: Protocol,
+        /// Environment to execute the commands and file actions in: `local`, `ssh://[user@]host[:port]` or `docker://container` (the `target` in `.cliff.toml` is used only when confirmed)
+        #[arg(long, value_parser = parse_target)]
+        target: Option<Arc<dyn ExecutionTarget>>
+    },
+    /// Measure how well the models plan the instructions of an eval suite, e.g. before and after a change of the prompts
+    Eval {
+        /// YAML file with the cases: the instructions and the checks of their plans
+        #[arg(long)]
+        suite: PathBuf,
+        /// Models to compare, comma-separated (defaults to the active model)
+        #[arg(long, value_delimiter = ',')]
+        models: Vec<String>,
+        /// Actions of the plans confirmed automatically in the directories of the cases: `none`, `reads`, `safe` or `all`, the other actions are denied
+        #[arg(long, value_enum, default_value = "safe", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+        /// Fail when the pass rate of a model is lower, in percent, e.g. in CI
+        #[arg(long)]
+        min_pass_rate: Option<f64>,
+    },
+    /// Create a project from a template and optionally let the LLM customize it
+    Scaffold(ScaffoldArgs),
+    /// Extract structured data from the context as JSON conforming to a JSON schema
+    Extract {
+        /// What to extract, e.g. "extract the product listings"
+        instruction: String,
+        /// File with the JSON schema the extracted data must conform to
+        #[arg(long)]
+        schema: String,
+    },
+    /// Compute the embedding of a text or a file with the embedding model, print it or store it in an index, or store the embeddings of the files of a directory
+    Embed {
+        /// Text or path of the file or the directory to embed
+        input: String,
+        /// Name of the index to store the embedding in instead of printing it
+        #[arg(long)]
+        store: Option<String>,
+    },
+    /// Find the entries of an index most similar to the query
+    Similar {
+        /// Text to search for
+        query: String,
+        /// Name of the index to search
+        #[arg(long)]
+        index: String,
+        /// Number of the most similar entries to show
+        #[arg(long, default_value = "5")]
+        top: usize,
+    },
+    /// Estimate the number of tokens of files or prompts, `-` reads the standard input
+    Tokens {
+        /// Paths of the files, texts or `-`
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /// Transform a file with the LLM according to an instruction, e.g. convert its format
+    Transform {
+        /// File to transform
+        #[arg(long = "in")]
+        input: String,
+        /// Instruction describing the transformation
+        #[arg(long)]
+        prompt: String,
+        /// File to write the result to (defaults to the input file)
+        #[arg(long)]
+        out: Option<String>,
+        /// Maximum size in bytes of the parts of a large file transformed separately
+        #[arg(long, default_value = "32768")]
+        chunk_size: usize,
+        /// Write the result without showing the diff and asking for confirmation: `safe` (inside the working directory) or `all`
+        #[arg(long, value_enum, default_value = "none", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+    },
+    /// Update cliff to the latest release
+    SelfUpdate {
+        /// Only check whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check the configuration, the models and the environment and suggest fixes for the problems found
+    Doctor {
+        /// Skip the test calls to the configured models
+        #[arg(long)]
+        skip_calls: bool,
+    },
+    /// Export the trace of a run: its plans, actions and LLM calls with their timings and tokens
+    Trace {
+        /// Id of the run, the latest run when not given
+        run_id: Option<String>,
+        /// Format of the trace: `chrome` trace events or OpenTelemetry `otlp` JSON
+        #[arg(long, value_enum, default_value = "chrome")]
+        format: TraceFormat,
+        /// File to write the trace to instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Review what the runs did after the fact
+    History(HistoryArgs),
+    /// Show usage examples of all the subcommands or of one of them
+    Examples {
+        /// Name of the subcommand, e.g. `ask`
+        command: Option<String>,
+    },
+    /// Manage LLM configurations
+    Config(ConfigArgs),
+    /// Manage reusable prompt snippets
+    Snippets(SnippetsArgs),
+    /// Manage the profiles, every profile has its own config and state
+    Profile(ProfileArgs),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct AskArgs {
+    /// The prompt/question to ask the LLM, `@name` at the start expands the snippet with that name (with `--batch` the instruction preceding every prompt),
+    /// `{file:<path>}`, `{file:<path>:last=<n>}` and `{cmd:<command>}` are replaced with the file content or the command output
+    #[arg(required_unless_present = "batch")]
+    pub(crate) prompt: Option<String>,
+    /// Snippet variable in the form key=value, can be repeated
+    #[arg(long = "var", value_parser = parse_variable)]
+    pub(crate) variables: Vec<(String, String)>,
+    /// Output only the code from the fenced code blocks of the answer
+    #[arg(long)]
+    pub(crate) code_only: bool,
+    /// Format of the answer: `markdown`, `plain`, `json` or `code`, the answers not conforming to it are asked to be repaired
+    #[arg(long, value_enum, conflicts_with_all = ["code_only", "follow_up"])]
+    pub(crate) format: Option<AnswerFormat>,
+    /// Shell command receiving the answer on its standard input, e.g. `wl-copy`
+    #[arg(long)]
+    pub(crate) pipe: Option<String>,
+    /// File to save the answer to
+    #[arg(long)]
+    pub(crate) save: Option<PathBuf>,
+    /// Follow up on the previous question, its prompt and answer are sent as the conversation history
+    #[arg(long = "continue", conflicts_with = "batch")]
+    pub(crate) follow_up: bool,
+    /// File with a prompt per line (plain text or a JSON object with a "prompt" field), every prompt is asked independently
+    #[arg(long, conflicts_with_all = ["pipe", "save"])]
+    pub(crate) batch: Option<PathBuf>,
+    /// Output format of the batch results: `text` or `jsonl`
+    #[arg(long, value_enum, default_value = "text", requires = "batch")]
+    pub(crate) output: OutputFormat,
+    /// Number of the batch prompts asked at the same time
+    #[arg(long, default_value = "1", requires = "batch")]
+    pub(crate) concurrency: usize,
+    /// Maximum number of the batch prompts asked per minute
+    #[arg(long, requires = "batch")]
+    pub(crate) rate_limit: Option<u32>,
+    /// Number of the candidate answers requested in parallel, shown side by side to choose one
+    #[arg(long = "n", default_value = "1", value_parser = clap::value_parser!(u8).range(1..=10), conflicts_with_all = ["batch", "follow_up"])]
+    pub(crate) candidates: u8,
+    /// Let the LLM judge the candidate answers and choose the best one instead of the user
+    #[arg(long)]
+    pub(crate) best_of: bool,
+    /// Configured LLM model judging the candidates (defaults to the active model)
+    #[arg(long, requires = "best_of")]
+    pub(crate) judge_model: Option<String>,
+    /// Configured models asked at the same time, e.g. `fast,smart`, their answers are shown side by side with the latencies
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["batch", "follow_up", "candidates", "code_only", "pipe", "save"])]
+    pub(crate) models: Vec<String>,
+    /// Validator of the answer, can be repeated: `regex:<pattern>`, `schema:<file>` or a shell command receiving the answer on its
+    /// standard input, e.g. `jq .`, the answers failing it are asked to be corrected
+    #[arg(long = "validate", value_parser = validators::parse_validator, conflicts_with_all = ["batch", "follow_up", "candidates", "models"])]
+    pub(crate) validators: Vec<Validator>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ActArgs {
+    /// The instruction or goal for the LLM
+    #[arg(required_unless_present = "plan")]
+    pub(crate) instruction: Option<String>,
+    /// Execute the plan saved in the JSON file instead of asking the LLM for one
+    #[arg(long, conflicts_with_all = ["instruction", "consistency", "review", "apply_review", "save_plan"])]
+    pub(crate) plan: Option<PathBuf>,
+    /// Save the plan to the JSON file before executing it, declare `params` in it to reuse it with `--plan`
+    #[arg(long)]
+    pub(crate) save_plan: Option<PathBuf>,
+    /// Value of a parameter of the plan in the form name=value, can be repeated, the user is asked for the others
+    #[arg(long = "param", value_parser = parse_variable)]
+    pub(crate) params: Vec<(String, String)>,
+    /// Automatically confirm actions in the plan: `reads` (read-only actions), `safe` (reads and writes inside the working directory) or `all`
+    #[arg(long, value_enum, default_value = "none", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+    pub(crate) auto_confirm: AutoConfirm,
+    /// What to do when an action fails: `ask`, `replan` or `abort` (defaults to the configured policy)
+    #[arg(long, value_enum)]
+    pub(crate) on_failure: Option<FailurePolicy>,
+    /// Ask the LLM to review the generated plan for risky, redundant or missing steps
+    #[arg(long, default_value = "false")]
+    pub(crate) review: bool,
+    /// Configured LLM model to use for the review (defaults to the active model)
+    #[arg(long)]
+    pub(crate) review_model: Option<String>,
+    /// Execute the revised plan suggested by the review instead of the original one
+    #[arg(long, default_value = "false")]
+    pub(crate) apply_review: bool,
+    /// Generate this many plans and use the one most of them agree on, reducing the impact of a single bad generation
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=5))]
+    pub(crate) consistency: Option<u8>,
+    /// What to do when the generated plans diverge: `reconcile` them with the LLM or use the most `conservative` one
+    #[arg(long, value_enum, default_value = "reconcile", requires = "consistency")]
+    pub(crate) on_divergence: DivergencePolicy,
+    /// Commit the changes of every step writing files or running commands to the new branch `cliff/<run id>`, the working tree has to be clean
+    #[arg(long)]
+    pub(crate) git_commits: bool,
+    /// Keep the scratch directory the plan puts its intermediate files into after the run instead of removing it
+    #[arg(long)]
+    pub(crate) keep_scratch: bool,
+    /// Environment to execute the commands and file actions in: `local`, `ssh://[user@]host[:port]` or `docker://container` (defaults to `target` in `.cliff.toml`)
+    #[arg(long, value_parser = parse_target)]
+    pub(crate) target: Option<Arc<dyn ExecutionTarget>>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ScaffoldArgs {
+    /// Name of the project template directory in the templates directory
+    pub(crate) template: String,
+    /// Directory to create the project in
+    #[arg(short, long, default_value = ".")]
+    pub(crate) target: String,
+    /// Template variable in the form key=value, can be repeated
+    #[arg(long = "var", value_parser = parse_variable)]
+    pub(crate) variables: Vec<(String, String)>,
+    /// Instruction for the LLM to customize the created project
+    pub(crate) instruction: Option<String>,
+    /// Automatically confirm actions when customizing the project: `reads`, `safe` or `all`
+    #[arg(long, value_enum, default_value = "none", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+    pub(crate) auto_confirm: AutoConfirm,
+    /// What to do when an action fails: `ask`, `replan` or `abort` (defaults to the configured policy)
+    #[arg(long, value_enum)]
+    pub(crate) on_failure: Option<FailurePolicy>,
+    /// Overwrite the files already existing in the target directory
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct ConfigArgs {
+    /// Configuration sub-command
+    #[command(subcommand)]
+    pub(crate) action: ConfigAction,
+}
+
+// Parsed once per run, the size of the largest variant does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigAction {
+    /// Add a new model configuration
+    Add {
+        #[arg(short, long)]
+        name: String,
+        /// Provider whose settings the model uses, e.g. `openai`, the other options override them
+        #[arg(long, visible_alias = "preset", value_parser = clap::builder::PossibleValuesParser::new(onboarding::provider_names()))]
+        provider: Option<String>,
+        #[arg(long)]
+        model_identifier: Option<String>,
+        /// API URL, `{{model}}` in it is replaced with the model identifier, e.g. the deployment of Azure OpenAI
+        #[arg(long, required_unless_present = "provider")]
+        api_url: Option<String>,
+        /// Parameter added to the query of the API URL, e.g. `api-version=2024-10-21`, can be repeated
+        #[arg(long = "query-param", value_parser = parse_variable)]
+        query_params: Vec<(String, String)>,
+        #[arg(long)]
+        api_key: Option<String>,
+        #[arg(long)]
+        api_key_header: Option<String>,
+        /// How the requests are made: `template` (the default) renders the request format, `openai`, `anthropic` and `gemini` use the API of the provider, `mock` answers without an API
+        #[arg(long, value_enum)]
+        kind: Option<ModelKind>,
+        /// Request body with the `{{messages}}`, `{{prompt}}` and `{{model}}` placeholders, required by the template models
+        #[arg(long)]
+        request_format: Option<String>,
+        /// JSONPath of the answer in the response, required by the template models
+        #[arg(long)]
+        response_json_path: Option<String>,
+        /// Proxy of the requests to the model instead of the one of the environment, e.g. socks5h://127.0.0.1:9050, `none` connects directly
+        #[arg(long)]
+        proxy: Option<String>,
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+    /// Set the default model
+    SetDefault {
+        /// Name of the model to set as default
+        name: String,
+    },
+    /// Set what the model supports, the JSON mode and the limits are applied to its requests
+    SetCapabilities {
+        /// Name of the model
+        name: String,
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+    /// Set the proxy of the requests to the model instead of the one of the environment
+    SetProxy {
+        /// Name of the model
+        name: String,
+        /// Proxy URL (http, https, socks5 or socks5h), `none` connects directly, no proxy uses the one of the environment
+        proxy: Option<String>,
+    },
+    /// Set the shell command adapting the request bodies of the model, e.g. for a gateway needing wrapper fields or signatures
+    SetRequestTransform {
+        /// Name of the model
+        name: String,
+        /// Shell command receiving the request body on its standard input and printing the body to send, no command sends the bodies as they are
+        command: Option<String>,
+    },
+    /// Set how the sessions continue the conversations the API of the model keeps, instead of resending the transcript
+    SetConversation {
+        /// Name of the model
+        name: String,
+        /// JSONPath of the conversation id in the response, e.g. `$.id`, no path resends the transcript every turn
+        #[arg(long, requires = "param")]
+        json_path: Option<String>,
+        /// Request field the conversation id is sent back in, e.g. `previous_response_id`
+        #[arg(long, requires = "json_path")]
+        param: Option<String>,
+    },
+    /// Set how the answers of the template model are streamed to `ask` and the sessions as they arrive
+    SetStream {
+        /// Name of the model
+        name: String,
+        /// Format of the streamed answer: `sse` (server-sent events) or `ndjson` (a JSON object per line), no format waits for the whole answer
+        #[arg(long, value_enum, requires = "json_path")]
+        format: Option<StreamFormat>,
+        /// JSONPath of the part of the answer in every streamed object, e.g. `$.choices[0].delta.content`
+        #[arg(long, requires = "format")]
+        json_path: Option<String>,
+    },
+    /// Set the model compressing the oversized context and history before they are sent to the main model
+    SetSummarizerModel {
+        /// Name of the configured summarizer model, no name disables the compression
+        name: Option<String>,
+    },
+    /// Set the model checking the context files and web pages for prompt injection in addition to the heuristics
+    SetGuardrailModel {
+        /// Name of the configured guardrail model, no name leaves only the heuristics
+        name: Option<String>,
+    },
+    /// Set the model computing the embeddings for `embed` and `similar`
+    SetEmbeddingModel {
+        /// Name of the configured embedding model
+        name: String,
+    },
+    /// Set the current model for this session (temporary override)
+    SetCurrent {
+         /// Name of the model to set as current
+        name: String,
+    },
+    /// Clear the current model selection, falling back to default
+    ClearCurrent,
+    /// List all configured models
+    List,
+    /// Delete a configured model
+    Delete {
+        /// Name of the model to delete
+        name: String,
+    },
+    /// Set what to do when an action fails during plan execution
+    SetOnFailure {
+        /// Failure policy: `ask`, `replan` or `abort`
+        #[arg(value_enum)]
+        policy: FailurePolicy,
+    },
+    /// Set the shell used to run commands, by default detected from `$SHELL`
+    SetShell {
+        /// Shell: `sh`, `bash`, `zsh`, `fish`, `nushell` or `powershell`
+        #[arg(value_enum)]
+        shell: Shell,
+        /// Run commands in a login shell
+        #[arg(long)]
+        login: bool,
+    },
+    /// Allow expanding the listed environment variables (e.g. `HOME,PROJECT_DIR`) in the paths of actions, no variables disable the expansion
+    SetEnvExpansion {
+        /// Comma-delimited names of the environment variables
+        #[arg(value_delimiter = ',')]
+        variables: Vec<String>,
+    },
+    /// Protect the paths matching the glob patterns (e.g. `~/.ssh/**,/etc/**,**/*.pem`) from being written, moved or deleted by any action, no patterns remove the protection
+    SetProtectedPaths {
+        /// Comma-delimited glob patterns
+        #[arg(value_delimiter = ',')]
+        patterns: Vec<String>,
+    },
+    /// Skip the paths matching the glob patterns (e.g. `**/*.min.js,fixtures/**`) when finding and listing files, loading directories as context and indexing them, no patterns remove the exclusion
+    SetExcludedPaths {
+        /// Comma-delimited glob patterns
+        #[arg(value_delimiter = ',')]
+        patterns: Vec<String>,
+    },
+    /// Limit the file changes of a single run, the action exceeding a limit stops the run, no limits remove the quotas
+    SetQuotas {
+        /// Maximum number of bytes written to the files
+        #[arg(long)]
+        max_bytes_written: Option<u64>,
+        /// Maximum number of the files created
+        #[arg(long)]
+        max_files_created: Option<u64>,
+        /// Maximum number of the files deleted
+        #[arg(long)]
+        max_files_deleted: Option<u64>,
+    },
+    /// Export the metrics of the runs and their steps, without options the metrics are not collected
+    SetMetrics {
+        /// Prometheus textfile the counters are accumulated in, e.g. for the textfile collector of the node exporter
+        #[arg(long)]
+        textfile: Option<PathBuf>,
+        /// Address of the statsd server the metrics are sent to, e.g. `127.0.0.1:8125`
+        #[arg(long)]
+        statsd: Option<String>,
+    },
+    /// Check the project after every step of a plan writing files, e.g. with `cargo check` in a Rust project, and show the
+    /// diagnostics of the edited files to the LLM, no options disable the checks
+    SetDiagnostics {
+        /// Check the projects with Cargo.toml, tsconfig.json or go.mod with `cargo check`, `tsc --noEmit` or `go vet`
+        #[arg(long)]
+        enabled: bool,
+        /// Command checking the project instead of the detected one, implies `--enabled`
+        #[arg(long)]
+        command: Option<String>,
+    },
+    /// Enable or disable the daily check for a new release of cliff
+    SetUpdateCheck {
+        /// `true` to check for new releases
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Set how many of the latest turns of a session are sent to the LLM verbatim, the older turns are summarized
+    SetSessionWindow {
+        /// Number of question and answer turns
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        max_turns: u64,
+    },
+    /// Set how the outputs of the executed actions are trimmed in the history sent to the LLM, the options not given
+    /// are reset to their defaults
+    SetOutputTrimming {
+        /// Lines kept from the start of a longer output
+        #[arg(long, default_value_t = TrimmingConfig::default().head_lines)]
+        head_lines: usize,
+        /// Lines kept from the end of a longer output, all the lines are kept when both are 0
+        #[arg(long, default_value_t = TrimmingConfig::default().tail_lines)]
+        tail_lines: usize,
+        /// Characters kept of a longer line, unlimited when 0
+        #[arg(long, default_value_t = TrimmingConfig::default().max_line_chars)]
+        max_line_chars: usize,
+        /// Keep the ANSI color and cursor codes
+        #[arg(long)]
+        keep_ansi: bool,
+        /// Keep the consecutive repeated lines instead of sending them once with their count
+        #[arg(long)]
+        keep_repeated: bool,
+    },
+    /// Set how many lines of the contents of the file actions the plans show, the options not given are reset to
+    /// their defaults, 0 shows all the lines
+    SetPlanPreview {
+        #[arg(long, default_value_t = PreviewConfig::default().create_file)]
+        create_file: usize,
+        #[arg(long, default_value_t = PreviewConfig::default().overwrite_file)]
+        overwrite_file: usize,
+        #[arg(long, default_value_t = PreviewConfig::default().replace_file_lines)]
+        replace_file_lines: usize,
+        #[arg(long, default_value_t = PreviewConfig::default().append_to_file)]
+        append_to_file: usize,
+    },
+    /// Show the current configuration path
+    Path,
+    /// Check a model: its request format, response JSON path and credentials, and make a test call
+    Test {
+        /// Name of the model, the active model when not given
+        name: Option<String>,
+    },
+    /// Add the models pulled into a running Ollama, named after them, e.g. `qwen2.5:7b`
+    DiscoverOllama {
+        /// URL of the Ollama server
+        #[arg(long, default_value = onboarding::OLLAMA_URL)]
+        url: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CapabilityArgs {
+    /// The API can be asked to answer with JSON (`response_format`, `format` or `responseMimeType`)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    json_mode: Option<bool>,
+    /// The API supports tool calls
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    tools: Option<bool>,
+    /// Images can be sent as context, in the `{{messages}}` of the request
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub(crate) vision: Option<bool>,
+    /// Maximum number of the tokens of the request and the answer, 0 for unlimited
+    #[arg(long)]
+    pub(crate) context_window: Option<usize>,
+    /// Maximum number of the tokens of the answer, 0 for unlimited
+    #[arg(long)]
+    pub(crate) max_output_tokens: Option<usize>,
+}
+
+impl CapabilityArgs {
+    // Updates the given capabilities, the others are kept
+    pub(crate) fn apply(self, capabilities: &mut ModelCapabilities) {
+        let limit = |tokens: usize| Some(tokens).filter(|tokens| *tokens > 0);
+        capabilities.supports_json_mode = self.json_mode.unwrap_or(capabilities.supports_json_mode);
+        capabilities.supports_tools = self.tools.unwrap_or(capabilities.supports_tools);
+        capabilities.supports_vision = self.vision.unwrap_or(capabilities.supports_vision);
+        if let Some(context_window) = self.context_window {
+            capabilities.context_window = limit(context_window);
+        }
+        if let Some(max_output_tokens) = self.max_output_tokens {
+            capabilities.max_output_tokens = limit(max_output_tokens);
+        }
+    }
+}
+
+impl Commands {
+    // Whether the command calls the LLM, the onboarding is offered only for such commands
+    pub(crate) fn requires_model(&self) -> bool {
+        match self {
+            Commands::Ask(_) | Commands::Act(_) | Commands::Session | Commands::Extract { .. } | Commands::Transform { .. } => true,
+            Commands::Scaffold(args) => args.instruction.is_some(),
+            Commands::Run { task, .. } => task.is_some(),
+            Commands::Daemon { .. } => true,
+            Commands::Eval { .. } => true,
+            Commands::Snippets(args) => matches!(args.action, SnippetsAction::Use { .. }),
+            Commands::Plans(args) => matches!(args.action, PlansAction::Run { .. }),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct SnippetsArgs {
+    /// Snippets sub-command
+    #[command(subcommand)]
+    pub(crate) action: SnippetsAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum SnippetsAction {
+    /// Add or replace a prompt snippet, `{{input}}` and other `{{variable}}` placeholders are filled in when it is used
+    Add {
+        /// Name of the snippet
+        name: String,
+        /// Text of the snippet
+        snippet: String,
+    },
+    /// List all the snippets
+    List,
+    /// Delete a snippet
+    Delete {
+        /// Name of the snippet to delete
+        name: String,
+    },
+    /// Ask the LLM using a snippet
+    Use {
+        /// Name of the snippet to use
+        name: String,
+        /// Text to substitute for the `{{input}}` placeholder
+        input: Option<String>,
+        /// Snippet variable in the form key=value, can be repeated
+        #[arg(long = "var", value_parser = parse_variable)]
+        variables: Vec<(String, String)>,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct PlansArgs {
+    /// Plans sub-command
+    #[command(subcommand)]
+    pub(crate) action: PlansAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum PlansAction {
+    /// List the plans with their descriptions and parameters
+    List,
+    /// Print the JSON of the plan, e.g. to save a customized copy into the plans directory
+    Show {
+        /// Name of the plan
+        name: String,
+    },
+    /// Execute the plan, the parameters not given are asked for
+    Run {
+        /// Name of the plan
+        name: String,
+        /// Value of a parameter of the plan in the form name=value, can be repeated
+        #[arg(long = "param", value_parser = parse_variable)]
+        params: Vec<(String, String)>,
+        /// Commit the changes of every step writing files or running commands to the new branch `cliff/<run id>`, the working tree has to be clean
+        #[arg(long)]
+        git_commits: bool,
+        /// Keep the scratch directory the plan puts its intermediate files into after the run instead of removing it
+        #[arg(long)]
+        keep_scratch: bool,
+        /// Automatically confirm actions in the plan: `reads` (read-only actions), `safe` (reads and writes inside the working directory) or `all`
+        #[arg(long, value_enum, default_value = "none", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+        auto_confirm: AutoConfirm,
+        /// What to do when an action fails: `ask`, `replan` or `abort` (defaults to the configured policy)
+        #[arg(long, value_enum)]
+        on_failure: Option<FailurePolicy>,
+        /// Environment to execute the commands and file actions in: `local`, `ssh://[user@]host[:port]` or `docker://container` (defaults to `target` in `.cliff.toml`)
+        #[arg(long, value_parser = parse_target)]
+        target: Option<Arc<dyn ExecutionTarget>>
+    },
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct HistoryArgs {
+    /// History sub-command
+    #[command(subcommand)]
+    pub(crate) action: HistoryAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum HistoryAction {
+    /// Show the combined diff of the files the run changed with its actions, as a patch applicable with `git apply`
+    Diff {
+        /// Id of the run, the latest run when not given
+        run_id: Option<String>,
+        /// File to write the patch to instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct ProfileArgs {
+    /// Profile sub-command
+    #[command(subcommand)]
+    pub(crate) action: ProfileAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ProfileAction {
+    /// List the profiles, the selected one is marked
+    List,
+    /// Create an empty profile, select it with `--profile <name>`
+    Create {
+        /// Name of the profile
+        name: String,
+    },
+    /// Delete the profile with its config and state
+    Delete {
+        /// Name of the profile to delete
+        name: String,
+    },
+}
+
+pub(crate) fn parse_variable(value: &str) -> Result<(String, String)> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or(anyhow!("Invalid variable '{}', expected key=value", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples;
+    use clap::CommandFactory;
+
+    // Splits the example command into arguments the way a shell does for the quotes used in the examples
+    fn split_example(command: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let (mut arg, mut quote, mut in_arg) = (String::new(), None, false);
+        for c in command.chars() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), c) => arg.push(c),
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    in_arg = true;
+                }
+                (None, ' ') => {
+                    if in_arg {
+                        args.push(std::mem::take(&mut arg));
+                    }
+                    in_arg = false;
+                }
+                (None, c) => {
+                    arg.push(c);
+                    in_arg = true;
+                }
+            }
+        }
+        if in_arg {
+            args.push(arg);
+        }
+        args
+    }
+
+    #[test]
+    fn test_examples_are_valid_commands() {
+        let command = Cli::command();
+        for (name, examples) in examples::EXAMPLES {
+            assert!(command.find_subcommand(name).is_some(), "No subcommand '{}'", name);
+            for example in *examples {
+                let args = split_example(example.command);
+                assert_eq!(args[1], *name, "Example of another subcommand: {}", example.command);
+                if let Err(e) = Cli::try_parse_from(&args) {
+                    panic!("Invalid example '{}': {}", example.command, e);
+                }
+            }
+        }
+    }
+}
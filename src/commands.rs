@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use crate::actions::target::{ExecutionTarget, ProtectedTarget, QuotaTarget};
+use crate::classification::AutoConfirm;
+use crate::config::{Config, FailurePolicy, Model, ProjectConfig};
+use crate::error::{ActionError, ConfigError};
+use crate::events::{self, Event};
+use crate::executor::ExecutionSettings;
+use crate::runs::{self, RunMetadata};
+use crate::{changes, git_commits, metrics, scratch, trace};
+use std::sync::Arc;
+
+pub(crate) mod act;
+pub(crate) mod ask;
+pub(crate) mod config;
+pub(crate) mod daemon;
+pub(crate) mod embed;
+pub(crate) mod eval;
+pub(crate) mod examples;
+pub(crate) mod extract;
+pub(crate) mod history;
+pub(crate) mod plans;
+pub(crate) mod profile;
+pub(crate) mod scaffold;
+pub(crate) mod serve;
+pub(crate) mod snippets;
+pub(crate) mod tasks;
+pub(crate) mod tokens;
+pub(crate) mod transform;
+
+// Options of executing a plan as a recorded run, common to `act`, `run` and `plans run`
+pub(crate) struct RunOptions {
+    pub(crate) auto_confirm: AutoConfirm,
+    pub(crate) on_failure: Option<FailurePolicy>,
+    pub(crate) target: Option<Arc<dyn ExecutionTarget>>,
+    pub(crate) git_commits: bool,
+    pub(crate) keep_scratch: bool,
+}
+
+pub(crate) fn get_active_model(config: &Config) -> Result<&Model> {
+    config.get_active_model().ok_or(ConfigError::NoActiveModel.into())
+}
+
+pub(crate) fn get_embedding_model(config: &Config) -> Result<&Model> {
+    config.get_embedding_model().ok_or(anyhow!("Error: No embedding model configured. Use 'cliff config add' and 'cliff config set-embedding-model'."))
+}
+
+// The given target or the target of `.cliff.toml` in the working directory, which is used only when confirmed
+pub(crate) fn project_target(target: Option<Arc<dyn ExecutionTarget>>, auto_confirm: AutoConfirm) -> Result<Arc<dyn ExecutionTarget>> {
+    match target {
+        Some(target) => Ok(target),
+        None => ProjectConfig::load(&std::env::current_dir()?)?.target(auto_confirm),
+    }
+}
+
+// Settings of executing the plans, writes on a remote target are never confirmed automatically as safe and the protected
+// paths are denied on every target
+pub(crate) fn execution_settings(auto_confirm: AutoConfirm, on_failure: Option<FailurePolicy>, target: Arc<dyn ExecutionTarget>, config: &Config) -> ExecutionSettings {
+    let auto_confirm = if !target.is_local() && auto_confirm == AutoConfirm::Safe {
+        println!("Writes on {} are not considered safe, only read-only actions are confirmed automatically.", target);
+        AutoConfirm::Reads
+    } else {
+        auto_confirm
+    };
+    let target: Arc<dyn ExecutionTarget> = Arc::new(ProtectedTarget::new(target));
+    let target: Arc<dyn ExecutionTarget> = if config.quotas.is_unlimited() {
+        target
+    } else {
+        Arc::new(QuotaTarget::new(target, config.quotas))
+    };
+    ExecutionSettings { auto_confirm, on_failure: on_failure.unwrap_or(config.on_failure), shell: config.shell, target }
+}
+
+// Records the run of `command` and executes the plan with `execute` on the target of the options, with the changes of the
+// steps committed and the scratch directory of the run when asked for
+pub(crate) async fn run_plan(command: &str, instruction: &str, model: &Model, options: RunOptions, config: &Config, execute: impl AsyncFnOnce(ExecutionSettings) -> Result<()>) -> Result<()> {
+    let mut run = RunMetadata::new(command, instruction, &model.name);
+    record_run(&run);
+    println!("Run id: {}", run.id);
+    let result = async {
+        let target = project_target(options.target, options.auto_confirm)?;
+        let settings = execution_settings(options.auto_confirm, options.on_failure, target, config);
+        if options.git_commits {
+            git_commits::enable(&run.id, settings.target.as_ref()).await?;
+        }
+        let _scratch = settings.target.is_local().then(|| scratch::create(&run.id, options.keep_scratch)).transpose()?;
+        execute(settings).await
+    }.await;
+    finish_run(&mut run, &result);
+    result
+}
+
+// The given run id or the id of the latest run
+pub(crate) fn run_id_or_latest(run_id: Option<String>) -> Result<String> {
+    match run_id {
+        Some(run_id) => Ok(run_id),
+        None => Ok(runs::latest_id()?
+            .ok_or_else(|| ActionError::NotFound("No runs were recorded yet".to_string()))?),
+    }
+}
+
+pub(crate) fn record_run(run: &RunMetadata) {
+    if let Err(e) = runs::record(run) {
+        eprintln!("Warning: Failed to record run '{}': {}", run.id, e);
+    }
+}
+
+// Records the finished run with its metrics, trace and file changes and prints its summary
+pub(crate) fn finish_run(run: &mut RunMetadata, result: &Result<()>) {
+    run.finish(result);
+    events::emit(Event::RunFinished { run });
+    record_run(run);
+    metrics::record_run(run);
+    if let Err(e) = trace::save(run) {
+        eprintln!("Warning: Failed to save the trace of run '{}': {:#}", run.id, e);
+    }
+    if let Err(e) = changes::save(run) {
+        eprintln!("Warning: Failed to save the changes of run '{}': {:#}", run.id, e);
+    }
+    println!("{}", run.summary());
+}
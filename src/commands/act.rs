@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Context, Result};
+use crate::cli::ActArgs;
+use crate::config::Config;
+use crate::consistency;
+use crate::error::IoResultExt;
+use crate::executor::{self, Plan, MAIN_PLAN_LABEL};
+use crate::llm::{ask_llm_for_plan, ask_llm_for_plan_review};
+use reqwest::Client;
+use std::path::Path;
+use super::{get_active_model, run_plan, RunOptions};
+
+pub(crate) async fn act(args: ActArgs, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    let ActArgs { instruction, plan: plan_file, save_plan, params, auto_confirm, on_failure, review, review_model, apply_review, consistency, on_divergence, git_commits, keep_scratch, target } = args;
+    let active_model = get_active_model(config)?;
+    let instruction = match &plan_file {
+        Some(path) => format!("plan {}", path.display()),
+        None => instruction.unwrap_or_default(),
+    };
+    let options = RunOptions { auto_confirm, on_failure, target, git_commits, keep_scratch };
+    run_plan("act", &instruction, active_model, options, config, async |settings| {
+        let mut plan = match (&plan_file, consistency) {
+            (Some(path), _) => load_plan(path)?,
+            (None, Some(samples)) => consistency::consistent_plan(active_model, &instruction, context, &settings, samples as usize, on_divergence, client).await.context("Error during LLM call")?,
+            (None, None) => ask_llm_for_plan(active_model, &instruction, context, &Vec::new(), &settings, client).await.context("Error during LLM call")?,
+        };
+        if let Some(path) = &save_plan {
+            std::fs::write(path, serde_json::to_string_pretty(&plan)?).with_action_context(|| format!("Failed to save the plan: {}", path.display()))?;
+            println!("Plan saved to {}.", path.display());
+        }
+        plan = plan.with_params(&params.into_iter().collect())?;
+        plan.display();
+        if review || apply_review {
+            let reviewer_model = match &review_model {
+                Some(name) => config.models.get(name).ok_or(anyhow!("Error: Review model '{}' not found.", name))?,
+                None => active_model
+            };
+            let plan_review = ask_llm_for_plan_review(reviewer_model, &instruction, &plan, client).await.context("Error during plan review")?;
+            plan_review.display();
+            if let (true, Some(revised_plan)) = (apply_review, plan_review.revised_plan) {
+                println!("Applying the revised plan.");
+                plan = revised_plan;
+                plan.display();
+            }
+        }
+        executor::execute_plan(&plan, active_model, client, &mut Vec::new(), settings, &[MAIN_PLAN_LABEL.to_string()]).await
+    }).await
+}
+
+fn load_plan(path: &Path) -> Result<Plan> {
+    let content = std::fs::read_to_string(path).with_action_context(|| format!("Failed to read the plan: {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse the plan: {}", path.display()))
+}
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Context, Result};
+use crate::answer_format::{extract_code, trim_boilerplate};
+use crate::batch::{self, BatchOptions};
+use crate::candidates;
+use crate::cli::AskArgs;
+use crate::config::{Config, ShellConfig};
+use crate::conversation::Turn;
+use crate::error::{ActionError, ConfigError, IoResultExt};
+use crate::history::{self, Exchange};
+use crate::llm::{ask_llm_follow_up, ask_llm_streaming, ask_llm_to_judge, ask_llm_validated};
+use crate::actions::run_command;
+use crate::{pager, placeholders, snippets};
+use colored::*;
+use reqwest::Client;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use super::get_active_model;
+
+pub(crate) async fn ask(args: AskArgs, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    let AskArgs { prompt, variables, code_only, format, pipe, save, follow_up, batch, output, concurrency, rate_limit, candidates, best_of, judge_model, models, validators } = args;
+    let active_model = get_active_model(config)?;
+    let variables = variables.into_iter().collect();
+    let prompt = prompt.map(|prompt| snippets::expand(&prompt, &config.snippets, &variables)).transpose()?;
+    let prompt = match prompt {
+        Some(prompt) => Some(placeholders::expand(&prompt, config.shell).await?),
+        None => None,
+    };
+    if !models.is_empty() {
+        let prompt = prompt.context("No prompt given")?;
+        let models = models
+            .iter()
+            .map(|name| config.models.get(name).ok_or(ConfigError::ModelNotFound(name.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let answers = candidates::ask_models(&models, &prompt, context, format, client).await?;
+        println!("{}", candidates::format_model_answers(&answers, candidates::terminal_width()));
+        if answers.iter().all(|answer| answer.answer.is_err()) {
+            return Err(anyhow!("All the models failed to answer"));
+        }
+        return Ok(());
+    }
+    if let Some(batch_path) = batch {
+        let content = std::fs::read_to_string(&batch_path)
+            .with_action_context(|| format!("Failed to read the batch file: {}", batch_path.display()))?;
+        let prompts = batch::read_prompts(&content)?;
+        let total = prompts.len();
+        let answer_output = AnswerOutput { code_only, pipe: None, save: None, printed: false };
+        let options = BatchOptions { instruction: prompt, answer_format: format, concurrency, rate_limit, format: output };
+        let failed = batch::run(
+            prompts, active_model, context, client, options,
+            |answer| answer_output.process(answer), &mut std::io::stdout(),
+        ).await?;
+        if failed > 0 {
+            eprintln!("Warning: {} of {} prompts failed.", failed, total);
+        }
+        return Ok(());
+    }
+    let prompt = prompt.context("No prompt given")?;
+    let mut streamed = false;
+    let answer = if follow_up {
+        let previous = history::last_exchange()?
+            .ok_or_else(|| ActionError::InvalidInput("There is no previous question to continue".to_string()))?;
+        let previous_turn = Turn { question: previous.prompt, answer: previous.answer };
+        ask_llm_follow_up(active_model, &previous_turn, &prompt, context, client).await.context("Error during LLM call")?
+    } else if candidates > 1 {
+        let judge_model = match &judge_model {
+            Some(name) => Some(config.models.get(name).ok_or(ConfigError::ModelNotFound(name.clone()))?),
+            None if best_of => Some(active_model),
+            None => None,
+        };
+        let mut answers = candidates::ask_candidates(active_model, &prompt, context, format, candidates as usize, client).await.context("Error during LLM call")?;
+        // Only the chosen answer is written to the standard output
+        eprintln!("{}\n", candidates::format_candidates(&answers, candidates::terminal_width()));
+        let chosen = match judge_model {
+            _ if answers.len() == 1 => 0,
+            Some(judge_model) => {
+                let judgement = ask_llm_to_judge(judge_model, &prompt, &answers, client).await.context("Error during LLM call")?;
+                eprintln!("{}", format!("Candidate {} chosen by '{}': {}", judgement.best, judge_model.name, judgement.reason).cyan());
+                judgement.best - 1
+            }
+            None => candidates::choose_candidate(answers.len())?,
+        };
+        answers.swap_remove(chosen)
+    } else if format.is_none() && validators.is_empty() && !code_only && std::io::stdout().is_terminal() {
+        // The answer is printed as it arrives, the answers written to pipes are only the processed ones
+        streamed = true;
+        let mut print_text = |text: &str| {
+            print!("{}", text.green());
+            let _ = std::io::stdout().flush();
+        };
+        let answer = ask_llm_streaming(active_model, &prompt, context, &mut print_text, client).await.context("Error during LLM call")?;
+        println!("\n");
+        answer
+    } else {
+        ask_llm_validated(active_model, &prompt, context, format, &validators, config.shell, client).await.context("Error during LLM call")?
+    };
+    if let Err(e) = history::record(Exchange::new(&prompt, &trim_boilerplate(&answer), &active_model.name)) {
+        eprintln!("Warning: Failed to record the answer in the history: {}", e);
+    }
+    let output = AnswerOutput { code_only, pipe, save, printed: streamed };
+    output.write(&output.process(&answer)?, config.shell).await
+}
+
+// Post-processing of the answer of `ask`: the answer is cleaned up, optionally reduced to its code and then
+// printed, piped into a command and saved to a file
+struct AnswerOutput {
+    code_only: bool,
+    pipe: Option<String>,
+    save: Option<PathBuf>,
+    // The answer was already printed as it was streamed
+    printed: bool,
+}
+
+impl AnswerOutput {
+    fn process(&self, answer: &str) -> Result<String> {
+        let answer = trim_boilerplate(answer);
+        if !self.code_only {
+            return Ok(answer);
+        }
+        extract_code(&answer)
+            .ok_or_else(|| ActionError::LlmMalformedResponse("The answer contains no fenced code blocks".to_string()).into())
+    }
+
+    async fn write(&self, answer: &str, shell_config: ShellConfig) -> Result<()> {
+        if !self.printed {
+            pager::page(&format!("{}\n", answer.green()));
+        }
+        if let Some(command) = &self.pipe {
+            run_command::pipe_to_command(answer, command, shell_config).await?;
+        }
+        if let Some(path) = &self.save {
+            std::fs::write(path, answer)
+                .with_action_context(|| format!("Failed to save the answer to {}", path.display()))?;
+            println!("Answer saved to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_post_processing() {
+        let answer = "<think>The user wants a script</think>\n\nHere is the script:\n```bash\necho hello\n```\nand the config:\n```\nkey = 1\n```\n";
+
+        assert_eq!(trim_boilerplate(answer), "Here is the script:\n```bash\necho hello\n```\nand the config:\n```\nkey = 1\n```");
+        assert_eq!(extract_code(answer).as_deref(), Some("echo hello\n\nkey = 1"));
+        assert_eq!(extract_code("No code here"), None);
+    }
+}
@@ -0,0 +1,317 @@
+use anyhow::{Context, Result};
+use crate::cli::ConfigAction;
+use crate::config::{Config, DiagnosticsConfig, MetricsConfig, Model, ModelKind, PreviewConfig, QuotaConfig, SessionConfig, ShellConfig, TrimmingConfig};
+use crate::error::{ActionError, ConfigError};
+use crate::{doctor, onboarding, proxy};
+use reqwest::Client;
+use std::net::ToSocketAddrs;
+use super::get_active_model;
+
+pub(crate) async fn handle_config_action(action: ConfigAction, config: &mut Config, client: &Client) -> Result<()> {
+    match action {
+        ConfigAction::Add { name, provider, api_url, query_params, api_key, api_key_header, model_identifier, kind, request_format, response_json_path, proxy, capabilities: capability_args } => {
+            if let Some(proxy) = &proxy {
+                proxy::validate(proxy)?;
+            }
+            let mut new_model = match &provider {
+                Some(provider) => onboarding::provider_model(provider, model_identifier.as_deref(), api_key.clone())?,
+                None => Model::default(),
+            };
+            new_model.name = name.clone();
+            new_model.api_url = api_url.unwrap_or(new_model.api_url);
+            new_model.query_params.extend(query_params);
+            new_model.api_key = api_key.or(new_model.api_key);
+            new_model.api_key_header = api_key_header.or(new_model.api_key_header);
+            new_model.model_identifier = model_identifier.or(new_model.model_identifier);
+            new_model.kind = kind.unwrap_or(new_model.kind);
+            new_model.request_format = request_format.unwrap_or(new_model.request_format);
+            new_model.response_json_path = response_json_path.unwrap_or(new_model.response_json_path);
+            new_model.proxy = proxy;
+            capability_args.apply(&mut new_model.capabilities);
+            if new_model.kind.is_template() && (new_model.request_format.is_empty() || new_model.response_json_path.is_empty()) {
+                return Err(ActionError::InvalidInput("The template models need --request-format and --response-json-path, or use --kind openai, anthropic or gemini".to_string()).into());
+            }
+            config.add_model(new_model);
+            config.save()?;
+            println!("Model '{}' added.", name);
+        }
+        ConfigAction::SetDefault { name } => {
+            config.set_default_model(&name)?;
+            config.save()?;
+            println!("Default model set to '{}'.", name);
+        }
+        ConfigAction::SetCapabilities { name, capabilities } => {
+            let model = config.models.get_mut(&name).ok_or(ConfigError::ModelNotFound(name.clone()))?;
+            capabilities.apply(&mut model.capabilities);
+            let summary = model.capabilities.summary();
+            config.save()?;
+            println!("Capabilities of the model '{}': {}.", name, summary);
+        }
+        ConfigAction::SetProxy { name, proxy } => {
+            if let Some(proxy) = &proxy {
+                proxy::validate(proxy)?;
+            }
+            let model = config.models.get_mut(&name).ok_or(ConfigError::ModelNotFound(name.clone()))?;
+            model.proxy = proxy;
+            let description = match &model.proxy {
+                Some(proxy) if proxy == proxy::DIRECT => "connects directly".to_string(),
+                Some(proxy) => format!("uses the proxy {}", proxy::redact(proxy)),
+                None => "uses the proxy of the environment".to_string(),
+            };
+            config.save()?;
+            println!("The model '{}' {}.", name, description);
+        }
+        ConfigAction::SetRequestTransform { name, command } => {
+            let model = config.models.get_mut(&name).ok_or(ConfigError::ModelNotFound(name.clone()))?;
+            model.request_transform = command;
+            match &model.request_transform {
+                Some(command) => println!("The request bodies of the model '{}' are transformed by: {}", name, command),
+                None => println!("The request bodies of the model '{}' are sent as they are.", name),
+            }
+            config.save()?;
+        }
+        ConfigAction::SetConversation { name, json_path, param } => {
+            let model = config.models.get_mut(&name).ok_or(ConfigError::ModelNotFound(name.clone()))?;
+            if matches!(model.kind, ModelKind::Anthropic | ModelKind::Gemini | ModelKind::Mock) {
+                return Err(ActionError::InvalidInput(format!("The {} models keep no conversations on the server", model.kind)).into());
+            }
+            model.conversation_json_path = json_path;
+            model.conversation_param = param;
+            match (&model.conversation_json_path, &model.conversation_param) {
+                (Some(json_path), Some(param)) => println!(
+                    "The sessions of the model '{}' send the conversation id at '{}' back in '{}' with only the new questions.", name, json_path, param
+                ),
+                _ => println!("The sessions of the model '{}' send the whole transcript every turn.", name),
+            }
+            config.save()?;
+        }
+        ConfigAction::SetStream { name, format, json_path } => {
+            let model = config.models.get_mut(&name).ok_or(ConfigError::ModelNotFound(name.clone()))?;
+            if !model.kind.is_template() {
+                return Err(ActionError::InvalidInput(format!("The {} models stream the answers of their API already", model.kind)).into());
+            }
+            model.stream_format = format;
+            model.stream_json_path = json_path;
+            match (&model.stream_format, &model.stream_json_path) {
+                (Some(format), Some(json_path)) => println!(
+                    "The answers of the model '{}' are streamed as {} with the parts at '{}'.", name, format, json_path
+                ),
+                _ => println!("The answers of the model '{}' are received at once.", name),
+            }
+            config.save()?;
+        }
+        ConfigAction::SetSummarizerModel { name: Some(name) } => {
+            config.set_summarizer_model(&name)?;
+            config.save()?;
+            println!("Summarizer model set to '{}'.", name);
+        }
+        ConfigAction::SetSummarizerModel { name: None } => {
+            config.summarizer_model = None;
+            config.save()?;
+            println!("Summarizer model cleared, the context is sent as it is.");
+        }
+        ConfigAction::SetGuardrailModel { name: Some(name) } => {
+            config.set_guardrail_model(&name)?;
+            config.save()?;
+            println!("Guardrail model set to '{}'.", name);
+        }
+        ConfigAction::SetGuardrailModel { name: None } => {
+            config.guardrail_model = None;
+            config.save()?;
+            println!("Guardrail model cleared, the context is checked only by the heuristics.");
+        }
+        ConfigAction::SetEmbeddingModel { name } => {
+            config.set_embedding_model(&name)?;
+            config.save()?;
+            println!("Embedding model set to '{}'.", name);
+        }
+        ConfigAction::SetCurrent { name } => {
+            config.set_current_model(&name)?;
+            println!("Current model for this session set to '{}'.", name);
+        }
+        ConfigAction::ClearCurrent => {
+            config.clear_current_model();
+            println!("Current model selection cleared. Using default model.");
+        }
+        ConfigAction::List => {
+            println!("Configured Models:");
+            if config.models.is_empty() {
+                println!("No models configured.");
+            } else {
+                for (name, model) in &config.models {
+                    let is_default = config.default_model.as_ref() == Some(name);
+                    let is_current = config.current_model.as_ref() == Some(name);
+                    let default_marker = if is_default { " (default)" } else { "" };
+                    let current_marker = if is_current && Some(name) != config.default_model.as_ref() { " (current)" } else { "" };
+
+                    println!(
+                        "  - {}{}{}: Kind={}, URL={}, Key={}, Identifier={}, Proxy={}, Capabilities={}",
+                        name,
+                        default_marker,
+                        current_marker,
+                        model.kind,
+                        model.api_url,
+                        model.api_key.as_deref().map_or("Not Set", |_|"Set"),
+                        model.model_identifier.as_deref().unwrap_or("Not Set"),
+                        model.proxy.as_deref().map_or("Environment".to_string(), proxy::redact),
+                        model.capabilities.summary()
+                    );
+                }
+            }
+            println!(
+                "\nActive model for next command (unless overridden): {}",
+                config.get_active_model().map_or("None", |m| &m.name)
+            );
+            println!(
+                "Shell for commands: {}{}",
+                config.shell.resolve().program(),
+                if config.shell.login { " (login shell)" } else { "" }
+            );
+            println!("Session window: {} turns", config.session.max_turns);
+            println!("Embedding model: {}", config.embedding_model.as_deref().unwrap_or("Not Set"));
+            println!("Summarizer model: {}", config.summarizer_model.as_deref().unwrap_or("Not Set"));
+            println!("Guardrail model: {}", config.guardrail_model.as_deref().unwrap_or("Not Set"));
+        }
+        ConfigAction::Delete { name } => {
+            config.delete_model(&name)?;
+            config.save()?;
+            println!("Model '{}' deleted.", name);
+        }
+        ConfigAction::SetOnFailure { policy } => {
+            config.on_failure = policy;
+            config.save()?;
+            println!("Failure policy set to '{:?}'.", policy);
+        }
+        ConfigAction::SetShell { shell, login } => {
+            config.shell = ShellConfig { name: Some(shell), login };
+            config.save()?;
+            println!("Shell set to '{}'{}.", shell.program(), if login { " (login shell)" } else { "" });
+        }
+        ConfigAction::SetEnvExpansion { variables } => {
+            config.expand_env_vars = variables;
+            config.save()?;
+            if config.expand_env_vars.is_empty() {
+                println!("Environment variable expansion in paths disabled.");
+            } else {
+                println!("Environment variables expanded in paths: {}.", config.expand_env_vars.join(", "));
+            }
+        }
+        ConfigAction::SetProtectedPaths { patterns } => {
+            if let Some(invalid) = patterns.iter().find(|pattern| glob::Pattern::new(pattern).is_err()) {
+                return Err(ActionError::InvalidInput(format!("Invalid glob pattern '{}'", invalid)).into());
+            }
+            config.protected_paths = patterns;
+            config.save()?;
+            if config.protected_paths.is_empty() {
+                println!("No paths are protected.");
+            } else {
+                println!("Protected paths: {}.", config.protected_paths.join(", "));
+            }
+        }
+        ConfigAction::SetExcludedPaths { patterns } => {
+            if let Some(invalid) = patterns.iter().find(|pattern| glob::Pattern::new(pattern).is_err()) {
+                return Err(ActionError::InvalidInput(format!("Invalid glob pattern '{}'", invalid)).into());
+            }
+            config.excluded_paths = patterns;
+            config.save()?;
+            if config.excluded_paths.is_empty() {
+                println!("Only the paths in the ignore files are skipped.");
+            } else {
+                println!("Excluded paths: {}.", config.excluded_paths.join(", "));
+            }
+        }
+        ConfigAction::SetQuotas { max_bytes_written, max_files_created, max_files_deleted } => {
+            config.quotas = QuotaConfig { max_bytes_written, max_files_created, max_files_deleted };
+            config.save()?;
+            if config.quotas.is_unlimited() {
+                println!("The file changes of a run are not limited.");
+            } else {
+                println!("Quotas of a run set: {}.", describe_quotas(&config.quotas));
+            }
+        }
+        ConfigAction::SetMetrics { textfile, statsd } => {
+            if let Some(statsd) = &statsd {
+                statsd.to_socket_addrs()
+                    .map_err(|e| ActionError::InvalidInput(format!("Invalid statsd address '{}': {}", statsd, e)))?;
+            }
+            config.metrics = MetricsConfig { textfile, statsd };
+            config.save()?;
+            match (&config.metrics.textfile, &config.metrics.statsd) {
+                (None, None) => println!("The metrics are not collected."),
+                (textfile, statsd) => {
+                    let exports: Vec<String> = [textfile.as_ref().map(|path| format!("the textfile {}", path.display())), statsd.as_ref().map(|address| format!("statsd at {}", address))]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    println!("The metrics of the runs are exported to {}.", exports.join(" and "));
+                }
+            }
+        }
+        ConfigAction::SetDiagnostics { enabled, command } => {
+            config.diagnostics = DiagnosticsConfig { enabled: enabled || command.is_some(), command };
+            config.save()?;
+            match (config.diagnostics.enabled, &config.diagnostics.command) {
+                (false, _) => println!("The project is not checked after the edits."),
+                (true, Some(command)) => println!("The project is checked with `{}` after the edits.", command),
+                (true, None) => println!("The detected project is checked after the edits."),
+            }
+        }
+        ConfigAction::SetUpdateCheck { enabled } => {
+            config.check_for_updates = enabled;
+            config.save()?;
+            println!("Check for new releases {}.", if enabled { "enabled" } else { "disabled" });
+        }
+        ConfigAction::SetSessionWindow { max_turns } => {
+            config.session = SessionConfig { max_turns: max_turns as usize };
+            config.save()?;
+            println!("Session window set to the last {} turns.", max_turns);
+        }
+        ConfigAction::SetOutputTrimming { head_lines, tail_lines, max_line_chars, keep_ansi, keep_repeated } => {
+            config.output_trimming = TrimmingConfig { head_lines, tail_lines, max_line_chars, strip_ansi: !keep_ansi, collapse_repeated: !keep_repeated };
+            config.save()?;
+            if head_lines + tail_lines == 0 {
+                println!("All the lines of the outputs are sent to the LLM.");
+            } else {
+                println!("The first {} and the last {} lines of the outputs are sent to the LLM.", head_lines, tail_lines);
+            }
+        }
+        ConfigAction::SetPlanPreview { create_file, overwrite_file, replace_file_lines, append_to_file } => {
+            config.plan_preview = PreviewConfig { create_file, overwrite_file, replace_file_lines, append_to_file };
+            config.save()?;
+            println!("Plan preview set, use --show-full to see the whole contents.");
+        }
+        ConfigAction::Path => {
+            let path = Config::config_path().context("Error determining config path")?;
+            println!("Config file path: {:?}", path)
+        }
+        ConfigAction::Test { name } => {
+            let model = match name {
+                Some(name) => config.models.get(&name).ok_or(ConfigError::ModelNotFound(name))?,
+                None => get_active_model(config)?,
+            };
+            doctor::test_model(model, client).await?;
+        }
+        ConfigAction::DiscoverOllama { url } => {
+            let added = onboarding::discover_ollama(config, &url, client).await?;
+            if added.is_empty() {
+                println!("No new models found in Ollama at {}, pull one with `ollama pull <model>`.", url);
+            } else {
+                config.save()?;
+                println!("Models added: {}", added.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_quotas(quotas: &QuotaConfig) -> String {
+    [
+        (quotas.max_bytes_written, "bytes written"),
+        (quotas.max_files_created, "files created"),
+        (quotas.max_files_deleted, "files deleted"),
+    ]
+        .iter()
+        .filter_map(|(max, what)| max.map(|max| format!("at most {} {}", max, what)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
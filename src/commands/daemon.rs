@@ -0,0 +1,19 @@
+use anyhow::Result;
+use crate::actions::confirm_action;
+use crate::actions::target::ExecutionTarget;
+use crate::classification::AutoConfirm;
+use crate::config::{Config, FailurePolicy, ProjectConfig};
+use crate::daemon::{self, DaemonOptions};
+use reqwest::Client;
+use std::sync::Arc;
+use super::{execution_settings, get_active_model, project_target};
+
+// Nobody answers the prompts of the daemon, the actions needing a confirmation are denied
+pub(crate) async fn run_daemon(options: DaemonOptions, auto_confirm: AutoConfirm, on_failure: Option<FailurePolicy>, target: Option<Arc<dyn ExecutionTarget>>, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    confirm_action::disable_prompts();
+    let project_config = ProjectConfig::load(&std::env::current_dir()?)?;
+    let active_model = get_active_model(config)?;
+    let target = project_target(target, auto_confirm)?;
+    let settings = execution_settings(auto_confirm, on_failure, target, config);
+    daemon::run(&options, &project_config, active_model, context, settings, client).await
+}
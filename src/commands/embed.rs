@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::embeddings::{self, EmbeddingIndex, IndexEntry};
+use crate::error::{ActionError, IoResultExt};
+use crate::llm::fetch_embedding;
+use crate::{chunking, fs};
+use reqwest::Client;
+use super::get_embedding_model;
+
+pub(crate) async fn embed(input: &str, store: Option<String>, config: &Config, client: &Client) -> Result<()> {
+    let embedding_model = get_embedding_model(config)?;
+    if let Some(dir) = fs::expand_home(input).ok().filter(|path| path.is_dir()) {
+        let name = store.ok_or_else(|| ActionError::InvalidInput(format!("'{}' is a directory, its files can only be stored in an index with --store", input)))?;
+        let path = EmbeddingIndex::index_path(&name)?;
+        let mut index = EmbeddingIndex::load_from(&path)?;
+        let files = fs::read_text_files(&dir);
+        for (file, content) in &files {
+            let file_name = file.display().to_string();
+            for chunk in chunking::chunk_source(file, content, embeddings::MAX_CHUNK_BYTES) {
+                // The header keeps the signatures of the definitions in the embedded text
+                let header = chunk.header(&file_name);
+                let text = format!("{}\n{}", header, chunk.text);
+                let vector = fetch_embedding(&text, embedding_model, client).await.context("Error during embedding call")?;
+                index.upsert(IndexEntry { source: header, vector });
+            }
+        }
+        index.save_to(&path)?;
+        println!("Stored the embeddings of {} files of '{}' in index '{}' ({} entries).", files.len(), input, name, index.entries.len());
+        return Ok(());
+    }
+    let text = match fs::expand_home(input) {
+        Ok(path) if path.is_file() => std::fs::read_to_string(&path)
+            .with_action_context(|| format!("Failed to read file: {}", input))?,
+        _ => input.to_string(),
+    };
+    let vector = fetch_embedding(&text, embedding_model, client).await.context("Error during embedding call")?;
+    match store {
+        Some(name) => {
+            let path = EmbeddingIndex::index_path(&name)?;
+            let mut index = EmbeddingIndex::load_from(&path)?;
+            index.upsert(IndexEntry { source: input.to_string(), vector });
+            index.save_to(&path)?;
+            println!("Stored the embedding of '{}' in index '{}' ({} entries).", input, name, index.entries.len());
+        }
+        None => println!("{}", serde_json::to_string(&vector)?),
+    }
+    Ok(())
+}
+
+pub(crate) async fn similar(query: &str, index: &str, top: usize, config: &Config, client: &Client) -> Result<()> {
+    let embedding_model = get_embedding_model(config)?;
+    let path = EmbeddingIndex::index_path(index)?;
+    if !path.exists() {
+        return Err(ActionError::NotFound(format!("Index '{}' does not exist, add entries with `cliff embed --store {}`", index, index)).into());
+    }
+    let embedding_index = EmbeddingIndex::load_from(&path)?;
+    let query_vector = fetch_embedding(query, embedding_model, client).await.context("Error during embedding call")?;
+    for (score, entry) in embedding_index.most_similar(&query_vector, top) {
+        println!("{:.4}  {}", score, entry.source);
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use crate::actions::confirm_action;
+use crate::actions::target::LocalTarget;
+use crate::classification::AutoConfirm;
+use crate::config::Config;
+use crate::error::{ActionError, ConfigError};
+use crate::eval;
+use reqwest::Client;
+use std::path::Path;
+use std::sync::Arc;
+use super::{execution_settings, get_active_model};
+
+// Fails when the pass rate is lower than `min_pass_rate`, the models default to the active model
+pub(crate) async fn run_eval(suite: &Path, models: &[String], auto_confirm: AutoConfirm, min_pass_rate: Option<f64>, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    confirm_action::disable_prompts();
+    let suite = eval::load(suite)?;
+    let models = if models.is_empty() {
+        vec![get_active_model(config)?]
+    } else {
+        models.iter().map(|name| config.models.get(name).ok_or(ConfigError::ModelNotFound(name.clone()))).collect::<Result<Vec<_>, _>>()?
+    };
+    let settings = execution_settings(auto_confirm, None, Arc::new(LocalTarget), config);
+    let pass_rate = eval::run(&suite, &models, context, &settings, client).await?;
+    if let Some(min_pass_rate) = min_pass_rate.filter(|min_pass_rate| pass_rate < *min_pass_rate) {
+        return Err(ActionError::ExpectationFailed(format!("The pass rate {:.0}% is lower than {:.0}%", pass_rate, min_pass_rate)).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use crate::error::ActionError;
+use crate::examples;
+use colored::*;
+
+// The examples of the subcommand, or of all the subcommands having examples
+pub(crate) fn show_examples(command: Option<String>) -> Result<()> {
+    match command {
+        Some(command) => {
+            let examples = examples::examples_of(&command).ok_or_else(|| ActionError::InvalidInput(format!(
+                "No examples for '{}', the subcommands with examples are: {}",
+                command, examples::EXAMPLES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+            )))?;
+            println!("{}", examples::format_examples(examples));
+        }
+        None => {
+            for (name, examples) in examples::EXAMPLES {
+                println!("{}\n{}\n", name.bold(), examples::format_examples(examples));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::error::{ActionError, IoResultExt};
+use crate::fs;
+use crate::llm::ask_llm_for_extraction;
+use reqwest::Client;
+use super::get_active_model;
+
+pub(crate) async fn extract(instruction: &str, schema: &str, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    let active_model = get_active_model(config)?;
+    let schema_content = std::fs::read_to_string(fs::expand_home(schema)?)
+        .with_action_context(|| format!("Failed to read the JSON schema: {}", schema))?;
+    let schema: serde_json::Value = serde_json::from_str(&schema_content)
+        .map_err(|e| ActionError::InvalidInput(format!("Failed to parse the JSON schema {}: {}", schema, e)))?;
+    let extracted = ask_llm_for_extraction(active_model, instruction, &schema, context, client).await.context("Error during LLM call")?;
+    println!("{}", serde_json::to_string_pretty(&extracted)?);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use crate::error::IoResultExt;
+use crate::trace::{self, TraceFormat};
+use crate::{changes, diff};
+use std::path::PathBuf;
+use super::run_id_or_latest;
+
+pub(crate) fn export_trace(run_id: Option<String>, format: TraceFormat, output: Option<PathBuf>) -> Result<()> {
+    let run_id = run_id_or_latest(run_id)?;
+    let trace = serde_json::to_string_pretty(&trace::export(&trace::load(&run_id)?, &run_id, format))?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, trace).with_action_context(|| format!("Failed to write the trace: {}", path.display()))?;
+            println!("Trace of run {} written to {}", run_id, path.display());
+        }
+        None => println!("{}", trace),
+    }
+    Ok(())
+}
+
+pub(crate) fn diff_run(run_id: Option<String>, output: Option<PathBuf>) -> Result<()> {
+    let run_id = run_id_or_latest(run_id)?;
+    let patch = changes::patch(&changes::load(&run_id)?);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, &patch).with_action_context(|| format!("Failed to write the patch: {}", path.display()))?;
+            println!("Changes of run {} written to {}", run_id, path.display());
+        }
+        None if patch.is_empty() => println!("The run {} changed no files with its actions.", run_id),
+        None => print!("{}", diff::colorize_unified_diff(&patch)),
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use crate::cli::PlansAction;
+use crate::config::Config;
+use crate::executor::{self, MAIN_PLAN_LABEL};
+use crate::gallery;
+use colored::*;
+use reqwest::Client;
+use super::{get_active_model, run_plan, RunOptions};
+
+pub(crate) async fn handle_plans_action(action: PlansAction, config: &Config, client: &Client) -> Result<()> {
+    match action {
+        PlansAction::List => {
+            for (name, gallery_plan) in gallery::plans()? {
+                let origin = if gallery_plan.built_in { "" } else { " (saved)" };
+                println!("{}{}: {}", name.bold(), origin, gallery_plan.plan.thought.as_deref().unwrap_or_default());
+                for (param, declaration) in &gallery_plan.plan.params {
+                    match &declaration.default {
+                        Some(default) => println!("  --param {}=... (default: {})", param, default),
+                        None => println!("  --param {}=...", param),
+                    }
+                }
+            }
+            println!("Save your own plans into {}", gallery::plans_dir()?.display());
+        }
+        PlansAction::Show { name } => {
+            println!("{}", serde_json::to_string_pretty(&gallery::find(&name)?)?);
+        }
+        PlansAction::Run { name, params, git_commits, keep_scratch, auto_confirm, on_failure, target } => {
+            let active_model = get_active_model(config)?;
+            let options = RunOptions { auto_confirm, on_failure, target, git_commits, keep_scratch };
+            run_plan("plans", &format!("plan {}", name), active_model, options, config, async |settings| {
+                let plan = gallery::find(&name)?.with_params(&params.into_iter().collect())?;
+                plan.display();
+                executor::execute_plan(&plan, active_model, client, &mut Vec::new(), settings, &[MAIN_PLAN_LABEL.to_string()]).await
+            }).await?;
+        }
+    }
+    Ok(())
+}
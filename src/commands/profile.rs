@@ -0,0 +1,26 @@
+use anyhow::Result;
+use crate::cli::ProfileAction;
+use crate::profiles;
+
+pub(crate) fn handle_profile_action(action: ProfileAction) -> Result<()> {
+    match action {
+        ProfileAction::List => {
+            println!("Profiles:");
+            for name in profiles::list()? {
+                let marker = if name == profiles::current() { " (selected)" } else { "" };
+                println!("  - {}{}", name, marker);
+            }
+        }
+        ProfileAction::Create { name } => {
+            profiles::create(&name)?;
+            println!("Profile '{}' created, use it with `cliff --profile {} ...`.", name, name);
+        }
+        ProfileAction::Delete { name } => {
+            for dir in profiles::delete(&name)? {
+                println!("Deleted {}", dir.display());
+            }
+            println!("Profile '{}' deleted.", name);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use crate::actions::target::LocalTarget;
+use crate::classification::writes_only_within;
+use crate::cli::ScaffoldArgs;
+use crate::config::{Config, Model};
+use crate::error::ActionError;
+use crate::executor::{self, ExecutionSettings, MAIN_PLAN_LABEL};
+use crate::llm::ask_llm_for_plan;
+use crate::templates;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use super::{execution_settings, get_active_model};
+
+pub(crate) async fn scaffold(args: ScaffoldArgs, config: &Config, client: &Client) -> Result<()> {
+    let ScaffoldArgs { template, target, variables, instruction, auto_confirm, on_failure, force } = args;
+    let variables: HashMap<String, String> = variables.into_iter().collect();
+    let created_files = templates::instantiate_project(&templates::templates_dir()?, &template, Path::new(&target), &variables, force)?;
+    println!("Created {} files from template '{}' in '{}'.", created_files.len(), template, target);
+    if let Some(instruction) = instruction {
+        let active_model = get_active_model(config)?;
+        let settings = execution_settings(auto_confirm, on_failure, Arc::new(LocalTarget), config);
+        customize_scaffold(&instruction, &target, &created_files, active_model, client, settings).await?;
+    }
+    Ok(())
+}
+
+async fn customize_scaffold(instruction: &str, target: &str, created_files: &[PathBuf], model: &Model, client: &Client, settings: ExecutionSettings) -> Result<()> {
+    let created_files: Vec<String> = created_files.iter().map(|path| path.display().to_string()).collect();
+    let scaffold_instruction = format!(
+        "A project was created from a template in the directory '{}' with the files:\n{}\n\nCustomize the project according to the following instruction, ONLY create or change files inside '{}' and do NOT run commands: {}",
+        target, created_files.join("\n"), target, instruction
+    );
+    let plan = ask_llm_for_plan(model, &scaffold_instruction, &created_files, &Vec::new(), &settings, client).await.context("Error during LLM call")?;
+    plan.display();
+    let target_dir = std::env::current_dir()?.join(target);
+    if let Some(action) = plan.steps.iter().find(|action| !writes_only_within(action, &target_dir)) {
+        return Err(ActionError::PolicyDenied(format!("The plan writes outside of the project directory '{}' or runs a command: {:?}", target, action)).into());
+    }
+    executor::execute_plan(&plan, model, client, &mut Vec::new(), settings, &[MAIN_PLAN_LABEL.to_string()]).await
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use crate::actions::target::ExecutionTarget;
+use crate::classification::AutoConfirm;
+use crate::config::Config;
+use crate::error::{self, ActionError};
+use crate::events::{self, Event};
+use crate::executor::{self, MAIN_PLAN_LABEL};
+use crate::llm::{ask_llm, ask_llm_for_plan};
+use crate::protocol::{self, Request};
+use crate::server::{self, ServerOptions};
+use reqwest::Client;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use super::{get_active_model, run_plan, RunOptions};
+
+// Answers the requests of `--protocol stdio` one by one until the standard input is closed, the failures of the
+// requests are reported as events and do not stop serving
+pub(crate) async fn serve_stdio(auto_confirm: AutoConfirm, target: Option<Arc<dyn ExecutionTarget>>, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    let mut requests = protocol::start()?;
+    while let Some(request) = requests.recv().await {
+        let (id, result) = match request {
+            Request::Ask { id, prompt } => {
+                let result = async {
+                    let answer = ask_llm(get_active_model(config)?, &prompt, context, client).await.context("Error during LLM call")?;
+                    events::emit(Event::Answer { id: id.as_deref(), text: &answer });
+                    Ok(())
+                }.await;
+                (id, result)
+            }
+            Request::Plan { id, instruction, git_commits, keep_scratch } => {
+                let result = async {
+                    let active_model = get_active_model(config)?;
+                    let options = RunOptions { auto_confirm, on_failure: None, target: target.clone(), git_commits, keep_scratch };
+                    run_plan("act", &instruction, active_model, options, config, async |settings| {
+                        let plan = ask_llm_for_plan(active_model, &instruction, context, &Vec::new(), &settings, client).await.context("Error during LLM call")?;
+                        plan.display();
+                        executor::execute_plan(&plan, active_model, client, &mut Vec::new(), settings, &[MAIN_PLAN_LABEL.to_string()]).await
+                    }).await
+                }.await;
+                (id, result)
+            }
+            Request::Answer { .. } => unreachable!("The answers are read by the prompts"),
+        };
+        if let Err(e) = result {
+            events::emit(Event::Error { id: id.as_deref(), kind: error::error_kind(&e), message: format!("{:#}", e) });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn serve_http(listen: SocketAddr, auto_confirm: AutoConfirm, target: Option<Arc<dyn ExecutionTarget>>) -> Result<()> {
+    let secret = std::env::var(server::SECRET_ENV_VAR).ok().filter(|secret| !secret.is_empty())
+        .ok_or_else(|| ActionError::InvalidInput(format!("Set the webhook secret in ${} to serve the webhooks", server::SECRET_ENV_VAR)))?;
+    let options = ServerOptions { listen, secret, auto_confirm, target: target.map(|target| target.to_string()), project_dir: std::env::current_dir()? };
+    server::serve(options).await
+}
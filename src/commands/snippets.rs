@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use crate::cli::SnippetsAction;
+use crate::config::Config;
+use crate::llm::ask_llm;
+use crate::snippets;
+use colored::*;
+use reqwest::Client;
+use super::get_active_model;
+
+pub(crate) async fn handle_snippets_action(action: SnippetsAction, config: &mut Config, context: &[String], client: &Client) -> Result<()> {
+    match action {
+        SnippetsAction::Add { name, snippet } => {
+            config.add_snippet(&name, &snippet);
+            config.save()?;
+            println!("Snippet '{}' added.", name);
+        }
+        SnippetsAction::List => {
+            println!("Snippets:");
+            if config.snippets.is_empty() {
+                println!("No snippets configured.");
+            } else {
+                let mut names: Vec<&String> = config.snippets.keys().collect();
+                names.sort();
+                for name in names {
+                    println!("  - @{}: {}", name, config.snippets[name]);
+                }
+            }
+        }
+        SnippetsAction::Delete { name } => {
+            config.delete_snippet(&name)?;
+            config.save()?;
+            println!("Snippet '{}' deleted.", name);
+        }
+        SnippetsAction::Use { name, input, variables } => {
+            let active_model = get_active_model(config)?;
+            let prompt = snippets::render(&name, input.as_deref().unwrap_or(""), &config.snippets, &variables.into_iter().collect())?;
+            let answer = ask_llm(active_model, &prompt, context, client).await.context("Error during LLM call")?;
+            println!("{}\n", answer.green());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use crate::config::{Config, ProjectConfig};
+use crate::tasks;
+use colored::*;
+use reqwest::Client;
+use super::{get_active_model, run_plan, RunOptions};
+
+pub(crate) fn list_tasks() -> Result<()> {
+    let project_config = ProjectConfig::load(&std::env::current_dir()?)?;
+    let mut tasks: Vec<_> = project_config.tasks.iter().collect();
+    tasks.sort_by_key(|(name, _)| *name);
+    if tasks.is_empty() {
+        println!("No tasks are defined in .cliff.toml.");
+    }
+    for (name, task) in tasks {
+        println!("{}: {}", name.bold(), task.instruction);
+        if let Some(verify) = &task.verify {
+            println!("  verify: {}", verify);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn run_task(name: &str, params: Vec<(String, String)>, options: RunOptions, config: &Config, context: &[String], client: &Client) -> Result<()> {
+    let project_config = ProjectConfig::load(&std::env::current_dir()?)?;
+    let task = &tasks::with_params(project_config.task(name)?, &params.into_iter().collect())?;
+    let active_model = get_active_model(config)?;
+    run_plan("run", &task.instruction, active_model, options, config, async |settings| {
+        let context = [task.context.as_slice(), context].concat();
+        tasks::run_task(task, active_model, &context, settings, client).await
+    }).await
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use crate::error::IoResultExt;
+use crate::{fs, tokens};
+
+// Every input is the standard input for `-`, a file when it is the path of one, otherwise the text itself
+pub(crate) fn count_tokens(inputs: &[String]) -> Result<()> {
+    let mut total = 0;
+    for input in inputs {
+        let text = if input == "-" {
+            std::io::read_to_string(std::io::stdin()).with_action_context(|| "Failed to read the standard input".to_string())?
+        } else {
+            match fs::expand_home(input) {
+                Ok(path) if path.is_file() => std::fs::read_to_string(&path)
+                    .with_action_context(|| format!("Failed to read file: {}", input))?,
+                _ => input.clone(),
+            }
+        };
+        let tokens = tokens::estimate_tokens(&text);
+        total += tokens;
+        println!("{:>8}  {}", tokens, input);
+    }
+    if inputs.len() > 1 {
+        println!("{:>8}  total", total);
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use crate::actions::confirm_action;
+use crate::actions::target::{ExecutionTarget, LocalTarget, ProtectedTarget};
+use crate::classification::AutoConfirm;
+use crate::config::Config;
+use crate::error::IoResultExt;
+use crate::executor::Action;
+use crate::{fs, transform};
+use reqwest::Client;
+use std::sync::Arc;
+use super::get_active_model;
+
+pub(crate) async fn transform_file(input: String, prompt: &str, out: Option<String>, chunk_size: usize, auto_confirm: AutoConfirm, config: &Config, client: &Client) -> Result<()> {
+    let active_model = get_active_model(config)?;
+    let content = std::fs::read_to_string(fs::expand_home(&input)?)
+        .with_action_context(|| format!("Failed to read file: {}", input))?;
+    let transformed = transform::transform(active_model, prompt, &input, &content, chunk_size, client).await?;
+    let out = out.unwrap_or(input);
+    let write_action = Action::OverwriteFileContents { action_idx: 0, path: out.clone(), content: transformed.clone() };
+    let target = ProtectedTarget::new(Arc::new(LocalTarget));
+    if confirm_action::confirm_generated_write(auto_confirm, &write_action, &out, &transformed, &target).await? {
+        target.write_file(&out, &transformed).await?;
+        println!("Transformed '{}' written.", out);
+    } else {
+        println!("The result was not written.");
+    }
+    Ok(())
+}
@@ -1,20 +1,331 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use crate::actions::confirm_action;
+use crate::actions::target::{parse_target, ExecutionTarget, LocalTarget};
+use crate::classification::AutoConfirm;
+use crate::error::{ActionError, ConfigError};
+use crate::profiles;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    fmt,
     fs::{self, create_dir_all},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Model {
     pub name: String,
     pub api_url: String,
     pub api_key: Option<String>,
     pub api_key_header: Option<String>,
     pub model_identifier: Option<String>,
+    // How the requests are made, by the request format and the response JSON path only for the template models
+    #[serde(default, skip_serializing_if = "ModelKind::is_template")]
+    pub kind: ModelKind,
+    #[serde(default)]
     pub request_format: String,
+    #[serde(default)]
     pub response_json_path: String,
+    // Proxy of the requests to the model instead of the one of the environment, "none" connects directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    // Shell command receiving the request body on its standard input and printing the body to send, e.g. to add the
+    // wrapper fields or the signature a gateway needs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_transform: Option<String>,
+    // JSONPath of the id of the server-side conversation in the response and the request field it is sent back in, the
+    // sessions then send only the new messages instead of the whole transcript
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_json_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_param: Option<String>,
+    // How the template model streams the answers and the JSONPath of the part of the answer in every streamed object, the
+    // requests of `ask` and the sessions then get `"stream": true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_format: Option<StreamFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_json_path: Option<String>,
+    // Parameters added to the query of the API URL, e.g. the `api-version` of Azure OpenAI. The `{{model}}` placeholder
+    // of the API URL is replaced with the model identifier, e.g. the name of an Azure deployment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query_params: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub capabilities: ModelCapabilities,
+}
+
+// What the model supports beyond plain text chat, set by the onboarding presets or manually. The unknown capabilities
+// are assumed to be unsupported and the limits to be unlimited
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelCapabilities {
+    // The API can be asked to answer with a JSON object, otherwise JSON is only asked for in the prompt
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub supports_json_mode: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub supports_tools: bool,
+    // Images can be sent in the `{{messages}}` of the request
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub supports_vision: bool,
+    // Maximum number of the tokens of the request and the answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<usize>,
+}
+
+impl ModelCapabilities {
+    // Short description for the model listing, e.g. "JSON mode, vision, context window 128000 tokens"
+    pub fn summary(&self) -> String {
+        let mut capabilities: Vec<String> = [(self.supports_json_mode, "JSON mode"), (self.supports_tools, "tools"), (self.supports_vision, "vision")]
+            .into_iter()
+            .filter(|(supported, _)| *supported)
+            .map(|(_, capability)| capability.to_string())
+            .collect();
+        if let Some(context_window) = self.context_window {
+            capabilities.push(format!("context window {} tokens", context_window));
+        }
+        if let Some(max_output_tokens) = self.max_output_tokens {
+            capabilities.push(format!("max output {} tokens", max_output_tokens));
+        }
+        if capabilities.is_empty() { "none".to_string() } else { capabilities.join(", ") }
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    /// Any HTTP API, the request is rendered from the request format and the answer selected with the response JSON path
+    #[default]
+    Template,
+    /// The OpenAI chat completions API and the compatible ones
+    #[value(name = "openai")]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// The Anthropic messages API
+    Anthropic,
+    /// The Gemini generateContent API
+    Gemini,
+    /// No API, answers with the answers from the file at the API URL in turn or echoes the question, for tests and demos
+    Mock,
+}
+
+impl ModelKind {
+    pub fn is_template(&self) -> bool {
+        *self == ModelKind::Template
+    }
+}
+
+impl fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ModelKind::Template => "template",
+            ModelKind::OpenAi => "openai",
+            ModelKind::Anthropic => "anthropic",
+            ModelKind::Gemini => "gemini",
+            ModelKind::Mock => "mock",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamFormat {
+    /// Server-sent events with a JSON object in every `data:` line, e.g. the OpenAI-compatible APIs
+    Sse,
+    /// A JSON object per line, e.g. the native API of Ollama
+    Ndjson,
+}
+
+impl fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StreamFormat::Sse => "server-sent events",
+            StreamFormat::Ndjson => "JSON lines",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Ask the user whether to retry, re-plan, skip the failed action or abort
+    Ask,
+    /// Ask the LLM for a new plan taking the failure into account
+    #[default]
+    Replan,
+    /// Stop the execution
+    Abort,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Shell {
+    Sh,
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    Powershell,
+}
+
+impl Shell {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Nushell => "nu",
+            Shell::Powershell => "pwsh",
+        }
+    }
+
+    // Shell of the program path such as `/usr/bin/fish` from `$SHELL`
+    fn from_program(path: &str) -> Option<Shell> {
+        let program = Path::new(path).file_stem()?.to_str()?;
+        match program {
+            "sh" | "dash" => Some(Shell::Sh),
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "nu" => Some(Shell::Nushell),
+            "pwsh" | "powershell" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+
+    // Arguments to run `command` with the shell, optionally as a login shell
+    pub fn args(&self, command: &str, login: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        match self {
+            Shell::Powershell => {
+                if login {
+                    args.push("-Login".to_string());
+                }
+                args.push("-Command".to_string());
+            }
+            _ => {
+                if login {
+                    args.push("-l".to_string());
+                }
+                args.push("-c".to_string());
+            }
+        }
+        args.push(command.to_string());
+        args
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShellConfig {
+    // Shell to run the commands with, detected from `$SHELL` when not configured
+    pub name: Option<Shell>,
+    #[serde(default)]
+    pub login: bool,
+}
+
+impl ShellConfig {
+    pub fn resolve(&self) -> Shell {
+        self.name
+            .or_else(|| std::env::var("SHELL").ok().and_then(|shell| Shell::from_program(&shell)))
+            .unwrap_or(Shell::Sh)
+    }
+}
+
+// Limits of the file changes of a single run, protecting against runaway plans, unlimited when not set
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct QuotaConfig {
+    pub max_bytes_written: Option<u64>,
+    pub max_files_created: Option<u64>,
+    pub max_files_deleted: Option<u64>,
+}
+
+impl QuotaConfig {
+    pub fn is_unlimited(&self) -> bool {
+        *self == QuotaConfig::default()
+    }
+}
+
+// Whether the project is checked after the steps writing files, e.g. with `cargo check`, to show the LLM the errors of its edits
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DiagnosticsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Command checking the project instead of the one detected from its files, e.g. `npm run lint`
+    pub command: Option<String>,
+}
+
+// Where the metrics of the runs are exported, they are not collected when neither is set
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct MetricsConfig {
+    // Prometheus textfile, e.g. in the directory of the textfile collector of the node exporter
+    pub textfile: Option<PathBuf>,
+    // Address of the statsd server, e.g. `127.0.0.1:8125`
+    pub statsd: Option<String>,
+}
+
+impl MetricsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.textfile.is_some() || self.statsd.is_some()
+    }
+}
+
+// How the outputs of the executed actions are trimmed in the history sent to the LLM, the full outputs are kept locally
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TrimmingConfig {
+    // Lines kept from the start and from the end of a longer output, all the lines are kept when both are 0
+    pub head_lines: usize,
+    pub tail_lines: usize,
+    // Characters kept of a longer line, unlimited when 0
+    pub max_line_chars: usize,
+    pub strip_ansi: bool,
+    // Whether the consecutive repeated lines are sent once with their count
+    pub collapse_repeated: bool,
+}
+
+impl Default for TrimmingConfig {
+    fn default() -> Self {
+        TrimmingConfig { head_lines: 50, tail_lines: 100, max_line_chars: 500, strip_ansi: true, collapse_repeated: true }
+    }
+}
+
+// Lines of the contents of the file actions shown in the plans before "(N more lines)", all the lines are shown when 0
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct PreviewConfig {
+    pub create_file: usize,
+    pub overwrite_file: usize,
+    pub replace_file_lines: usize,
+    pub append_to_file: usize,
+}
+
+impl PreviewConfig {
+    pub fn full() -> Self {
+        PreviewConfig { create_file: 0, overwrite_file: 0, replace_file_lines: 0, append_to_file: 0 }
+    }
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        PreviewConfig { create_file: 20, overwrite_file: 20, replace_file_lines: 10, append_to_file: 10 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SessionConfig {
+    // Number of the latest question and answer turns sent verbatim, the older turns are summarized
+    pub max_turns: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig { max_turns: 10 }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -22,21 +333,123 @@ pub struct Config {
     pub models: HashMap<String, Model>,
     pub default_model: Option<String>,
     pub current_model: Option<String>,
+    #[serde(default)]
+    pub snippets: HashMap<String, String>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    #[serde(default)]
+    pub shell: ShellConfig,
+    // Environment variables which are expanded in the paths of actions, no expansion when empty
+    #[serde(default)]
+    pub expand_env_vars: Vec<String>,
+    // Glob patterns of the paths which are never written, moved or deleted by the actions, e.g. `~/.ssh/**`
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    // Glob patterns of the paths skipped by the file discovery in addition to `.gitignore` and `.cliffignore`, e.g. `**/*.min.js`
+    #[serde(default)]
+    pub excluded_paths: Vec<String>,
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub output_trimming: TrimmingConfig,
+    #[serde(default)]
+    pub plan_preview: PreviewConfig,
+    // Configured model computing the embeddings for `embed` and `similar`
+    pub embedding_model: Option<String>,
+    // Configured model compressing the oversized context and history before they are sent to the main model
+    pub summarizer_model: Option<String>,
+    // Configured model classifying the fetched context as prompt injection in addition to the heuristics
+    pub guardrail_model: Option<String>,
+    // Whether to notify about new releases, checked at most once a day
+    #[serde(default)]
+    pub check_for_updates: bool,
+}
+
+// Named instruction of the project run with `cliff run <name>`, e.g. `tasks.test-fix = { instruction = "...", verify = "cargo test" }`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Task {
+    pub instruction: String,
+    // Command checking the result of the plan, a new plan is made with its output while it fails
+    pub verify: Option<String>,
+    // Files or URLs provided as context in addition to the ones given on the command line
+    #[serde(default)]
+    pub context: Vec<String>,
+    #[serde(default = "Task::default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl Task {
+    fn default_max_attempts() -> u32 {
+        3
+    }
+}
+
+// Settings of the project in the working directory, checked into the project as `.cliff.toml`
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ProjectConfig {
+    // Machine to execute the plans on, e.g. "ssh://deploy@example.com"
+    pub target: Option<String>,
+    #[serde(default)]
+    pub tasks: HashMap<String, Task>,
+}
+
+impl ProjectConfig {
+    const PROJECT_CONFIG_FILENAME: &'static str = ".cliff.toml";
+
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let path = project_dir.join(Self::PROJECT_CONFIG_FILENAME);
+        if !path.exists() {
+            return Ok(ProjectConfig::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read project config file: {:?}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse project config file: {:?}", path))
+    }
+
+    pub fn task(&self, name: &str) -> Result<&Task> {
+        self.tasks.get(name).ok_or_else(|| {
+            let mut names: Vec<&str> = self.tasks.keys().map(String::as_str).collect();
+            names.sort();
+            ActionError::NotFound(format!(
+                "Task '{}' is not defined in {}, the defined tasks: {}",
+                name, Self::PROJECT_CONFIG_FILENAME, if names.is_empty() { "none".to_string() } else { names.join(", ") }
+            )).into()
+        })
+    }
+
+    // The target the plans are executed on without `--target`, a remote one only after the user confirms it
+    pub fn target(&self, auto_confirm: AutoConfirm) -> Result<Arc<dyn ExecutionTarget>> {
+        let Some(target) = &self.target else {
+            return Ok(Arc::new(LocalTarget));
+        };
+        let target = parse_target(target).with_context(|| format!("Invalid target in {}", Self::PROJECT_CONFIG_FILENAME))?;
+        if !target.is_local() {
+            confirm_action::confirm_project_target(target.as_ref(), Self::PROJECT_CONFIG_FILENAME, auto_confirm)?;
+        }
+        Ok(target)
+    }
 }
 
 impl Config {
     const CONFIG_FILENAME: &'static str = "config.toml";
-    const CONFIG_DIR_NAME: &'static str = "cliff";
 
 
+    pub(crate) fn config_dir() -> Result<PathBuf> {
+        profiles::config_dir()
+    }
+
     pub(crate) fn config_path() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
-            .with_context(|| "Failed to find config directory")?
-            .join(Self::CONFIG_DIR_NAME);
-        Ok(config_dir.join(Self::CONFIG_FILENAME))
+        Ok(Self::config_dir()?.join(Self::CONFIG_FILENAME))
     }
 
-    fn create_config_dir(path: &PathBuf) -> Result<()> {
+    fn create_config_dir(path: &Path) -> Result<()> {
         if let Some(parent) = path.parent() {
             create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory: {:?}", parent))?; 
@@ -84,7 +497,7 @@ impl Config {
             model_setter(self, model_name.to_string());
             Ok(())
         } else {
-            anyhow::bail!("Model '{}' not found in configuration.", model_name);
+            Err(ConfigError::ModelNotFound(model_name.to_string()).into())
         }
     }
 
@@ -93,6 +506,30 @@ impl Config {
         self.set_model(name, |config, name| config.default_model = Some(name))
     }
 
+    pub fn set_embedding_model(&mut self, name: &str) -> Result<()> {
+        self.set_model(name, |config, name| config.embedding_model = Some(name))
+    }
+
+    pub fn get_embedding_model(&self) -> Option<&Model> {
+        self.embedding_model.as_ref().and_then(|name| self.models.get(name))
+    }
+
+    pub fn set_summarizer_model(&mut self, name: &str) -> Result<()> {
+        self.set_model(name, |config, name| config.summarizer_model = Some(name))
+    }
+
+    pub fn get_summarizer_model(&self) -> Option<&Model> {
+        self.summarizer_model.as_ref().and_then(|name| self.models.get(name))
+    }
+
+    pub fn set_guardrail_model(&mut self, name: &str) -> Result<()> {
+        self.set_model(name, |config, name| config.guardrail_model = Some(name))
+    }
+
+    pub fn get_guardrail_model(&self) -> Option<&Model> {
+        self.guardrail_model.as_ref().and_then(|name| self.models.get(name))
+    }
+
     pub fn set_current_model(&mut self, name: &str) -> Result<()> {
         self.set_model(name, |config, name| config.current_model = Some(name))
     }
@@ -101,6 +538,18 @@ impl Config {
         self.current_model = None;
     }
 
+    pub fn add_snippet(&mut self, name: &str, snippet: &str) {
+        self.snippets.insert(name.to_string(), snippet.to_string());
+    }
+
+    pub fn delete_snippet(&mut self, name: &str) -> Result<()> {
+        if self.snippets.remove(name).is_some() {
+            Ok(())
+        } else {
+            anyhow::bail!("Snippet '{}' not found in configuration.", name);
+        }
+    }
+
     pub fn delete_model(&mut self, name: &str) -> Result<()> {
         if self.models.remove(name).is_some() {
             if self.default_model == Some(name.to_string()) {
@@ -111,7 +560,7 @@ impl Config {
             }
             Ok(())
         } else {
-            anyhow::bail!("Model '{}' not found in configuration.", name);
+            Err(ConfigError::ModelNotFound(name.to_string()).into())
         }
     }
 }
@@ -128,10 +577,13 @@ mod tests {
             name: "test-model".to_string(),
             api_url: "http://localhost:8080".to_string(),
             api_key: Some("test-key".to_string()),
-            api_key_header: None,
             model_identifier: Some("gpt-test".to_string()),
             request_format: "test-format".to_string(),
             response_json_path: "$".to_string(),
+            proxy: Some("socks5h://127.0.0.1:9050".to_string()),
+            query_params: BTreeMap::from([("api-version".to_string(), "2024-10-21".to_string())]),
+            capabilities: ModelCapabilities { supports_json_mode: true, context_window: Some(128000), ..ModelCapabilities::default() },
+            ..Model::default()
         };
         config.add_model(model.clone());
         config.set_default_model("test-model")?; 
@@ -143,6 +595,12 @@ mod tests {
         assert_eq!(config.models.len(), 1);
         assert_eq!(deserialized.models.len(), 1);
         assert_eq!(config.models.get("test-model").unwrap().api_url, deserialized.models.get("test-model").unwrap().api_url);
+        assert_eq!(deserialized.models["test-model"].capabilities, model.capabilities);
+        assert_eq!(deserialized.models["test-model"].proxy, model.proxy);
+        assert_eq!(deserialized.models["test-model"].query_params, model.query_params);
+        // The capabilities are kept next to the other fields of the model and the unsupported ones are omitted
+        assert!(serialized.contains("supports_json_mode = true\n"));
+        assert!(!serialized.contains("supports_vision"));
         Ok(())
     }
 
@@ -150,8 +608,8 @@ mod tests {
     fn test_get_active_model() -> Result<()> {
         let mut config = Config::default();
         let request_format = r#"{"input": "{{prompt}}"}"#;
-        let model1 = Model { name: "model1".to_string(), api_url: "url1".to_string(), api_key: None, api_key_header: None, model_identifier: None, request_format: request_format.to_string(), response_json_path: "$".to_string() };
-        let model2 = Model { name: "model2".to_string(), api_url: "url2".to_string(), api_key: None, api_key_header: None, model_identifier: None, request_format: request_format.to_string(), response_json_path: "$".to_string() };
+        let model1 = Model { name: "model1".to_string(), api_url: "url1".to_string(), request_format: request_format.to_string(), response_json_path: "$".to_string(), ..Model::default() };
+        let model2 = Model { name: "model2".to_string(), api_url: "url2".to_string(), request_format: request_format.to_string(), response_json_path: "$".to_string(), ..Model::default() };
         config.add_model(model1.clone());
         config.add_model(model2.clone());
 
@@ -172,7 +630,7 @@ mod tests {
     fn test_set_default_current_model_errors() {
         let mut config = Config::default();
         let request_format = r#"{"input": "{{prompt}}"}"#;
-        let model = Model { name: "model1".to_string(), api_url: "url1".to_string(), api_key: None, api_key_header: None, model_identifier: None, request_format: request_format.to_string(), response_json_path: "$".to_string() };
+        let model = Model { name: "model1".to_string(), api_url: "url1".to_string(), request_format: request_format.to_string(), response_json_path: "$".to_string(), ..Model::default() };
         config.add_model(model.clone());
 
         assert!(config.set_default_model("model1").is_ok());
@@ -181,4 +639,22 @@ mod tests {
         assert!(config.set_current_model("model1").is_ok());
         assert!(config.set_current_model("nonexistent").is_err());
     }
+
+    #[test]
+    fn test_project_target_needs_confirmation() -> Result<()> {
+        let project_config = ProjectConfig { target: Some("ssh://deploy@example.com".to_string()), ..ProjectConfig::default() };
+        let error = project_config.target(AutoConfirm::All).unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "policy_denied");
+        assert!(ProjectConfig::default().target(AutoConfirm::All)?.is_local());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shell_args() {
+        assert_eq!(Shell::from_program("/usr/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::from_program("/opt/bin/unknown-shell"), None);
+        assert_eq!(Shell::Bash.args("ls", true), vec!["-l", "-c", "ls"]);
+        assert_eq!(Shell::Powershell.args("ls", false), vec!["-Command", "ls"]);
+        assert_eq!(ShellConfig { name: Some(Shell::Zsh), login: false }.resolve(), Shell::Zsh);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::classification::{classify, ActionClass};
+use crate::config::Model;
+use crate::executor::{Action, ExecutionSettings, Plan};
+use crate::llm::{ask_llm_for_plan, ask_llm_to_reconcile_plans};
+use colored::*;
+use reqwest::Client;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+// Free text of the actions, the plans differing only in it have the same structure
+const FREE_TEXT_FIELDS: [&str; 10] = ["action_idx", "content", "replacement_lines", "prompt", "instruction", "question", "questions", "choices", "validation_regex", "variables"];
+
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq)]
+pub enum DivergencePolicy {
+    /// Ask the LLM to reconcile the plans into one
+    #[default]
+    Reconcile,
+    /// Use the plan running the fewest commands and writing the fewest files
+    Conservative,
+}
+
+// The action without its free text, e.g. the command of RunCommand or the path of CreateFile
+pub(crate) fn step_signature(action: &Action) -> Value {
+    let mut signature = serde_json::to_value(action).unwrap_or_default();
+    if let Value::Object(fields) = &mut signature {
+        for field in FREE_TEXT_FIELDS {
+            fields.remove(field);
+        }
+    }
+    signature
+}
+
+fn plan_signature(plan: &Plan) -> Vec<Value> {
+    plan.steps.iter().map(step_signature).collect()
+}
+
+// Index of a plan with the structure shared by more than half of the plans
+fn majority(plans: &[Plan]) -> Option<usize> {
+    let signatures: Vec<Vec<Value>> = plans.iter().map(plan_signature).collect();
+    (0..plans.len()).find(|&i| signatures.iter().filter(|signature| **signature == signatures[i]).count() * 2 > plans.len())
+}
+
+// Commands are riskier than writes, which are riskier than reads, the shorter plan is preferred when equally risky
+fn risk(plan: &Plan) -> (usize, usize, usize) {
+    let classes: Vec<ActionClass> = plan.steps.iter().map(classify).collect();
+    let commands = classes.iter().filter(|class| **class == ActionClass::Execute).count();
+    let writes = classes.iter().filter(|class| matches!(class, ActionClass::Write { .. })).count();
+    (commands, writes, plan.steps.len())
+}
+
+fn most_conservative(plans: &[Plan]) -> usize {
+    (0..plans.len()).min_by_key(|&i| risk(&plans[i])).unwrap_or_default()
+}
+
+/// Generates `samples` plans in parallel and uses the plan most of them agree on, when they diverge the plans are
+/// reconciled by the LLM or the most conservative one is used
+pub async fn consistent_plan(
+    model_config: &Model,
+    instruction: &str,
+    context_sources: &[String],
+    settings: &ExecutionSettings,
+    samples: usize,
+    on_divergence: DivergencePolicy,
+    client: &Client,
+) -> Result<Plan> {
+    let model_config = Arc::new(model_config.clone());
+    let (instruction, context_sources) = (Arc::new(instruction.to_string()), Arc::new(context_sources.to_vec()));
+    let mut requests = JoinSet::new();
+    for index in 0..samples {
+        let (model_config, instruction, context_sources) = (model_config.clone(), instruction.clone(), context_sources.clone());
+        let (settings, client) = (settings.clone(), client.clone());
+        requests.spawn(async move {
+            (index, ask_llm_for_plan(&model_config, &instruction, &context_sources, &[], &settings, &client).await)
+        });
+    }
+    let mut plans = Vec::new();
+    let mut last_error = None;
+    while let Some(joined) = requests.join_next().await {
+        match joined? {
+            (index, Ok(plan)) => plans.push((index, plan)),
+            (index, Err(e)) => {
+                eprintln!("Warning: Plan {} of {} could not be generated: {:#}", index + 1, samples, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    plans.sort_by_key(|(index, _)| *index);
+    let mut plans: Vec<Plan> = plans.into_iter().map(|(_, plan)| plan).collect();
+    if plans.is_empty() {
+        return Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No plans were requested")));
+    }
+    if let Some(agreed) = majority(&plans) {
+        let agreeing = plans.iter().filter(|plan| plan_signature(plan) == plan_signature(&plans[agreed])).count();
+        println!("{}", format!("{} of {} generated plans agree, using the majority plan.", agreeing, plans.len()).green());
+        return Ok(plans.swap_remove(agreed));
+    }
+    match on_divergence {
+        DivergencePolicy::Conservative => {
+            let chosen = most_conservative(&plans);
+            println!("{}", format!("The {} generated plans diverge, using the most conservative one (plan {}).", plans.len(), chosen + 1).yellow());
+            Ok(plans.swap_remove(chosen))
+        }
+        DivergencePolicy::Reconcile => {
+            println!("{}", format!("The {} generated plans diverge, asking the LLM to reconcile them.", plans.len()).yellow());
+            ask_llm_to_reconcile_plans(&model_config, &instruction, &plans, client).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_plan_agreement() {
+        let plan = |steps: Vec<Action>| Plan { thought: None, steps, params: BTreeMap::new(), expectations: BTreeMap::new(), preconditions: Vec::new() };
+        let read = Action::ReadFile { action_idx: 0, path: "Cargo.toml".to_string() };
+        let create = |content: &str| Action::CreateFile { action_idx: 1, path: "notes.md".to_string(), content: content.to_string(), if_not_exists: false, overwrite: true };
+        let command = Action::RunCommand { action_idx: 1, command: "rm -rf target".to_string() };
+
+        // The content of the file differs, but the plans do the same
+        let plans = vec![plan(vec![read.clone(), create("a")]), plan(vec![read.clone(), command.clone()]), plan(vec![read.clone(), create("b")])];
+        assert_eq!(majority(&plans), Some(0));
+        let plans = vec![plan(vec![read.clone(), command.clone()]), plan(vec![read.clone(), create("a")])];
+        assert_eq!(majority(&plans), None);
+        assert_eq!(most_conservative(&plans), 1);
+    }
+}
@@ -0,0 +1,200 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use crate::prompt::DATA_RULES;
+use std::fmt;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::System => write!(f, "System"),
+            Role::User => write!(f, "User"),
+            Role::Assistant => write!(f, "Assistant"),
+        }
+    }
+}
+
+// Message in the format of the OpenAI-compatible chat APIs, substituted for `{{messages}}` in the request format
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    // Data URLs of the images, sent as the content parts of the message
+    pub images: Vec<String>,
+    // The message ends a prefix repeated in the requests, marked for the prompt caching of the APIs supporting it
+    pub cacheable: bool,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        ChatMessage { role, content: content.into(), images: Vec::new(), cacheable: false }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage::new(Role::User, content)
+    }
+
+    pub fn with_images(self, images: Vec<String>) -> Self {
+        ChatMessage { images, ..self }
+    }
+
+    pub fn cacheable(self) -> Self {
+        ChatMessage { cacheable: true, ..self }
+    }
+}
+
+// The content is a string unless there are images, then it is an array of the text and the image parts
+impl Serialize for ChatMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut message = serializer.serialize_struct("ChatMessage", 2)?;
+        message.serialize_field("role", &self.role)?;
+        if self.images.is_empty() {
+            message.serialize_field("content", &self.content)?;
+        } else {
+            let parts: Vec<_> = std::iter::once(json!({ "type": "text", "text": self.content }))
+                .chain(self.images.iter().map(|url| json!({ "type": "image_url", "image_url": { "url": url } })))
+                .collect();
+            message.serialize_field("content", &parts)?;
+        }
+        message.end()
+    }
+}
+
+// Messages as a single prompt for the request formats without `{{messages}}`
+pub fn transcript(messages: &[ChatMessage]) -> String {
+    match messages {
+        [message] => message.content.clone(),
+        _ => messages
+            .iter()
+            .map(|message| format!("{}: {}", message.role, message.content))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+const SUMMARY_INSTRUCTION: &str = "Summarize the conversation so far in a few sentences, keep the facts, decisions and open questions needed to continue it. Output only the summary.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub question: String,
+    pub answer: String,
+}
+
+// History of a session, only the last `max_turns` turns are sent verbatim, the older ones are replaced by their summary
+#[derive(Debug)]
+pub struct Conversation {
+    context: Option<String>,
+    summary: Option<String>,
+    turns: Vec<Turn>,
+    max_turns: usize,
+}
+
+impl Conversation {
+    pub fn new(context: Option<String>, max_turns: usize) -> Self {
+        Conversation { context, summary: None, turns: Vec::new(), max_turns }
+    }
+
+    // Messages asking `question` after the history of the conversation
+    pub fn messages(&self, question: &str) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        if let Some(context) = &self.context {
+            messages.push(ChatMessage::new(Role::System, format!("{}\nContext:\n{}", DATA_RULES, context)).cacheable());
+        }
+        messages.extend(self.history(&self.turns));
+        messages.push(ChatMessage::user(question));
+        messages
+    }
+
+    // Messages asking to summarize the previous summary together with the overflowing turns
+    pub fn summary_request(&self) -> Vec<ChatMessage> {
+        let mut messages = self.history(self.overflow());
+        messages.push(ChatMessage::user(SUMMARY_INSTRUCTION));
+        messages
+    }
+
+    fn history(&self, turns: &[Turn]) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        if let Some(summary) = &self.summary {
+            messages.push(ChatMessage::new(Role::System, format!("Summary of the earlier conversation:\n{}", summary)));
+        }
+        for turn in turns {
+            messages.push(ChatMessage::user(turn.question.clone()));
+            messages.push(ChatMessage::new(Role::Assistant, turn.answer.clone()));
+        }
+        messages
+    }
+
+    pub fn push(&mut self, question: &str, answer: &str) {
+        self.turns.push(Turn { question: question.to_string(), answer: answer.to_string() });
+    }
+
+    // Oldest turns not fitting into the window which should be summarized
+    pub fn overflow(&self) -> &[Turn] {
+        &self.turns[..self.turns.len().saturating_sub(self.max_turns)]
+    }
+
+    // Replaces the overflowing turns with the summary of them and of the previous summary
+    pub fn compact(&mut self, summary: String) {
+        let overflow = self.overflow().len();
+        self.turns.drain(..overflow);
+        self.summary = Some(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversation_window() {
+        let mut conversation = Conversation::new(Some("notes.txt".to_string()), 2);
+        conversation.push("q1", "a1");
+        conversation.push("q2", "a2");
+        assert!(conversation.overflow().is_empty());
+
+        conversation.push("q3", "a3");
+        assert_eq!(conversation.overflow(), &[Turn { question: "q1".to_string(), answer: "a1".to_string() }]);
+
+        assert_eq!(conversation.summary_request(), vec![
+            ChatMessage::user("q1"),
+            ChatMessage::new(Role::Assistant, "a1"),
+            ChatMessage::user(SUMMARY_INSTRUCTION),
+        ]);
+
+        conversation.compact("Asked q1".to_string());
+        assert_eq!(conversation.messages("q4"), vec![
+            ChatMessage::new(Role::System, format!("{}\nContext:\nnotes.txt", DATA_RULES)).cacheable(),
+            ChatMessage::new(Role::System, "Summary of the earlier conversation:\nAsked q1"),
+            ChatMessage::user("q2"),
+            ChatMessage::new(Role::Assistant, "a2"),
+            ChatMessage::user("q3"),
+            ChatMessage::new(Role::Assistant, "a3"),
+            ChatMessage::user("q4"),
+        ]);
+        assert_eq!(
+            serde_json::to_string(&ChatMessage::new(Role::Assistant, "a\n\"b\"")).unwrap(),
+            r#"{"role":"assistant","content":"a\n\"b\""}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ChatMessage::user("What is it?").with_images(vec!["data:image/png;base64,AA==".to_string()])).unwrap(),
+            r#"{"role":"user","content":[{"type":"text","text":"What is it?"},{"type":"image_url","image_url":{"url":"data:image/png;base64,AA=="}}]}"#
+        );
+    }
+
+    #[test]
+    fn test_transcript() {
+        assert_eq!(transcript(&[ChatMessage::user("Hello")]), "Hello");
+        assert_eq!(
+            transcript(&[ChatMessage::user("Hello"), ChatMessage::new(Role::Assistant, "Hi")]),
+            "User: Hello\n\nAssistant: Hi"
+        );
+    }
+}
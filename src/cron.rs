@@ -0,0 +1,198 @@
+use anyhow::Result;
+use crate::error::ActionError;
+
+const SECS_PER_MINUTE: i64 = 60;
+const SECS_PER_HOUR: i64 = 60 * SECS_PER_MINUTE;
+const SECS_PER_DAY: i64 = 24 * SECS_PER_HOUR;
+// A schedule without any matching time in this many days never matches, e.g. "0 0 31 2 *"
+const MAX_SEARCH_DAYS: i64 = 5 * 366;
+
+// Cron schedule of the standard 5 fields: minute, hour, day of month, month and day of week (0 or 7 is Sunday)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    // As in cron, when both the day of month and the day of week are restricted a day matching either one matches
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+// Values `min..=max` of the field allowed by the expression, e.g. "*/15", "1-5" or "0,30"
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>> {
+    let invalid = || ActionError::InvalidInput(format!("Invalid cron field '{}', expected values from {} to {}", field, min, max));
+    let mut allowed = vec![false; max as usize + 1];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse().map_err(|_| invalid())?, end.parse().map_err(|_| invalid())?),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // A single value with a step means from the value to the maximum
+                    (value, if item.contains('/') { max } else { value })
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(invalid().into());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(ActionError::InvalidInput(format!(
+                "Invalid cron schedule '{}', expected 5 fields: minute hour day-of-month month day-of-week", expression
+            )).into());
+        };
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+        Ok(CronSchedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(day_of_month, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: day_of_month != "*",
+            days_of_week_restricted: day_of_week != "*",
+        })
+    }
+
+    fn matches_day(&self, days: i64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        if !self.months[month as usize] {
+            return false;
+        }
+        let day_of_month = self.days_of_month[day as usize];
+        let day_of_week = self.days_of_week[weekday(days) as usize];
+        match (self.days_of_month_restricted, self.days_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+
+    // First matching minute after `after`, both in seconds since the epoch of the same time zone
+    fn next_after_in_zone(&self, after: i64) -> Option<i64> {
+        let mut time = (after.div_euclid(SECS_PER_MINUTE) + 1) * SECS_PER_MINUTE;
+        let last = time + MAX_SEARCH_DAYS * SECS_PER_DAY;
+        while time < last {
+            let days = time.div_euclid(SECS_PER_DAY);
+            let seconds_of_day = time.rem_euclid(SECS_PER_DAY);
+            if !self.matches_day(days) {
+                time = (days + 1) * SECS_PER_DAY;
+            } else if !self.hours[(seconds_of_day / SECS_PER_HOUR) as usize] {
+                time = (time.div_euclid(SECS_PER_HOUR) + 1) * SECS_PER_HOUR;
+            } else if !self.minutes[(seconds_of_day % SECS_PER_HOUR / SECS_PER_MINUTE) as usize] {
+                time += SECS_PER_MINUTE;
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    /// First time matching the schedule in the local time zone after the unix timestamp `after`
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let local = self.next_after_in_zone(after + local_offset(after))?;
+        Some(local - local_offset(local - local_offset(after)))
+    }
+}
+
+// Offset of the local time zone from UTC in seconds at the unix timestamp
+#[cfg(unix)]
+fn local_offset(timestamp: i64) -> i64 {
+    let time = timestamp as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: localtime_r only writes to the provided `tm`
+    let converted = unsafe { !libc::localtime_r(&time, &mut tm).is_null() };
+    if converted { tm.tm_gmtoff as i64 } else { 0 }
+}
+
+// The schedules are in UTC on the platforms without `localtime_r`
+#[cfg(not(unix))]
+fn local_offset(_timestamp: i64) -> i64 {
+    0
+}
+
+// Year, month and day of the days since 1970-01-01, the algorithm of http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Day of the week of the days since 1970-01-01 (a Thursday), 0 is Sunday
+fn weekday(days: i64) -> u32 {
+    (days + 4).rem_euclid(7) as u32
+}
+
+/// Local date and time of the unix timestamp as `YYYY-MM-DD HH:MM`
+pub fn format_local(timestamp: i64) -> String {
+    let local = timestamp + local_offset(timestamp);
+    let (year, month, day) = civil_from_days(local.div_euclid(SECS_PER_DAY));
+    let seconds_of_day = local.rem_euclid(SECS_PER_DAY);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds_of_day / SECS_PER_HOUR, seconds_of_day % SECS_PER_HOUR / SECS_PER_MINUTE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2026-10-16 12:34:56 UTC, a Friday
+    const NOW: i64 = 1_792_154_096;
+
+    #[test]
+    fn test_cron_schedule() -> Result<()> {
+        assert_eq!(civil_from_days(NOW / SECS_PER_DAY), (2026, 10, 16));
+        assert_eq!(weekday(NOW / SECS_PER_DAY), 5);
+
+        let day = NOW - NOW % SECS_PER_DAY;
+        let daily = CronSchedule::parse("0 9 * * *")?;
+        assert_eq!(daily.next_after_in_zone(NOW), Some(day + SECS_PER_DAY + 9 * SECS_PER_HOUR));
+        let quarter_hourly = CronSchedule::parse("*/15 12-13 * * *")?;
+        assert_eq!(quarter_hourly.next_after_in_zone(NOW), Some(day + 12 * SECS_PER_HOUR + 45 * SECS_PER_MINUTE));
+        // Monday 2026-10-19 or the 1st of November, whichever is first
+        let weekly = CronSchedule::parse("30 8 1 * 1")?;
+        assert_eq!(weekly.next_after_in_zone(NOW), Some(day + 3 * SECS_PER_DAY + 8 * SECS_PER_HOUR + 30 * SECS_PER_MINUTE));
+        assert_eq!(CronSchedule::parse("@weekly")?, CronSchedule::parse("0 0 * * 7")?);
+        assert_eq!(CronSchedule::parse("0 0 31 2 *")?.next_after_in_zone(NOW), None);
+
+        assert!(CronSchedule::parse("0 9 * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        Ok(())
+    }
+}
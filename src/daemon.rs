@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use crate::actions::run_command::pipe_to_command;
+use crate::changes;
+use crate::config::{Model, ProjectConfig};
+use crate::cron::{self, CronSchedule};
+use crate::error::ActionError;
+use crate::executor::ExecutionSettings;
+use crate::metrics;
+use crate::profiles;
+use crate::runs::{self, RunMetadata, RunStatus};
+use crate::tasks;
+use crate::trace;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LOG_FILENAME: &str = "daemon.log";
+
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    // None to run the tasks once right away
+    pub schedule: Option<CronSchedule>,
+    pub tasks: Vec<String>,
+    // Values of the `{{name}}` placeholders of the task instructions
+    pub params: HashMap<String, String>,
+    // Shell command receiving a summary of every finished task on its standard input
+    pub notify: Option<String>,
+}
+
+fn log_path() -> Result<PathBuf> {
+    let data_dir = profiles::data_dir()?;
+    Ok(data_dir.join(LOG_FILENAME))
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+// Line of the daemon log about the finished run of the task
+fn log_line(task: &str, run: &RunMetadata) -> String {
+    let finished_at = run.finished_at.map_or(now(), |finished_at| finished_at as i64);
+    let status = match run.status {
+        RunStatus::Running => "running",
+        RunStatus::Succeeded => "succeeded",
+        RunStatus::Failed => "failed",
+    };
+    match &run.error {
+        Some(error) => format!("{} task={} run={} status={} error={}", cron::format_local(finished_at), task, run.id, status, error.replace('\n', " ")),
+        None => format!("{} task={} run={} status={}", cron::format_local(finished_at), task, run.id, status),
+    }
+}
+
+fn append_log(line: &str) -> Result<()> {
+    let path = log_path()?;
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).with_context(|| format!("Failed to create data directory: {:?}", parent))?;
+    }
+    let mut log = OpenOptions::new().create(true).append(true).open(&path)
+        .with_context(|| format!("Failed to open the daemon log: {:?}", path))?;
+    writeln!(log, "{}", line).with_context(|| format!("Failed to write the daemon log: {:?}", path))
+}
+
+// Runs every task of the round, the failures are logged and notified but do not stop the other tasks, returns the
+// number of the failed tasks
+async fn run_round(
+    options: &DaemonOptions,
+    project_config: &ProjectConfig,
+    model: &Model,
+    context: &[String],
+    settings: &ExecutionSettings,
+    client: &Client,
+) -> Result<usize> {
+    let mut failed = 0;
+    for name in &options.tasks {
+        let task = &tasks::with_params(project_config.task(name)?, &options.params)?;
+        let mut run = RunMetadata::new("daemon", &task.instruction, &model.name);
+        if let Err(e) = runs::record(&run) {
+            eprintln!("Warning: Failed to record run '{}': {}", run.id, e);
+        }
+        println!("Running task '{}' (run {})", name, run.id);
+        let task_context = [task.context.as_slice(), context].concat();
+        settings.target.start_run();
+        let result = tasks::run_task(task, model, &task_context, settings.clone(), client).await;
+        run.finish(&result);
+        if let Err(e) = runs::record(&run) {
+            eprintln!("Warning: Failed to record run '{}': {}", run.id, e);
+        }
+        metrics::record_run(&run);
+        if let Err(e) = trace::save(&run) {
+            eprintln!("Warning: Failed to save the trace of run '{}': {:#}", run.id, e);
+        }
+        if let Err(e) = changes::save(&run) {
+            eprintln!("Warning: Failed to save the changes of run '{}': {:#}", run.id, e);
+        }
+        if result.is_err() {
+            failed += 1;
+        }
+        let line = log_line(name, &run);
+        println!("{}", line);
+        if let Err(e) = append_log(&line) {
+            eprintln!("Warning: {:#}", e);
+        }
+        if let Some(notify) = &options.notify {
+            if let Err(e) = pipe_to_command(&line, notify, settings.shell).await {
+                eprintln!("Warning: Failed to notify about task '{}': {:#}", name, e);
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Runs the tasks of the project on the schedule, or once when there is no schedule. The actions not confirmed
+/// automatically by the settings are denied as nobody answers the prompts
+pub async fn run(
+    options: &DaemonOptions,
+    project_config: &ProjectConfig,
+    model: &Model,
+    context: &[String],
+    settings: ExecutionSettings,
+    client: &Client,
+) -> Result<()> {
+    // Unknown tasks are reported right away instead of at the first scheduled time
+    for name in &options.tasks {
+        tasks::with_params(project_config.task(name)?, &options.params)?;
+    }
+    let Some(schedule) = &options.schedule else {
+        let failed = run_round(options, project_config, model, context, &settings, client).await?;
+        if failed > 0 {
+            return Err(ActionError::CommandFailed { message: format!("{} of {} tasks failed", failed, options.tasks.len()), exit_code: None }.into());
+        }
+        return Ok(());
+    };
+    loop {
+        let now = now();
+        let next = schedule.next_after(now)
+            .ok_or_else(|| ActionError::InvalidInput("The schedule never matches".to_string()))?;
+        println!("Next run at {}", cron::format_local(next));
+        tokio::time::sleep(Duration::from_secs((next - now).max(0) as u64)).await;
+        run_round(options, project_config, model, context, &settings, client).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_line() {
+        let mut run = RunMetadata::new("daemon", "Summarize the day", "gpt");
+        run.finish::<()>(&Err(anyhow::anyhow!("Network error\nretry later")));
+        let line = log_line("daily-summary", &run);
+        assert!(line.ends_with(&format!("task=daily-summary run={} status=failed error=Network error retry later", run.id)));
+    }
+}
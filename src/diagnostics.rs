@@ -0,0 +1,91 @@
+use crate::actions::run_command;
+use crate::actions::ActionOutput;
+use crate::classification::{classify, ActionClass};
+use crate::config::DiagnosticsConfig;
+use crate::executor::{Action, ExecutionSettings};
+use std::path::Path;
+use std::sync::OnceLock;
+
+// Files of the recognized projects and the commands checking them, the first project file found in the working directory decides
+const PROJECT_CHECKS: [(&str, &str); 3] = [
+    ("Cargo.toml", "cargo check --quiet --message-format=short"),
+    ("tsconfig.json", "npx --no-install tsc --noEmit --pretty false"),
+    ("go.mod", "go vet ./..."),
+];
+
+// The project is checked after the steps writing files only when enabled in the config
+static CONFIG: OnceLock<DiagnosticsConfig> = OnceLock::new();
+
+pub fn enable(config: DiagnosticsConfig) {
+    let _ = CONFIG.set(config);
+}
+
+// Configured command or the command checking the project in `dir`
+fn check_command(config: &DiagnosticsConfig, dir: &Path) -> Option<String> {
+    config.command.clone().or_else(|| {
+        PROJECT_CHECKS.iter()
+            .find(|(project_file, _)| dir.join(project_file).is_file())
+            .map(|(_, command)| command.to_string())
+    })
+}
+
+/// Checks the project after the step writing files when enabled, the returned check is added to the history so that
+/// the LLM sees the errors it introduced, the failures to run the check are warned about
+pub async fn check_step(action: &Action, settings: &ExecutionSettings) -> Option<(Action, ActionOutput)> {
+    let config = CONFIG.get().filter(|config| config.enabled)?;
+    let ActionClass::Write { paths } = classify(action) else {
+        return None;
+    };
+    if !settings.target.is_local() {
+        return None;
+    }
+    let command = check_command(config, &std::env::current_dir().ok()?)?;
+    println!("Checking the project after the edit of {}.", paths.join(", "));
+    match run_command::execute(&command, settings.shell, settings.target.as_ref()).await {
+        Ok(ActionOutput::Command { exit_code, stdout, stderr }) => {
+            let check = Action::RunCommand { action_idx: 0, command };
+            let output = ActionOutput::Command { exit_code, stdout: edited_file_lines(&stdout, &paths), stderr: edited_file_lines(&stderr, &paths) };
+            Some((check, output))
+        }
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Warning: Failed to check the project with `{}`: {:#}", command, e);
+            None
+        }
+    }
+}
+
+// The lines of the diagnostics mentioning the edited files, all the lines when none does, e.g. as the edit broke its callers
+fn edited_file_lines(output: &str, paths: &[String]) -> String {
+    let paths: Vec<&str> = paths.iter().map(|path| path.trim_start_matches("./")).collect();
+    let lines: Vec<&str> = output.lines().filter(|line| paths.iter().any(|path| line.contains(path))).collect();
+    if lines.is_empty() {
+        output.to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_command() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = DiagnosticsConfig { enabled: true, command: None };
+        assert_eq!(check_command(&config, dir.path()), None);
+        std::fs::write(dir.path().join("go.mod"), "module example.com/app\n")?;
+        assert_eq!(check_command(&config, dir.path()).as_deref(), Some("go vet ./..."));
+        let config = DiagnosticsConfig { enabled: true, command: Some("make lint".to_string()) };
+        assert_eq!(check_command(&config, dir.path()).as_deref(), Some("make lint"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_edited_file_lines() {
+        let output = "src/lib.rs:3:5: error[E0425]: cannot find value `x` in this scope\nsrc/main.rs:10:1: warning: unused import\nerror: could not compile `app`";
+        assert_eq!(edited_file_lines(output, &["./src/lib.rs".to_string()]), "src/lib.rs:3:5: error[E0425]: cannot find value `x` in this scope");
+        assert_eq!(edited_file_lines(output, &["src/config.rs".to_string()]), output);
+    }
+}
@@ -0,0 +1,117 @@
+use colored::*;
+use crate::consistency::step_signature;
+use crate::executor::Action;
+use serde_json::Value;
+use similar::{capture_diff_slices, Algorithm, DiffOp, TextDiff};
+
+// Unified diff of `old` and `new` limited to `max_lines` lines
+pub(crate) fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str, max_lines: usize) -> String {
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(old_label, new_label)
+        .to_string();
+    let diff_lines: Vec<&str> = diff.lines().collect();
+    if diff_lines.len() > max_lines {
+        format!("{}\n... ({} more diff lines not shown)", diff_lines[..max_lines].join("\n"), diff_lines.len() - max_lines)
+    } else {
+        diff
+    }
+}
+
+// Unified diff with the added lines in green, the removed ones in red and the file headers in bold
+pub(crate) fn colorize_unified_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| match line {
+            _ if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff ") => line.bold().to_string(),
+            _ if line.starts_with('+') => line.green().to_string(),
+            _ if line.starts_with('-') => line.red().to_string(),
+            _ if line.starts_with("@@") => line.cyan().to_string(),
+            _ => line.to_string(),
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum StepChange<'a> {
+    Kept(&'a Action),
+    Removed(&'a Action),
+    Added(&'a Action),
+    // Same action on the same target with a different free text, e.g. the content of a file
+    Changed { old: &'a Action, new: &'a Action },
+}
+
+// The action without its number, the same step keeps it when the plan is renumbered
+fn step_key(action: &Action) -> String {
+    let mut key = serde_json::to_value(action).unwrap_or_default();
+    if let Value::Object(fields) = &mut key {
+        fields.remove("action_idx");
+    }
+    key.to_string()
+}
+
+// Changes turning the `old` steps into the `new` ones
+pub(crate) fn plan_diff<'a>(old: &'a [Action], new: &'a [Action]) -> Vec<StepChange<'a>> {
+    let old_keys: Vec<String> = old.iter().map(step_key).collect();
+    let new_keys: Vec<String> = new.iter().map(step_key).collect();
+    let mut changes = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+        match op {
+            DiffOp::Equal { new_index, len, .. } => changes.extend(new[new_index..new_index + len].iter().map(StepChange::Kept)),
+            DiffOp::Delete { old_index, old_len, .. } => changes.extend(old[old_index..old_index + old_len].iter().map(StepChange::Removed)),
+            DiffOp::Insert { new_index, new_len, .. } => changes.extend(new[new_index..new_index + new_len].iter().map(StepChange::Added)),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                for i in 0..old_len.max(new_len) {
+                    match (old.get(old_index + i).filter(|_| i < old_len), new.get(new_index + i).filter(|_| i < new_len)) {
+                        (Some(old), Some(new)) if step_signature(old) == step_signature(new) => changes.push(StepChange::Changed { old, new }),
+                        (old, new) => {
+                            changes.extend(old.map(StepChange::Removed));
+                            changes.extend(new.map(StepChange::Added));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    changes
+}
+
+// Lines of the plan diff, the removed steps are shown with their old numbers
+pub(crate) fn format_plan_diff(changes: &[StepChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match change {
+            StepChange::Kept(action) => format!("  {}", action.describe()),
+            StepChange::Removed(action) => format!("- {}", action.describe()).red().to_string(),
+            StepChange::Added(action) => format!("+ {}", action.describe()).green().to_string(),
+            StepChange::Changed { old, new } => format!("~ {}
+    (was: {})", new.describe(), old.describe()).yellow().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(action_idx: u32, command: &str) -> Action {
+        Action::RunCommand { action_idx, command: command.to_string() }
+    }
+
+    #[test]
+    fn test_plan_diff() {
+        let create = |action_idx: u32, content: &str| Action::CreateFile { action_idx, path: "config.toml".to_string(), content: content.to_string(), if_not_exists: false, overwrite: true };
+        let old = vec![command(3, "cargo build"), create(4, "debug = true"), command(5, "cargo test"), command(6, "cargo fmt")];
+        let new = vec![command(1, "cargo clean"), command(2, "cargo build"), create(3, "debug = false"), command(4, "cargo test")];
+        assert_eq!(plan_diff(&old, &new), vec![
+            StepChange::Added(&new[0]),
+            StepChange::Kept(&new[1]),
+            StepChange::Changed { old: &old[1], new: &new[2] },
+            StepChange::Kept(&new[3]),
+            StepChange::Removed(&old[3]),
+        ]);
+        let replaced = vec![command(1, "make")];
+        assert_eq!(plan_diff(&old[..1], &replaced), vec![StepChange::Removed(&old[0]), StepChange::Added(&replaced[0])]);
+    }
+}
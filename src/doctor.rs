@@ -0,0 +1,307 @@
+use anyhow::Result;
+use crate::config::{Config, Model};
+use crate::error::{error_kind, ActionError};
+use crate::llm::{ask_llm, validate_request_format};
+use crate::{history::AskHistory, templates};
+use colored::*;
+use jsonpath_lib::select as jsonpath_select;
+use reqwest::Client;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const TEST_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+// Optional tools used by some of the actions and the execution targets
+const OPTIONAL_TOOLS: [(&str, &str); 3] = [
+    ("git", "the git actions"),
+    ("docker", "the docker:// targets and the devops actions"),
+    ("ssh", "the ssh:// targets"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+    // How to fix the problem found by the check
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check { name: name.into(), status: Status::Ok, detail: detail.into(), fix: None }
+    }
+
+    fn warning(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { name: name.into(), status: Status::Warning, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { name: name.into(), status: Status::Error, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn print(&self) {
+        let (mark, name) = match self.status {
+            Status::Ok => ("✓".green(), self.name.normal()),
+            Status::Warning => ("!".yellow(), self.name.yellow()),
+            Status::Error => ("✗".red(), self.name.red()),
+        };
+        println!("{} {}: {}", mark, name, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("    {} {}", "fix:".bold(), fix);
+        }
+    }
+}
+
+// The config is read without `Config::load` so that a broken config is reported instead of failing the doctor
+fn check_config(path: &Path) -> (Check, Option<Config>) {
+    let name = "config";
+    if !path.exists() {
+        return (Check::warning(name, format!("{} does not exist", path.display()), "Run any cliff command to create it"), None);
+    }
+    let parsed = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| toml::from_str::<Config>(&content).map_err(anyhow::Error::from));
+    match parsed {
+        Ok(config) => (Check::ok(name, format!("{} is valid", path.display())), Some(config)),
+        Err(e) => (Check::error(name, format!("{} cannot be read: {:#}", path.display(), e), "Fix the file or move it away to start with a new config"), None),
+    }
+}
+
+fn check_model_references(config: &Config) -> Vec<Check> {
+    if config.models.is_empty() {
+        return vec![Check::error("models", "no models are configured", "Run `cliff ask` in a terminal to set up a model or add one with `cliff config add`")];
+    }
+    let references = [
+        ("default model", &config.default_model, "cliff config set-default"),
+        ("current model", &config.current_model, "cliff config set-current"),
+        ("embedding model", &config.embedding_model, "cliff config set-embedding-model"),
+    ];
+    let mut checks = vec![Check::ok("models", format!("{} configured", config.models.len()))];
+    for (name, reference, command) in references {
+        match reference {
+            Some(model) if !config.models.contains_key(model) => {
+                checks.push(Check::error(name, format!("'{}' is not a configured model", model), format!("Choose another model with `{}`", command)))
+            }
+            Some(model) => checks.push(Check::ok(name, model.clone())),
+            None if name == "default model" && config.current_model.is_none() => {
+                checks.push(Check::error(name, "not set", format!("Choose the model with `{}`", command)))
+            }
+            None => {}
+        }
+    }
+    checks
+}
+
+// Checks which do not need the network: the request format, the response JSON path and the credentials
+fn check_model_config(model: &Model) -> Vec<Check> {
+    let name = format!("model '{}'", model.name);
+    let mut checks = Vec::new();
+    if let Err(e) = validate_request_format(model) {
+        checks.push(Check::error(&name, format!("{:#}", e), "Check the quoting of `{{prompt}}` and `{{messages}}` in the request format, `{{messages}}` must not be quoted"));
+    }
+    if let Err(e) = jsonpath_select(&serde_json::Value::Null, &model.response_json_path) {
+        checks.push(Check::error(&name, format!("the response JSON path '{}' is invalid: {}", model.response_json_path, e), "Use a JSON path such as `$.choices[0].message.content`"));
+    }
+    let expects_key = model.api_key_header.as_deref().is_some_and(|header| header.contains("{{api_key}}"));
+    match (&model.api_key, &model.api_key_header) {
+        (_, Some(header)) if !header.contains(':') => {
+            checks.push(Check::error(&name, format!("the API key header '{}' is not of the form 'Header-Name: value'", header), "Set the header like `--api-key-header=\"Authorization: Bearer {{api_key}}\"`"))
+        }
+        (None, _) if expects_key => {
+            checks.push(Check::error(&name, "the API key header needs an API key but none is configured", "Add the model again with `--api-key`"))
+        }
+        (Some(key), _) if key.trim().is_empty() => {
+            checks.push(Check::error(&name, "the API key is empty", "Add the model again with `--api-key=$<VARIABLE>` after setting the environment variable"))
+        }
+        (Some(key), _) if key.starts_with('$') => {
+            checks.push(Check::warning(&name, format!("the API key '{}' looks like an unexpanded environment variable", key), "Add the model again with the variable unquoted so that the shell expands it"))
+        }
+        _ => {}
+    }
+    if checks.is_empty() {
+        checks.push(Check::ok(&name, "the request format, the response path and the credentials are valid"));
+    }
+    checks
+}
+
+async fn check_model_call(model: &Model, client: &Client) -> Check {
+    let name = format!("model '{}' endpoint", model.name);
+    let answer = tokio::time::timeout(TEST_CALL_TIMEOUT, ask_llm(model, "Reply with the single word OK", &[], client))
+        .await
+        .unwrap_or_else(|_| Err(ActionError::Timeout(format!("No answer in {} seconds", TEST_CALL_TIMEOUT.as_secs())).into()));
+    let e = match answer {
+        Ok(_) => return Check::ok(name, format!("{} answered", model.api_url)),
+        Err(e) => e,
+    };
+    let fix = match error_kind(&e) {
+        "network" | "timeout" => format!("Check that {} is correct and reachable, e.g. that the local server is running", model.api_url),
+        "permission_denied" => "The credentials were rejected, add the model again with a valid `--api-key`".to_string(),
+        _ => "The endpoint rejected the request or its answer was not understood, check the request format and the response JSON path against the API documentation".to_string(),
+    };
+    Check::error(name, format!("{:#}", e), fix)
+}
+
+// Whether the program is found in one of the `PATH` directories
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", program, std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).map(|dir| dir.join(&file_name)).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+fn check_tools() -> Vec<Check> {
+    OPTIONAL_TOOLS
+        .iter()
+        .map(|(tool, used_by)| match find_in_path(tool) {
+            Some(path) => Check::ok(*tool, path.display().to_string()),
+            None => Check::warning(*tool, format!("not found in PATH, {} are not available", used_by), format!("Install {} to use {}", tool, used_by)),
+        })
+        .collect()
+}
+
+fn check_shell(config: &Config) -> Check {
+    let shell = config.shell.resolve();
+    let origin = if config.shell.name.is_some() { "configured" } else { "detected from $SHELL" };
+    match find_in_path(shell.program()) {
+        Some(path) => Check::ok("shell", format!("{} ({}), {}", shell.program(), origin, path.display())),
+        None => Check::error(
+            "shell", format!("{} ({}) is not found in PATH, the commands of the plans cannot be run", shell.program(), origin),
+            "Choose an installed shell with `cliff config set-shell`",
+        ),
+    }
+}
+
+// Creates and removes a probe file in the directory, or in its nearest existing ancestor when it is not created yet
+fn check_writable(name: &str, dir: &Path) -> Check {
+    let Some(existing) = dir.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return Check::error(name, format!("{} has no existing ancestor directory", dir.display()), format!("Create {}", dir.display()));
+    };
+    let probe = existing.join(".cliff-doctor-probe");
+    match fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe)) {
+        Ok(()) if existing == dir => Check::ok(name, format!("{} is writable", dir.display())),
+        Ok(()) => Check::ok(name, format!("{} does not exist yet and can be created", dir.display())),
+        Err(e) => Check::error(name, format!("{} is not writable: {}", existing.display(), e), format!("Fix the permissions of {}", existing.display())),
+    }
+}
+
+fn check_state_dirs() -> Vec<Check> {
+    let dirs: [(&str, Result<PathBuf>); 3] = [
+        ("config directory", Config::config_dir()),
+        ("templates directory", templates::templates_dir()),
+        ("data directory", AskHistory::history_path().map(|path| path.parent().map(Path::to_path_buf).unwrap_or(path))),
+    ];
+    dirs.into_iter()
+        .map(|(name, dir)| match dir {
+            Ok(dir) => check_writable(name, &dir),
+            Err(e) => Check::error(name, format!("{:#}", e), "Set $HOME or the platform specific directory variables such as $XDG_DATA_HOME"),
+        })
+        .collect()
+}
+
+/// Checks the configuration of the model and makes a test call, fails when any of the checks fails
+pub async fn test_model(model: &Model, client: &Client) -> Result<()> {
+    let mut checks = check_model_config(model);
+    if checks.iter().all(|check| check.status != Status::Error) {
+        checks.push(check_model_call(model, client).await);
+    }
+    report(&checks)
+}
+
+// Prints the checks and a summary, fails when any of the checks failed
+fn report(checks: &[Check]) -> Result<()> {
+    for check in checks {
+        check.print();
+    }
+    let errors = checks.iter().filter(|check| check.status == Status::Error).count();
+    let warnings = checks.iter().filter(|check| check.status == Status::Warning).count();
+    println!("\n{} errors, {} warnings", errors, warnings);
+    if errors > 0 {
+        return Err(ActionError::InvalidInput(format!("{} of the checks failed", errors)).into());
+    }
+    Ok(())
+}
+
+/// Checks the configuration and the environment of cliff and prints the problems with the ways to fix them,
+/// fails when any of the checks fails
+pub async fn run(client: &Client, skip_calls: bool) -> Result<()> {
+    let (config_check, config) = check_config(&Config::config_path()?);
+    let mut checks = vec![config_check];
+    if let Some(config) = &config {
+        checks.extend(check_model_references(config));
+        let mut models: Vec<&Model> = config.models.values().collect();
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        for model in models {
+            let config_checks = check_model_config(model);
+            let valid = config_checks.iter().all(|check| check.status != Status::Error);
+            checks.extend(config_checks);
+            if valid && !skip_calls {
+                checks.push(check_model_call(model, client).await);
+            }
+        }
+        checks.push(check_shell(config));
+    }
+    checks.extend(check_tools());
+    checks.extend(check_state_dirs());
+
+    report(&checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_model(request_format: &str, api_key: Option<&str>) -> Model {
+        Model {
+            name: "test".to_string(),
+            api_url: "http://localhost/api".to_string(),
+            api_key: api_key.map(str::to_string),
+            api_key_header: Some("Authorization: Bearer {{api_key}}".to_string()),
+            model_identifier: Some("test-model".to_string()),
+            request_format: request_format.to_string(),
+            response_json_path: "$.choices[0].message.content".to_string(),
+            ..Model::default()
+        }
+    }
+
+    #[test]
+    fn test_check_model_config() {
+        let valid = test_model(r#"{"model": "{{model}}", "messages": {{messages}}}"#, Some("secret"));
+        assert_eq!(check_model_config(&valid).iter().map(|check| check.status).collect::<Vec<_>>(), vec![Status::Ok]);
+
+        let quoted_messages = test_model(r#"{"messages": "{{messages}}"}"#, Some("secret"));
+        assert_eq!(check_model_config(&quoted_messages)[0].status, Status::Error);
+
+        let missing_key = test_model(r#"{"input": "{{prompt}}"}"#, None);
+        assert!(check_model_config(&missing_key)[0].detail.contains("none is configured"));
+
+        let unexpanded_key = test_model(r#"{"input": "{{prompt}}"}"#, Some("$OPENAI_API_KEY"));
+        assert_eq!(check_model_config(&unexpanded_key)[0].status, Status::Warning);
+    }
+
+    #[test]
+    fn test_check_config_and_references() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "models = [")?;
+        let (check, config) = check_config(&path);
+        assert_eq!(check.status, Status::Error);
+        assert!(config.is_none());
+
+        fs::write(&path, "default_model = \"missing\"\n[models.test]\nname = \"test\"\napi_url = \"http://localhost\"\nrequest_format = \"{}\"\nresponse_json_path = \"$.a\"\n")?;
+        let (check, config) = check_config(&path);
+        assert_eq!(check.status, Status::Ok);
+        let references = check_model_references(&config.unwrap());
+        assert!(references.iter().any(|check| check.name == "default model" && check.status == Status::Error));
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::profiles;
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+};
+
+// The files of the indexed directories are embedded in chunks of at most this size, well within the inputs of the embedding models
+pub const MAX_CHUNK_BYTES: usize = 4000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    // File path, chunk header or the text itself the embedding was computed for
+    pub source: String,
+    pub vector: Vec<f32>,
+}
+
+// Named collection of embeddings stored as `<name>.json` in the indexes directory
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EmbeddingIndex {
+    #[serde(default)]
+    pub entries: Vec<IndexEntry>,
+}
+
+impl EmbeddingIndex {
+    const INDEXES_DIR_NAME: &'static str = "indexes";
+
+    pub(crate) fn index_path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            anyhow::bail!("Invalid index name '{}'", name);
+        }
+        let data_dir = profiles::data_dir()?;
+        Ok(data_dir.join(Self::INDEXES_DIR_NAME).join(format!("{}.json", name)))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(EmbeddingIndex::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read embedding index: {:?}", path))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse embedding index: {:?}", path))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("Failed to create indexes directory: {:?}", parent))?;
+        }
+        let content = serde_json::to_string(self)
+            .with_context(|| "Failed to serialize embedding index")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write embedding index: {:?}", path))
+    }
+
+    // Adds the entry replacing the existing entry with the same source
+    pub fn upsert(&mut self, entry: IndexEntry) {
+        match self.entries.iter_mut().find(|existing| existing.source == entry.source) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    // At most `top` entries most similar to the query vector, the most similar first
+    pub fn most_similar(&self, query: &[f32], top: usize) -> Vec<(f32, &IndexEntry)> {
+        let mut scored: Vec<(f32, &IndexEntry)> = self.entries
+            .iter()
+            .map(|entry| (cosine_similarity(query, &entry.vector), entry))
+            .collect();
+        scored.sort_by(|(score_a, _), (score_b, _)| score_b.total_cmp(score_a));
+        scored.truncate(top);
+        scored
+    }
+}
+
+// Cosine similarity of the vectors, 0 for vectors of different dimensions or zero vectors
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_index_most_similar() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("notes.json");
+
+        let mut index = EmbeddingIndex::load_from(&path)?;
+        index.upsert(IndexEntry { source: "cats.md".to_string(), vector: vec![1.0, 0.0] });
+        index.upsert(IndexEntry { source: "dogs.md".to_string(), vector: vec![0.0, 1.0] });
+        index.upsert(IndexEntry { source: "cats.md".to_string(), vector: vec![0.8, 0.6] });
+        index.save_to(&path)?;
+
+        let index = EmbeddingIndex::load_from(&path)?;
+        let similar = index.most_similar(&[1.0, 0.0], 1);
+        assert_eq!(index.entries.len(), 2);
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].1.source, "cats.md");
+        assert!((similar[0].0 - 0.8).abs() < 1e-6);
+        assert!(EmbeddingIndex::index_path("../escape").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use reqwest::StatusCode;
+use std::io;
+use thiserror::Error;
+
+// Error classes of the failed actions, attached to the anyhow errors returned by the actions so that
+// the executor and the exit code can depend on the class of the failure
+#[derive(Debug, Error)]
+pub enum ActionError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    Timeout(String),
+    #[error("{0}")]
+    PolicyDenied(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0}")]
+    LlmMalformedResponse(String),
+    #[error("{message}")]
+    CommandFailed { message: String, exit_code: Option<i32> },
+    #[error("{0}")]
+    ExpectationFailed(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    Network(String),
+    #[error("{0}")]
+    Io(String),
+}
+
+impl ActionError {
+    pub fn from_io(context: String, error: io::Error) -> Self {
+        let message = format!("{}: {}", context, error);
+        match error.kind() {
+            io::ErrorKind::NotFound => ActionError::NotFound(message),
+            io::ErrorKind::PermissionDenied => ActionError::PermissionDenied(message),
+            io::ErrorKind::TimedOut => ActionError::Timeout(message),
+            _ => ActionError::Io(message),
+        }
+    }
+
+    pub fn from_reqwest(context: String, error: reqwest::Error) -> Self {
+        let message = format!("{}: {}", context, error);
+        if error.is_timeout() {
+            ActionError::Timeout(message)
+        } else {
+            ActionError::Network(message)
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ActionError::NotFound(_) => "not_found",
+            ActionError::PermissionDenied(_) => "permission_denied",
+            ActionError::Timeout(_) => "timeout",
+            ActionError::PolicyDenied(_) => "policy_denied",
+            ActionError::InvalidInput(_) => "invalid_input",
+            ActionError::LlmMalformedResponse(_) => "llm_malformed_response",
+            ActionError::CommandFailed { .. } => "command_failed",
+            ActionError::ExpectationFailed(_) => "expectation_failed",
+            ActionError::AlreadyExists(_) => "already_exists",
+            ActionError::PreconditionFailed(_) => "precondition_failed",
+            ActionError::Network(_) => "network",
+            ActionError::Io(_) => "io",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ActionError::NotFound(_) | ActionError::PreconditionFailed(_) => 3,
+            ActionError::PermissionDenied(_) | ActionError::PolicyDenied(_) => 4,
+            ActionError::Timeout(_) | ActionError::Network(_) => 5,
+            ActionError::InvalidInput(_) | ActionError::LlmMalformedResponse(_) | ActionError::AlreadyExists(_) => 6,
+            ActionError::CommandFailed { .. } | ActionError::ExpectationFailed(_) => 7,
+            ActionError::Io(_) => 8,
+        }
+    }
+}
+
+// Failures of the calls to the LLM APIs, the messages are concise and the response bodies are shortened
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("The API of the model '{model}' rejected the credentials with status {status}: {detail}")]
+    Unauthorized { model: String, status: StatusCode, detail: String },
+    #[error("The API request of the model '{model}' failed with status {status}: {detail}")]
+    RequestFailed { model: String, status: StatusCode, detail: String },
+    #[error("The request format of the model '{model}' does not produce valid JSON: {detail}")]
+    InvalidRequestFormat { model: String, detail: String },
+    #[error("The response JSON path '{path}' of the model '{model}' is invalid: {detail}")]
+    InvalidJsonPath { model: String, path: String, detail: String },
+    #[error("Nothing found at the response JSON path '{path}' of the model '{model}' in the response: {detail}")]
+    ValueNotFound { model: String, path: String, detail: String },
+    #[error("The model '{model}' does not support {capability}: {detail}")]
+    Unsupported { model: String, capability: String, detail: String },
+    #[error("The request of about {tokens} tokens does not fit the context window of {limit} tokens of the model '{model}'")]
+    ContextWindowExceeded { model: String, tokens: usize, limit: usize },
+}
+
+impl LlmError {
+    pub fn model(&self) -> &str {
+        match self {
+            LlmError::Unauthorized { model, .. }
+            | LlmError::RequestFailed { model, .. }
+            | LlmError::InvalidRequestFormat { model, .. }
+            | LlmError::InvalidJsonPath { model, .. }
+            | LlmError::ValueNotFound { model, .. }
+            | LlmError::Unsupported { model, .. }
+            | LlmError::ContextWindowExceeded { model, .. } => model,
+        }
+    }
+
+    // Kind of the failure in terms of the action error kinds
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LlmError::Unauthorized { .. } => "permission_denied",
+            LlmError::RequestFailed { .. } => "other",
+            LlmError::InvalidRequestFormat { .. } | LlmError::Unsupported { .. } | LlmError::ContextWindowExceeded { .. } => "invalid_input",
+            LlmError::InvalidJsonPath { .. } | LlmError::ValueNotFound { .. } => "llm_malformed_response",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LlmError::Unauthorized { .. } => 4,
+            LlmError::RequestFailed { .. } => 1,
+            LlmError::InvalidRequestFormat { .. } | LlmError::InvalidJsonPath { .. } | LlmError::ValueNotFound { .. }
+            | LlmError::Unsupported { .. } | LlmError::ContextWindowExceeded { .. } => 6,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("No active model configured.")]
+    NoActiveModel,
+    #[error("Model '{0}' not found in configuration.")]
+    ModelNotFound(String),
+    #[error("Invalid proxy '{proxy}': {detail}")]
+    InvalidProxy { proxy: String, detail: String },
+}
+
+// Kind of the ActionError or LlmError in the chain of `error`, "other" for the errors not originating from actions
+pub fn error_kind(error: &anyhow::Error) -> &'static str {
+    match (error.downcast_ref::<ActionError>(), error.downcast_ref::<LlmError>()) {
+        (Some(action_error), _) => action_error.kind(),
+        (None, Some(llm_error)) => llm_error.kind(),
+        (None, None) => "other",
+    }
+}
+
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    match (error.downcast_ref::<ActionError>(), error.downcast_ref::<LlmError>()) {
+        (Some(action_error), _) => action_error.exit_code(),
+        (None, Some(llm_error)) => llm_error.exit_code(),
+        (None, None) => 1,
+    }
+}
+
+pub(crate) trait IoResultExt<T> {
+    fn with_action_context<F: FnOnce() -> String>(self, context: F) -> Result<T, ActionError>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn with_action_context<F: FnOnce() -> String>(self, context: F) -> Result<T, ActionError> {
+        self.map_err(|error| ActionError::from_io(context(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_error_kind_survives_context() {
+        let result: anyhow::Result<String> = std::fs::read_to_string("nonexistent_file.txt")
+            .with_action_context(|| "Failed to read file: nonexistent_file.txt".to_string())
+            .context("Failed to execute step");
+        let error = result.unwrap_err();
+
+        assert_eq!(error_kind(&error), "not_found");
+        assert_eq!(error.downcast_ref::<ActionError>().map(ActionError::exit_code), Some(3));
+        assert_eq!(error_kind(&anyhow::anyhow!("Unexpected")), "other");
+    }
+}
@@ -0,0 +1,317 @@
+use anyhow::Result;
+use crate::actions::target::DirectoryTarget;
+use crate::config::{Model, ShellConfig};
+use crate::error::{ActionError, IoResultExt};
+use crate::executor::{self, ExecutionSettings, Plan, MAIN_PLAN_LABEL};
+use crate::llm::ask_llm_for_plan;
+use colored::*;
+use regex::Regex;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Instructions whose plans are checked, e.g. to compare the models or to measure a change of the prompts
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    pub cases: Vec<Case>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    pub name: String,
+    pub instruction: String,
+    // How many times the instruction is planned, the plans of the same instruction differ from run to run
+    #[serde(default = "one_run")]
+    pub runs: usize,
+    // Files created in the directory of the case before the plan is made, by their paths relative to it
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    pub expect: Vec<Check>,
+}
+
+fn one_run() -> usize {
+    1
+}
+
+// Check of a plan, the file and command checks execute the plan in the directory of the case first. Written as a map
+// with one key in the suites, e.g. `plan_contains: create_file`
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(try_from = "CheckFields")]
+pub enum Check {
+    // The plan has a step with the action, e.g. `run_command`
+    PlanContains(String),
+    // The plan has no step with the action, e.g. `delete_file`
+    PlanOmits(String),
+    // The file, relative to the directory of the case, matches the regular expression after the plan is executed
+    FileMatches { path: String, regex: String },
+    // The shell command succeeds in the directory of the case after the plan is executed
+    CommandSucceeds(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckFields {
+    plan_contains: Option<String>,
+    plan_omits: Option<String>,
+    file_matches: Option<FileMatch>,
+    command_succeeds: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileMatch {
+    path: String,
+    regex: String,
+}
+
+impl TryFrom<CheckFields> for Check {
+    type Error = String;
+
+    fn try_from(fields: CheckFields) -> Result<Check, String> {
+        let mut checks: Vec<Check> = [
+            fields.plan_contains.map(Check::PlanContains),
+            fields.plan_omits.map(Check::PlanOmits),
+            fields.file_matches.map(|FileMatch { path, regex }| Check::FileMatches { path, regex }),
+            fields.command_succeeds.map(Check::CommandSucceeds),
+        ].into_iter().flatten().collect();
+        match (checks.pop(), checks.is_empty()) {
+            (Some(check), true) => Ok(check),
+            _ => Err("expected exactly one of plan_contains, plan_omits, file_matches or command_succeeds".to_string()),
+        }
+    }
+}
+
+impl Check {
+    fn needs_execution(&self) -> bool {
+        matches!(self, Check::FileMatches { .. } | Check::CommandSucceeds(_))
+    }
+
+    // Why the check failed, None when it passed
+    async fn failure(&self, plan: &Plan, case_dir: &Path, shell_config: ShellConfig) -> Result<Option<String>> {
+        let has_step = |action: &str| plan.steps.iter().any(|step| step.name() == action);
+        Ok(match self {
+            Check::PlanContains(action) => (!has_step(action)).then(|| format!("the plan has no {} step", action)),
+            Check::PlanOmits(action) => has_step(action).then(|| format!("the plan has a {} step", action)),
+            Check::FileMatches { path, regex } => match std::fs::read_to_string(case_dir.join(path)) {
+                Ok(content) if Regex::new(regex)?.is_match(&content) => None,
+                Ok(_) => Some(format!("'{}' does not match '{}'", path, regex)),
+                Err(_) => Some(format!("'{}' cannot be read", path)),
+            },
+            Check::CommandSucceeds(command) => {
+                let shell = shell_config.resolve();
+                let status = tokio::process::Command::new(shell.program())
+                    .args(shell.args(command, shell_config.login))
+                    .current_dir(case_dir)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .await
+                    .with_action_context(|| format!("Failed to run the check command: {}", command))?;
+                (!status.success()).then(|| format!("`{}` failed with {}", command, status))
+            }
+        })
+    }
+}
+
+fn parse(content: &str) -> Result<Suite> {
+    Ok(serde_yaml::from_str(content)?)
+}
+
+// The files of a case and of its checks stay inside the directory of the case
+fn is_relative_inside(path: &str) -> bool {
+    Path::new(path).components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Reads the suite, e.g. `evals.yaml`, failing on the invalid regular expressions and the paths outside the directory of
+/// the case before any plan is made
+pub fn load(path: &Path) -> Result<Suite> {
+    let content = std::fs::read_to_string(path).with_action_context(|| format!("Failed to read the eval suite: {}", path.display()))?;
+    let suite = parse(&content).map_err(|e| ActionError::InvalidInput(format!("Failed to parse the eval suite {}: {}", path.display(), e)))?;
+    for case in &suite.cases {
+        let check_paths = case.expect.iter().filter_map(|check| match check {
+            Check::FileMatches { path, .. } => Some(path),
+            _ => None,
+        });
+        if let Some(file) = case.files.keys().chain(check_paths).find(|file| !is_relative_inside(file)) {
+            return Err(ActionError::InvalidInput(format!("The path '{}' of the case '{}' is not a relative path inside its directory", file, case.name)).into());
+        }
+        for check in &case.expect {
+            if let Check::FileMatches { regex, .. } = check {
+                Regex::new(regex).map_err(|e| ActionError::InvalidInput(format!("Invalid regex of the case '{}': {}", case.name, e)))?;
+            }
+        }
+    }
+    Ok(suite)
+}
+
+// Passed runs of a case by model, in the order of the models
+struct CaseResult<'a> {
+    case: &'a Case,
+    passed: Vec<usize>,
+}
+
+/// Runs every case of the suite with every model and prints the pass rates, returns the lowest pass rate of the models
+/// in percent. The plans are executed on a target with the directory of the run as the root of the file actions
+pub async fn run(suite: &Suite, models: &[&Model], context: &[String], settings: &ExecutionSettings, client: &Client) -> Result<f64> {
+    let mut results = Vec::new();
+    for case in &suite.cases {
+        let mut passed = Vec::new();
+        for model in models {
+            let mut passed_runs = 0;
+            for run in 1..=case.runs {
+                println!("\n{}", format!("=== Case '{}' with '{}', run {}/{} ===", case.name, model.name, run, case.runs).bold());
+                let failures = run_case(case, model, context, settings, client).await;
+                match failures {
+                    Ok(failures) if failures.is_empty() => {
+                        passed_runs += 1;
+                        println!("{}", format!("Case '{}' passed.", case.name).green());
+                    }
+                    Ok(failures) => println!("{}", format!("Case '{}' failed: {}", case.name, failures.join(", ")).red()),
+                    Err(e) => println!("{}", format!("Case '{}' failed: {:#}", case.name, e).red()),
+                }
+            }
+            passed.push(passed_runs);
+        }
+        results.push(CaseResult { case, passed });
+    }
+    let model_names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
+    println!("\n{}", report(&results, &model_names));
+    Ok((0..models.len()).map(|i| pass_rate(&results, i)).fold(100.0, f64::min))
+}
+
+// Plans the instruction in a new directory, executes the plan when a check needs it and returns the failed checks
+async fn run_case(case: &Case, model: &Model, context: &[String], settings: &ExecutionSettings, client: &Client) -> Result<Vec<String>> {
+    let case_dir = create_case_dir(case)?;
+    let settings = ExecutionSettings { target: Arc::new(DirectoryTarget::new(&case_dir)), ..settings.clone() };
+    let result = async {
+        let plan = ask_llm_for_plan(model, &case.instruction, context, &Vec::new(), &settings, client).await?;
+        plan.display();
+        if case.expect.iter().any(Check::needs_execution) {
+            executor::execute_plan(&plan, model, client, &mut Vec::new(), settings.clone(), &[MAIN_PLAN_LABEL.to_string()]).await?;
+        }
+        let mut failures = Vec::new();
+        for check in &case.expect {
+            failures.extend(check.failure(&plan, &case_dir, settings.shell).await?);
+        }
+        Ok(failures)
+    }.await;
+    if let Err(e) = std::fs::remove_dir_all(&case_dir) {
+        eprintln!("Warning: Failed to remove the directory of the case {}: {}", case_dir.display(), e);
+    }
+    result
+}
+
+// New directory with the files of the case in the temporary directory of the system, e.g. `/tmp/cliff-eval-1760000000000`
+fn create_case_dir(case: &Case) -> Result<PathBuf> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or_default();
+    let case_dir = std::env::temp_dir().join(format!("cliff-eval-{}-{}", std::process::id(), millis));
+    std::fs::create_dir_all(&case_dir).with_action_context(|| format!("Failed to create the directory of the case: {}", case_dir.display()))?;
+    for (path, content) in &case.files {
+        let path = case_dir.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_action_context(|| format!("Failed to create the directory: {}", parent.display()))?;
+        }
+        std::fs::write(&path, content).with_action_context(|| format!("Failed to create the file of the case: {}", path.display()))?;
+    }
+    Ok(case_dir)
+}
+
+// Percentage of the passed runs of the model over all the cases
+fn pass_rate(results: &[CaseResult], model: usize) -> f64 {
+    let runs: usize = results.iter().map(|result| result.case.runs).sum();
+    let passed: usize = results.iter().map(|result| result.passed[model]).sum();
+    if runs == 0 { 100.0 } else { passed as f64 * 100.0 / runs as f64 }
+}
+
+// Table of the passed runs of every case by model and of the pass rates of the models
+fn report(results: &[CaseResult], models: &[&str]) -> String {
+    let mut rows = vec![std::iter::once("Case".to_string()).chain(models.iter().map(|model| model.to_string())).collect::<Vec<_>>()];
+    for result in results {
+        let passed = result.passed.iter().map(|passed| format!("{}/{}", passed, result.case.runs));
+        rows.push(std::iter::once(result.case.name.clone()).chain(passed).collect());
+    }
+    let pass_rates = (0..models.len()).map(|model| format!("{:.0}%", pass_rate(results, model)));
+    rows.push(std::iter::once("Pass rate".to_string()).chain(pass_rates).collect());
+    let widths: Vec<usize> = (0..rows[0].len()).map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or_default()).collect();
+    rows.iter()
+        .map(|row| row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect::<Vec<_>>().join("  ").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Action;
+
+    #[test]
+    fn test_parse_suite_and_report() -> Result<()> {
+        let suite = parse(r#"
+            cases:
+              - name: hello
+                instruction: Create hello.txt saying hello
+                runs: 2
+                expect:
+                  - plan_contains: create_file
+                  - file_matches: { path: hello.txt, regex: "(?i)hello" }
+              - name: tests
+                instruction: Run the tests
+                files:
+                  test.sh: "exit 0"
+                expect:
+                  - plan_omits: delete_file
+                  - command_succeeds: sh test.sh
+        "#)?;
+        assert_eq!(suite.cases[0].expect[1], Check::FileMatches { path: "hello.txt".to_string(), regex: "(?i)hello".to_string() });
+        assert_eq!((suite.cases[1].runs, suite.cases[1].files.len()), (1, 1));
+        assert!(!suite.cases[1].expect[0].needs_execution() && suite.cases[1].expect[1].needs_execution());
+        assert!(parse("cases: [{name: both, instruction: List the files, expect: [{plan_contains: list_directory, plan_omits: run_command}]}]").is_err());
+        assert!(is_relative_inside("src/main.rs") && !is_relative_inside("../main.rs") && !is_relative_inside("/etc/hosts"));
+
+        let results = vec![
+            CaseResult { case: &suite.cases[0], passed: vec![2, 1] },
+            CaseResult { case: &suite.cases[1], passed: vec![1, 0] },
+        ];
+        assert_eq!(report(&results, &["gpt", "llama-local"]), [
+            "Case       gpt   llama-local",
+            "hello      2/2   1/2",
+            "tests      1/1   0/1",
+            "Pass rate  100%  33%",
+        ].join("\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_failures() -> Result<()> {
+        let case_dir = tempfile::tempdir()?;
+        std::fs::write(case_dir.path().join("hello.txt"), "Hello, world!")?;
+        let plan = Plan {
+            thought: None,
+            steps: vec![Action::RunCommand { action_idx: 0, command: "cat hello.txt".to_string() }],
+            params: BTreeMap::new(),
+            expectations: BTreeMap::new(),
+            preconditions: Vec::new(),
+        };
+        let shell = ShellConfig::default();
+        let failure = |check: Check| {
+            let plan = &plan;
+            let case_dir = case_dir.path().to_path_buf();
+            async move { check.failure(plan, &case_dir, shell).await }
+        };
+
+        assert_eq!(failure(Check::PlanContains("run_command".to_string())).await?, None);
+        assert_eq!(failure(Check::PlanOmits("run_command".to_string())).await?, Some("the plan has a run_command step".to_string()));
+        assert_eq!(failure(Check::FileMatches { path: "hello.txt".to_string(), regex: "world".to_string() }).await?, None);
+        assert_eq!(failure(Check::FileMatches { path: "bye.txt".to_string(), regex: ".".to_string() }).await?, Some("'bye.txt' cannot be read".to_string()));
+        assert_eq!(failure(Check::CommandSucceeds("test -f hello.txt".to_string())).await?, None);
+        assert!(failure(Check::CommandSucceeds("test -f bye.txt".to_string())).await?.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::error::IoResultExt;
+use crate::executor::{Action, Plan};
+use crate::runs::RunMetadata;
+use serde::Serialize;
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Where the events are written as JSON lines, nowhere unless `--events-fd` or `--events-file` is given
+static SINK: Mutex<Option<File>> = Mutex::new(None);
+
+/// Progress of the execution for the programs driving cliff, e.g. editors and GUIs, one JSON object per line with the
+/// `event` field naming it, e.g. `{"event": "step_started", "time_ms": 1760000000000, "plan": "main", "step": 1, ...}`
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    PlanGenerated { plan: &'a Plan },
+    // `step` is 1-based within the plan at `plan`, e.g. "main > sub-plan of step 2"
+    StepStarted { plan: &'a str, step: usize, action: &'a Action },
+    StepConfirmed { plan: &'a str, step: usize, confirmed: bool },
+    StepOutput { plan: &'a str, step: usize, output: &'a ActionOutput },
+    StepFailed { plan: &'a str, step: usize, kind: &'a str, message: String },
+    RunFinished { run: &'a RunMetadata },
+    // With `--protocol stdio`, the prompt printed before waits for an `answer` request
+    InputRequested { secret: bool },
+    Answer { id: Option<&'a str>, text: &'a str },
+    Error { id: Option<&'a str>, kind: &'a str, message: String },
+}
+
+/// Appends the events to the file, e.g. a named pipe read by the driving program
+pub fn write_to_file(path: &Path) -> Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)
+        .with_action_context(|| format!("Failed to open the events file: {}", path.display()))?;
+    *SINK.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(file);
+    Ok(())
+}
+
+/// Writes the events to the file descriptor inherited from the driving program, e.g. `3` for `cliff --events-fd 3 ... 3>events.jsonl`
+#[cfg(unix)]
+pub fn write_to_fd(fd: i32) -> Result<()> {
+    use std::os::fd::FromRawFd;
+    // The descriptor must be open, `fstat` tells without taking it over
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if fd < 0 || unsafe { libc::fstat(fd, &mut stat) } != 0 {
+        return Err(crate::error::ActionError::InvalidInput(format!("The events file descriptor {} is not open", fd)).into());
+    }
+    // SAFETY: the descriptor is open and is used only by the events from now on
+    let file = unsafe { File::from_raw_fd(fd) };
+    *SINK.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(file);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write_to_fd(_fd: i32) -> Result<()> {
+    Err(crate::error::ActionError::InvalidInput("--events-fd is supported only on Unix, use --events-file".to_string()).into())
+}
+
+/// Writes the event when the events are enabled, the events stop with a warning when they cannot be written
+pub fn emit(event: Event<'_>) {
+    let mut sink = SINK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(file) = sink.as_mut() else {
+        return;
+    };
+    let mut line = json!(event);
+    line["time_ms"] = json!(SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as u64).unwrap_or_default());
+    if let Err(e) = writeln!(file, "{}", line).and_then(|()| file.flush()) {
+        eprintln!("Warning: Failed to write the event, no more events are written: {}", e);
+        *sink = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_events_as_json_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("events.jsonl");
+        write_to_file(&path)?;
+        let action = Action::RunCommand { action_idx: 0, command: "cargo test".to_string() };
+
+        emit(Event::StepStarted { plan: "events test", step: 1, action: &action });
+        emit(Event::StepFailed { plan: "events test", step: 1, kind: "command_failed", message: "exit status: 101".to_string() });
+        *SINK.lock().unwrap() = None;
+
+        // The plans executed by the other tests at the same time write their events too
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(&path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?
+            .into_iter()
+            .filter(|event| event["plan"] == "events test")
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "step_started");
+        assert_eq!(events[0]["action"]["command"], "cargo test");
+        assert!(events[0]["time_ms"].as_u64().is_some());
+        assert_eq!((events[1]["event"].as_str(), events[1]["kind"].as_str()), (Some("step_failed"), Some("command_failed")));
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use clap::Command;
+
+// Usage examples of the subcommands, shown after the help of every subcommand and by `cliff examples`
+pub struct Example {
+    pub description: &'static str,
+    pub command: &'static str,
+}
+
+const fn example(description: &'static str, command: &'static str) -> Example {
+    Example { description, command }
+}
+
+pub const EXAMPLES: &[(&str, &[Example])] = &[
+    ("ask", &[
+        example("Ask a question", r#"cliff ask "What is the capital of France?""#),
+        example("Ask about a file", r#"cliff ask -c ./LICENSE "Summarize the main points of this document.""#),
+        example("Follow up on the previous answer", r#"cliff ask --continue "And what about Germany?""#),
+        example("Let the LLM choose the best of 3 answers", r#"cliff ask --n 3 --best-of "Suggest a name for a CLI tool managing dotfiles""#),
+        example("Compare the answers of a local and a hosted model", r#"cliff ask --models llama-local,gpt "Write a regex matching ISO 8601 dates""#),
+        example("Keep only the code of the answer and save it", r#"cliff ask --code-only --save sort.py "Write a Python function sorting a list of dates""#),
+        example("Get the answer as valid JSON", r#"cliff ask --format=json "List the three primary colors as a JSON array of strings""#),
+        example("Ask every prompt of a file, 4 at a time", r#"cliff ask --batch prompts.txt --output jsonl --concurrency 4 "Translate to German:""#),
+    ]),
+    ("act", &[
+        example("Let the LLM plan and execute a task", r#"cliff act "Create a file named hello.txt with the content 'Hello, world!'""#),
+        example("Confirm the read-only actions automatically", r#"cliff act --auto-confirm=reads "Find the largest files in this directory""#),
+        example("Review the plan before executing it", r#"cliff act --review "Remove the build artifacts""#),
+        example("Answer the prompts with the lines of a file", r#"cliff act --input-script=answers.txt "Ask me for a file name and create the file""#),
+        example("Use the plan most of 3 generated plans agree on", r#"cliff act --consistency 3 "Upgrade the dependencies and fix the build""#),
+        example("Execute a saved plan with its parameters", "cliff act --plan new-service.json --param project_name=invoices"),
+        example("Commit every step to a new branch for the review", r#"cliff act --git-commits "Split src/main.rs into modules""#),
+        example("Execute the plan in a container", r#"cliff act --target=docker://web "Find out why nginx does not start""#),
+        example("Replay the web responses recorded with --record-web", r#"cliff act --model replay --replay-web web.json "Summarize the latest release notes of ripgrep""#),
+    ]),
+    ("run", &[
+        example("List the tasks of the project", "cliff run"),
+        example("Run a task of the project", "cliff run test-fix --auto-confirm=safe"),
+    ]),
+    ("plans", &[
+        example("List the reusable plans", "cliff plans list"),
+        example("Create a Rust command line project from the built-in plan", "cliff plans run rust-cli --param project_name=greeter"),
+        example("Containerize the application in the working directory", "cliff plans run containerize --param port=3000"),
+    ]),
+    ("daemon", &[
+        example("Run a task every morning at 9", r#"cliff daemon --schedule "0 9 * * *" --task daily-summary"#),
+        example("Run the tasks once, e.g. from an external scheduler", "cliff daemon --once --task daily-summary --task test-fix --auto-confirm=safe"),
+    ]),
+    ("eval", &[
+        example("Compare the pass rates of two models on an eval suite", "cliff eval --suite evals.yaml --models gpt,llama-local"),
+        example("Fail when less than 90% of the cases pass, e.g. in CI", "cliff eval --suite evals.yaml --min-pass-rate 90"),
+    ]),
+    ("serve", &[
+        example("Serve the webhooks of the project tasks", "cliff serve --listen 0.0.0.0:8787 --auto-confirm=safe"),
+        example("Serve the JSON requests of an editor extension on the standard input and output", "cliff serve --protocol stdio"),
+    ]),
+    ("session", &[
+        example("Start an interactive session", "cliff session"),
+        example("Start a session about a file", "cliff session -c ./README.md"),
+    ]),
+    ("scaffold", &[
+        example("Create a project from a template", "cliff scaffold rust-cli --target my-tool --var name=my-tool"),
+        example("Let the LLM customize the created project", r#"cliff scaffold rust-cli --target my-tool "Add a subcommand printing the version""#),
+    ]),
+    ("extract", &[
+        example("Extract JSON conforming to a schema", r#"cliff extract -c listings.html --schema listings.schema.json "Extract the product listings""#),
+    ]),
+    ("embed", &[
+        example("Store the embedding of a file in an index", "cliff embed ./notes/rust.md --store notes"),
+    ]),
+    ("similar", &[
+        example("Find the most similar entries of an index", r#"cliff similar --index notes --top 3 "error handling""#),
+    ]),
+    ("tokens", &[
+        example("Estimate the tokens of files", "cliff tokens src/main.rs README.md"),
+        example("Estimate the tokens of the standard input", "cliff tokens -"),
+    ]),
+    ("transform", &[
+        example("Convert a file to another format", r#"cliff transform --in data.csv --out data.json --prompt "Convert to a JSON array of objects""#),
+    ]),
+    ("self-update", &[
+        example("Check for a new release", "cliff self-update --check"),
+        example("Install the latest release", "cliff self-update"),
+    ]),
+    ("doctor", &[
+        example("Diagnose the configuration and the environment", "cliff doctor"),
+        example("Diagnose without calling the models", "cliff doctor --skip-calls"),
+    ]),
+    ("trace", &[
+        example("Open the trace of the latest run in chrome://tracing or Perfetto", "cliff trace --output run.json"),
+        example("Export the trace of a run as OpenTelemetry OTLP/JSON", "cliff trace 1760000000000-1a2b --format otlp --output trace.json"),
+    ]),
+    ("history", &[
+        example("Review what the latest run changed", "cliff history diff"),
+        example("Export the changes of a run as a patch", "cliff history diff 1760000000000-1a2b --output run.patch"),
+    ]),
+    ("config", &[
+        example("Add a model", r#"cliff config add --name=mistral --api-url=https://openrouter.ai/api/v1/chat/completions --api-key=$OPENROUTER_API_KEY --api-key-header="Authorization: Bearer {{api_key}}" --model-identifier=mistralai/mistral-small-24b-instruct-2501:free --request-format='{"model": "{{model}}", "messages": {{messages}}}' --response-json-path='$.choices[0].message.content'"#),
+        example("Make the model the default one", "cliff config set-default mistral"),
+        example("Make the model the default one of a profile", "cliff config set-default mistral --profile work"),
+        example("Test the model", "cliff config test mistral"),
+        example("Describe what the model supports", "cliff config set-capabilities mistral --json-mode --context-window 32768"),
+        example("Compress oversized context with a cheaper model", "cliff config set-summarizer-model llama-local"),
+        example("Check the context for prompt injection with a model", "cliff config set-guardrail-model llama-local"),
+        example("Send the requests of a model through Tor", "cliff config set-proxy gpt socks5h://127.0.0.1:9050"),
+        example("Continue the sessions of a model in the conversations kept by its API", "cliff config set-conversation responses --json-path='$.id' --param=previous_response_id"),
+        example("Print the answers of a model as they arrive", "cliff config set-stream mistral-chat --format=sse --json-path='$.choices[0].delta.content'"),
+        example("Wrap the request bodies of a model for a gateway", "cliff config set-request-transform corp-gpt 'jq -c \"{payload: ., tenant: \\\"research\\\"}\"'"),
+        example("Add a local model served on a Unix socket", r#"cliff config add --name=llama-socket --api-url=unix:///run/llama/llama.sock:/v1/chat/completions --request-format='{"model": "{{model}}", "messages": {{messages}}}' --response-json-path='$.choices[0].message.content'"#),
+        example("Protect the SSH keys and the system configuration from the actions", "cliff config set-protected-paths '~/.ssh/**,/etc/**'"),
+        example("Skip the minified scripts when finding files and loading directories as context", "cliff config set-excluded-paths '**/*.min.js'"),
+        example("Check the project after every edit of a plan, e.g. with cargo check", "cliff config set-diagnostics --enabled"),
+        example("Stop the runs creating more than 200 files", "cliff config set-quotas --max-files-created 200"),
+        example("Send only the last 80 lines of long outputs back to the LLM", "cliff config set-output-trimming --head-lines 0 --tail-lines 80"),
+        example("Export the metrics of the runs for Prometheus", "cliff config set-metrics --textfile /var/lib/node_exporter/textfile_collector/cliff.prom"),
+        example("Add an OpenAI model without writing the request format", "cliff config add --name=gpt --preset=openai --model-identifier=gpt-4o --api-key=$OPENAI_API_KEY"),
+        example("Add a deployment of Azure OpenAI", "cliff config add --name=azure-gpt --kind=openai --api-url='https://my-resource.openai.azure.com/openai/deployments/{{model}}' --model-identifier=gpt-4o-prod --query-param=api-version=2024-10-21 --api-key-header='api-key: {{api_key}}' --api-key=$AZURE_OPENAI_API_KEY"),
+        example("Show the first 40 lines of the created files in the plans", "cliff config set-plan-preview --create-file 40"),
+        example("Add the models pulled into the local Ollama", "cliff config discover-ollama"),
+        example("Add a model using the native Anthropic API", "cliff config add --name=claude --provider=anthropic --api-key=$ANTHROPIC_API_KEY"),
+        example("List the models", "cliff config list"),
+    ]),
+    ("snippets", &[
+        example("Add a snippet", r#"cliff snippets add review "Review the following code for bugs: {{input}}""#),
+        example("Use the snippet", r#"cliff snippets use review "$(cat src/main.rs)""#),
+    ]),
+    ("profile", &[
+        example("Create a profile, e.g. for the work API keys", "cliff profile create work"),
+        example("List the profiles", "cliff profile list"),
+        example("Delete a profile with its config and state", "cliff profile delete work"),
+    ]),
+    ("examples", &[
+        example("Show the examples of a subcommand", "cliff examples ask"),
+    ]),
+];
+
+pub fn examples_of(command: &str) -> Option<&'static [Example]> {
+    EXAMPLES.iter().find(|(name, _)| *name == command).map(|(_, examples)| *examples)
+}
+
+pub fn format_examples(examples: &[Example]) -> String {
+    let lines: Vec<String> = examples
+        .iter()
+        .map(|example| format!("  # {}\n  {}", example.description, example.command))
+        .collect();
+    format!("Examples:\n{}", lines.join("\n\n"))
+}
+
+/// Adds the examples of every subcommand after its help
+pub fn with_examples(command: Command) -> Command {
+    EXAMPLES.iter().fold(command, |command, (name, examples)| {
+        command.mut_subcommand(*name, |subcommand| subcommand.after_help(format_examples(examples)))
+    })
+}
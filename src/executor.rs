@@ -1,283 +1,813 @@
 use anyhow::{Context, Result};
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::{BTreeMap, HashMap};
 use colored::*;
-use crate::config::Model;
+use crate::config::{FailurePolicy, Model, ShellConfig};
+use crate::error::{error_kind, ActionError};
+use crate::changes;
+use crate::git_commits;
+use crate::diagnostics;
+use crate::events::{self, Event};
+use crate::expectations::{self, Expectation, Precondition};
+use crate::guardrails;
+use crate::input;
+use crate::metrics;
+use crate::pager;
+use crate::preview;
+use crate::trace;
+use crate::classification::AutoConfirm;
+use crate::diff::{format_plan_diff, plan_diff};
 use reqwest::Client;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
 use crate::llm::ask_llm_for_plan;
+use crate::actions::confirm_action::{ensure_prompts_enabled, FailureDecision};
+use crate::actions::ask_user::FormQuestion;
+use crate::actions::hash_file::HashAlgorithm;
+use crate::actions::{ActionOutput, SKIPPED_OUTPUT};
+use crate::actions::target::ExecutionTarget;
+#[cfg(feature = "devops")]
+use crate::actions::{docker_logs, docker_ps, kubectl_get};
 use crate::actions::{
-    create_file, read_file, search_web, read_web_page, run_command, ask_user,
+    create_file, create_file_from_template, read_file, read_files, tail_file, search_web, read_web_page, run_command, ask_user,
     overwrite_file, replace_file_lines, confirm_action, delete_file, append_to_file,
-    move_file, copy_file, list_directory, check_path_exists, find_files,
+    move_file, copy_file, list_directory, show_tree, check_path_exists, list_processes, get_port_usage, system_info, find_files, find_symbol, hash_file, compare_files,
     ask_llm_to_create_file, ask_llm_to_overwrite_file, ask_llm_to_replace_file_lines
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+// The doc comments of the actions are their descriptions in the schema of the plans sent to the models calling tools
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 #[serde(tag = "action", rename_all = "snake_case")]
 pub enum Action {
-    //Create file on the machine of the user, `content` will be written out *literally*, no output
-    CreateFile { action_idx: u32, path: String, content: String },
-    //Ask Llm to reply with a CreateFile action for the file with `path`, output the result of CreateFile
+    /// Create file on the machine of the user, `content` will be written out *literally*, an existing file is kept with
+    /// `if_not_exists` and is an error without `overwrite`, no output
+    CreateFile {
+        action_idx: u32,
+        path: String,
+        content: String,
+        #[serde(default)]
+        if_not_exists: bool,
+        #[serde(default = "overwrite_by_default")]
+        overwrite: bool
+    },
+    /// Create file at `path` by rendering the user `template` with `variables`, no output
+    CreateFileFromTemplate { action_idx: u32, template: String, path: String, variables: HashMap<String, String> },
+    /// Ask Llm to reply with a CreateFile action for the file with `path`, output the result of CreateFile
     AskLlmToCreateFile {action_idx: u32, path: String},
-    //Search the web using the provided `query`, output the results
+    /// Search the web using the provided `query`, output the results
     SearchWeb { action_idx: u32, query: String },
-    //Read the content of the web page at the given `url`, output the result
+    /// Read the content of the web page at the given `url`, output the result
     ReadWebPage { action_idx: u32, url: String },
-    //Run command on the machine of the user, `command` is the command to execute, output the result
+    /// Run command on the machine of the user, `command` is the command to execute, output the result
     RunCommand { action_idx: u32, command: String },
-    //Ask the user the specified `question`, output the result
-    AskUser { action_idx: u32, question: String },
-    //Delete the file at the specified `path`, no output
+    /// Ask the user the specified `question`, as a numbered menu of `choices` if any, re-asking until the answer matches
+    /// `validation_regex` if any, output the answer or the chosen choice
+    AskUser {
+        action_idx: u32,
+        question: String,
+        #[serde(default)]
+        choices: Vec<String>,
+        #[serde(default)]
+        validation_regex: Option<String>
+    },
+    /// Ask the user several `questions` at once, output the answers by their labels, the secret answers are not output
+    AskUserForm { action_idx: u32, questions: Vec<FormQuestion> },
+    /// Delete the file at the specified `path`, no output
     DeleteFile { action_idx: u32, path: String },
-    // "content" will not be expanded and will be treated _literally_
+    /// "content" will not be expanded and will be treated _literally_
     OverwriteFileContents { action_idx: u32, path: String, content: String },
-    //Ask Llm to reply with a OverwriteFileContents action for the file with `path`, output the result of OverwriteFileContents
+    /// Ask Llm to reply with a OverwriteFileContents action for the file with `path`, output the result of OverwriteFileContents
     AskLlmToOverwriteFileContents {action_idx: u32, path: String},
-    // Ask LLM to output a response to the user (using the knowledge of previous actions and their outputs)
+    /// Ask LLM to output a response to the user (using the knowledge of previous actions and their outputs)
     AskLlm { action_idx: u32, prompt: String },
-    // AskLlmForPlan provides the ability for the LLM to respond with a new subplan
-    // 'instruction' guides the sub-plan generation.
-    // 'context_sources' provides file paths or URLs for context.
-    // the previously executed actions and their outputs are *always* provided to LLM in this action
+    /// AskLlmForPlan provides the ability for the LLM to respond with a new subplan
+    /// 'instruction' guides the sub-plan generation.
+    /// 'context_sources' provides file paths or URLs for context.
+    /// the previously executed actions and their outputs are *always* provided to LLM in this action
     AskLlmForPlan {
         action_idx: u32,
         instruction: String,
         context_sources: Vec<String>
     },
-    //Read the content of the file at the specified `path`, output the result
+    /// Read the content of the file at the specified `path`, output the result
     ReadFile { action_idx: u32, path: String },
-    //Find files matching the given `pattern`, output the result
-    FindFiles { action_idx: u32, pattern: String },
-    // "replacement_lines" will not be expanded and will be treated _literally_
-    //Replace lines from `from_line_idx` to `until_line_idx` in the file at `path` with `replacement_lines`, output the result
+    /// Read the contents of all the files at the specified `paths` at once, output the contents labeled with the file paths
+    ReadFiles { action_idx: u32, paths: Vec<String> },
+    /// Read the last `lines` lines of the file at `path` without loading the whole file, output the lines
+    TailFile { action_idx: u32, path: String, lines: usize },
+    /// Find files under `path` (current directory by default) matching the given `pattern` and skipping ignored files, optionally only the files with content matching the `containing` regex, output the result
+    FindFiles {
+        action_idx: u32,
+        pattern: String,
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        containing: Option<String>
+    },
+    /// Find the definitions of the symbol `name` in the files under the current directory, only in the files of `language` if given, output the locations with the signatures
+    FindSymbol {
+        action_idx: u32,
+        name: String,
+        #[serde(default)]
+        language: Option<String>
+    },
+    /// "replacement_lines" will not be expanded and will be treated _literally_
+    /// Replace lines from `from_line_idx` to `until_line_idx` in the file at `path` with `replacement_lines`, output the result
     ReplaceFileLines {action_idx: u32, path: String, from_line_idx: usize, until_line_idx: usize, replacement_lines: String},
-    //Ask LLM to output a ReplaceFileLines action for the file with `path`, output the result of ReplaceFileLines
+    /// Ask LLM to output a ReplaceFileLines action for the file with `path`, output the result of ReplaceFileLines
     AskLlmToReplaceFileLines {action_idx: u32, path: String},
-    // Append content to the file at the specified `path`, no output
+    /// Append content to the file at the specified `path`, no output
     AppendToFile { action_idx: u32, path: String, content: String },
-    // Move the file from `source` to `destination`, no output
-    MoveFile { action_idx: u32, source: String, destination: String },
-    // Copy the file from `source` to `destination`, no output
-    CopyFile { action_idx: u32, source: String, destination: String },
-    // List the contents of the directory at `path`, output the result
-    ListDirectory { action_idx: u32, path: String },
-    // Check if the path exists, output "true" or "false"
+    /// Move the file from `source` to `destination`, an existing `destination` is an error without `overwrite`, no output
+    MoveFile {
+        action_idx: u32,
+        source: String,
+        destination: String,
+        #[serde(default = "overwrite_by_default")]
+        overwrite: bool
+    },
+    /// Copy the file from `source` to `destination`, an existing `destination` is an error without `overwrite`, no output
+    CopyFile {
+        action_idx: u32,
+        source: String,
+        destination: String,
+        #[serde(default = "overwrite_by_default")]
+        overwrite: bool
+    },
+    /// List the contents of the directory at `path` (all nested entries if `recursive`) matching the optional `glob`, at most `max_entries`, output the result
+    ListDirectory {
+        action_idx: u32,
+        path: String,
+        #[serde(default)]
+        recursive: bool,
+        #[serde(default)]
+        glob: Option<String>,
+        #[serde(default)]
+        max_entries: Option<usize>
+    },
+    /// Show the directory tree at `path` up to `max_depth` levels deep skipping ignored files, output the tree
+    ShowTree {
+        action_idx: u32,
+        path: String,
+        #[serde(default)]
+        max_depth: Option<usize>,
+        #[serde(default)]
+        include_sizes: bool
+    },
+    /// Check if the path exists, output "true" or "false"
     CheckPathExists { action_idx: u32, path: String },
+    /// Compute the checksum of the file at `path` with `algorithm` (sha256 by default), output the hex digest
+    HashFile {
+        action_idx: u32,
+        path: String,
+        #[serde(default)]
+        algorithm: HashAlgorithm
+    },
+    /// Compare the files at `path_a` and `path_b`, output "identical" or a unified diff
+    CompareFiles { action_idx: u32, path_a: String, path_b: String },
+    /// List the running processes with the name or command line containing `filter` (all if none), output the result
+    ListProcesses {
+        action_idx: u32,
+        #[serde(default)]
+        filter: Option<String>
+    },
+    /// Find the sockets bound to `port` and the processes holding them, only on Linux, output the result
+    GetPortUsage { action_idx: u32, port: u16 },
+    /// Take a snapshot of the OS details and the CPU, memory and disk usage, output the result
+    SystemInfo { action_idx: u32 },
+    /// List the Docker containers with their images, statuses and ports, output the result
+    #[cfg(feature = "devops")]
+    DockerPs { action_idx: u32 },
+    /// Read the last `lines` lines of the logs of the Docker `container`, output the result
+    #[cfg(feature = "devops")]
+    DockerLogs { action_idx: u32, container: String, lines: usize },
+    /// Get the Kubernetes `resource` (e.g. "pods", "deployment/web") in `namespace` (the current one by default), output a summary of every object
+    #[cfg(feature = "devops")]
+    KubectlGet {
+        action_idx: u32,
+        resource: String,
+        #[serde(default)]
+        namespace: Option<String>
+    },
+}
+
+// The plans written before the flag existed overwrite the existing files
+fn overwrite_by_default() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "PlanJson", into = "PlanJson")]
 pub struct Plan {
     pub thought: Option<String>,
     pub steps: Vec<Action>,
+    // Parameters of a saved plan by their names, the `{{name}}` placeholders in the steps are replaced with their values
+    pub params: BTreeMap<String, PlanParam>,
+    // Expectations on the results of the steps by the indices of the steps
+    pub expectations: BTreeMap<usize, Expectation>,
+    // Conditions checked before the first step, e.g. the tools the plan uses
+    pub preconditions: Vec<Precondition>,
+}
+
+// Plan as written by the LLM and saved to the files, the expectations are the `expect` fields of the steps
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct PlanJson {
+    thought: Option<String>,
+    steps: Vec<PlanStep>,
+    // The parameters are written by the users in the saved plans, never by the LLM
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(skip)]
+    params: BTreeMap<String, PlanParam>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    preconditions: Vec<Precondition>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct PlanStep {
+    #[serde(flatten)]
+    action: Action,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expect: Option<Expectation>,
+}
+
+impl From<PlanJson> for Plan {
+    fn from(plan: PlanJson) -> Self {
+        let expectations = plan.steps.iter().enumerate().filter_map(|(i, step)| Some((i, step.expect.clone()?))).collect();
+        Plan { thought: plan.thought, steps: plan.steps.into_iter().map(|step| step.action).collect(), params: plan.params, expectations, preconditions: plan.preconditions }
+    }
+}
+
+impl From<Plan> for PlanJson {
+    fn from(mut plan: Plan) -> Self {
+        let steps = plan.steps.into_iter().enumerate().map(|(i, action)| PlanStep { action, expect: plan.expectations.remove(&i) }).collect();
+        PlanJson { thought: plan.thought, steps, params: plan.params, preconditions: plan.preconditions }
+    }
+}
+
+/// JSON schema of the plans written by the LLM, e.g. the parameters of the tool the models supporting tools are made to
+/// call with the plan
+pub fn plan_schema() -> serde_json::Value {
+    let settings = SchemaSettings::draft07().with(|settings| settings.inline_subschemas = true);
+    let mut schema = serde_json::to_value(settings.into_generator().into_root_schema_for::<PlanJson>()).unwrap_or_default();
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+        schema.remove("title");
+    }
+    schema
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlanParam {
+    // Question asking for the value when it is not given with `--param`
+    #[serde(default)]
+    pub prompt: Option<String>,
+    // Value used when the user answers with an empty line
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+// Action executed as part of the plan described by `plan`, e.g. "main > sub-plan of step 2"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExecutedAction {
+    pub plan: String,
+    pub action: Action,
+    pub output: ActionOutput,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionSettings {
+    pub auto_confirm: AutoConfirm,
+    pub on_failure: FailurePolicy,
+    pub shell: ShellConfig,
+    // Environment in which the commands and the file actions of the plan are executed
+    pub target: Arc<dyn ExecutionTarget>,
+}
+
+pub const MAX_PLAN_DEPTH: usize = 5;
+pub const MAIN_PLAN_LABEL: &str = "main";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlanReview {
+    pub critique: String,
+    pub revised_plan: Option<Plan>,
 }
 
 impl Action {
-    async fn execute(&self, execution_history: &mut Vec<(Action, Option<String>)>, model_config: &Model, client: &Client, current_auto_confirm: bool) -> Result<Option<String>> {
+    // Actions inspecting the local machine which cannot be executed on a remote target
+    fn is_local_only(&self) -> bool {
+        match self {
+            Action::TailFile { .. }
+            | Action::FindFiles { .. }
+            | Action::FindSymbol { .. }
+            | Action::ListDirectory { .. }
+            | Action::ShowTree { .. }
+            | Action::ListProcesses { .. }
+            | Action::GetPortUsage { .. }
+            | Action::SystemInfo { .. } => true,
+            #[cfg(feature = "devops")]
+            Action::DockerPs { .. } | Action::DockerLogs { .. } | Action::KubectlGet { .. } => true,
+            _ => false,
+        }
+    }
+
+    // Name of the action in the plans, e.g. `run_command`
+    pub fn name(&self) -> String {
+        serde_json::to_value(self).ok().and_then(|action| action["action"].as_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    // Description of the action shown to the user
+    pub fn describe(&self) -> String {
         match self {
-            Action::CreateFile { path, content, .. } => {
-                create_file::execute(path, content).await
+            Action::CreateFile { action_idx, path, content, if_not_exists, overwrite } => {
+                let content = preview::shorten(content, preview::limits().create_file);
+                format!("{}. Create file '{}'{} with content:\n{}", action_idx, path, existing_file_handling(*if_not_exists, *overwrite), content)
+            },
+            Action::RunCommand { action_idx, command } => format!("{}. Run command: `{}`", action_idx, command),
+            Action::SearchWeb { action_idx, query } => format!("{}. Search web for: '{}'", action_idx, query),
+            Action::AskUser { action_idx, question, choices, .. } if !choices.is_empty() => format!("{}. Ask user: '{}' choosing one of: {}", action_idx, question, choices.join(", ")),
+            Action::AskUser { action_idx, question, .. } => format!("{}. Ask user: '{}'", action_idx, question),
+            Action::AskUserForm { action_idx, questions } => {
+                let labels: Vec<&str> = questions.iter().map(|question| question.label.as_str()).collect();
+                format!("{}. Ask user to fill in: {}", action_idx, labels.join(", "))
+            },
+            Action::AskLlmToReplaceFileLines { action_idx, path } => format!("{}. Ask LLM to generate ReplaceFileLines action for path: '{}'", action_idx, path),
+            Action::DeleteFile { action_idx, path } => format!("{}. Delete file: '{}'", action_idx, path),
+            Action::OverwriteFileContents { action_idx, path, content } => {
+                format!("{}. Edit file '{}' with content:\n{}", action_idx, path, preview::shorten(content, preview::limits().overwrite_file))
+            },
+            Action::AskLlm { action_idx, prompt } => format!("{}. Ask LLM with prompt: '{}'", action_idx, prompt),
+            Action::AskLlmForPlan { action_idx, instruction, context_sources } => { // Removed earlier_action_indices
+                format!(
+                    "{}. Ask LLM for sub-plan:\n  Instruction: {}\n  Context Sources: {:?}",
+                    action_idx, instruction, context_sources
+                )
+            },
+            Action::CreateFileFromTemplate { action_idx, template, path, variables } => format!("{}. Create file '{}' from template '{}' with variables: {:?}", action_idx, path, template, variables),
+            Action::AskLlmToCreateFile { action_idx, path } => format!("{}. Ask LLM to generate CreateFile action for path: '{}'", action_idx, path),
+            Action::ReadFile { action_idx, path } => format!("{}. Read file: '{}'", action_idx, path),
+            Action::ReadFiles { action_idx, paths } => format!("{}. Read files: {:?}", action_idx, paths),
+            Action::FindFiles { action_idx, pattern, path, containing } => {
+                let location = path.as_ref().map(|path| format!(" in '{}'", path)).unwrap_or_default();
+                let content_filter = containing.as_ref().map(|containing| format!(" containing '{}'", containing)).unwrap_or_default();
+                format!("{}. Find files matching pattern: '{}'{}{}", action_idx, pattern, location, content_filter)
+            },
+            Action::FindSymbol { action_idx, name, language } => match language {
+                Some(language) => format!("{}. Find the definitions of {} symbol '{}'", action_idx, language, name),
+                None => format!("{}. Find the definitions of symbol '{}'", action_idx, name),
+            },
+            Action::ReadWebPage { action_idx, url } => format!("{}. Read web page: '{}'", action_idx, url),
+            Action::ReplaceFileLines { action_idx, path, from_line_idx, until_line_idx, replacement_lines: new_contents } => {
+                let content = preview::shorten(new_contents, preview::limits().replace_file_lines);
+                format!("{}. Replace lines {} to {} in file '{}' with content:\n{}", action_idx, from_line_idx, until_line_idx, path, content)
+            },
+            Action::AskLlmToOverwriteFileContents { action_idx, path } => format!("{}. Ask LLM to generate OverwriteFileContents action for path: '{}'", action_idx, path),
+            Action::AppendToFile { action_idx, path, content } => {
+                let content = preview::shorten(content, preview::limits().append_to_file);
+                format!("{}. Append to file '{}' with content:\n{}", action_idx, path, content)
+            },
+            Action::MoveFile { action_idx, source, destination, overwrite } => {
+                format!("{}. Move file from '{}' to '{}'{}", action_idx, source, destination, existing_file_handling(false, *overwrite))
+            },
+            Action::CopyFile { action_idx, source, destination, overwrite } => {
+                format!("{}. Copy file from '{}' to '{}'{}", action_idx, source, destination, existing_file_handling(false, *overwrite))
+            },
+            Action::ListDirectory { action_idx, path, recursive, glob, .. } => {
+                let mode = if *recursive { " recursively" } else { "" };
+                match glob {
+                    Some(glob) => format!("{}. List directory '{}'{} matching '{}'", action_idx, path, mode, glob),
+                    None => format!("{}. List directory '{}'{}", action_idx, path, mode),
+                }
+            },
+            Action::ShowTree { action_idx, path, max_depth, .. } => match max_depth {
+                Some(max_depth) => format!("{}. Show directory tree '{}' up to depth {}", action_idx, path, max_depth),
+                None => format!("{}. Show directory tree '{}'", action_idx, path),
+            },
+            Action::TailFile { action_idx, path, lines } => format!("{}. Read the last {} lines of file '{}'", action_idx, lines, path),
+            Action::CheckPathExists { action_idx, path } => format!("{}. Check if path exists '{}'", action_idx, path),
+            Action::HashFile { action_idx, path, algorithm } => format!("{}. Compute {:?} checksum of file '{}'", action_idx, algorithm, path),
+            Action::CompareFiles { action_idx, path_a, path_b } => format!("{}. Compare files '{}' and '{}'", action_idx, path_a, path_b),
+            Action::ListProcesses { action_idx, filter } => match filter {
+                Some(filter) => format!("{}. List processes matching '{}'", action_idx, filter),
+                None => format!("{}. List processes", action_idx),
+            },
+            Action::GetPortUsage { action_idx, port } => format!("{}. Find processes using port {}", action_idx, port),
+            Action::SystemInfo { action_idx } => format!("{}. Show system resource usage", action_idx),
+            #[cfg(feature = "devops")]
+            Action::DockerPs { action_idx } => format!("{}. List Docker containers", action_idx),
+            #[cfg(feature = "devops")]
+            Action::DockerLogs { action_idx, container, lines } => format!("{}. Read the last {} lines of the logs of container '{}'", action_idx, lines, container),
+            #[cfg(feature = "devops")]
+            Action::KubectlGet { action_idx, resource, namespace } => match namespace {
+                Some(namespace) => format!("{}. Get Kubernetes '{}' in namespace '{}'", action_idx, resource, namespace),
+                None => format!("{}. Get Kubernetes '{}'", action_idx, resource),
+            },
+        }
+    }
+
+    async fn execute(&self, execution_history: &mut Vec<ExecutedAction>, model_config: &Model, client: &Client, settings: &ExecutionSettings, plan_path: &[String]) -> Result<ActionOutput> {
+        if !settings.target.is_local() && self.is_local_only() {
+            return Err(ActionError::InvalidInput(format!(
+                "Action {:?} can only be executed on the local machine, not on {}, use RunCommand instead", self, settings.target
+            )).into());
+        }
+        match self {
+            Action::CreateFile { path, content, if_not_exists, overwrite, .. } => {
+                create_file::execute(path, content, *if_not_exists, *overwrite, settings.target.as_ref()).await
+            },
+            Action::CreateFileFromTemplate { template, path, variables, .. } => {
+                create_file_from_template::execute(template, path, variables, settings.target.as_ref()).await
             },
             Action::AskLlmToCreateFile { path, .. } => {
-                ask_llm_to_create_file::execute(path, model_config, execution_history, client).await
+                ask_llm_to_create_file::execute(path, model_config, execution_history, client, settings.auto_confirm, settings.target.as_ref()).await
             },
             Action::AskLlmToOverwriteFileContents { path, .. } => {
-                ask_llm_to_overwrite_file::execute(path, model_config, execution_history, client).await
+                ask_llm_to_overwrite_file::execute(path, model_config, execution_history, client, settings.auto_confirm, settings.target.as_ref()).await
             },
             Action::OverwriteFileContents { path, content, .. } => {
-                overwrite_file::execute(path, content).await
+                overwrite_file::execute(path, content, settings.target.as_ref()).await
             },
             Action::DeleteFile { path, .. } => {
-                delete_file::execute(path).await
+                delete_file::execute(path, settings.target.as_ref()).await
             },
             Action::RunCommand { command, .. } => {
-                run_command::execute(command).await
+                run_command::execute(command, settings.shell, settings.target.as_ref()).await
             },
             Action::AskLlm { prompt, .. } => {
-                let response = crate::llm::ask_llm_with_history(model_config, prompt, &execution_history, client).await.context("Failed to get response from LLM")?;
+                let response = crate::llm::ask_llm_with_history(model_config, prompt, execution_history, client).await.context("Failed to get response from LLM")?;
                 println!("{}", response.green());
-                Ok(Some(response))
+                Ok(ActionOutput::text(response))
             },
-            Action::AskLlmForPlan { instruction, context_sources, .. } => {
+            Action::AskLlmForPlan { action_idx, instruction, context_sources } => {
                 let sub_plan = ask_llm_for_plan(
                     model_config,
                     instruction,
                     context_sources,
-                    &execution_history,
+                    execution_history,
+                    settings,
                     client,
                 ).await.context("Failed to get sub-plan from LLM")?;
+                let sub_plan_path = nested_plan_path(plan_path, format!("sub-plan of action {}", action_idx))?;
                 sub_plan.display();
-                println!("--- Starting Sub-Plan Execution ---");
-                execute_plan(&sub_plan, model_config, client, execution_history, current_auto_confirm).await?;
-                println!("--- Sub-Plan Execution Finished ---");
-                Ok(None)
+                println!("--- Starting Sub-Plan Execution [{}] ---", sub_plan_path.join(" > "));
+                execute_plan(&sub_plan, model_config, client, execution_history, settings.clone(), &sub_plan_path).await?;
+                println!("--- Sub-Plan Execution Finished [{}] ---", sub_plan_path.join(" > "));
+                Ok(ActionOutput::None)
             },
             Action::AskLlmToReplaceFileLines { path, .. } => {
-                ask_llm_to_replace_file_lines::execute(path, model_config, execution_history, client).await
+                ask_llm_to_replace_file_lines::execute(path, model_config, execution_history, client, settings.auto_confirm, settings.target.as_ref()).await
             },
             Action::SearchWeb { query, .. } => {
-                search_web::execute(query).await
+                search_web::execute(client, query).await
             },
             Action::ReadWebPage { url, .. } => {
                 read_web_page::execute(client, url).await
             },
-            Action::AskUser { question, .. } => {
-                ask_user::execute(question).await
+            Action::AskUser { question, choices, validation_regex, .. } => {
+                ask_user::execute(question, choices, validation_regex.as_deref()).await
+            },
+            Action::AskUserForm { questions, .. } => {
+                ask_user::execute_form(questions).await
             },
             Action::ReadFile { path, .. } => {
-                read_file::execute(path).await
+                read_file::execute(path, settings.target.as_ref()).await
+            },
+            Action::ReadFiles { paths, .. } => {
+                read_files::execute(paths, settings.target.clone()).await
+            },
+            Action::TailFile { path, lines, .. } => {
+                tail_file::execute(path, *lines).await
+            },
+            Action::FindFiles { pattern, path, containing, .. } => {
+                find_files::execute(pattern, path.as_deref(), containing.as_deref()).await
             },
-            Action::FindFiles { pattern, .. } => {
-                find_files::execute(pattern).await
+            Action::FindSymbol { name, language, .. } => {
+                find_symbol::execute(name, language.as_deref()).await
             },
             Action::ReplaceFileLines { path, from_line_idx, until_line_idx, replacement_lines: new_contents, .. } => {
-                replace_file_lines::execute(path, *from_line_idx, *until_line_idx, new_contents).await
+                replace_file_lines::execute(path, *from_line_idx, *until_line_idx, new_contents, settings.target.as_ref()).await
             },
             Action::AppendToFile { path, content, .. } => {
-                append_to_file::execute(path, content).await
+                append_to_file::execute(path, content, settings.target.as_ref()).await
             },
-            Action::MoveFile { source, destination, .. } => {
-                move_file::execute(source, destination).await
+            Action::MoveFile { source, destination, overwrite, .. } => {
+                move_file::execute(source, destination, *overwrite, settings.target.as_ref()).await
             },
-            Action::CopyFile { source, destination, .. } => {
-                copy_file::execute(source, destination).await
+            Action::CopyFile { source, destination, overwrite, .. } => {
+                copy_file::execute(source, destination, *overwrite, settings.target.as_ref()).await
             },
-            Action::ListDirectory { path, .. } => {
-                list_directory::execute(path).await
+            Action::ListDirectory { path, recursive, glob, max_entries, .. } => {
+                list_directory::execute(path, *recursive, glob.as_deref(), *max_entries).await
+            },
+            Action::ShowTree { path, max_depth, include_sizes, .. } => {
+                show_tree::execute(path, *max_depth, *include_sizes).await
             },
             Action::CheckPathExists { path, .. } => {
-                check_path_exists::execute(path).await
+                check_path_exists::execute(path, settings.target.as_ref()).await
+            },
+            Action::HashFile { path, algorithm, .. } => {
+                hash_file::execute(path, *algorithm, settings.target.as_ref()).await
+            },
+            Action::CompareFiles { path_a, path_b, .. } => {
+                compare_files::execute(path_a, path_b, settings.target.as_ref()).await
+            },
+            Action::ListProcesses { filter, .. } => {
+                list_processes::execute(filter.as_deref()).await
+            },
+            Action::GetPortUsage { port, .. } => {
+                get_port_usage::execute(*port).await
+            },
+            Action::SystemInfo { .. } => {
+                system_info::execute().await
+            },
+            #[cfg(feature = "devops")]
+            Action::DockerPs { .. } => {
+                docker_ps::execute().await
+            },
+            #[cfg(feature = "devops")]
+            Action::DockerLogs { container, lines, .. } => {
+                docker_logs::execute(container, *lines).await
+            },
+            #[cfg(feature = "devops")]
+            Action::KubectlGet { resource, namespace, .. } => {
+                kubectl_get::execute(resource, namespace.as_deref()).await
             },
         }
     }
 }
 
 impl Plan {
+    /// The plan with the placeholders of its parameters replaced with the `given` values, the user is asked for the
+    /// other values
+    pub fn with_params(&self, given: &HashMap<String, String>) -> Result<Plan> {
+        if let Some(unknown) = given.keys().find(|name| !self.params.contains_key(*name)) {
+            return Err(ActionError::InvalidInput(format!("The plan has no parameter '{}'", unknown)).into());
+        }
+        let mut values = BTreeMap::new();
+        for (name, param) in &self.params {
+            let value = match given.get(name) {
+                Some(value) => value.clone(),
+                None => ask_for_param(name, param)?,
+            };
+            values.insert(name.clone(), value);
+        }
+        self.fill_params(&values)
+    }
+
+    fn fill_params(&self, values: &BTreeMap<String, String>) -> Result<Plan> {
+        fn fill_text(text: &str, values: &BTreeMap<String, String>) -> String {
+            values.iter().fold(text.to_string(), |text, (name, value)| text.replace(&format!("{{{{{}}}}}", name), value))
+        }
+        fn fill(value: &mut serde_json::Value, values: &BTreeMap<String, String>) {
+            match value {
+                serde_json::Value::String(text) => *text = fill_text(text, values),
+                serde_json::Value::Array(items) => items.iter_mut().for_each(|item| fill(item, values)),
+                serde_json::Value::Object(fields) => fields.values_mut().for_each(|field| fill(field, values)),
+                _ => {}
+            }
+        }
+        let mut plan = serde_json::to_value(Plan { params: BTreeMap::new(), ..self.clone() })?;
+        fill(&mut plan, values);
+        serde_json::from_value(plan).context("Failed to fill in the parameters of the plan")
+    }
+
+    // Long plans, e.g. with the contents of the created files, are paged instead of scrolling past the confirmation
     pub fn display(&self) {
-        println!("\n--- Proposed Plan ---");
+        pager::page(&self.render());
+    }
+
+    fn render(&self) -> String {
+        let mut lines = vec!["\n--- Proposed Plan ---".to_string()];
         if let Some(thought) = &self.thought {
-            println!("Thought: {}", thought);
+            lines.push(format!("Thought: {}", thought.cyan()));
         }
         if self.steps.is_empty() {
-            println!("No actions planned.");
-            return;
+            lines.push("No actions planned.".to_string());
+            return lines.join("\n");
         }
-        for action in self.steps.iter() {
-            match action {
-                Action::CreateFile { action_idx, path, content } => println!("{}. Create file '{}' with content:\n{}", action_idx, path, content),
-                Action::RunCommand { action_idx, command } => println!("{}. Run command: `{}`", action_idx, command),
-                Action::SearchWeb { action_idx, query } => println!("{}. Search web for: '{}'", action_idx, query),
-                Action::AskUser { action_idx, question } => println!("{}. Ask user: '{}'", action_idx, question),
-                Action::AskLlmToReplaceFileLines { action_idx, path } => println!("{}. Ask LLM to generate ReplaceFileLines action for path: '{}'", action_idx, path),
-                Action::DeleteFile { action_idx, path } => println!("{}. Delete file: '{}'", action_idx, path),
-                Action::OverwriteFileContents { action_idx, path, content } => println!("{}. Edit file '{}' with content:\n{}", action_idx, path, content),
-                Action::AskLlm { action_idx, prompt } => println!("{}. Ask LLM with prompt: '{}'", action_idx, prompt),
-                Action::AskLlmForPlan { action_idx, instruction, context_sources } => { // Removed earlier_action_indices
-                    println!(
-                        "{}. Ask LLM for sub-plan:\n  Instruction: {}\n  Context Sources: {:?}",
-                        action_idx, instruction, context_sources
-                    );
-                },
-                Action::AskLlmToCreateFile { action_idx, path } => println!("{}. Ask LLM to generate CreateFile action for path: '{}'", action_idx, path),
-                Action::ReadFile { action_idx, path } => println!("{}. Read file: '{}'", action_idx, path),
-                Action::FindFiles { action_idx, pattern } => println!("{}. Find files matching pattern: '{}'", action_idx, pattern),
-                Action::ReadWebPage { action_idx, url } => println!("{}. Read web page: '{}'", action_idx, url),
-                Action::ReplaceFileLines { action_idx, path, from_line_idx, until_line_idx, replacement_lines: new_contents } => {
-                    let content_snippet = if new_contents.len() > 50 {
-                        format!("{}...", &new_contents[..50])
-                    } else {
-                        new_contents.clone()
-                    };
-                    println!("{}. Replace lines {} to {} in file '{}' with content: '{}'", action_idx, from_line_idx, until_line_idx, path, content_snippet);
-                },
-                Action::AskLlmToOverwriteFileContents { action_idx, path } => println!("{}. Ask LLM to generate OverwriteFileContents action for path: '{}'", action_idx, path),
-                Action::AppendToFile { action_idx, path, content } => {
-                     let content_snippet = if content.len() > 50 {
-                        format!("{}...", &content[..50])
-                    } else {
-                        content.clone()
-                    };
-                    println!("{}. Append to file '{}' with content: '{}'", action_idx, path, content_snippet);
-                },
-                Action::MoveFile { action_idx, source, destination } => println!("{}. Move file from '{}' to '{}'", action_idx, source, destination),
-                Action::CopyFile { action_idx, source, destination } => println!("{}. Copy file from '{}' to '{}'", action_idx, source, destination),
-                Action::ListDirectory { action_idx, path } => println!("{}. List directory '{}'", action_idx, path),
-                Action::CheckPathExists { action_idx, path } => println!("{}. Check if path exists '{}'", action_idx, path),
+        for precondition in &self.preconditions {
+            lines.push(format!("Requires: {}", precondition.to_string().yellow()));
+        }
+        for (i, action) in self.steps.iter().enumerate() {
+            // The title of the step stands out from the file contents following it
+            let description = action.describe();
+            match description.split_once('\n') {
+                Some((title, details)) => lines.push(format!("{}\n{}", title.bold(), details)),
+                None => lines.push(description.bold().to_string()),
+            }
+            if let Some(expectation) = self.expectations.get(&i) {
+                lines.push(format!("   Expect: {}", expectation.to_string().yellow()));
             }
         }
-        println!("--------------------");
+        lines.push("--------------------".to_string());
+        lines.join("\n")
+    }
+
+    // Shows how the plan differs from the `old_steps` it replaces instead of the whole plan, e.g. after a failure
+    pub fn display_changes(&self, old_steps: &[Action]) {
+        println!("\n--- Changes to the Remaining Plan ---");
+        if let Some(thought) = &self.thought {
+            println!("Thought: {}", thought);
+        }
+        let changes = plan_diff(old_steps, &self.steps);
+        if changes.is_empty() {
+            println!("No actions planned.");
+        } else {
+            println!("{}", format_plan_diff(&changes));
+        }
+        println!("-------------------------------------");
+    }
+}
+
+impl PlanReview {
+    pub fn display(&self) {
+        println!("\n--- Plan Review ---");
+        println!("{}", self.critique.yellow());
+        if self.revised_plan.is_some() {
+            println!("The reviewer suggested a revised plan.");
+        }
+        println!("-------------------");
+    }
+}
+
+fn ask_for_param(name: &str, param: &PlanParam) -> Result<String> {
+    ensure_prompts_enabled(&format!("The parameter '{}' of the plan", name))?;
+    let prompt = param.prompt.clone().unwrap_or_else(|| format!("Value of '{}'?", name));
+    match &param.default {
+        Some(default) => print!("{} [{}] ", prompt.green(), default),
+        None => print!("{} ", prompt.green()),
+    }
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let answer = input::read_line()?.map(|answer| answer.trim().to_string()).filter(|answer| !answer.is_empty());
+    answer
+        .or_else(|| param.default.clone())
+        .ok_or_else(|| ActionError::InvalidInput(format!("No value was given for the parameter '{}' of the plan", name)).into())
+}
+
+// How the action treats an existing file, shown with its description, e.g. " unless it exists"
+fn existing_file_handling(if_not_exists: bool, overwrite: bool) -> &'static str {
+    match (if_not_exists, overwrite) {
+        (true, _) => " unless it exists",
+        (false, false) => " without overwriting",
+        (false, true) => "",
     }
 }
 
+// Path of the plan nested into the plan at `plan_path`, fails if the nesting would exceed MAX_PLAN_DEPTH
+fn nested_plan_path(plan_path: &[String], label: String) -> Result<Vec<String>> {
+    if plan_path.len() >= MAX_PLAN_DEPTH {
+        return Err(ActionError::PolicyDenied(format!(
+            "Maximum plan nesting depth of {} reached at [{}], refusing to start '{}'",
+            MAX_PLAN_DEPTH, plan_path.join(" > "), label
+        )).into());
+    }
+    let mut nested_plan_path = plan_path.to_vec();
+    nested_plan_path.push(label);
+    Ok(nested_plan_path)
+}
+
 pub fn execute_plan<'a>(
     plan: &'a Plan,
     model_config: &'a Model,
     client: &'a Client,
-    execution_history: &'a mut Vec<(Action, Option<String>)>,
-    auto_confirm: bool,
+    execution_history: &'a mut Vec<ExecutedAction>,
+    settings: ExecutionSettings,
+    plan_path: &'a [String],
 ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
     Box::pin(async move {
-        println!("\n--- Executing Plan ---");
-        if plan.steps.is_empty() {
-            println!("No actions to execute.");
-            return Ok(());
-        }
-        let mut current_auto_confirm = auto_confirm;
+        let started = SystemTime::now();
+        let result = async {
+            let plan_label = plan_path.join(" > ");
+            let indent = "  ".repeat(plan_path.len().saturating_sub(1));
+            println!("\n{}--- Executing Plan [{}] (depth {}/{}) ---", indent, plan_label, plan_path.len(), MAX_PLAN_DEPTH);
+            if plan.steps.is_empty() {
+                println!("{}No actions to execute.", indent);
+                return Ok(());
+            }
+            guardrails::confirm_plan_execution()?;
+            expectations::check_preconditions(&plan.preconditions, settings.shell, settings.target.as_ref()).await?;
+            let mut settings = settings;
 
-        for (i, action) in plan.steps.iter().enumerate() {
-            println!("\n--- Step {}/{}: {:?} ---", i + 1, plan.steps.len(), action);
+            for (i, action) in plan.steps.iter().enumerate() {
+                println!("\n{}--- [{}] Step {}/{}: {:?} ---", indent, plan_label, i + 1, plan.steps.len(), action);
+                events::emit(Event::StepStarted { plan: &plan_label, step: i + 1, action });
 
-            let (new_auto_confirm, confirmed) = confirm_action::execute(current_auto_confirm).await?;
-            current_auto_confirm = new_auto_confirm;
-            if confirmed {
-                match action.execute(execution_history, &model_config, &client, current_auto_confirm).await {
-                    Ok(output) => {
-                        execution_history.push((action.clone(), output));
-                    }
-                    Err(e) => {
+                let (new_auto_confirm, confirmed) = confirm_action::execute(settings.auto_confirm, action).await?;
+                settings.auto_confirm = new_auto_confirm;
+                events::emit(Event::StepConfirmed { plan: &plan_label, step: i + 1, confirmed });
+                if confirmed {
+                    loop {
+                        changes::record_before(action, settings.target.as_ref()).await;
+                        let started = SystemTime::now();
+                        let result = action.execute(execution_history, model_config, client, &settings, plan_path).await;
+                        let result = match plan.expectations.get(&i) {
+                            Some(expectation) => match result.or_else(|e| expectation.accepted_failure(&e).ok_or(e)) {
+                                Ok(output) => expectation.check(&output, settings.target.as_ref()).await.map(|()| output),
+                                Err(e) => Err(e),
+                            },
+                            None => result,
+                        };
+                        changes::record_after(action, settings.target.as_ref()).await;
+                        metrics::record_step(&action.name(), started.elapsed().unwrap_or_default(), result.is_ok());
+                        let mut attributes = vec![("plan", plan_label.clone()), ("step", (i + 1).to_string()), ("action", action.describe())];
+                        if let Err(e) = &result {
+                            attributes.push(("error", format!("{:#}", e)));
+                        }
+                        trace::record(action.name(), "action", started, &attributes);
+                        let e = match result {
+                            Ok(output) => {
+                                if !matches!(output, ActionOutput::None | ActionOutput::Text { .. } | ActionOutput::Command { .. }) {
+                                    println!("{}Result: {}", indent, output);
+                                }
+                                events::emit(Event::StepOutput { plan: &plan_label, step: i + 1, output: &output });
+                                git_commits::commit_step(action, &plan_label, i + 1).await;
+                                execution_history.push(ExecutedAction { plan: plan_label.clone(), action: action.clone(), output });
+                                if let Some((check, output)) = diagnostics::check_step(action, &settings).await {
+                                    execution_history.push(ExecutedAction { plan: plan_label.clone(), action: check, output });
+                                }
+                                break;
+                            }
+                            Err(e) => e,
+                        };
                         eprintln!("Action {:?} failed: {}", action, e);
-                        let instruction = format!(
-                            "Action {:?} failed with error: {}. The history of previous actions is provided. Generate a new plan to achieve the original objective, taking this failure into account.",
-                            action, e
-                        );
-                        execution_history.push((action.clone(), Some(format!("ERROR: {}", e))));
-                        println!("Asking LLM for a new plan due to error...");
-                        // Ask LLM for a new plan
-                        match ask_llm_for_plan(
-                            model_config,
-                            &instruction,
-                            &Vec::new(), // No extra context sources for now
-                            &execution_history,
-                            client,
-                        ).await {
-                            Ok(new_plan) => {
-                                println!("Received new plan from LLM.");
-                                new_plan.display();
-                                return execute_plan(&new_plan, model_config, client, execution_history, current_auto_confirm).await;
+                        events::emit(Event::StepFailed { plan: &plan_label, step: i + 1, kind: error_kind(&e), message: e.to_string() });
+                        execution_history.push(ExecutedAction { plan: plan_label.clone(), action: action.clone(), output: ActionOutput::Error { kind: error_kind(&e).to_string(), message: e.to_string() } });
+                        let decision = match settings.on_failure {
+                            // A policy violation would most likely be repeated by a retry or a new plan
+                            _ if matches!(e.downcast_ref::<ActionError>(), Some(ActionError::PolicyDenied(_))) => FailureDecision::Abort,
+                            FailurePolicy::Ask => confirm_action::ask_failure_decision().await?,
+                            FailurePolicy::Replan => FailureDecision::Replan,
+                            FailurePolicy::Abort => FailureDecision::Abort,
+                        };
+                        match decision {
+                            FailureDecision::Retry => println!("Retrying step {}.", i + 1),
+                            FailureDecision::Skip => {
+                                println!("{}Skipping failed step {}.", indent, i + 1);
+                                if let Some(failed) = execution_history.last_mut() {
+                                    failed.output = ActionOutput::Error { kind: error_kind(&e).to_string(), message: format!("{} ({})", e, SKIPPED_OUTPUT) };
+                                }
+                                break;
                             }
-                            Err(llm_err) => {
-                                eprintln!("Failed to get a new plan from LLM: {}", llm_err);
-                                return Err(llm_err.context("Failed to get recovery plan from LLM after action failure"));
+                            FailureDecision::Abort => return Err(e.context(format!("Aborted after action {:?} failed", action))),
+                            FailureDecision::Replan => {
+                                let instruction = format!(
+                                    "Action {:?} failed with error of kind '{}': {}. The history of previous actions is provided. Generate a new plan to achieve the original objective, taking this failure into account.",
+                                    action, error_kind(&e), e
+                                );
+                                let recovery_plan_path = nested_plan_path(plan_path, format!("recovery of step {}", i + 1))
+                                    .map_err(|depth_err| depth_err.context(format!("Action {:?} failed: {}", action, e)))?;
+                                println!("Asking LLM for a new plan due to error...");
+                                // Ask LLM for a new plan
+                                match ask_llm_for_plan(
+                                    model_config,
+                                    &instruction,
+                                    &Vec::new(), // No extra context sources for now
+                                    execution_history,
+                                    &settings,
+                                    client,
+                                ).await {
+                                    Ok(new_plan) => {
+                                        println!("Received new plan from LLM.");
+                                        new_plan.display_changes(&plan.steps[i..]);
+                                        return execute_plan(&new_plan, model_config, client, execution_history, settings, &recovery_plan_path).await;
+                                    }
+                                    Err(llm_err) => {
+                                        eprintln!("Failed to get a new plan from LLM: {}", llm_err);
+                                        return Err(llm_err.context("Failed to get recovery plan from LLM after action failure"));
+                                    }
+                                }
                             }
                         }
                     }
+                } else {
+                    println!("{}Skipping step {}.", indent, i + 1);
+                    execution_history.push(ExecutedAction { plan: plan_label.clone(), action: action.clone(), output: ActionOutput::Skipped });
                 }
-            } else {
-                println!("Skipping step {}.", i + 1);
             }
+            println!("\n{}--- Plan Execution Finished [{}] ---", indent, plan_label);
+            Ok(())
+        }.await;
+        let mut attributes = vec![("steps", plan.steps.len().to_string())];
+        if let Err(e) = &result {
+            attributes.push(("error", format!("{:#}", e)));
         }
-        println!("\n--- Plan Execution Finished ---");
-        Ok(())
+        trace::record(format!("plan {}", plan_path.join(" > ")), "plan", started, &attributes);
+        result
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Shell;
+    use crate::actions::target::{parse_target, LocalTarget};
     use tempfile::NamedTempFile;
     use std::io::Write;
-    use tokio; // Import tokio for the test attribute
+    use std::fs;
+    use serde_json::json;
 
     #[test]
     fn test_plan_serialization() -> Result<()> {
@@ -288,6 +818,8 @@ mod tests {
                     action_idx: 0,
                     path: "hello.sh".to_string(),
                     content: "#!/bin/bash\necho 'Hello World!'".to_string(),
+                    if_not_exists: false,
+                    overwrite: true,
                 },
                 Action::RunCommand {
                     action_idx: 1,
@@ -295,13 +827,18 @@ mod tests {
                 },
                 Action::AskUser {
                     action_idx: 2,
-                    question: "Script executed.".to_string()
+                    question: "Script executed.".to_string(),
+                    choices: Vec::new(),
+                    validation_regex: None,
                 },
                 Action::ReadWebPage {
                     action_idx: 1,
                     url: "https://example.com".to_string(),
                 },
             ],
+            params: BTreeMap::new(),
+            expectations: BTreeMap::new(),
+            preconditions: Vec::new(),
         };
 
         let serialized_plan = serde_json::to_string_pretty(&plan)?;
@@ -310,12 +847,55 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_plan_schema() {
+        let schema = plan_schema();
+        let steps = schema["properties"]["steps"]["items"]["oneOf"].as_array().cloned().unwrap_or_default();
+        let run_command = steps.iter().find(|step| step["properties"]["action"]["enum"][0] == "run_command").expect("RunCommand is in the schema");
+
+        assert!(run_command["description"].as_str().is_some_and(|description| description.starts_with("Run command")));
+        assert_eq!(run_command["required"], json!(["action", "action_idx", "command"]));
+        assert!(schema["properties"]["steps"]["items"]["properties"]["expect"].is_object());
+        assert!(schema["properties"]["preconditions"].is_object());
+        assert!(schema["properties"]["params"].is_null() && schema["$schema"].is_null());
+    }
+
+    #[test]
+    fn test_plan_params() -> Result<()> {
+        let plan: Plan = serde_json::from_str(r#"{
+            "thought": "Create the project {{project_name}}",
+            "steps": [{"action": "run_command", "action_idx": 0, "command": "cargo new {{project_name}} --vcs {{vcs}}"}],
+            "params": {"project_name": {"prompt": "Project name?"}, "vcs": {"default": "git"}}
+        }"#)?;
+
+        let filled = plan.fill_params(&BTreeMap::from([("project_name".to_string(), "demo".to_string()), ("vcs".to_string(), "none".to_string())]))?;
+        assert_eq!(filled.thought.as_deref(), Some("Create the project demo"));
+        assert_eq!(filled.steps, vec![Action::RunCommand { action_idx: 0, command: "cargo new demo --vcs none".to_string() }]);
+        assert!(filled.params.is_empty());
+
+        let error = plan.with_params(&HashMap::from([("name".to_string(), "demo".to_string())])).unwrap_err();
+        assert_eq!(error.to_string(), "The plan has no parameter 'name'");
+        Ok(())
+    }
+
     fn create_temp_file(content: &str) -> Result<NamedTempFile> {
         let mut temp_file = NamedTempFile::new()?;
         writeln!(temp_file, "{}", content)?;
         Ok(temp_file)
     }
 
+    fn test_settings() -> ExecutionSettings {
+        ExecutionSettings { auto_confirm: AutoConfirm::All, on_failure: FailurePolicy::Abort, shell: ShellConfig { name: Some(Shell::Sh), login: false }, target: Arc::new(LocalTarget) }
+    }
+
+    fn test_model_config() -> Model {
+        Model {
+            name: "default".to_string(),
+            api_url: "http://localhost:8000".to_string(),
+            ..Model::default()
+        }
+    }
+
     fn read_file_content(path: &std::path::Path) -> Result<String> {
         fs::read_to_string(path).context("Failed to read temp file")
     }
@@ -339,18 +919,10 @@ mod tests {
         };
 
         let mut history = Vec::new();
-        let model_config = Model {
-            name: "default".to_string(),
-            api_url: "http://localhost:8000".to_string(),
-            api_key: None,
-            api_key_header: None,
-            model_identifier: None,
-            request_format: "".to_string(),
-            response_json_path: "".to_string(),
-        };
+        let model_config = test_model_config();
         let client = Client::new();
 
-        action.execute(&mut history, &model_config, &client, true).await?;
+        action.execute(&mut history, &model_config, &client, &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?;
 
         let content = read_file_content(temp_file.path())?;
         assert_eq!(content.trim(), expected_content);
@@ -422,4 +994,357 @@ mod tests {
             "completely\nnew\ncontent",
         ).await
     }
+
+    #[tokio::test]
+    async fn test_read_files() -> Result<()> {
+        let first = create_temp_file("first")?;
+        let second = create_temp_file("second")?;
+        let large = create_temp_file(&"x".repeat(100 * 1024))?;
+        let first_path = first.path().to_str().unwrap().to_string();
+        let second_path = second.path().to_str().unwrap().to_string();
+        let action = Action::ReadFiles {
+            action_idx: 0,
+            paths: vec![first_path.clone(), second_path.clone(), "nonexistent_file.txt".to_string(), large.path().to_str().unwrap().to_string()],
+        };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert!(output.starts_with(&format!("--- File: {} ---\nfirst\n", first_path)));
+        assert!(output.contains(&format!("--- File: {} ---\nsecond\n", second_path)));
+        assert!(output.contains("--- File: nonexistent_file.txt ---\nERROR: Failed to read file"));
+        assert!(output.ends_with(&format!("{}\n... [truncated {} of {} bytes]", "x".repeat(64 * 1024), 36 * 1024 + 1, 100 * 1024 + 1)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_show_tree() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("src"))?;
+        fs::create_dir_all(dir.path().join("target"))?;
+        fs::write(dir.path().join(".gitignore"), "target/\n")?;
+        fs::write(dir.path().join("src").join("main.rs"), "fn main() {}")?;
+        fs::write(dir.path().join("target").join("cliff"), "binary")?;
+        let path = dir.path().to_str().unwrap().to_string();
+        let action = Action::ShowTree { action_idx: 0, path: path.clone(), max_depth: None, include_sizes: true };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert_eq!(output, format!("{}/\n  .gitignore (8 bytes)\n  src/\n    main.rs (12 bytes)", path));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_directory_recursive_with_glob() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("src").join("actions"))?;
+        fs::write(dir.path().join("src").join("main.rs"), "fn main() {}")?;
+        fs::write(dir.path().join("src").join("actions").join("ask_user.rs"), "")?;
+        fs::write(dir.path().join("README.md"), "# cliff")?;
+        let action = Action::ListDirectory {
+            action_idx: 0,
+            path: dir.path().to_str().unwrap().to_string(),
+            recursive: true,
+            glob: Some("*.rs".to_string()),
+            max_entries: Some(1),
+        };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert_eq!(output, "src/actions/ask_user.rs (file, 0 bytes)\n... (1 more entries not shown)");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_files_containing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("src"))?;
+        fs::create_dir_all(dir.path().join("target"))?;
+        fs::write(dir.path().join(".gitignore"), "target/\n")?;
+        fs::write(dir.path().join("src").join("main.rs"), "tokio::spawn(async {});")?;
+        fs::write(dir.path().join("src").join("lib.rs"), "fn lib() {}")?;
+        fs::write(dir.path().join("target").join("generated.rs"), "tokio::spawn(async {});")?;
+        let path = dir.path().to_str().unwrap().to_string();
+        let action = Action::FindFiles {
+            action_idx: 0,
+            pattern: "**/*.rs".to_string(),
+            path: Some(path.clone()),
+            containing: Some(r"tokio::spawn\(".to_string()),
+        };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert_eq!(output, std::path::Path::new(&path).join("src").join("main.rs").display().to_string());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hash_file() -> Result<()> {
+        let temp_file = create_temp_file("hello")?;
+        let action = Action::HashFile {
+            action_idx: 0,
+            path: temp_file.path().to_str().unwrap().to_string(),
+            algorithm: HashAlgorithm::Sha256,
+        };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?;
+
+        assert_eq!(output, ActionOutput::text("5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tail_file() -> Result<()> {
+        let content = (1..=5000).map(|line_idx| format!("line {}", line_idx)).collect::<Vec<_>>().join("\n");
+        let temp_file = create_temp_file(&content)?;
+        let tail = |lines: usize| Action::TailFile { action_idx: 0, path: temp_file.path().to_str().unwrap().to_string(), lines };
+
+        let last_lines = tail(3).execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+        let all_lines = tail(10000).execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert_eq!(last_lines, "line 4998\nline 4999\nline 5000");
+        assert_eq!(all_lines.lines().count(), 5000);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_process_inspection() -> Result<()> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let pid = std::process::id();
+        let process_name = fs::read_to_string("/proc/self/comm")?.trim().to_string();
+
+        let processes = Action::ListProcesses { action_idx: 0, filter: Some(process_name.to_uppercase()) }
+            .execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+        let port_usage = Action::GetPortUsage { action_idx: 1, port }
+            .execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        // The names of sysinfo are not truncated to the 15 characters of `comm`
+        assert!(processes.lines().any(|line| line.starts_with(&format!("pid {}: {}", pid, process_name))));
+        assert!(port_usage.starts_with(&format!("tcp 127.0.0.1:{} LISTEN pid {} ({}", port, pid, process_name)), "{}", port_usage);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_system_info() -> Result<()> {
+        let action = Action::SystemInfo { action_idx: 0 };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert!(output.starts_with(&format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_only_action_on_remote_target() -> Result<()> {
+        let settings = ExecutionSettings { target: parse_target("docker://web")?, ..test_settings() };
+        let action = Action::ListDirectory { action_idx: 0, path: ".".to_string(), recursive: false, glob: None, max_entries: None };
+
+        let error = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &settings, &[MAIN_PLAN_LABEL.to_string()]).await.unwrap_err();
+
+        assert_eq!(error_kind(&error), "invalid_input");
+        assert!(error.to_string().contains("not on docker://web"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compare_files() -> Result<()> {
+        let original = create_temp_file("line1\nline2")?;
+        let same = create_temp_file("line1\nline2")?;
+        let changed = create_temp_file("line1\nchanged")?;
+        let compare = |path_a: &NamedTempFile, path_b: &NamedTempFile| Action::CompareFiles {
+            action_idx: 0,
+            path_a: path_a.path().to_str().unwrap().to_string(),
+            path_b: path_b.path().to_str().unwrap().to_string(),
+        };
+
+        let identical = compare(&original, &same).execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+        let diff = compare(&original, &changed).execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?.compact();
+
+        assert_eq!(identical, "identical");
+        assert!(diff.contains("-line2\n+changed"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_plan_path_depth_limit() -> Result<()> {
+        let mut plan_path = vec![MAIN_PLAN_LABEL.to_string()];
+        for depth in 1..MAX_PLAN_DEPTH {
+            plan_path = nested_plan_path(&plan_path, format!("sub-plan of action {}", depth))?;
+        }
+        assert_eq!(plan_path.len(), MAX_PLAN_DEPTH);
+        assert_eq!(plan_path[..2].join(" > "), "main > sub-plan of action 1");
+
+        let error = nested_plan_path(&plan_path, "recovery of step 1".to_string()).unwrap_err();
+        assert!(error.to_string().starts_with("Maximum plan nesting depth of 5 reached"));
+        assert_eq!(error_kind(&error), "policy_denied");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_plan_aborts_on_failure() -> Result<()> {
+        let plan = Plan {
+            thought: None,
+            steps: vec![
+                Action::ReadFile { action_idx: 0, path: "nonexistent_file.txt".to_string() },
+                Action::CheckPathExists { action_idx: 1, path: "nonexistent_file.txt".to_string() },
+            ],
+            params: BTreeMap::new(),
+            expectations: BTreeMap::new(),
+            preconditions: Vec::new(),
+        };
+        let mut history = Vec::new();
+
+        let result = execute_plan(&plan, &test_model_config(), &Client::new(), &mut history, test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await;
+
+        assert!(result.is_err());
+        assert_eq!(history.len(), 1);
+        assert!(history[0].output.compact().starts_with("ERROR: Failed to read file"));
+        assert!(history[0].output.compact().ends_with("[not_found]"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_plan_checks_expectations() -> Result<()> {
+        let plan: Plan = serde_json::from_str(r#"{
+            "thought": null,
+            "steps": [
+                {"action": "run_command", "action_idx": 0, "command": "grep -q missing /dev/null", "expect": {"exit_code": 1}},
+                {"action": "run_command", "action_idx": 1, "command": "echo '3 passed; 1 failed'", "expect": {"contains": "0 failed"}},
+                {"action": "check_path_exists", "action_idx": 2, "path": "Cargo.toml"}
+            ]
+        }"#)?;
+        assert_eq!(plan.expectations.get(&1), Some(&Expectation { contains: Some("0 failed".to_string()), ..Expectation::default() }));
+        assert_eq!(serde_json::from_str::<Plan>(&serde_json::to_string(&plan)?)?, plan);
+        let mut history = Vec::new();
+
+        let result = execute_plan(&plan, &test_model_config(), &Client::new(), &mut history, test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await;
+
+        assert!(result.is_err());
+        assert_eq!(history.len(), 2);
+        assert!(matches!(history[0].output, ActionOutput::Command { exit_code: Some(1), .. }));
+        assert!(history[1].output.compact().starts_with("ERROR: The expectation of the step is not met: the output does not contain '0 failed'"));
+        assert!(history[1].output.compact().ends_with("[expectation_failed]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_plan() -> Result<()> {
+        let plan: Plan = serde_json::from_str(r#"{
+            "thought": "Write and run the script",
+            "steps": [
+                {"action": "create_file", "action_idx": 0, "path": "hello.sh", "content": "echo hello\necho bye"},
+                {"action": "run_command", "action_idx": 1, "command": "sh hello.sh", "expect": {"contains": "bye"}}
+            ],
+            "preconditions": [{"tool": "sh"}]
+        }"#)?;
+
+        let rendered = plan.render();
+
+        assert!(rendered.contains("Write and run the script"));
+        assert!(rendered.contains("'sh' is installed"));
+        assert!(rendered.contains("0. Create file 'hello.sh'"));
+        assert!(rendered.contains("\necho hello\necho bye\n"));
+        assert!(rendered.contains("   Expect: "));
+        assert_eq!(rendered.lines().last(), Some("--------------------"));
+
+        let long_file = Action::CreateFile { action_idx: 2, path: "long.txt".to_string(), content: "line\n".repeat(25), if_not_exists: false, overwrite: true };
+        assert!(long_file.describe().ends_with("line\nline\n(5 more lines)"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_plan_checks_preconditions() -> Result<()> {
+        let plan: Plan = serde_json::from_str(r#"{
+            "thought": null,
+            "steps": [{"action": "run_command", "action_idx": 0, "command": "echo deploying"}],
+            "preconditions": [{"path_exists": "Cargo.toml"}, {"path_exists": "missing/deploy.yaml"}]
+        }"#)?;
+        assert_eq!(serde_json::from_str::<Plan>(&serde_json::to_string(&plan)?)?, plan);
+        let mut history = Vec::new();
+
+        let error = execute_plan(&plan, &test_model_config(), &Client::new(), &mut history, test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await.unwrap_err();
+
+        assert_eq!(error.to_string(), "The preconditions of the plan are not met, no step was executed: 'missing/deploy.yaml' exists");
+        assert!(history.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_actions_keep_existing_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let existing = dir.path().join("config.toml").display().to_string();
+        let source = dir.path().join("config.new").display().to_string();
+        fs::write(&existing, "port = 8080")?;
+        fs::write(&source, "port = 9090")?;
+        let run = |action: Action| async move {
+            action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await
+        };
+
+        let output = run(Action::CreateFile { action_idx: 0, path: existing.clone(), content: "".to_string(), if_not_exists: true, overwrite: true }).await?;
+        assert!(output.to_string().ends_with("already exists, it was not changed"));
+        let error = run(Action::CreateFile { action_idx: 1, path: existing.clone(), content: "".to_string(), if_not_exists: false, overwrite: false }).await.unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "already_exists");
+        let error = run(Action::MoveFile { action_idx: 2, source: source.clone(), destination: existing.clone(), overwrite: false }).await.unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "already_exists");
+        assert_eq!(fs::read_to_string(&existing)?, "port = 8080");
+
+        run(Action::CopyFile { action_idx: 3, source, destination: existing.clone(), overwrite: true }).await?;
+        assert_eq!(fs::read_to_string(&existing)?, "port = 9090");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_command_structured_output() -> Result<()> {
+        let action = Action::RunCommand { action_idx: 0, command: "echo hello && echo warning >&2".to_string() };
+
+        let output = action.execute(&mut Vec::new(), &test_model_config(), &Client::new(), &test_settings(), &[MAIN_PLAN_LABEL.to_string()]).await?;
+
+        assert_eq!(output, ActionOutput::Command { exit_code: Some(0), stdout: "hello".to_string(), stderr: "warning".to_string() });
+        assert_eq!(output.compact(), "hello\nstderr: warning");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_skipped_step_in_history() -> Result<()> {
+        let script = NamedTempFile::new()?;
+        fs::write(script.path(), "n\n")?;
+        input::use_script(script.path())?;
+        let server = httpmock::MockServer::start();
+        let answer = server.mock(|when, then| {
+            when.method(httpmock::Method::POST).path("/chat").body_contains("Question: Was the greeting printed?").body_contains(SKIPPED_OUTPUT);
+            then.status(200).json_body(json!({ "answer": "No, the command was skipped" }));
+        });
+        let recovery_plan = server.mock(|when, then| {
+            when.method(httpmock::Method::POST).path("/chat").body_contains("Generate a new plan").body_contains(SKIPPED_OUTPUT);
+            then.status(200).json_body(json!({ "answer": r#"{"steps": []}"# }));
+        });
+        let model_config = Model {
+            api_url: server.url("/chat"),
+            request_format: r#"{"messages": {{messages}}}"#.to_string(),
+            response_json_path: "$.answer".to_string(),
+            ..test_model_config()
+        };
+        let plan = Plan {
+            thought: None,
+            steps: vec![
+                Action::RunCommand { action_idx: 0, command: "echo hello".to_string() },
+                Action::AskLlm { action_idx: 1, prompt: "Was the greeting printed?".to_string() },
+                Action::ReadFile { action_idx: 2, path: "/nonexistent/greeting.txt".to_string() },
+            ],
+            params: BTreeMap::new(),
+            expectations: BTreeMap::new(),
+            preconditions: Vec::new(),
+        };
+        let settings = ExecutionSettings { auto_confirm: AutoConfirm::Reads, on_failure: FailurePolicy::Replan, ..test_settings() };
+        let mut history = Vec::new();
+
+        execute_plan(&plan, &model_config, &Client::new(), &mut history, settings, &[MAIN_PLAN_LABEL.to_string()]).await?;
+
+        assert_eq!(history[0].output, ActionOutput::Skipped);
+        assert_eq!(history[1].output, ActionOutput::text("No, the command was skipped"));
+        answer.assert();
+        recovery_plan.assert();
+        Ok(())
+    }
 }
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use crate::actions::ActionOutput;
+use crate::actions::target::{quote, ExecutionTarget};
+use crate::config::{Shell, ShellConfig};
+use crate::error::{ActionError, IoResultExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Expectation on the result of a step of the plan checked after the step, e.g. `{"contains": "0 failed", "exit_code": 0}`,
+// a failed expectation is a failure of the step
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    // Text the output of the step has to contain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    // Exit code of the command of the step, a command failing with it is not a failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    // Path which has to exist after the step, e.g. the built artifact
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_exists: Option<String>,
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if let Some(text) = &self.contains {
+            conditions.push(format!("output contains '{}'", text));
+        }
+        if let Some(exit_code) = self.exit_code {
+            conditions.push(format!("exit code {}", exit_code));
+        }
+        if let Some(path) = &self.path_exists {
+            conditions.push(format!("'{}' exists", path));
+        }
+        write!(f, "{}", conditions.join(", "))
+    }
+}
+
+impl Expectation {
+    /// The output of the command which failed with the expected exit code, e.g. `grep` not finding a match
+    pub fn accepted_failure(&self, error: &anyhow::Error) -> Option<ActionOutput> {
+        match error.downcast_ref::<ActionError>() {
+            Some(ActionError::CommandFailed { message, exit_code }) if self.exit_code.is_some() && *exit_code == self.exit_code => {
+                Some(ActionOutput::Command { exit_code: *exit_code, stdout: String::new(), stderr: message.clone() })
+            }
+            _ => None,
+        }
+    }
+
+    /// Fails with the unmet expectations of the step
+    pub async fn check(&self, output: &ActionOutput, target: &dyn ExecutionTarget) -> Result<()> {
+        let mut unmet = Vec::new();
+        if let Some(text) = &self.contains {
+            if !output.to_string().contains(text.as_str()) {
+                unmet.push(format!("the output does not contain '{}'", text));
+            }
+        }
+        if let Some(expected) = self.exit_code {
+            match output {
+                ActionOutput::Command { exit_code, .. } if *exit_code == Some(expected) => {}
+                ActionOutput::Command { exit_code, .. } => unmet.push(format!(
+                    "the exit code is {} instead of {}", exit_code.map_or("none".to_string(), |code| code.to_string()), expected
+                )),
+                _ => unmet.push(format!("the step ran no command to exit with {}", expected)),
+            }
+        }
+        if let Some(path) = &self.path_exists {
+            if !target.exists(path).await? {
+                unmet.push(format!("the path '{}' does not exist", path));
+            }
+        }
+        if unmet.is_empty() {
+            return Ok(());
+        }
+        // The output is kept in the error as the failed step is not recorded with its output, e.g. for the recovery plan
+        Err(ActionError::ExpectationFailed(format!(
+            "The expectation of the step is not met: {}. The output of the step: {}", unmet.join(", "), output.compact()
+        )).into())
+    }
+}
+
+// Condition checked before the first step of the plan, e.g. `{"tool": "docker"}`, `{"path_exists": "Cargo.toml"}` or
+// `{"command": "docker info"}`, so that a missing tool fails the plan before it changes anything
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Precondition {
+    // Program which has to be on the `PATH`
+    Tool(String),
+    // Path which has to exist
+    PathExists(String),
+    // Shell command which has to succeed
+    Command(String),
+}
+
+impl fmt::Display for Precondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Precondition::Tool(tool) => write!(f, "'{}' is installed", tool),
+            Precondition::PathExists(path) => write!(f, "'{}' exists", path),
+            Precondition::Command(command) => write!(f, "`{}` succeeds", command),
+        }
+    }
+}
+
+impl Precondition {
+    async fn is_met(&self, shell_config: ShellConfig, target: &dyn ExecutionTarget) -> Result<bool> {
+        let shell = shell_config.resolve();
+        let (program, args) = match self {
+            Precondition::PathExists(path) => return target.exists(path).await,
+            Precondition::Tool(tool) if shell == Shell::Powershell => {
+                (shell.program(), shell.args(&format!("Get-Command '{}' -ErrorAction Stop", tool.replace('\'', "''")), false))
+            }
+            // `command -v` is the portable lookup of the POSIX shells, the other shells do not agree on one
+            Precondition::Tool(tool) => ("sh", vec!["-c".to_string(), format!("command -v {}", quote(tool))]),
+            Precondition::Command(command) => (shell.program(), shell.args(command, shell_config.login)),
+        };
+        let status = target.command(program, &args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await
+            .with_action_context(|| format!("Failed to check the precondition: {}", self))?;
+        Ok(status.success())
+    }
+}
+
+/// Fails with all the unmet preconditions of the plan before any of its steps is executed
+pub async fn check_preconditions(preconditions: &[Precondition], shell_config: ShellConfig, target: &dyn ExecutionTarget) -> Result<()> {
+    let mut unmet = Vec::new();
+    for precondition in preconditions {
+        if !precondition.is_met(shell_config, target).await? {
+            unmet.push(precondition.to_string());
+        }
+    }
+    if unmet.is_empty() {
+        return Ok(());
+    }
+    Err(ActionError::PreconditionFailed(format!("The preconditions of the plan are not met, no step was executed: {}", unmet.join(", "))).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::target::LocalTarget;
+
+    #[tokio::test]
+    async fn test_check_expectation() -> Result<()> {
+        let output = ActionOutput::Command { exit_code: Some(0), stdout: "test result: ok. 3 passed; 0 failed".to_string(), stderr: String::new() };
+        let met = Expectation { contains: Some("0 failed".to_string()), exit_code: Some(0), path_exists: Some("Cargo.toml".to_string()) };
+        met.check(&output, &LocalTarget).await?;
+
+        let unmet = Expectation { contains: Some("4 passed".to_string()), exit_code: None, path_exists: Some("dist/app".to_string()) };
+        let error = unmet.check(&output, &LocalTarget).await.unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "expectation_failed");
+        assert!(error.to_string().starts_with("The expectation of the step is not met: the output does not contain '4 passed', the path 'dist/app' does not exist."));
+        assert_eq!(met.to_string(), "output contains '0 failed', exit code 0, 'Cargo.toml' exists");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_preconditions() -> Result<()> {
+        let met = [Precondition::Tool("sh".to_string()), Precondition::PathExists("Cargo.toml".to_string()), Precondition::Command("test 1 -eq 1".to_string())];
+        check_preconditions(&met, ShellConfig::default(), &LocalTarget).await?;
+
+        let unmet = [Precondition::Tool("no-such-tool-1a2b".to_string()), Precondition::Command("exit 3".to_string()), Precondition::PathExists("Cargo.toml".to_string())];
+        let error = check_preconditions(&unmet, ShellConfig::default(), &LocalTarget).await.unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "precondition_failed");
+        assert_eq!(error.to_string(), "The preconditions of the plan are not met, no step was executed: 'no-such-tool-1a2b' is installed, `exit 3` succeeds");
+        Ok(())
+    }
+
+    #[test]
+    fn test_accepted_failure() {
+        let error: anyhow::Error = ActionError::CommandFailed { message: "Command failed with status: exit status: 1".to_string(), exit_code: Some(1) }.into();
+        let expectation = Expectation { exit_code: Some(1), ..Expectation::default() };
+        assert!(matches!(expectation.accepted_failure(&error), Some(ActionOutput::Command { exit_code: Some(1), .. })));
+        assert_eq!(Expectation::default().accepted_failure(&error), None);
+    }
+}
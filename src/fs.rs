@@ -1,13 +1,285 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
 use anyhow::Result;
+use crate::error::ActionError;
+
+// Environment variables which may be expanded in the paths of actions, empty unless enabled in the config
+static ENV_ALLOWLIST: OnceLock<Vec<String>> = OnceLock::new();
+
+pub(crate) fn allow_env_vars(allowlist: Vec<String>) {
+    let _ = ENV_ALLOWLIST.set(allowlist);
+}
+
+pub(crate) fn env_allowlist() -> &'static [String] {
+    ENV_ALLOWLIST.get().map_or(&[], Vec::as_slice)
+}
+
+// Glob patterns of the paths no action may write, move or delete, e.g. `~/.ssh/**`
+static PROTECTED_PATHS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub(crate) fn protect_paths(patterns: Vec<String>) {
+    let _ = PROTECTED_PATHS.set(patterns);
+}
+
+pub(crate) fn protected_paths() -> &'static [String] {
+    PROTECTED_PATHS.get().map_or(&[], Vec::as_slice)
+}
+
+// Fails when the path matches one of the protected patterns, regardless of the confirmations of the user. The paths on
+// a remote target are matched as written, `~/` standing for the home directory there
+pub(crate) fn ensure_writable(path: &str, local: bool, patterns: &[String]) -> Result<()> {
+    if local {
+        check_protected(path, patterns)
+    } else {
+        check_protected_remote(path, patterns)
+    }
+}
+
+fn protected_error(path: &str, pattern: &str) -> anyhow::Error {
+    ActionError::PolicyDenied(format!("The path '{}' is protected by the pattern '{}' in the config", path, pattern)).into()
+}
+
+// The remote files can't be resolved locally, `..` is removed from the path before matching
+fn check_protected_remote(path: &str, patterns: &[String]) -> Result<()> {
+    let normalized_path = normalize_path(Path::new(path));
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+    match patterns.iter().find(|pattern| glob::Pattern::new(pattern).is_ok_and(|glob_pattern| glob_pattern.matches_path_with(&normalized_path, options))) {
+        Some(pattern) => Err(protected_error(path, pattern)),
+        None => Ok(()),
+    }
+}
+
+fn check_protected(path: &str, patterns: &[String]) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let expanded_path = expand_home(path)?;
+    let absolute_path = normalize_path(&env::current_dir()?.join(expanded_path));
+    // The symlinks in the existing part of the path would otherwise lead around the patterns
+    let resolved_path = resolve_symlinks(&absolute_path);
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+    for pattern in patterns {
+        let expanded_pattern = match pattern.strip_prefix("~/") {
+            Some(relative_pattern) => format!("{}/{}", env::var("HOME")?, relative_pattern),
+            None => pattern.clone(),
+        };
+        let Ok(glob_pattern) = glob::Pattern::new(&expanded_pattern) else {
+            continue;
+        };
+        if [&absolute_path, &resolved_path].iter().any(|candidate| glob_pattern.matches_path_with(candidate, options)) {
+            return Err(protected_error(path, pattern));
+        }
+    }
+    Ok(())
+}
 
 pub(crate) fn expand_home(path: &str) -> Result<PathBuf> {
-    let expanded_path = if path.starts_with("~/") {
+    let path = expand_env_vars(path, env_allowlist())?;
+    let path = path.as_str();
+    let expanded_path = if let Some(relative_path) = path.strip_prefix("~/") {
         let home = env::var("HOME")?;
-        PathBuf::from(home).join(&path[2..])
+        PathBuf::from(home).join(relative_path)
     } else {
         PathBuf::from(path)
     };
     Ok(expanded_path)
 }
+
+// Expands `$VAR`, `${VAR}` and on Windows `%VAR%` for the variables in `allowlist`, paths are left intact when it is empty
+fn expand_env_vars(path: &str, allowlist: &[String]) -> Result<String> {
+    if allowlist.is_empty() {
+        return Ok(path.to_string());
+    }
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let reference = if rest[start..].starts_with('%') {
+            after.find('%').filter(|_| cfg!(windows)).map(|end| (&after[..end], end + 1))
+        } else if let Some(braced) = after.strip_prefix('{') {
+            braced.find('}').map(|end| (&braced[..end], end + 2))
+        } else {
+            let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+            Some((&after[..end], end))
+        };
+        match reference.filter(|(name, _)| !name.is_empty()) {
+            Some((name, consumed)) => {
+                if !allowlist.iter().any(|allowed| allowed == name) {
+                    return Err(ActionError::PolicyDenied(format!("Environment variable '{}' in path '{}' is not allowed to be expanded", name, path)).into());
+                }
+                let value = env::var(name)
+                    .map_err(|_| ActionError::NotFound(format!("Environment variable '{}' in path '{}' is not set", name, path)))?;
+                expanded.push_str(&value);
+                rest = &after[consumed..];
+            }
+            None => {
+                expanded.push_str(&rest[start..start + 1]);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+// The absolute path with the symlinks of its longest existing ancestor resolved, the rest does not exist yet
+fn resolve_symlinks(absolute_path: &Path) -> PathBuf {
+    absolute_path.ancestors()
+        .find_map(|ancestor| {
+            let canonical = ancestor.canonicalize().ok()?;
+            Some(canonical.join(absolute_path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| absolute_path.to_path_buf())
+}
+
+// Whether the path is inside `dir` after resolving the symlinks, e.g. `./link/passwd` with `link -> /etc` is not
+pub(crate) fn is_within_dir(path: &str, dir: &Path) -> bool {
+    match expand_home(path) {
+        Ok(expanded_path) => {
+            let dir = normalize_path(dir);
+            resolve_symlinks(&normalize_path(&dir.join(expanded_path))).starts_with(resolve_symlinks(&dir))
+        }
+        Err(_) => false,
+    }
+}
+
+// Glob patterns of the paths skipped by the file discovery in addition to the ignore files, e.g. `**/*.min.js`
+static EXCLUDED_PATHS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub(crate) fn exclude_paths(patterns: Vec<String>) {
+    let _ = EXCLUDED_PATHS.set(patterns);
+}
+
+// Ignore file of cliff read in addition to `.gitignore` and `.ignore`, e.g. for the files only the LLM should not see
+const CLIFF_IGNORE_FILE: &str = ".cliffignore";
+
+// Walks the directory tree at `root` in file name order, skipping `.git` and the entries excluded by the ignore files or the configured patterns
+pub(crate) fn walk_dir(root: &Path, max_depth: Option<usize>) -> impl Iterator<Item = ignore::DirEntry> {
+    walk_dir_excluding(root, max_depth, EXCLUDED_PATHS.get().map_or(&[], Vec::as_slice))
+}
+
+fn walk_dir_excluding(root: &Path, max_depth: Option<usize>, excluded_paths: &[String]) -> impl Iterator<Item = ignore::DirEntry> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root);
+    for pattern in excluded_paths {
+        // Invalid patterns are rejected when configured
+        let _ = overrides.add(&format!("!{}", pattern));
+    }
+    let mut walker = ignore::WalkBuilder::new(root);
+    walker
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(CLIFF_IGNORE_FILE)
+        .max_depth(max_depth)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|entry| entry.file_name() != ".git");
+    if let Ok(overrides) = overrides.build() {
+        walker.overrides(overrides);
+    }
+    // The root is not skipped with `min_depth` which would also skip its ignore files
+    walker.build().filter_map(|entry| entry.ok()).filter(|entry| entry.depth() > 0)
+}
+
+pub(crate) fn is_dir(entry: &ignore::DirEntry) -> bool {
+    entry.file_type().is_some_and(|file_type| file_type.is_dir())
+}
+
+// Contents of the files walked under `dir`, the files which are not valid UTF-8 are skipped as binary
+pub(crate) fn read_text_files(dir: &Path) -> Vec<(PathBuf, String)> {
+    walk_dir(dir, None)
+        .filter(|entry| !is_dir(entry))
+        .filter_map(|entry| Some((entry.path().to_path_buf(), std::fs::read_to_string(entry.path()).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_dir_honors_ignore_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for path in ["src/main.rs", "src/debug.log", "src/keep.log", "target/debug/cliff", "docs/generated/api.md", "docs/notes.md", "vendor/lib.min.js", ".git/HEAD"] {
+            std::fs::create_dir_all(root.join(path).parent().unwrap())?;
+            std::fs::write(root.join(path), "")?;
+        }
+        std::fs::write(root.join(".gitignore"), "# build output\ntarget/\n*.log\n!keep.log\n")?;
+        std::fs::write(root.join("docs/.cliffignore"), "/generated\n")?;
+
+        let walked: Vec<String> = walk_dir_excluding(root, None, &["*.min.js".to_string()])
+            .filter(|entry| !is_dir(entry))
+            .map(|entry| entry.path().strip_prefix(root).unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(walked, vec![".gitignore", "docs/.cliffignore", "docs/notes.md", "src/keep.log", "src/main.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_protected_paths() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let patterns = vec![format!("{}/secrets/**", root.display()), "**/*.pem".to_string()];
+        std::fs::create_dir(root.join("secrets"))?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("secrets"), root.join("link"))?;
+
+        assert!(check_protected(&format!("{}/notes.txt", root.display()), &patterns).is_ok());
+        assert!(check_protected(&format!("{}/secrets/new/key", root.display()), &patterns).is_err());
+        assert!(check_protected(&format!("{}/other/../secrets/key", root.display()), &patterns).is_err());
+        assert!(check_protected(&format!("{}/certs/server.pem", root.display()), &patterns).is_err());
+        #[cfg(unix)]
+        assert!(check_protected(&format!("{}/link/key", root.display()), &patterns).is_err());
+        let error = check_protected("/etc/hosts", &["/etc/**".to_string()]).unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "policy_denied");
+        assert!(check_protected("/etc/hosts", &[]).is_ok());
+
+        let remote_patterns = vec!["~/.ssh/**".to_string(), "/etc/**".to_string()];
+        assert!(check_protected_remote("~/.ssh/authorized_keys", &remote_patterns).is_err());
+        assert!(check_protected_remote("/var/../etc/hosts", &remote_patterns).is_err());
+        assert!(check_protected_remote("~/notes.txt", &remote_patterns).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_within_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outside = tempfile::tempdir()?;
+        assert!(is_within_dir("src/new/main.rs", dir.path()));
+        assert!(!is_within_dir("src/../../notes.txt", dir.path()));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link"))?;
+            assert!(!is_within_dir("link/passwd", dir.path()));
+            assert!(!is_within_dir(&format!("{}/link/new/passwd", dir.path().display()), dir.path()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_env_vars() -> Result<()> {
+        let home = env::var("HOME")?;
+        let allowlist = vec!["HOME".to_string()];
+        assert_eq!(expand_env_vars("$HOME/project", &[])?, "$HOME/project");
+        assert_eq!(expand_env_vars("$HOME/project", &allowlist)?, format!("{}/project", home));
+        assert_eq!(expand_env_vars("${HOME}_backup/$", &allowlist)?, format!("{}_backup/$", home));
+        let error = expand_env_vars("$PATH/bin", &allowlist).unwrap_err();
+        assert_eq!(crate::error::error_kind(&error), "policy_denied");
+        Ok(())
+    }
+}
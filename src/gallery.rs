@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::error::ActionError;
+use crate::executor::Plan;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const PLANS_DIR_NAME: &str = "plans";
+
+// Plans shipped with cliff by their names, the data files are embedded at build time
+const BUILT_IN_PLANS: [(&str, &str); 3] = [
+    ("containerize", include_str!("../plans/containerize.json")),
+    ("github-actions", include_str!("../plans/github-actions.json")),
+    ("rust-cli", include_str!("../plans/rust-cli.json")),
+];
+
+/// Plan of the gallery: a built-in plan or a plan saved by the user into the plans directory
+pub struct GalleryPlan {
+    pub plan: Plan,
+    pub built_in: bool,
+}
+
+/// Directory of the plans of the user, e.g. saved with `cliff act --save-plan`, they take precedence over the built-in
+/// plans with the same names
+pub fn plans_dir() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join(PLANS_DIR_NAME))
+}
+
+fn parse(name: &str, content: &str) -> Result<Plan> {
+    serde_json::from_str(content).with_context(|| format!("Failed to parse the plan '{}'", name))
+}
+
+/// All the plans by their names
+pub fn plans() -> Result<BTreeMap<String, GalleryPlan>> {
+    let mut plans = BTreeMap::new();
+    for (name, content) in BUILT_IN_PLANS {
+        plans.insert(name.to_string(), GalleryPlan { plan: parse(name, content)?, built_in: true });
+    }
+    let dir = plans_dir()?;
+    if dir.is_dir() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read the plans directory: {:?}", dir))? {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).filter(|_| path.extension().is_some_and(|ext| ext == "json")) else {
+                continue;
+            };
+            let content = fs::read_to_string(&path).with_context(|| format!("Failed to read the plan: {:?}", path))?;
+            plans.insert(name.to_string(), GalleryPlan { plan: parse(name, &content)?, built_in: false });
+        }
+    }
+    Ok(plans)
+}
+
+pub fn find(name: &str) -> Result<Plan> {
+    let mut plans = plans()?;
+    match plans.remove(name) {
+        Some(gallery_plan) => Ok(gallery_plan.plan),
+        None => {
+            let names: Vec<String> = plans.into_keys().collect();
+            Err(ActionError::NotFound(format!("No plan named '{}', the plans are: {}", name, names.join(", "))).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_plans() -> Result<()> {
+        for (name, content) in BUILT_IN_PLANS {
+            let plan = parse(name, content)?;
+            assert!(plan.thought.is_some(), "The plan '{}' has no description", name);
+            assert!(!plan.params.is_empty(), "The plan '{}' has no parameters", name);
+            let values = plan.params.keys().map(|param| (param.clone(), "value".to_string())).collect();
+            let filled = serde_json::to_string(&plan.with_params(&values)?)?;
+            assert!(!filled.contains("{{"), "The plan '{}' has undeclared parameters", name);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use crate::actions::target::ExecutionTarget;
+use crate::classification::{classify, ActionClass};
+use crate::error::{ActionError, IoResultExt};
+use crate::executor::Action;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+// Longer subjects of the commits are shortened, as git tooling shows only about this many characters
+const MAX_SUBJECT_CHARS: usize = 72;
+
+// Repository the steps of the run are committed to, the steps are not committed unless it is set
+static ACTIVE: OnceLock<StepCommits> = OnceLock::new();
+
+#[derive(Debug)]
+struct StepCommits {
+    repo: PathBuf,
+    run_id: String,
+}
+
+async fn git(repo: &Path, args: &[&str]) -> Result<std::process::Output> {
+    Ok(Command::new("git").arg("-C").arg(repo).args(args).output().await
+        .with_action_context(|| format!("Failed to run git {}", args.join(" ")))?)
+}
+
+async fn git_checked(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = git(repo, args).await?;
+    if !output.status.success() {
+        return Err(ActionError::CommandFailed {
+            message: format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()),
+            exit_code: output.status.code(),
+        }.into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl StepCommits {
+    // Switches the clean repository containing `dir` to the new branch of the run
+    async fn start(dir: &Path, run_id: &str) -> Result<StepCommits> {
+        let repo = git_checked(dir, &["rev-parse", "--show-toplevel"]).await
+            .map_err(|e| ActionError::InvalidInput(format!("The steps can be committed only in a git repository: {}", e)))?;
+        let repo = PathBuf::from(repo);
+        if !git_checked(&repo, &["status", "--porcelain"]).await?.is_empty() {
+            return Err(ActionError::InvalidInput(
+                "The working tree has uncommitted changes, commit or stash them first so that every commit has only the changes of its step".to_string()
+            ).into());
+        }
+        git_checked(&repo, &["switch", "--create", &branch_name(run_id)]).await?;
+        Ok(StepCommits { repo, run_id: run_id.to_string() })
+    }
+
+    // Commits all the changes of the working tree, false when the step changed nothing
+    async fn commit(&self, action: &Action, plan_label: &str, step: usize) -> Result<bool> {
+        git_checked(&self.repo, &["add", "--all"]).await?;
+        if git(&self.repo, &["diff", "--cached", "--quiet"]).await?.status.success() {
+            return Ok(false);
+        }
+        let (subject, body) = commit_message(action, plan_label, step, &self.run_id);
+        git_checked(&self.repo, &["commit", "--quiet", "--message", &subject, "--message", &body]).await?;
+        Ok(true)
+    }
+}
+
+fn branch_name(run_id: &str) -> String {
+    format!("cliff/{}", run_id)
+}
+
+/// Commits the changes of every step that writes files or runs commands to the new branch `cliff/<run id>` of the
+/// repository in the working directory, which has to be clean
+pub async fn enable(run_id: &str, target: &dyn ExecutionTarget) -> Result<()> {
+    if !target.is_local() {
+        return Err(ActionError::InvalidInput(format!("The steps can be committed only on the local machine, not on {}", target)).into());
+    }
+    let step_commits = StepCommits::start(&std::env::current_dir()?, run_id).await?;
+    println!("Committing the changes of every step to the new branch {}.", branch_name(run_id));
+    let _ = ACTIVE.set(step_commits);
+    Ok(())
+}
+
+/// Commits the changes of the executed step when enabled, the failures to commit are warned about
+pub async fn commit_step(action: &Action, plan_label: &str, step: usize) {
+    let Some(step_commits) = ACTIVE.get() else {
+        return;
+    };
+    if classify(action) == ActionClass::Read {
+        return;
+    }
+    if let Err(e) = step_commits.commit(action, plan_label, step).await {
+        eprintln!("Warning: Failed to commit the step: {:#}", e);
+    }
+}
+
+// Subject and body of the commit of the step, the subject is the description of the action without its content
+fn commit_message(action: &Action, plan_label: &str, step: usize, run_id: &str) -> (String, String) {
+    let description = action.describe();
+    let first_line = description.lines().next().unwrap_or_default().trim_end_matches(':');
+    let first_line = first_line.split_once(". ").map_or(first_line, |(_, rest)| rest);
+    let subject = match first_line.char_indices().nth(MAX_SUBJECT_CHARS - 3) {
+        Some((end, _)) => format!("{}...", &first_line[..end]),
+        None => first_line.to_string(),
+    };
+    let body = format!("Step {} of the plan [{}] of the cliff run {}.\n\nAction: {}", step, plan_label, run_id, action.name());
+    (subject, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_message() {
+        let create = Action::CreateFile { action_idx: 2, path: "src/lib.rs".to_string(), content: "pub fn f() {}".to_string(), if_not_exists: false, overwrite: true };
+        assert_eq!(commit_message(&create, "main", 3, "1760000000000-1a2b"), (
+            "Create file 'src/lib.rs' with content".to_string(),
+            "Step 3 of the plan [main] of the cliff run 1760000000000-1a2b.\n\nAction: create_file".to_string(),
+        ));
+        let command = Action::RunCommand { action_idx: 0, command: format!("echo {}", "x".repeat(100)) };
+        let (subject, _) = commit_message(&command, "main", 1, "1");
+        assert_eq!(subject.chars().count(), MAX_SUBJECT_CHARS);
+        assert!(subject.starts_with("Run command: `echo xxx"));
+    }
+
+    #[tokio::test]
+    async fn test_step_commits() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = dir.path();
+        git_checked(repo, &["init", "--quiet"]).await?;
+        git_checked(repo, &["config", "user.name", "Test"]).await?;
+        git_checked(repo, &["config", "user.email", "test@example.com"]).await?;
+        std::fs::write(repo.join("notes.txt"), "draft")?;
+        assert!(StepCommits::start(repo, "1").await.is_err());
+        git_checked(repo, &["add", "--all"]).await?;
+        git_checked(repo, &["commit", "--quiet", "--message", "Initial"]).await?;
+
+        let step_commits = StepCommits::start(repo, "1760000000000-1a2b").await?;
+        assert_eq!(git_checked(repo, &["branch", "--show-current"]).await?, "cliff/1760000000000-1a2b");
+        let delete = Action::DeleteFile { action_idx: 0, path: "notes.txt".to_string() };
+        std::fs::remove_file(repo.join("notes.txt"))?;
+        assert!(step_commits.commit(&delete, "main", 1).await?);
+        assert!(!step_commits.commit(&delete, "main", 2).await?);
+        assert_eq!(git_checked(repo, &["log", "--format=%s"]).await?, "Delete file: 'notes.txt'\nInitial");
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::profiles;
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub prompt: String,
+    pub answer: String,
+    pub model: String,
+    pub asked_at: u64,
+}
+
+impl Exchange {
+    pub fn new(prompt: &str, answer: &str, model: &str) -> Self {
+        Exchange {
+            prompt: prompt.to_string(),
+            answer: answer.to_string(),
+            model: model.to_string(),
+            asked_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+        }
+    }
+}
+
+// Latest questions asked with `ask` and their answers, oldest first
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AskHistory {
+    #[serde(default)]
+    pub exchanges: Vec<Exchange>,
+}
+
+impl AskHistory {
+    const HISTORY_FILENAME: &'static str = "history.toml";
+    const MAX_EXCHANGES: usize = 50;
+
+    pub(crate) fn history_path() -> Result<PathBuf> {
+        let data_dir = profiles::data_dir()?;
+        Ok(data_dir.join(Self::HISTORY_FILENAME))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(AskHistory::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ask history: {:?}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse ask history: {:?}", path))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("Failed to create history directory: {:?}", parent))?;
+        }
+        let content = toml::to_string_pretty(self)
+            .with_context(|| "Failed to serialize ask history")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write ask history: {:?}", path))
+    }
+
+    // Appends the exchange dropping the oldest ones above the limit
+    pub fn push(&mut self, exchange: Exchange) {
+        self.exchanges.push(exchange);
+        let excess = self.exchanges.len().saturating_sub(Self::MAX_EXCHANGES);
+        self.exchanges.drain(..excess);
+    }
+
+    pub fn last(&self) -> Option<&Exchange> {
+        self.exchanges.last()
+    }
+}
+
+/// Appends the exchange to the ask history
+pub fn record(exchange: Exchange) -> Result<()> {
+    let path = AskHistory::history_path()?;
+    let mut history = AskHistory::load_from(&path)?;
+    history.push(exchange);
+    history.save_to(&path)
+}
+
+/// The last exchange of the ask history, None when nothing was asked yet
+pub fn last_exchange() -> Result<Option<Exchange>> {
+    Ok(AskHistory::load_from(&AskHistory::history_path()?)?.last().cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_history_keeps_latest_exchanges() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("history.toml");
+
+        let mut history = AskHistory::load_from(&path)?;
+        for idx in 0..AskHistory::MAX_EXCHANGES + 2 {
+            history.push(Exchange::new(&format!("question {}", idx), "answer", "test-model"));
+        }
+        history.save_to(&path)?;
+
+        let history = AskHistory::load_from(&path)?;
+        assert_eq!(history.exchanges.len(), AskHistory::MAX_EXCHANGES);
+        assert_eq!(history.exchanges[0].prompt, "question 2");
+        assert_eq!(history.last().map(|exchange| exchange.prompt.as_str()), Some("question 51"));
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use crate::events::{self, Event};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::{Mutex, OnceLock};
+
+/// Environment variable with the path of the input script, used when `--input-script` is not given
+pub const SCRIPT_ENV_VAR: &str = "CLIFF_INPUT_SCRIPT";
+
+// Answers read instead of the standard input, e.g. in the integration tests and the demo runs
+static SCRIPT: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+// Answers sent by the program driving cliff with `--protocol stdio`
+static ANSWERS: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+
+/// Answers the prompts with the lines of the file, in order, instead of reading them from the standard input. The
+/// lines starting with `#` are comments
+pub fn use_script(path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read the input script: {:?}", path))?;
+    let _ = SCRIPT.set(Mutex::new(parse_script(&content)));
+    Ok(())
+}
+
+/// Answers the prompts with the `answer` requests of `--protocol stdio`, each prompt emits an `input_requested` event
+pub fn use_answers(answers: Receiver<String>) {
+    let _ = ANSWERS.set(Mutex::new(answers));
+}
+
+// Waits for the next answer, None when the driving program closed the standard input
+fn requested_answer(answers: &Mutex<Receiver<String>>, secret: bool) -> Option<String> {
+    let _ = io::stdout().flush();
+    events::emit(Event::InputRequested { secret });
+    answers.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv().ok()
+}
+
+fn parse_script(content: &str) -> VecDeque<String> {
+    content.lines().filter(|line| !line.starts_with('#')).map(str::to_string).collect()
+}
+
+/// Whether the prompts can be answered: an input script or the driving program answers them or the standard input is a terminal
+pub fn is_interactive() -> bool {
+    SCRIPT.get().is_some() || ANSWERS.get().is_some() || io::stdin().is_terminal()
+}
+
+/// Answer to the prompt printed before, without the line break, None at the end of the input. The scripted answers are
+/// echoed as if typed
+pub fn read_line() -> Result<Option<String>> {
+    if let Some(script) = SCRIPT.get() {
+        let answer = script.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pop_front();
+        println!("{}", answer.as_deref().unwrap_or_default());
+        return Ok(answer);
+    }
+    if let Some(answers) = ANSWERS.get() {
+        return Ok(requested_answer(answers, false));
+    }
+    read_stdin_line()
+}
+
+/// Answer to the prompt printed before like `read_line`, but not echoed when typed in a terminal, the scripted answers
+/// are echoed masked
+pub fn read_secret() -> Result<Option<String>> {
+    if let Some(script) = SCRIPT.get() {
+        let answer = script.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pop_front();
+        println!("{}", if answer.is_some() { "********" } else { "" });
+        return Ok(answer);
+    }
+    if let Some(answers) = ANSWERS.get() {
+        return Ok(requested_answer(answers, true));
+    }
+    #[cfg(unix)]
+    if io::stdin().is_terminal() {
+        return read_without_echo();
+    }
+    read_stdin_line()
+}
+
+fn read_stdin_line() -> Result<Option<String>> {
+    let mut input = String::new();
+    if io::stdin().lock().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(input.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+#[cfg(unix)]
+fn read_without_echo() -> Result<Option<String>> {
+    let fd = libc::STDIN_FILENO;
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return read_stdin_line();
+    }
+    let original = termios;
+    termios.c_lflag &= !libc::ECHO;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    let answer = read_stdin_line();
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    // The line break typed was not echoed either
+    println!();
+    answer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script("# confirm the plan\ny\n\nhello.txt\n#comment\nall");
+        assert_eq!(script, VecDeque::from(["y", "", "hello.txt", "all"].map(String::from)));
+    }
+}
@@ -1,6 +1,163 @@
+use serde_json::{Map, Value};
+
 pub(crate) fn strip_json_fence(s: &str) -> &str {
     s.trim().strip_prefix("```json")
      .and_then(|s| s.strip_suffix("```"))
      .map(str::trim)
      .unwrap_or(s)
-}
\ No newline at end of file
+}
+// Violations of the JSON schema by the value, each with the JSON pointer of the violating part. Supports the common
+// keywords: type, enum, const, properties, required, additionalProperties, items, min/maxItems, min/maxLength,
+// minimum/maximum, pattern and anyOf
+pub(crate) fn schema_violations(value: &Value, schema: &Value) -> Vec<String> {
+    let mut violations = Vec::new();
+    collect_violations(value, schema, "", &mut violations);
+    violations
+}
+
+fn collect_violations(value: &Value, schema: &Value, pointer: &str, violations: &mut Vec<String>) {
+    let location = if pointer.is_empty() { "/" } else { pointer };
+    let Some(schema) = schema.as_object() else {
+        if schema == &Value::Bool(false) {
+            violations.push(format!("{}: no value is allowed", location));
+        }
+        return;
+    };
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            violations.push(format!("{}: expected {}, found {}", location, types.join(" or "), type_name(value)));
+            return;
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            violations.push(format!("{}: {} is not one of {}", location, value, Value::Array(allowed.clone())));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            violations.push(format!("{}: expected {}, found {}", location, expected, value));
+        }
+    }
+    if let Some(Value::Array(alternatives)) = schema.get("anyOf") {
+        if !alternatives.iter().any(|alternative| schema_violations(value, alternative).is_empty()) {
+            violations.push(format!("{}: matches none of the anyOf schemas", location));
+        }
+    }
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        violations.push(format!("{}: missing required property '{}'", location, name));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, property_value) in object {
+                let property_pointer = format!("{}/{}", pointer, name);
+                match (properties.and_then(|properties| properties.get(name)), schema.get("additionalProperties")) {
+                    (Some(property_schema), _) => collect_violations(property_value, property_schema, &property_pointer, violations),
+                    (None, Some(additional_schema)) => collect_violations(property_value, additional_schema, &property_pointer, violations),
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (item_idx, item) in items.iter().enumerate() {
+                    collect_violations(item, item_schema, &format!("{}/{}", pointer, item_idx), violations);
+                }
+            }
+            check_bounds(items.len() as f64, schema, "minItems", "maxItems", "items", location, violations);
+        }
+        Value::String(text) => {
+            check_bounds(text.chars().count() as f64, schema, "minLength", "maxLength", "characters", location, violations);
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) if !regex.is_match(text) => violations.push(format!("{}: '{}' does not match the pattern '{}'", location, text, pattern)),
+                    Ok(_) => {}
+                    Err(e) => violations.push(format!("{}: invalid pattern '{}' in the schema: {}", location, pattern, e)),
+                }
+            }
+        }
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                check_bounds(number, schema, "minimum", "maximum", "as the value", location, violations);
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+fn check_bounds(actual: f64, schema: &Map<String, Value>, min_keyword: &str, max_keyword: &str, unit: &str, location: &str, violations: &mut Vec<String>) {
+    if let Some(min) = schema.get(min_keyword).and_then(Value::as_f64) {
+        if actual < min {
+            violations.push(format!("{}: expected at least {} {}, found {}", location, min, unit, actual));
+        }
+    }
+    if let Some(max) = schema.get(max_keyword).and_then(Value::as_f64) {
+        if actual > max {
+            violations.push(format!("{}: expected at most {} {}, found {}", location, max, unit, actual));
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_violations() {
+        let schema = json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "price": { "type": "number", "minimum": 0 },
+                    "currency": { "enum": ["EUR", "USD"] }
+                },
+                "required": ["name", "price"],
+                "additionalProperties": false
+            }
+        });
+
+        assert!(schema_violations(&json!([{ "name": "Lamp", "price": 19.9, "currency": "EUR" }]), &schema).is_empty());
+        assert_eq!(
+            schema_violations(&json!([{ "name": "", "currency": "GBP", "color": "red" }, "Chair"]), &schema),
+            vec![
+                "/0: missing required property 'price'",
+                "/0/name: expected at least 1 characters, found 0",
+                "/0/currency: \"GBP\" is not one of [\"EUR\",\"USD\"]",
+                "/0/color: no value is allowed",
+                "/1: expected object, found string",
+            ]
+        );
+    }
+}
@@ -1,107 +1,133 @@
-use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
-use crate::config::{Config, Model};
-use crate::llm::{ask_llm, ask_llm_for_plan, start_llm_ask_session};
-use colored::*;
-use reqwest::Client;
+use anyhow::Result;
+use clap::{CommandFactory, FromArgMatches};
+use crate::cli::{Cli, Commands, HistoryAction, HistoryArgs};
+use crate::commands::{get_active_model, RunOptions};
+use crate::config::{Config, PreviewConfig};
+use crate::daemon::DaemonOptions;
+use crate::protocol::Protocol;
+use crate::llm::start_llm_ask_session;
+use std::path::PathBuf;
 
 mod actions;
+mod answer_format;
+mod backend;
+mod batch;
+mod candidates;
+mod changes;
+mod chunking;
+mod classification;
+mod cli;
+mod commands;
 mod config;
+mod consistency;
+mod conversation;
+mod cron;
+mod daemon;
+mod diagnostics;
+mod diff;
+mod doctor;
+mod embeddings;
+mod error;
+mod eval;
+mod events;
+mod examples;
+mod expectations;
 mod executor;
 mod llm;
+mod metrics;
 mod fs;
+mod gallery;
+mod git_commits;
+mod guardrails;
+mod history;
+mod input;
+mod onboarding;
+mod pager;
+mod placeholders;
+mod policy;
+mod preview;
+mod profiles;
+mod project;
+mod prompt;
+mod protocol;
+mod proxy;
+mod report;
+mod runs;
+mod scratch;
+mod server;
+mod snippets;
+#[cfg(feature = "syntax")]
+mod syntax;
+mod tasks;
+mod templates;
+mod tokens;
+mod unix_socket;
+mod update;
+mod validators;
+mod web_fixtures;
+mod trace;
+mod transform;
+mod trimming;
 mod json;
 
-#[derive(Parser, Debug)]
-#[command(author, version, about = "CLIFF: Command Line Interface Friendly & Facilitator", long_about = "CLIFF: Command Line Interface Friendly & Facilitator")]
-struct Cli {
-    /// Command to execute
-    #[command(subcommand)]
-    command: Commands,
-    /// Configured LLM model to use to execute the command
-    #[arg(short, long, global = true)]
-    model: Option<String>,
-    /// Files or URLs to provide as context
-    #[arg(short, long, value_delimiter = ',')]
-    context: Vec<String>
-}
-
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Start interactive session
-    #[command(name = "session")]
-    Session,
-    /// Ask a question to the configured LLM
-    Ask {
-        /// The prompt/question to ask the LLM
-        prompt: String
-    },
-    /// Ask the LLM to generate a plan and execute it
-    Act {
-        /// The instruction or goal for the LLM
-        instruction: String,
-        /// Automatically confirm and execute all actions in the plan
-        #[arg(long, default_value = "false")]
-        auto_confirm: bool
-    },
-    /// Manage LLM configurations
-    Config(ConfigArgs),
-}
-
-#[derive(Parser, Debug)]
-struct ConfigArgs {
-    /// Configuration sub-command
-    #[command(subcommand)]
-    action: ConfigAction,
-}
 
-#[derive(Subcommand, Debug)]
-enum ConfigAction {
-    /// Add a new model configuration
-    Add {
-        #[arg(short, long)]
-        name: String,
-        #[arg(long)]
-        model_identifier: Option<String>,
-        #[arg(long)]
-        api_url: String,
-        #[arg(long)]
-        api_key: Option<String>,
-        #[arg(long)]
-        api_key_header: Option<String>,
-        #[arg(long)]
-        request_format: String,
-        #[arg(long)]
-        response_json_path: String,
-    },
-    /// Set the default model
-    SetDefault {
-        /// Name of the model to set as default
-        name: String,
-    },
-    /// Set the current model for this session (temporary override)
-    SetCurrent {
-         /// Name of the model to set as current
-        name: String,
-    },
-    /// Clear the current model selection, falling back to default
-    ClearCurrent,
-    /// List all configured models
-    List,
-    /// Delete a configured model
-    Delete {
-        /// Name of the model to delete
-        name: String,
-    },
-    /// Show the current configuration path
-    Path,
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        report::print_error(&e);
+        std::process::exit(error::exit_code(&e));
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+async fn run() -> Result<()> {
+    let cli = Cli::from_arg_matches(&examples::with_examples(Cli::command()).get_matches())?;
+    let client = proxy::client(None)?;
+    if let Some(profile) = &cli.profile {
+        profiles::select(profile)?;
+    }
+    if let Some(script) = cli.input_script.clone().or_else(|| std::env::var_os(input::SCRIPT_ENV_VAR).map(PathBuf::from)) {
+        input::use_script(&script)?;
+        pager::disable();
+    }
+    if cli.no_pager {
+        pager::disable();
+    }
+    if let Some(fd) = cli.events_fd {
+        events::write_to_fd(fd)?;
+    }
+    if let Some(path) = &cli.events_file {
+        events::write_to_file(path)?;
+    }
+    if let Some(path) = &cli.record_web {
+        web_fixtures::record(path)?;
+    }
+    if let Some(path) = &cli.replay_web {
+        web_fixtures::replay(path)?;
+    }
+    if let Commands::Profile(args) = cli.command {
+        return commands::profile::handle_profile_action(args.action);
+    }
+    // The doctor runs before the config is loaded to diagnose a broken config
+    if let Commands::Doctor { skip_calls } = cli.command {
+        return doctor::run(&client, skip_calls).await;
+    }
     let mut config = Config::load()?;
-    let client = Client::new();
+    fs::allow_env_vars(config.expand_env_vars.clone());
+    fs::protect_paths(config.protected_paths.clone());
+    fs::exclude_paths(config.excluded_paths.clone());
+    metrics::enable(config.metrics.clone());
+    diagnostics::enable(config.diagnostics.clone());
+    trimming::use_rules(config.output_trimming);
+    preview::use_limits(if cli.show_full { PreviewConfig::full() } else { config.plan_preview });
+    if let Some(summarizer) = config.get_summarizer_model() {
+        llm::use_summarizer(summarizer.clone());
+    }
+    if let Some(guardrail) = config.get_guardrail_model() {
+        llm::use_guardrail(guardrail.clone());
+    }
+    if config.models.is_empty() && cli.command.requires_model() && input::is_interactive() {
+        onboarding::run_wizard(&mut config, &client).await?;
+    }
 
     if let Some(model_name) = &cli.model {
         if config.set_current_model(model_name).is_err() {
@@ -109,98 +135,56 @@ async fn main() -> Result<()> {
         }
     }
     let context = &cli.context;
-    let active_model = get_active_model(&config)?;
+    let notify_about_updates = config.check_for_updates && !matches!(cli.command, Commands::SelfUpdate { .. });
 
     match cli.command {
-        Commands::Ask { prompt } => {
-            let answer = ask_llm(active_model, &prompt, &context, &client).await.context("Error during LLM call")?;
-            println!("{}\n", answer.green());
+        Commands::Ask(args) => commands::ask::ask(args, &config, context, &client).await?,
+        Commands::Act(args) => commands::act::act(args, &config, context, &client).await?,
+        Commands::Run { task: None, .. } => commands::tasks::list_tasks()?,
+        Commands::Run { task: Some(name), params, git_commits, keep_scratch, auto_confirm, on_failure, target } => {
+            let options = RunOptions { auto_confirm, on_failure, target, git_commits, keep_scratch };
+            commands::tasks::run_task(&name, params, options, &config, context, &client).await?;
         }
-        Commands::Act { instruction, auto_confirm } => {
-            let plan = ask_llm_for_plan(active_model, &instruction, &context, &Vec::new(), &client).await.context("Error during LLM call")?;
-            plan.display();
-            executor::execute_plan(&plan, active_model, &client, &mut Vec::new(), auto_confirm).await?;
+        Commands::Plans(args) => commands::plans::handle_plans_action(args.action, &config, &client).await?,
+        Commands::Daemon { schedule, tasks, params, once: _, auto_confirm, on_failure, notify, target } => {
+            let options = DaemonOptions { schedule, tasks, params: params.into_iter().collect(), notify };
+            commands::daemon::run_daemon(options, auto_confirm, on_failure, target, &config, context, &client).await?;
         }
-        Commands::Session => {
-            start_llm_ask_session(active_model, context, &client).await?
+        Commands::Eval { suite, models, auto_confirm, min_pass_rate } => {
+            commands::eval::run_eval(&suite, &models, auto_confirm, min_pass_rate, &config, context, &client).await?;
         }
-        Commands::Config(args) => {
-            handle_config_action(args.action, &mut config)?;
+        Commands::Serve { auto_confirm, protocol: Protocol::Stdio, target, .. } => {
+            commands::serve::serve_stdio(auto_confirm, target, &config, context, &client).await?;
         }
-    }
-
-    Ok(())
-}
-
-fn get_active_model(config: &Config) -> Result<&Model> {
-    config.get_active_model().ok_or(anyhow!("Error: No active model configured. Use 'cognitor config add' and 'cognitor config set-default'."))
-}
-
-fn handle_config_action(action: ConfigAction, config: &mut Config) -> Result<()> {
-    match action {
-        ConfigAction::Add { name, api_url, api_key, api_key_header, model_identifier, request_format, response_json_path } => {
-            let new_model = Model {
-                name: name.clone(),
-                api_url,
-                api_key,
-                api_key_header,
-                model_identifier,
-                request_format,
-                response_json_path,
-            };
-            config.add_model(new_model);
-            config.save()?;
-            println!("Model '{}' added.", name);
-        }
-        ConfigAction::SetDefault { name } => {
-            config.set_default_model(&name)?;
-            config.save()?;
-            println!("Default model set to '{}'.", name);
-        }
-        ConfigAction::SetCurrent { name } => {
-            config.set_current_model(&name)?;
-            println!("Current model for this session set to '{}'.", name);
-        }
-        ConfigAction::ClearCurrent => {
-            config.clear_current_model();
-            println!("Current model selection cleared. Using default model.");
+        Commands::Serve { listen, auto_confirm, protocol: Protocol::Http, target } => {
+            commands::serve::serve_http(listen, auto_confirm, target).await?;
         }
-        ConfigAction::List => {
-            println!("Configured Models:");
-            if config.models.is_empty() {
-                println!("No models configured.");
-            } else {
-                for (name, model) in &config.models {
-                    let is_default = config.default_model.as_ref() == Some(name);
-                    let is_current = config.current_model.as_ref() == Some(name);
-                    let default_marker = if is_default { " (default)" } else { "" };
-                    let current_marker = if is_current && Some(name) != config.default_model.as_ref() { " (current)" } else { "" };
-
-                    println!(
-                        "  - {}{}{}: URL={}, Key={}, Identifier={}",
-                        name,
-                        default_marker,
-                        current_marker,
-                        model.api_url,
-                        model.api_key.as_deref().map_or("Not Set", |_|"Set"),
-                        model.model_identifier.as_deref().unwrap_or("Not Set")
-                    );
-                }
-            }
-            println!(
-                "\nActive model for next command (unless overridden): {}",
-                config.get_active_model().map_or("None", |m| &m.name)
-            );
+        Commands::Extract { instruction, schema } => commands::extract::extract(&instruction, &schema, &config, context, &client).await?,
+        Commands::Embed { input, store } => commands::embed::embed(&input, store, &config, &client).await?,
+        Commands::Similar { query, index, top } => commands::embed::similar(&query, &index, top, &config, &client).await?,
+        Commands::Tokens { inputs } => commands::tokens::count_tokens(&inputs)?,
+        Commands::Transform { input, prompt, out, chunk_size, auto_confirm } => {
+            commands::transform::transform_file(input, &prompt, out, chunk_size, auto_confirm, &config, &client).await?;
         }
-        ConfigAction::Delete { name } => {
-            config.delete_model(&name)?;
-            config.save()?;
-            println!("Model '{}' deleted.", name);
+        Commands::Scaffold(args) => commands::scaffold::scaffold(args, &config, &client).await?,
+        Commands::Session => {
+            let active_model = get_active_model(&config)?;
+            start_llm_ask_session(active_model, context, config.session, &client).await?
         }
-        ConfigAction::Path => {
-            let path = Config::config_path().context("Error determining config path")?;
-            println!("Config file path: {:?}", path)
+        Commands::SelfUpdate { check } => {
+            update::self_update(&client, check).await?;
         }
+        Commands::Trace { run_id, format, output } => commands::history::export_trace(run_id, format, output)?,
+        Commands::History(HistoryArgs { action: HistoryAction::Diff { run_id, output } }) => commands::history::diff_run(run_id, output)?,
+        Commands::Examples { command } => commands::examples::show_examples(command)?,
+        Commands::Doctor { .. } => unreachable!("The doctor runs before the config is loaded"),
+        Commands::Profile(_) => unreachable!("The profiles are managed before the config is loaded"),
+        Commands::Config(args) => commands::config::handle_config_action(args.action, &mut config, &client).await?,
+        Commands::Snippets(args) => commands::snippets::handle_snippets_action(args.action, &mut config, context, &client).await?,
     }
+    if notify_about_updates {
+        update::notify_if_outdated(&client).await;
+    }
+
     Ok(())
 }
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use crate::config::MetricsConfig;
+use crate::runs::{RunMetadata, RunStatus};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// Prefix of the names of the metrics
+const PREFIX: &str = "cliff";
+
+// Where the metrics are exported, the metrics are not collected unless it is set
+static EXPORT: OnceLock<MetricsConfig> = OnceLock::new();
+// Measurements not exported yet, they are exported when a run finishes
+static PENDING: Mutex<Vec<Measurement>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq)]
+enum Measurement {
+    Count { name: &'static str, labels: Vec<(&'static str, String)>, value: u64 },
+    Duration { name: &'static str, labels: Vec<(&'static str, String)>, duration: Duration },
+}
+
+/// Collects the metrics of the runs and exports them as configured, nothing is collected when no export is configured
+pub fn enable(config: MetricsConfig) {
+    if config.is_enabled() {
+        let _ = EXPORT.set(config);
+    }
+}
+
+fn record(measurement: Measurement) {
+    if EXPORT.get().is_some() {
+        PENDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(measurement);
+    }
+}
+
+/// Records an executed step of a plan, `action` is its snake_case name
+pub fn record_step(action: &str, duration: Duration, succeeded: bool) {
+    let status = if succeeded { "succeeded" } else { "failed" };
+    record(Measurement::Count { name: "steps", labels: vec![("action", action.to_string()), ("status", status.to_string())], value: 1 });
+    record(Measurement::Duration { name: "step_duration", labels: vec![("action", action.to_string())], duration });
+}
+
+/// Records the finished run with its tokens and exports all the pending measurements, the export failures are warned about
+pub fn record_run(run: &RunMetadata) {
+    let Some(config) = EXPORT.get() else {
+        return;
+    };
+    let status = match run.status {
+        RunStatus::Running => "running",
+        RunStatus::Succeeded => "succeeded",
+        RunStatus::Failed => "failed",
+    };
+    record(Measurement::Count { name: "runs", labels: vec![("command", run.command.clone()), ("status", status.to_string())], value: 1 });
+    if let Some(finished_at) = run.finished_at {
+        let duration = Duration::from_secs(finished_at.saturating_sub(run.started_at));
+        record(Measurement::Duration { name: "run_duration", labels: vec![("command", run.command.clone())], duration });
+    }
+    if let Some(tokens) = &run.tokens {
+        for (kind, value) in [("prompt", tokens.prompt_tokens), ("cached_prompt", tokens.cached_prompt_tokens), ("completion", tokens.completion_tokens)] {
+            record(Measurement::Count { name: "tokens", labels: vec![("model", run.model.clone()), ("kind", kind.to_string())], value });
+        }
+    }
+    let measurements = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    if let Some(textfile) = &config.textfile {
+        if let Err(e) = update_textfile(textfile, &measurements) {
+            eprintln!("Warning: Failed to export the metrics: {:#}", e);
+        }
+    }
+    if let Some(statsd) = &config.statsd {
+        if let Err(e) = send_statsd(statsd, &measurements) {
+            eprintln!("Warning: Failed to send the metrics to {}: {:#}", statsd, e);
+        }
+    }
+}
+
+fn series(name: &str, suffix: &str, labels: &[(&'static str, String)]) -> String {
+    let labels: Vec<String> = labels.iter().map(|(label, value)| format!("{}=\"{}\"", label, value.replace('\\', "\\\\").replace('"', "\\\""))).collect();
+    format!("{}_{}{}{{{}}}", PREFIX, name, suffix, labels.join(","))
+}
+
+// Adds the measurements to the counters of the Prometheus textfile, e.g. for the textfile collector of the node
+// exporter, the counters accumulate over the invocations of `cliff`
+fn update_textfile(path: &Path, measurements: &[Measurement]) -> Result<()> {
+    let mut values: BTreeMap<String, f64> = match fs::read_to_string(path) {
+        Ok(content) => content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.rsplit_once(' '))
+            .filter_map(|(series, value)| Some((series.to_string(), value.parse().ok()?)))
+            .collect(),
+        Err(_) => BTreeMap::new(),
+    };
+    for measurement in measurements {
+        match measurement {
+            Measurement::Count { name, labels, value } => *values.entry(series(name, "_total", labels)).or_default() += *value as f64,
+            Measurement::Duration { name, labels, duration } => {
+                *values.entry(series(name, "_seconds_sum", labels)).or_default() += duration.as_secs_f64();
+                *values.entry(series(name, "_seconds_count", labels)).or_default() += 1.0;
+            }
+        }
+    }
+    let mut content = String::new();
+    let mut family = String::new();
+    for (series, value) in &values {
+        let name = series.split('{').next().unwrap_or_default();
+        let (current_family, kind) = match name.strip_suffix("_sum").or_else(|| name.strip_suffix("_count")) {
+            Some(summary) => (summary, "summary"),
+            None => (name, "counter"),
+        };
+        if current_family != family {
+            family = current_family.to_string();
+            content.push_str(&format!("# TYPE {} {}\n", family, kind));
+        }
+        content.push_str(&format!("{} {}\n", series, value));
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create the metrics directory: {:?}", parent))?;
+    }
+    // Renamed into place so that the collector never reads a partially written file
+    let temporary = path.with_extension("prom.tmp");
+    fs::write(&temporary, content).with_context(|| format!("Failed to write the metrics: {:?}", temporary))?;
+    fs::rename(&temporary, path).with_context(|| format!("Failed to write the metrics: {:?}", path))
+}
+
+// The measurements as statsd lines, the label values are a part of the name, e.g. `cliff.steps.run_command.failed:1|c`
+fn statsd_lines(measurements: &[Measurement]) -> Vec<String> {
+    let name = |name: &str, labels: &[(&'static str, String)]| {
+        let values: Vec<String> = labels.iter().map(|(_, value)| value.replace(['.', ':', '|', ' '], "_")).collect();
+        [vec![PREFIX.to_string(), name.to_string()], values].concat().join(".")
+    };
+    measurements
+        .iter()
+        .map(|measurement| match measurement {
+            Measurement::Count { name: metric, labels, value } => format!("{}:{}|c", name(metric, labels), value),
+            Measurement::Duration { name: metric, labels, duration } => format!("{}:{}|ms", name(metric, labels), duration.as_millis()),
+        })
+        .collect()
+}
+
+fn send_statsd(address: &str, measurements: &[Measurement]) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to open a UDP socket")?;
+    for line in statsd_lines(measurements) {
+        socket.send_to(line.as_bytes(), address)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_export() -> Result<()> {
+        let measurements = [
+            Measurement::Count { name: "steps", labels: vec![("action", "run_command".to_string()), ("status", "failed".to_string())], value: 1 },
+            Measurement::Duration { name: "step_duration", labels: vec![("action", "run_command".to_string())], duration: Duration::from_millis(1500) },
+        ];
+        assert_eq!(statsd_lines(&measurements), ["cliff.steps.run_command.failed:1|c", "cliff.step_duration.run_command:1500|ms"]);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cliff.prom");
+        update_textfile(&path, &measurements)?;
+        update_textfile(&path, &measurements)?;
+        assert_eq!(fs::read_to_string(&path)?, [
+            "# TYPE cliff_step_duration_seconds summary",
+            "cliff_step_duration_seconds_count{action=\"run_command\"} 2",
+            "cliff_step_duration_seconds_sum{action=\"run_command\"} 3",
+            "# TYPE cliff_steps_total counter",
+            "cliff_steps_total{action=\"run_command\",status=\"failed\"} 2",
+            "",
+        ].join("\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use crate::config::{Config, Model, ModelCapabilities, ModelKind, StreamFormat};
+use crate::error::ActionError;
+use crate::llm::ask_llm;
+use crate::input;
+use colored::*;
+use reqwest::Client;
+use serde_json::Value;
+use std::io::{self, Write};
+use std::time::Duration;
+
+pub const OLLAMA_URL: &str = "http://localhost:11434";
+const OLLAMA_DETECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Settings of a well-known LLM provider, the user only chooses the model and enters the API key
+#[derive(Debug, Clone, PartialEq)]
+struct ProviderPreset {
+    name: &'static str,
+    kind: ModelKind,
+    api_url: &'static str,
+    // None authenticates the way of the kind of the model
+    api_key_header: Option<&'static str>,
+    // Environment variable the API key is usually kept in, None for the providers without authentication
+    api_key_env: Option<&'static str>,
+    default_model: &'static str,
+    request_format: &'static str,
+    response_json_path: &'static str,
+    // How the answers are streamed, None for the providers whose streaming needs another endpoint or a native kind
+    stream_format: Option<StreamFormat>,
+    stream_json_path: Option<&'static str>,
+    // Capabilities of the default model, the JSON mode and the tools are features of the API shared by all the models
+    capabilities: ModelCapabilities,
+}
+
+const OLLAMA: ProviderPreset = ProviderPreset {
+    name: "ollama",
+    kind: ModelKind::Template,
+    api_url: "http://localhost:11434/api/chat",
+    api_key_header: None,
+    api_key_env: None,
+    default_model: "llama3.2",
+    request_format: r#"{"model": "{{model}}", "messages": {{messages}}, "stream": false}"#,
+    response_json_path: "$.message.content",
+    stream_format: Some(StreamFormat::Ndjson),
+    stream_json_path: Some("$.message.content"),
+    capabilities: ModelCapabilities {
+        supports_json_mode: true,
+        supports_tools: true,
+        supports_vision: false,
+        context_window: None,
+        max_output_tokens: None,
+    },
+};
+
+const PRESETS: [ProviderPreset; 5] = [
+    OLLAMA,
+    ProviderPreset {
+        name: "openai",
+        kind: ModelKind::Template,
+        api_url: "https://api.openai.com/v1/chat/completions",
+        api_key_header: Some("Authorization: Bearer {{api_key}}"),
+        api_key_env: Some("OPENAI_API_KEY"),
+        default_model: "gpt-4o-mini",
+        request_format: r#"{"model": "{{model}}", "messages": {{messages}}}"#,
+        response_json_path: "$.choices[0].message.content",
+        stream_format: Some(StreamFormat::Sse),
+        stream_json_path: Some("$.choices[0].delta.content"),
+        capabilities: ModelCapabilities {
+            supports_json_mode: true,
+            supports_tools: true,
+            supports_vision: true,
+            context_window: Some(128_000),
+            max_output_tokens: Some(16_384),
+        },
+    },
+    ProviderPreset {
+        name: "openrouter",
+        kind: ModelKind::Template,
+        api_url: "https://openrouter.ai/api/v1/chat/completions",
+        api_key_header: Some("Authorization: Bearer {{api_key}}"),
+        api_key_env: Some("OPENROUTER_API_KEY"),
+        default_model: "mistralai/mistral-small-24b-instruct-2501:free",
+        request_format: r#"{"model": "{{model}}", "messages": {{messages}}}"#,
+        response_json_path: "$.choices[0].message.content",
+        stream_format: Some(StreamFormat::Sse),
+        stream_json_path: Some("$.choices[0].delta.content"),
+        // The JSON mode and the tools depend on the routed model
+        capabilities: ModelCapabilities {
+            supports_json_mode: false,
+            supports_tools: false,
+            supports_vision: false,
+            context_window: Some(32_768),
+            max_output_tokens: None,
+        },
+    },
+    ProviderPreset {
+        name: "gemini",
+        kind: ModelKind::Gemini,
+        api_url: "https://generativelanguage.googleapis.com/v1beta/models/{{model}}",
+        // The API key is sent as the `key` query parameter
+        api_key_header: None,
+        api_key_env: Some("GEMINI_API_KEY"),
+        default_model: "gemini-2.0-flash",
+        request_format: "",
+        response_json_path: "",
+        stream_format: None,
+        stream_json_path: None,
+        capabilities: ModelCapabilities {
+            supports_json_mode: true,
+            supports_tools: true,
+            supports_vision: true,
+            context_window: Some(1_048_576),
+            max_output_tokens: Some(8_192),
+        },
+    },
+    ProviderPreset {
+        name: "anthropic",
+        kind: ModelKind::Anthropic,
+        api_url: "https://api.anthropic.com/v1/messages",
+        api_key_header: Some("x-api-key: {{api_key}}"),
+        api_key_env: Some("ANTHROPIC_API_KEY"),
+        default_model: "claude-sonnet-4-5",
+        request_format: "",
+        response_json_path: "",
+        stream_format: None,
+        stream_json_path: None,
+        // JSON answers are a forced tool use, the API has no JSON mode
+        capabilities: ModelCapabilities {
+            supports_json_mode: false,
+            supports_tools: true,
+            supports_vision: true,
+            context_window: Some(200_000),
+            max_output_tokens: Some(64_000),
+        },
+    },
+];
+
+impl ProviderPreset {
+    fn model(&self, model_identifier: &str, api_key: Option<String>) -> Model {
+        Model {
+            name: self.name.to_string(),
+            // Gemini has the model in the URL
+            api_url: self.api_url.replace("{{model}}", model_identifier),
+            api_key,
+            api_key_header: self.api_key_header.map(str::to_string),
+            model_identifier: Some(model_identifier.to_string()),
+            kind: self.kind,
+            request_format: self.request_format.to_string(),
+            response_json_path: self.response_json_path.to_string(),
+            stream_format: self.stream_format,
+            stream_json_path: self.stream_json_path.map(str::to_string),
+            capabilities: if model_identifier == self.default_model {
+                self.capabilities.clone()
+            } else {
+                ModelCapabilities {
+                    supports_json_mode: self.capabilities.supports_json_mode,
+                    supports_tools: self.capabilities.supports_tools,
+                    ..ModelCapabilities::default()
+                }
+            },
+            ..Model::default()
+        }
+    }
+}
+
+/// Names of the providers with presets
+pub fn provider_names() -> Vec<&'static str> {
+    PRESETS.iter().map(|preset| preset.name).collect()
+}
+
+/// Model with the settings of the provider, its default model unless the model identifier is given. Without the API
+/// key the one of the environment variable the provider's keys are usually kept in is used
+pub fn provider_model(provider: &str, model_identifier: Option<&str>, api_key: Option<String>) -> Result<Model> {
+    let preset = PRESETS.iter().find(|preset| preset.name == provider)
+        .ok_or_else(|| ActionError::InvalidInput(format!("Unknown provider '{}', the providers are: {}", provider, provider_names().join(", "))))?;
+    let api_key = api_key.or_else(|| preset.api_key_env.and_then(|name| std::env::var(name).ok()));
+    Ok(preset.model(model_identifier.unwrap_or(preset.default_model), api_key))
+}
+
+// Names of the models in the response of the Ollama `/api/tags` endpoint
+fn ollama_model_names(tags: &Value) -> Vec<String> {
+    tags["models"]
+        .as_array()
+        .map(|models| models.iter().filter_map(|model| model["name"].as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+// Models of the locally running Ollama, None when Ollama is not running
+async fn detect_ollama(client: &Client) -> Option<Vec<String>> {
+    let response = client.get(format!("{}/api/tags", OLLAMA_URL))
+        .timeout(OLLAMA_DETECTION_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+    let tags: Value = response.json().await.ok()?;
+    Some(ollama_model_names(&tags))
+}
+
+/// Adds the models pulled into the Ollama at the URL, e.g. `http://localhost:11434`, which are not configured yet, named
+/// after the Ollama models, e.g. `qwen2.5:7b`. Returns the names of the added models
+pub async fn discover_ollama(config: &mut Config, url: &str, client: &Client) -> Result<Vec<String>> {
+    let url = url.trim_end_matches('/');
+    let response = client.get(format!("{}/api/tags", url))
+        .send()
+        .await
+        .map_err(|e| ActionError::from_reqwest(format!("Failed to list the models of Ollama at {}", url), e))?;
+    let tags: Value = response.json().await
+        .map_err(|e| ActionError::LlmMalformedResponse(format!("Failed to parse the models of Ollama at {}: {}", url, e)))?;
+    let mut added = Vec::new();
+    for model_identifier in ollama_model_names(&tags) {
+        if config.models.contains_key(&model_identifier) {
+            continue;
+        }
+        let mut model = OLLAMA.model(&model_identifier, None);
+        model.name = model_identifier.clone();
+        model.api_url = format!("{}/api/chat", url);
+        config.add_model(model);
+        added.push(model_identifier);
+    }
+    Ok(added)
+}
+
+fn read_answer(question: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+        _ => print!("{}: ", question),
+    }
+    io::stdout().flush()?;
+    let input = input::read_line()?.unwrap_or_default();
+    let answer = input.trim();
+    Ok(if answer.is_empty() { default.unwrap_or_default().to_string() } else { answer.to_string() })
+}
+
+/// Walks the user through configuring the first model: chooses a provider, the model and the API key, tests the
+/// model with a short call and saves it as the default model
+pub async fn run_wizard(config: &mut Config, client: &Client) -> Result<()> {
+    println!("{}", "No LLM models are configured yet, let's set one up.".yellow());
+    let ollama_models = detect_ollama(client).await;
+    match &ollama_models {
+        Some(models) if !models.is_empty() => println!("Found a local Ollama with the models: {}", models.join(", ")),
+        Some(_) => println!("Found a local Ollama without any pulled models, pull one with `ollama pull {}`.", OLLAMA.default_model),
+        None => {}
+    }
+    for (preset_idx, preset) in PRESETS.iter().enumerate() {
+        println!("  {}. {}", preset_idx + 1, preset.name);
+    }
+    let default_choice = if ollama_models.is_some() { "1" } else { "2" };
+    let preset = loop {
+        let choice = read_answer("Choose the provider", Some(default_choice))?;
+        match choice.parse::<usize>().ok().and_then(|choice| PRESETS.get(choice.wrapping_sub(1))) {
+            Some(preset) => break preset,
+            None => println!("Unknown choice '{}'.", choice),
+        }
+    };
+
+    let default_model = match &ollama_models {
+        Some(models) if preset == &OLLAMA => models.first().map(String::as_str).unwrap_or(preset.default_model),
+        _ => preset.default_model,
+    };
+    let model_identifier = read_answer("Model", Some(default_model))?;
+    let api_key = match preset.api_key_env.map(|name| (name, std::env::var(name).ok())) {
+        None => None,
+        Some((env_name, Some(env_key))) if read_answer(&format!("Use the API key from ${}? (Y/n)", env_name), None)?.to_lowercase() != "n" => Some(env_key),
+        Some(_) => Some(read_answer("API key", None)?).filter(|key| !key.is_empty()),
+    };
+    let model = preset.model(&model_identifier, api_key);
+
+    println!("Testing the model...");
+    match ask_llm(&model, "Reply with the single word OK", &[], client).await {
+        Ok(answer) => println!("{} {}", "The model replied:".green(), answer.trim()),
+        Err(e) => {
+            eprintln!("{} {:#}", "The test call failed:".red(), e);
+            if read_answer("Save the model anyway? (y/N)", None)?.to_lowercase() != "y" {
+                anyhow::bail!("No model configured, run the command again or add a model with 'cliff config add'");
+            }
+        }
+    }
+    let name = model.name.clone();
+    config.add_model(model);
+    config.set_default_model(&name)?;
+    config.save().context("Failed to save the configuration")?;
+    println!("Model '{}' saved as the default model, more models can be added with 'cliff config add'.\n", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_presets() {
+        let tags = serde_json::json!({ "models": [{ "name": "qwen2.5:7b" }, { "name": "llama3.2:latest" }] });
+        assert_eq!(ollama_model_names(&tags), vec!["qwen2.5:7b", "llama3.2:latest"]);
+
+        let gemini = PRESETS.iter().find(|preset| preset.name == "gemini").unwrap().model("gemini-2.5-pro", Some("key".to_string()));
+        assert_eq!((gemini.kind, gemini.api_url.as_str()), (ModelKind::Gemini, "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-pro"));
+        assert_eq!(gemini.api_key.as_deref(), Some("key"));
+        // The limits of the default model are not assumed for the other models of the provider
+        assert!(gemini.capabilities.supports_json_mode);
+        assert_eq!(gemini.capabilities.context_window, None);
+
+        let anthropic = provider_model("anthropic", None, Some("key".to_string())).unwrap();
+        assert_eq!((anthropic.kind, anthropic.model_identifier.as_deref()), (ModelKind::Anthropic, Some("claude-sonnet-4-5")));
+        assert!(provider_model("unknown", None, None).is_err());
+
+        let ollama = OLLAMA.model("qwen2.5:7b", None);
+        assert_eq!(ollama.api_key_header, None);
+        assert_eq!(ollama.model_identifier.as_deref(), Some("qwen2.5:7b"));
+        assert_eq!((ollama.stream_format, ollama.stream_json_path.as_deref()), (Some(StreamFormat::Ndjson), Some("$.message.content")));
+    }
+
+    #[tokio::test]
+    async fn test_discover_ollama() -> Result<()> {
+        let server = httpmock::MockServer::start();
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/api/tags");
+            then.status(200).json_body(serde_json::json!({ "models": [{ "name": "qwen2.5:7b" }, { "name": "llama3.2:latest" }] }));
+        });
+        let mut config = Config::default();
+        let existing = Model { name: "llama3.2:latest".to_string(), ..OLLAMA.model("llama3.2:latest", None) };
+        config.add_model(existing);
+
+        let added = discover_ollama(&mut config, &format!("{}/", server.base_url()), &Client::new()).await?;
+
+        assert_eq!(added, vec!["qwen2.5:7b"]);
+        let qwen = &config.models["qwen2.5:7b"];
+        assert_eq!(qwen.api_url, server.url("/api/chat"));
+        assert_eq!(qwen.model_identifier.as_deref(), Some("qwen2.5:7b"));
+        Ok(())
+    }
+}
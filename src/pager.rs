@@ -0,0 +1,55 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Height of the terminal when neither `$LINES` nor `stty` tell it
+const DEFAULT_TERMINAL_HEIGHT: usize = 40;
+// `-R` keeps the colors, `-F` quits at once when the text fits the screen after all, `/` searches in `less`
+const DEFAULT_PAGER: &str = "less -RF";
+
+// Disabled with `--no-pager` and for the input scripts, whose runs nobody scrolls through
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn terminal_height() -> usize {
+    if let Some(lines) = std::env::var("LINES").ok().and_then(|lines| lines.parse().ok()) {
+        return lines;
+    }
+    // `stty` reads the size of the terminal of its standard input, e.g. "50 120"
+    Command::new("stty")
+        .arg("size")
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).split_whitespace().next()?.parse().ok())
+        .unwrap_or(DEFAULT_TERMINAL_HEIGHT)
+}
+
+/// Prints the text, through `$PAGER` (`less` by default) when it is longer than the terminal so that it can be
+/// scrolled and searched. The text is printed as it is when the pager cannot be started
+pub fn page(text: &str) {
+    let fits = text.lines().count() < terminal_height().saturating_sub(1);
+    if fits || DISABLED.load(Ordering::Relaxed) || !io::stdout().is_terminal() || !io::stdin().is_terminal() {
+        println!("{}", text);
+        return;
+    }
+    let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().expect("The pager is not empty");
+    let child = Command::new(program).args(words).stdin(Stdio::piped()).spawn();
+    let Ok(mut child) = child else {
+        println!("{}", text);
+        return;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager before the end closes the pipe, which is not an error
+        let _ = writeln!(stdin, "{}", text);
+    }
+    if let Err(e) = child.wait() {
+        eprintln!("Warning: Failed to wait for the pager '{}': {}", pager, e);
+    }
+}
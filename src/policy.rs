@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Decisions of the user remembered for the project in the working directory, kept apart from `.cliff.toml` which is
+// written by hand
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ProjectPolicy {
+    // Commands executed without asking, matched exactly
+    #[serde(default)]
+    pub allowed_commands: Vec<String>,
+}
+
+impl ProjectPolicy {
+    pub const POLICY_FILENAME: &'static str = ".cliff-policy.toml";
+
+    pub fn path(project_dir: &Path) -> PathBuf {
+        project_dir.join(Self::POLICY_FILENAME)
+    }
+
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let path = Self::path(project_dir);
+        if !path.exists() {
+            return Ok(ProjectPolicy::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read project policy file: {:?}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse project policy file: {:?}", path))
+    }
+
+    pub fn save(&self, project_dir: &Path) -> Result<()> {
+        let path = Self::path(project_dir);
+        let content = toml::to_string_pretty(self).context("Failed to serialize project policy")?;
+        fs::write(&path, content).with_context(|| format!("Failed to write project policy file: {:?}", path))
+    }
+
+    pub fn allows_command(&self, command: &str) -> bool {
+        self.allowed_commands.iter().any(|allowed| allowed == command.trim())
+    }
+
+    /// Remembers that the command is always allowed in the project
+    pub fn allow_command(project_dir: &Path, command: &str) -> Result<()> {
+        let mut policy = Self::load(project_dir)?;
+        if !policy.allows_command(command) {
+            policy.allowed_commands.push(command.trim().to_string());
+            policy.save(project_dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_command() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(!ProjectPolicy::load(dir.path())?.allows_command("cargo build"));
+
+        ProjectPolicy::allow_command(dir.path(), "cargo build ")?;
+        ProjectPolicy::allow_command(dir.path(), "cargo build")?;
+
+        let policy = ProjectPolicy::load(dir.path())?;
+        assert_eq!(policy.allowed_commands, vec!["cargo build".to_string()]);
+        assert!(policy.allows_command("cargo build"));
+        assert!(!policy.allows_command("cargo build --release"));
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use crate::config::PreviewConfig;
+use std::sync::OnceLock;
+
+static LIMITS: OnceLock<PreviewConfig> = OnceLock::new();
+
+/// Limits of the contents shown in the plans, the default limits are used when not set
+pub fn use_limits(limits: PreviewConfig) {
+    let _ = LIMITS.set(limits);
+}
+
+pub fn limits() -> PreviewConfig {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+/// The first `max_lines` lines of the content followed by the number of the omitted ones, the whole content when
+/// `max_lines` is 0
+pub fn shorten(content: &str, max_lines: usize) -> String {
+    let line_count = content.lines().count();
+    if max_lines == 0 || line_count <= max_lines {
+        return content.to_string();
+    }
+    let shown: Vec<&str> = content.lines().take(max_lines).collect();
+    format!("{}\n({} more lines)", shown.join("\n"), line_count - max_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("a\nb\nc\nd", 2), "a\nb\n(2 more lines)");
+        assert_eq!(shorten("a\nb", 2), "a\nb");
+        assert_eq!(shorten("a\nb\nc", 0), "a\nb\nc");
+    }
+}
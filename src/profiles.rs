@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use crate::error::{ActionError, IoResultExt};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const DIR_NAME: &str = "cliff";
+const PROFILES_DIR_NAME: &str = "profiles";
+
+// Profile of the process selected with `--profile`, the default profile when not selected
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(ActionError::InvalidInput(format!(
+            "Invalid profile name '{}', expected letters, digits, '-' and '_'", name
+        )).into());
+    }
+    Ok(())
+}
+
+// Config and data directories of the profile, the default profile uses the directories of cliff themselves
+fn dirs_of(name: &str) -> Result<(PathBuf, PathBuf)> {
+    let config_dir = dirs::config_dir().with_context(|| "Failed to find config directory")?.join(DIR_NAME);
+    let data_dir = dirs::data_dir().with_context(|| "Failed to find data directory")?.join(DIR_NAME);
+    if name == DEFAULT_PROFILE {
+        return Ok((config_dir, data_dir));
+    }
+    Ok((config_dir.join(PROFILES_DIR_NAME).join(name), data_dir.join(PROFILES_DIR_NAME).join(name)))
+}
+
+fn exists(name: &str) -> Result<bool> {
+    Ok(name == DEFAULT_PROFILE || dirs_of(name)?.0.is_dir())
+}
+
+/// Selects the profile whose config and state are used by the rest of the process
+pub fn select(name: &str) -> Result<()> {
+    validate_name(name)?;
+    if !exists(name)? {
+        return Err(ActionError::NotFound(format!(
+            "Profile '{}' does not exist, create it with `cliff profile create {}`", name, name
+        )).into());
+    }
+    PROFILE.set(name.to_string())
+        .map_err(|_| ActionError::InvalidInput("The profile is already selected".to_string()).into())
+}
+
+pub fn current() -> &'static str {
+    PROFILE.get().map(String::as_str).unwrap_or(DEFAULT_PROFILE)
+}
+
+/// Directory of the config file and the templates of the selected profile
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(dirs_of(current())?.0)
+}
+
+/// Directory of the history, runs, indexes and other state of the selected profile
+pub fn data_dir() -> Result<PathBuf> {
+    Ok(dirs_of(current())?.1)
+}
+
+/// Names of the profiles, the default profile first
+pub fn list() -> Result<Vec<String>> {
+    let profiles_dir = dirs_of(DEFAULT_PROFILE)?.0.join(PROFILES_DIR_NAME);
+    let mut names = Vec::new();
+    if profiles_dir.is_dir() {
+        for entry in fs::read_dir(&profiles_dir).with_action_context(|| format!("Failed to list the profiles in {}", profiles_dir.display()))? {
+            let entry = entry.with_action_context(|| format!("Failed to list the profiles in {}", profiles_dir.display()))?;
+            if entry.path().is_dir() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(names)
+}
+
+pub fn create(name: &str) -> Result<()> {
+    validate_name(name)?;
+    if exists(name)? {
+        return Err(ActionError::InvalidInput(format!("Profile '{}' already exists", name)).into());
+    }
+    let (config_dir, data_dir) = dirs_of(name)?;
+    for dir in [&config_dir, &data_dir] {
+        fs::create_dir_all(dir).with_action_context(|| format!("Failed to create the profile directory {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Deletes the config and the state of the profile, returns the deleted directories
+pub fn delete(name: &str) -> Result<Vec<PathBuf>> {
+    validate_name(name)?;
+    if name == DEFAULT_PROFILE {
+        return Err(ActionError::PolicyDenied("The default profile cannot be deleted".to_string()).into());
+    }
+    if name == current() {
+        return Err(ActionError::PolicyDenied(format!("Profile '{}' is in use and cannot be deleted", name)).into());
+    }
+    if !exists(name)? {
+        return Err(ActionError::NotFound(format!("Profile '{}' does not exist", name)).into());
+    }
+    let (config_dir, data_dir) = dirs_of(name)?;
+    let mut deleted = Vec::new();
+    for dir in [config_dir, data_dir] {
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).with_action_context(|| format!("Failed to delete the profile directory {}", dir.display()))?;
+            deleted.push(dir);
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_dirs() -> Result<()> {
+        let (default_config, default_data) = dirs_of(DEFAULT_PROFILE)?;
+        let (config, data) = dirs_of("work")?;
+        assert_eq!(config, default_config.join("profiles").join("work"));
+        assert_eq!(data, default_data.join("profiles").join("work"));
+        assert!(validate_name("work_2").is_ok());
+        assert!(validate_name("../work").is_err());
+        assert!(validate_name("").is_err());
+        assert!(delete(DEFAULT_PROFILE).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// Project recognized by its manifest, described in the planning prompt so that the LLM does not spend steps on finding it out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub language: String,
+    pub manifest: &'static str,
+    pub name: Option<String>,
+    pub tool: String,
+    pub build: Option<String>,
+    pub test: Option<String>,
+}
+
+impl Project {
+    // e.g. "Rust project `cliff` (Cargo.toml), tool: cargo, build: `cargo build`, test: `cargo test`"
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} project", self.language);
+        if let Some(name) = &self.name {
+            description.push_str(&format!(" `{}`", name));
+        }
+        description.push_str(&format!(" ({}), tool: {}", self.manifest, self.tool));
+        if let Some(build) = &self.build {
+            description.push_str(&format!(", build: `{}`", build));
+        }
+        if let Some(test) = &self.test {
+            description.push_str(&format!(", test: `{}`", test));
+        }
+        description
+    }
+}
+
+/// Projects with their manifests in `dir`, several in a polyglot repository, e.g. a Rust backend with a web frontend
+pub fn detect(dir: &Path) -> Vec<Project> {
+    [rust_project(dir), node_project(dir), python_project(dir), go_project(dir)].into_iter().flatten().collect()
+}
+
+// Summary of the projects in the working directory for the planning prompt, empty when none is recognized
+pub fn summary() -> String {
+    let projects = std::env::current_dir().map(|dir| detect(&dir)).unwrap_or_default();
+    projects.iter().map(|project| format!("Project in the working directory: {}.", project.describe())).collect::<Vec<_>>().join(" ")
+}
+
+fn read_toml(path: &Path) -> Option<toml::Table> {
+    fs::read_to_string(path).ok()?.parse().ok()
+}
+
+fn toml_string(table: &toml::Table, section: &str, key: &str) -> Option<String> {
+    table.get(section)?.get(key)?.as_str().map(str::to_string)
+}
+
+fn rust_project(dir: &Path) -> Option<Project> {
+    let manifest = read_toml(&dir.join("Cargo.toml"))?;
+    let (build, test) = match manifest.contains_key("workspace") {
+        true => ("cargo build --workspace", "cargo test --workspace"),
+        false => ("cargo build", "cargo test"),
+    };
+    Some(Project {
+        language: "Rust".to_string(),
+        manifest: "Cargo.toml",
+        name: toml_string(&manifest, "package", "name"),
+        tool: "cargo".to_string(),
+        build: Some(build.to_string()),
+        test: Some(test.to_string()),
+    })
+}
+
+fn node_project(dir: &Path) -> Option<Project> {
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(dir.join("package.json")).ok()?).ok()?;
+    let tool = [("pnpm-lock.yaml", "pnpm"), ("yarn.lock", "yarn"), ("bun.lockb", "bun"), ("bun.lock", "bun")]
+        .into_iter()
+        .find(|(lock_file, _)| dir.join(lock_file).is_file())
+        .map_or("npm", |(_, tool)| tool);
+    let script = |name: &str| manifest["scripts"][name].as_str().map(|_| format!("{} run {}", tool, name));
+    let language = if dir.join("tsconfig.json").is_file() { "TypeScript" } else { "JavaScript" };
+    Some(Project {
+        language: language.to_string(),
+        manifest: "package.json",
+        name: manifest["name"].as_str().map(str::to_string),
+        tool: tool.to_string(),
+        build: script("build"),
+        test: script("test"),
+    })
+}
+
+fn python_project(dir: &Path) -> Option<Project> {
+    let manifest = read_toml(&dir.join("pyproject.toml"))?;
+    let (tool, runner) = if dir.join("uv.lock").is_file() {
+        ("uv", "uv run ")
+    } else if dir.join("poetry.lock").is_file() || manifest.get("tool").and_then(|tool| tool.get("poetry")).is_some() {
+        ("poetry", "poetry run ")
+    } else {
+        ("pip", "")
+    };
+    let name = toml_string(&manifest, "project", "name")
+        .or_else(|| manifest.get("tool")?.get("poetry")?.get("name")?.as_str().map(str::to_string));
+    Some(Project {
+        language: "Python".to_string(),
+        manifest: "pyproject.toml",
+        name,
+        tool: tool.to_string(),
+        build: None,
+        test: Some(format!("{}pytest", runner)),
+    })
+}
+
+fn go_project(dir: &Path) -> Option<Project> {
+    let manifest = fs::read_to_string(dir.join("go.mod")).ok()?;
+    let module = manifest.lines().find_map(|line| line.trim().strip_prefix("module ")).map(|module| module.trim().to_string());
+    Some(Project {
+        language: "Go".to_string(),
+        manifest: "go.mod",
+        name: module,
+        tool: "go".to_string(),
+        build: Some("go build ./...".to_string()),
+        test: Some("go test ./...".to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_projects() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(detect(dir.path()).is_empty());
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"server\"\nversion = \"0.1.0\"\n")?;
+        fs::write(dir.path().join("package.json"), r#"{"name": "web", "scripts": {"test": "vitest"}}"#)?;
+        fs::write(dir.path().join("pnpm-lock.yaml"), "")?;
+        fs::write(dir.path().join("tsconfig.json"), "{}")?;
+
+        let descriptions: Vec<String> = detect(dir.path()).iter().map(Project::describe).collect();
+
+        assert_eq!(descriptions, vec![
+            "Rust project `server` (Cargo.toml), tool: cargo, build: `cargo build`, test: `cargo test`",
+            "TypeScript project `web` (package.json), tool: pnpm, test: `pnpm run test`",
+        ]);
+        Ok(())
+    }
+}
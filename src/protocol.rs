@@ -0,0 +1,111 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::error::ActionError;
+use crate::events::{self, Event};
+use crate::input;
+use crate::pager;
+use serde::Deserialize;
+use std::io::{self, BufRead};
+use std::sync::mpsc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    // Webhooks triggering the tasks of the project
+    Http,
+    // Commands as JSON lines on the standard input, events as JSON lines on the standard output, e.g. for editor extensions
+    Stdio,
+}
+
+/// Command of the program driving cliff, one JSON object per line with the `command` field naming it, e.g.
+/// `{"command": "plan", "id": "1", "instruction": "Run the tests"}`. The `id` is repeated in the answer and error events
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    Ask { id: Option<String>, prompt: String },
+    // `git_commits` and `keep_scratch` work as the options of `act`
+    Plan {
+        id: Option<String>,
+        instruction: String,
+        #[serde(default)]
+        git_commits: bool,
+        #[serde(default)]
+        keep_scratch: bool,
+    },
+    // Answers the question asked with the `input_requested` event, e.g. "y" to confirm a step
+    #[serde(alias = "confirm")]
+    Answer { text: String },
+}
+
+/// Turns the standard output into the events and the standard input into the requests. The text printed otherwise
+/// goes to the standard error, the answers are read by the prompts, the other requests are returned in order
+pub fn start() -> Result<UnboundedReceiver<Request>> {
+    redirect_stdout_to_events()?;
+    pager::disable();
+    let (answer_sender, answer_receiver) = mpsc::channel();
+    input::use_answers(answer_receiver);
+    let (request_sender, request_receiver) = unbounded_channel();
+    // The prompts block the runtime thread waiting for the answers, the standard input is read on a thread of its own
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sent = match serde_json::from_str(&line) {
+                Ok(Request::Answer { text }) => answer_sender.send(text).is_ok(),
+                Ok(request) => request_sender.send(request).is_ok(),
+                Err(e) => {
+                    events::emit(Event::Error { id: None, kind: "invalid_input", message: format!("Invalid request '{}': {}", line, e) });
+                    true
+                }
+            };
+            if !sent {
+                break;
+            }
+        }
+    });
+    Ok(request_receiver)
+}
+
+// The events keep the original standard output, everything printed goes to the standard error
+#[cfg(unix)]
+fn redirect_stdout_to_events() -> Result<()> {
+    use std::io::Write;
+    io::stdout().flush()?;
+    let events_fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if events_fd < 0 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(ActionError::Io(format!("Failed to redirect the standard output: {}", io::Error::last_os_error())).into());
+    }
+    events::write_to_fd(events_fd)
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout_to_events() -> Result<()> {
+    Err(ActionError::InvalidInput("--protocol stdio is supported only on Unix".to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requests() {
+        let parse = |line: &str| serde_json::from_str::<Request>(line).map_err(|e| e.to_string());
+
+        assert_eq!(
+            parse(r#"{"command": "plan", "id": "1", "instruction": "Run the tests"}"#),
+            Ok(Request::Plan { id: Some("1".to_string()), instruction: "Run the tests".to_string(), git_commits: false, keep_scratch: false })
+        );
+        assert_eq!(
+            parse(r#"{"command": "plan", "instruction": "Run the tests", "git_commits": true}"#),
+            Ok(Request::Plan { id: None, instruction: "Run the tests".to_string(), git_commits: true, keep_scratch: false })
+        );
+        assert_eq!(parse(r#"{"command": "ask", "prompt": "What is Rust?"}"#), Ok(Request::Ask { id: None, prompt: "What is Rust?".to_string() }));
+        assert_eq!(parse(r#"{"command": "confirm", "text": "y"}"#), Ok(Request::Answer { text: "y".to_string() }));
+        assert!(parse(r#"{"command": "plan", "instructions": "Run the tests"}"#).is_err());
+        assert!(parse(r#"{"command": "shutdown"}"#).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use crate::config::Model;
+use crate::error::ConfigError;
+use reqwest::{Client, NoProxy, Proxy};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use url::Url;
+
+/// Proxy of a model connecting it directly, ignoring the proxy of the environment
+pub const DIRECT: &str = "none";
+
+// Requests the proxies of the environment apply to, the more specific ones take precedence
+const ENV_PROXIES: [(&str, &str, &str); 3] = [("http", "HTTP_PROXY", "http_proxy"), ("https", "HTTPS_PROXY", "https_proxy"), ("all", "ALL_PROXY", "all_proxy")];
+
+fn invalid(proxy: &str, detail: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidProxy { proxy: redact(proxy), detail: detail.into() }
+}
+
+/// The proxy URL without the password
+pub fn redact(proxy: &str) -> String {
+    match Url::parse(proxy) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("***"));
+            url.to_string()
+        }
+        _ => proxy.to_string(),
+    }
+}
+
+/// Checks that the proxy is a supported URL or `none`
+pub fn validate(proxy: &str) -> Result<()> {
+    if proxy == DIRECT {
+        return Ok(());
+    }
+    let url = Url::parse(proxy).map_err(|e| invalid(proxy, e.to_string()))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" if url.host_str().is_some() => Ok(()),
+        "http" | "https" | "socks5" | "socks5h" => Err(invalid(proxy, "the URL has no host").into()),
+        scheme => Err(invalid(proxy, format!("the scheme '{}' is not supported, use http, https, socks5 or socks5h", scheme)).into()),
+    }
+}
+
+// The proxy of the environment variable, like curl the proxy without a scheme is an HTTP proxy. The invalid proxies are
+// reported and ignored
+fn env_proxy(names: [&str; 2]) -> Option<String> {
+    let (name, value) = names.iter().find_map(|name| std::env::var(name).ok().filter(|value| !value.trim().is_empty()).map(|value| (name, value)))?;
+    let proxy = if value.contains("://") { value.trim().to_string() } else { format!("http://{}", value.trim()) };
+    match validate(&proxy) {
+        Ok(()) => Some(proxy),
+        Err(e) => {
+            eprintln!("Warning: The proxy of {} is ignored: {:#}", name, e);
+            None
+        }
+    }
+}
+
+/// HTTP client using `proxy` for all the requests, or the proxies of the environment (`HTTP_PROXY`, `HTTPS_PROXY`,
+/// `ALL_PROXY` except for the hosts of `NO_PROXY`) when it is not set
+pub fn client(proxy: Option<&str>) -> Result<Client> {
+    let proxies: Vec<(&str, String)> = match proxy {
+        Some(DIRECT) => Vec::new(),
+        Some(proxy) => vec![("all", proxy.to_string())],
+        None => ENV_PROXIES.iter().filter_map(|(target, upper, lower)| env_proxy([upper, lower]).map(|proxy| (*target, proxy))).collect(),
+    };
+    let no_proxy = if proxy.is_none() { NoProxy::from_env() } else { None };
+    // The proxies of the environment are applied here rather than by reqwest to report the invalid ones
+    let mut builder = Client::builder().no_proxy();
+    for (target, proxy) in proxies {
+        validate(&proxy)?;
+        let configured = match target {
+            "http" => Proxy::http(&proxy),
+            "https" => Proxy::https(&proxy),
+            _ => Proxy::all(&proxy),
+        };
+        builder = builder.proxy(configured.map_err(|e| invalid(&proxy, e.to_string()))?.no_proxy(no_proxy.clone()));
+    }
+    Ok(builder.build()?)
+}
+
+/// The client of the model when the model has its own proxy, otherwise the default client
+pub fn model_client(model_config: &Model, default: &Client) -> Result<Client> {
+    static CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+    let Some(proxy) = model_config.proxy.as_deref() else {
+        return Ok(default.clone());
+    };
+    let mut clients = CLIENTS.get_or_init(Default::default).lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = clients.get(proxy) {
+        return Ok(client.clone());
+    }
+    let client = client(Some(proxy))?;
+    clients.insert(proxy.to_string(), client.clone());
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    // Minimal SOCKS5 proxy accepting one connection with a username and password, returns the credentials and the host
+    // it was asked to connect to
+    async fn fake_socks(listener: TcpListener, target: String) -> Result<(String, String)> {
+        let (mut stream, _) = listener.accept().await?;
+        let mut greeting = [0u8; 2];
+        stream.read_exact(&mut greeting).await?;
+        let mut methods = vec![0u8; greeting[1] as usize];
+        stream.read_exact(&mut methods).await?;
+        stream.write_all(&[0x05, 0x02]).await?;
+        let mut authentication = [0u8; 2];
+        stream.read_exact(&mut authentication).await?;
+        let mut username = vec![0u8; authentication[1] as usize];
+        stream.read_exact(&mut username).await?;
+        let mut password = vec![0u8; stream.read_u8().await? as usize];
+        stream.read_exact(&mut password).await?;
+        stream.write_all(&[0x01, 0x00]).await?;
+        let credentials = format!("{}:{}", String::from_utf8_lossy(&username), String::from_utf8_lossy(&password));
+        let mut request = [0u8; 5];
+        stream.read_exact(&mut request).await?;
+        assert_eq!(request[3], 0x03, "the host name is resolved by the proxy");
+        let mut host = vec![0u8; request[4] as usize + 2];
+        stream.read_exact(&mut host).await?;
+        stream.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 0]).await?;
+        let mut upstream = TcpStream::connect(target).await?;
+        let _ = copy_bidirectional(&mut stream, &mut upstream).await;
+        Ok((credentials, String::from_utf8_lossy(&host[..host.len() - 2]).to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_socks5_proxy() -> Result<()> {
+        let server = MockServer::start_async().await;
+        let page = server.mock(|when, then| {
+            when.method(GET).path("/page").header("host", format!("localhost:{}", server.port()));
+            then.status(200).body("through the proxy");
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let proxy = format!("socks5h://user:secret@{}", listener.local_addr()?);
+        let socks = tokio::spawn(fake_socks(listener, server.address().to_string()));
+
+        let client = client(Some(&proxy))?;
+        let body = client.get(format!("http://localhost:{}/page", server.port())).send().await?.text().await?;
+
+        page.assert();
+        assert_eq!(body, "through the proxy");
+        // The connection is kept alive until the client is dropped
+        drop(client);
+        assert_eq!(socks.await??, ("user:secret".to_string(), "localhost".to_string()));
+        assert_eq!(redact(&proxy), proxy.replace("secret", "***"));
+        assert!(validate("socks4://localhost:1080").is_err());
+        Ok(())
+    }
+}
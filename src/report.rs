@@ -0,0 +1,81 @@
+use crate::error::{ActionError, ConfigError, LlmError};
+use colored::*;
+use reqwest::StatusCode;
+
+// Next step for the user to fix the failure, None when the message says it all
+fn remediation_hint(error: &anyhow::Error) -> Option<String> {
+    if let Some(llm_error) = error.downcast_ref::<LlmError>() {
+        let model = llm_error.model();
+        return Some(match llm_error {
+            LlmError::Unauthorized { .. } => format!(
+                "Check the API key of the model with `cliff config test {}`, add the model again with a valid `--api-key` to replace the key", model
+            ),
+            LlmError::RequestFailed { status, .. } if *status == StatusCode::NOT_FOUND => format!(
+                "Check the API URL and the model identifier of the model '{}' with `cliff config list`", model
+            ),
+            LlmError::RequestFailed { status, .. } if *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() => format!(
+                "The provider is overloaded or unavailable, try again later or use another model with `--model`, `cliff config test {}` checks the model", model
+            ),
+            LlmError::RequestFailed { .. } | LlmError::InvalidRequestFormat { .. } => format!(
+                "The request format does not match the API of the provider, check it with `cliff config test {}`", model
+            ),
+            LlmError::InvalidJsonPath { .. } | LlmError::ValueNotFound { .. } => format!(
+                "The response JSON path does not match the responses of the provider, check it with `cliff config test {}`", model
+            ),
+            LlmError::Unsupported { .. } => format!(
+                "Use a model supporting it with `--model`, or when the model does support it, update its capabilities with `cliff config set-capabilities {}`", model
+            ),
+            LlmError::ContextWindowExceeded { .. } => format!(
+                "Provide less context, use a model with a larger context window with `--model`, or check the context window of the model with `cliff config list`, it is set with `cliff config set-capabilities {}`", model
+            ),
+        });
+    }
+    match error.downcast_ref::<ConfigError>() {
+        Some(ConfigError::ModelNotFound(_)) => {
+            return Some("List the configured models with `cliff config list` or add the model with `cliff config add`".to_string())
+        }
+        Some(ConfigError::InvalidProxy { .. }) => {
+            return Some("Use an http, https, socks5 (local DNS) or socks5h (DNS by the proxy) URL in HTTP_PROXY, HTTPS_PROXY and ALL_PROXY, or for a model with `cliff config set-proxy`, `none` connects directly".to_string())
+        }
+        Some(ConfigError::NoActiveModel) => {
+            return Some("Add a model with `cliff config add` and make it the default one with `cliff config set-default <name>`, see `cliff examples config`".to_string())
+        }
+        None => {}
+    }
+    match error.downcast_ref::<ActionError>()? {
+        ActionError::Network(_) | ActionError::Timeout(_) => {
+            Some("Check the network connection and the proxy (HTTP_PROXY, HTTPS_PROXY, ALL_PROXY or the proxy of the model), `cliff doctor` checks the reachability of the configured models".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Prints the error with its causes on a single line and a hint how to fix it instead of the debug form of the chain
+pub fn print_error(error: &anyhow::Error) {
+    eprintln!("{} {:#}", "Error:".red().bold(), error);
+    if let Some(hint) = remediation_hint(error) {
+        eprintln!("{} {}", "hint:".yellow().bold(), hint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_remediation_hint() {
+        let unauthorized: anyhow::Result<()> = Err(LlmError::Unauthorized {
+            model: "gpt".to_string(),
+            status: StatusCode::UNAUTHORIZED,
+            detail: "Incorrect API key provided".to_string(),
+        }.into());
+        let error = unauthorized.context("Error during LLM call").unwrap_err();
+        assert!(remediation_hint(&error).unwrap().contains("`cliff config test gpt`"));
+        assert_eq!(format!("{:#}", error), "Error during LLM call: The API of the model 'gpt' rejected the credentials with status 401 Unauthorized: Incorrect API key provided");
+
+        let missing_model = anyhow::Error::from(ConfigError::ModelNotFound("gpt".to_string()));
+        assert!(remediation_hint(&missing_model).unwrap().contains("`cliff config list`"));
+        assert_eq!(remediation_hint(&anyhow::anyhow!("Unexpected")), None);
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::backend::{self, TokenUsage};
+use crate::profiles;
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunMetadata {
+    pub id: String,
+    pub command: String,
+    pub instruction: String,
+    pub model: String,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    /// Tokens of the run as reported by the APIs of the models, None when they do not report them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<TokenUsage>,
+    // Usage of the process when the run started, the tokens of the run are counted from it
+    #[serde(skip)]
+    tokens_at_start: TokenUsage,
+}
+
+impl RunMetadata {
+    pub fn new(command: &str, instruction: &str, model: &str) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        RunMetadata {
+            id: format!("{}-{:x}", now.as_millis(), std::process::id()),
+            command: command.to_string(),
+            instruction: instruction.to_string(),
+            model: model.to_string(),
+            started_at: now.as_secs(),
+            finished_at: None,
+            status: RunStatus::Running,
+            error: None,
+            tokens: None,
+            tokens_at_start: backend::usage(),
+        }
+    }
+
+    pub fn finish<T>(&mut self, result: &Result<T>) {
+        self.finished_at = Some(unix_timestamp());
+        let tokens = backend::usage().since(self.tokens_at_start);
+        self.tokens = (tokens != TokenUsage::default()).then_some(tokens);
+        match result {
+            Ok(_) => self.status = RunStatus::Succeeded,
+            Err(e) => {
+                self.status = RunStatus::Failed;
+                self.error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Line reporting the end of the run
+    pub fn summary(&self) -> String {
+        match &self.tokens {
+            Some(tokens) if tokens.cached_prompt_tokens > 0 => format!(
+                "Run {} finished: {:?} ({} prompt tokens, {} of them cached, and {} completion tokens)",
+                self.id, self.status, tokens.prompt_tokens, tokens.cached_prompt_tokens, tokens.completion_tokens
+            ),
+            Some(tokens) => format!("Run {} finished: {:?} ({} prompt and {} completion tokens)", self.id, self.status, tokens.prompt_tokens, tokens.completion_tokens),
+            None => format!("Run {} finished: {:?}", self.id, self.status),
+        }
+    }
+}
+
+const RUNS_DIR_NAME: &str = "runs";
+// Number of the latest runs kept, the records of the older runs are deleted
+const MAX_RUNS: usize = 1000;
+
+fn runs_dir() -> Result<PathBuf> {
+    Ok(profiles::data_dir()?.join(RUNS_DIR_NAME))
+}
+
+/// Records the run in its own file, replacing the earlier record of the same run, the records of the older runs beyond
+/// the latest ones are deleted
+pub fn record(run: &RunMetadata) -> Result<()> {
+    record_in(&runs_dir()?, run)
+}
+
+/// Id of the latest started run, None when no run is recorded
+pub fn latest_id() -> Result<Option<String>> {
+    latest_id_in(&runs_dir()?)
+}
+
+fn record_in(dir: &Path, run: &RunMetadata) -> Result<()> {
+    create_dir_all(dir).with_context(|| format!("Failed to create the runs directory: {:?}", dir))?;
+    let content = toml::to_string_pretty(run).with_context(|| "Failed to serialize the run")?;
+    // Written to a temporary file first so that the concurrent runs never read a partially written record
+    let path = dir.join(format!("{}.toml", run.id));
+    let temporary_path = dir.join(format!(".{}.toml.tmp", run.id));
+    fs::write(&temporary_path, content).with_context(|| format!("Failed to write the run: {:?}", temporary_path))?;
+    fs::rename(&temporary_path, &path).with_context(|| format!("Failed to write the run: {:?}", path))?;
+
+    let recorded = recorded_runs(dir)?;
+    if recorded.len() > MAX_RUNS {
+        for (_, old) in &recorded[..recorded.len() - MAX_RUNS] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(())
+}
+
+fn latest_id_in(dir: &Path) -> Result<Option<String>> {
+    Ok(recorded_runs(dir)?.pop().map(|(id, _)| id))
+}
+
+// Ids and files of the recorded runs from the oldest to the latest, the ids start with the start time in milliseconds
+fn recorded_runs(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut recorded: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read the runs directory: {:?}", dir))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect();
+    recorded.sort_by_key(|(id, _)| (started_at_millis(id), id.clone()));
+    Ok(recorded)
+}
+
+fn started_at_millis(id: &str) -> u128 {
+    id.split('-').next().and_then(|millis| millis.parse().ok()).unwrap_or_default()
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn load_from(dir: &Path, id: &str) -> Result<RunMetadata> {
+        let path = dir.join(format!("{}.toml", id));
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read the run: {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse the run: {:?}", path))
+    }
+
+    #[test]
+    fn test_record_run_lifecycle() -> Result<()> {
+        let dir = tempdir()?;
+        assert_eq!(latest_id_in(dir.path())?, None);
+
+        let mut run = RunMetadata::new("act", "Say hello", "test-model");
+        record_in(dir.path(), &run)?;
+        run.finish::<()>(&Err(anyhow::anyhow!("Command failed")));
+        record_in(dir.path(), &run)?;
+
+        assert_eq!(latest_id_in(dir.path())?.as_deref(), Some(run.id.as_str()));
+        let stored = load_from(dir.path(), &run.id)?;
+        assert_eq!(stored.id, run.id);
+        assert_eq!(stored.status, RunStatus::Failed);
+        assert_eq!(stored.error.as_deref(), Some("Command failed"));
+        assert!(stored.finished_at.is_some());
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_runs_are_deleted() -> Result<()> {
+        let dir = tempdir()?;
+        let mut run = RunMetadata::new("act", "Say hello", "test-model");
+        for millis in 0..MAX_RUNS + 2 {
+            run.id = format!("{}-1", 1_000_000 + millis);
+            record_in(dir.path(), &run)?;
+        }
+
+        let recorded = recorded_runs(dir.path())?;
+        assert_eq!(recorded.len(), MAX_RUNS);
+        assert_eq!(recorded[0].0, "1000002-1");
+        assert_eq!(latest_id_in(dir.path())?, Some(format!("{}-1", 1_000_001 + MAX_RUNS)));
+        Ok(())
+    }
+}
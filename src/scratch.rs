@@ -0,0 +1,57 @@
+use anyhow::Result;
+use crate::error::IoResultExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Scratch directory of the run in this process, offered to the LLM for the intermediate files of the plans
+static SCRATCH_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Removes the scratch directory with everything the plans put into it at the end of the run unless it is kept
+pub struct ScratchDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+/// Creates the scratch directory of the run in the temporary directory of the system, e.g. `/tmp/cliff-1760000000000-1a2b`
+pub fn create(run_id: &str, keep: bool) -> Result<ScratchDir> {
+    let path = std::env::temp_dir().join(format!("cliff-{}", run_id));
+    std::fs::create_dir_all(&path)
+        .with_action_context(|| format!("Failed to create the scratch directory: {}", path.display()))?;
+    let _ = SCRATCH_DIR.set(path.clone());
+    Ok(ScratchDir { path, keep })
+}
+
+pub fn path() -> Option<&'static Path> {
+    SCRATCH_DIR.get().map(PathBuf::as_path)
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if self.keep {
+            println!("Scratch directory kept: {}", self.path.display());
+        } else if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            eprintln!("Warning: Failed to remove the scratch directory {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_dir_removed_unless_kept() -> Result<()> {
+        let scratch = create("test-removed", false)?;
+        let dir = scratch.path.clone();
+        std::fs::write(dir.join("download.zip"), "zip")?;
+        drop(scratch);
+        assert!(!dir.exists());
+
+        let scratch = create("test-kept", true)?;
+        let dir = scratch.path.clone();
+        drop(scratch);
+        assert!(dir.is_dir());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::classification::AutoConfirm;
+use crate::config::ProjectConfig;
+use crate::error::{ActionError, IoResultExt};
+use crate::profiles;
+use crate::tasks;
+use ring::hmac;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Child;
+
+pub const SECRET_ENV_VAR: &str = "CLIFF_WEBHOOK_SECRET";
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const LOGS_DIR_NAME: &str = "webhook-runs";
+// Query parameter asking to stream the log of the run in the response instead of returning right away
+const STREAM_PARAM: &str = "stream";
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub listen: SocketAddr,
+    // Shared secret of the webhooks, sent as a bearer token or used to sign the body like the GitHub webhooks do
+    pub secret: String,
+    // Actions of the triggered tasks confirmed automatically, the others are denied
+    pub auto_confirm: AutoConfirm,
+    // Target of the triggered tasks, the target of `.cliff.toml` is not used without it
+    pub target: Option<String>,
+    // Directory with the `.cliff.toml` defining the tasks, the tasks run in it
+    pub project_dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    // Header names are lowercase
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Response { status, content_type: "application/json", body: body.to_string() }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response::json(status, json!({ "error": message.into() }))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |text: &str| urlencoding::decode(&text.replace('+', " ")).map(|text| text.into_owned()).unwrap_or_else(|_| text.to_string());
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+async fn read_request(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Request> {
+    let invalid = |message: &str| ActionError::InvalidInput(message.to_string());
+    let mut head = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(invalid("The connection closed before the end of the request headers").into());
+        }
+        if line.trim_end().is_empty() {
+            break;
+        }
+        head.push(line.trim_end().to_string());
+        if head.iter().map(String::len).sum::<usize>() > MAX_HEADER_BYTES {
+            return Err(invalid("The request headers are too large").into());
+        }
+    }
+    let request_line = head.first().ok_or_else(|| invalid("Empty request"))?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("Invalid request line").into());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers: HashMap<String, String> = head[1..]
+        .iter()
+        .filter_map(|header| header.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let content_length: usize = headers.get("content-length").map_or(Ok(0), |length| length.parse())
+        .map_err(|_| invalid("Invalid Content-Length"))?;
+    if content_length > MAX_BODY_BYTES {
+        return Err(ActionError::PolicyDenied(format!("The request body is larger than {} bytes", MAX_BODY_BYTES)).into());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok(Request { method: method.to_string(), path: path.to_string(), query: parse_query(query), headers, body })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// The secret is accepted as a bearer token or as the key of the `X-Hub-Signature-256` HMAC of the body
+fn authenticate(request: &Request, secret: &str) -> bool {
+    if let Some(token) = request.headers.get("authorization").and_then(|value| value.strip_prefix("Bearer ")) {
+        return constant_time_eq(token.trim().as_bytes(), secret.as_bytes());
+    }
+    let signature = request.headers.get("x-hub-signature-256")
+        .and_then(|value| value.strip_prefix("sha256="))
+        .and_then(hex_decode);
+    match signature {
+        Some(signature) => hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()), &request.body, &signature).is_ok(),
+        None => false,
+    }
+}
+
+// Parameters of the task: the query parameters and the `params` object of a JSON body
+fn task_params(request: &Request) -> HashMap<String, String> {
+    let mut params: HashMap<String, String> = request.query.iter()
+        .filter(|(name, _)| name.as_str() != STREAM_PARAM)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if let Ok(Value::Object(body)) = serde_json::from_slice::<Value>(&request.body) {
+        if let Some(Value::Object(body_params)) = body.get("params") {
+            for (name, value) in body_params {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    other => other.to_string(),
+                };
+                params.insert(name.clone(), value);
+            }
+        }
+    }
+    params
+}
+
+fn logs_dir() -> Result<PathBuf> {
+    let data_dir = profiles::data_dir()?;
+    Ok(data_dir.join(LOGS_DIR_NAME))
+}
+
+fn new_run_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    format!("{}-{:x}-{}", millis, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+fn is_valid_run_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// Runs the task non-interactively in a child `cliff daemon --once` with the output written to the log of the run
+fn spawn_task(name: &str, params: &HashMap<String, String>, options: &ServerOptions, log_path: &Path) -> Result<Child> {
+    let log = std::fs::File::create(log_path).with_action_context(|| format!("Failed to create the run log {}", log_path.display()))?;
+    let auto_confirm = options.auto_confirm.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+    let mut command = tokio::process::Command::new(std::env::current_exe().with_action_context(|| "Failed to locate the current binary".to_string())?);
+    command.current_dir(&options.project_dir)
+        .args(["daemon", "--once", "--task", name])
+        .arg(format!("--auto-confirm={}", auto_confirm))
+        .arg(format!("--profile={}", profiles::current()))
+        .args(options.target.iter().map(|target| format!("--target={}", target)))
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().with_action_context(|| format!("Failed to open the run log {}", log_path.display()))?)
+        .stderr(log);
+    for (param, value) in params {
+        command.arg("--param").arg(format!("{}={}", param, value));
+    }
+    Ok(command.spawn().with_action_context(|| format!("Failed to start the task '{}'", name))?)
+}
+
+async fn write_head(stream: &mut TcpStream, status: u16, content_type: &str, content_length: Option<usize>, run_id: Option<&str>) -> Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nConnection: close\r\n", status, reason(status), content_type);
+    if let Some(content_length) = content_length {
+        head.push_str(&format!("Content-Length: {}\r\n", content_length));
+    }
+    if let Some(run_id) = run_id {
+        head.push_str(&format!("X-Cliff-Run: {}\r\n", run_id));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    Ok(())
+}
+
+// Sends the log of the run as it is written until the task finishes, the response ends with the exit status
+async fn stream_log(stream: &mut TcpStream, mut child: Child, log_path: &Path, run_id: &str) -> Result<()> {
+    write_head(stream, 200, "text/plain; charset=utf-8", None, Some(run_id)).await?;
+    let mut log = tokio::fs::File::open(log_path).await?;
+    let mut position = 0;
+    loop {
+        let status = child.try_wait()?;
+        log.seek(std::io::SeekFrom::Start(position)).await?;
+        let mut appended = Vec::new();
+        position += log.read_to_end(&mut appended).await? as u64;
+        stream.write_all(&appended).await?;
+        if let Some(status) = status {
+            stream.write_all(format!("\n[run {} finished: {}]\n", run_id, status).as_bytes()).await?;
+            return Ok(());
+        }
+        tokio::time::sleep(LOG_POLL_INTERVAL).await;
+    }
+}
+
+async fn trigger_task(stream: &mut TcpStream, request: &Request, name: &str, options: &ServerOptions) -> Result<Option<Response>> {
+    let project_config = ProjectConfig::load(&options.project_dir)?;
+    let params = task_params(request);
+    let task = match project_config.task(name) {
+        Ok(task) => task,
+        Err(e) => return Ok(Some(Response::error(404, e.to_string()))),
+    };
+    if let Err(e) = tasks::with_params(task, &params) {
+        return Ok(Some(Response::error(400, format!("{:#}", e))));
+    }
+    let run_id = new_run_id();
+    let log_path = logs_dir()?.join(format!("{}.log", run_id));
+    tokio::fs::create_dir_all(log_path.parent().unwrap_or(Path::new("."))).await?;
+    let child = spawn_task(name, &params, options, &log_path)?;
+    println!("Task '{}' triggered, run {}", name, run_id);
+    if request.query.contains_key(STREAM_PARAM) {
+        stream_log(stream, child, &log_path, &run_id).await?;
+        return Ok(None);
+    }
+    // The finished child is reaped in the background
+    tokio::spawn(async move {
+        let mut child = child;
+        let _ = child.wait().await;
+    });
+    Ok(Some(Response::json(202, json!({ "run": run_id, "log": format!("/runs/{}/log", run_id) }))))
+}
+
+async fn run_log(run_id: &str) -> Result<Response> {
+    if !is_valid_run_id(run_id) {
+        return Ok(Response::error(404, "Unknown run"));
+    }
+    match tokio::fs::read_to_string(logs_dir()?.join(format!("{}.log", run_id))).await {
+        Ok(log) => Ok(Response { status: 200, content_type: "text/plain; charset=utf-8", body: log }),
+        Err(_) => Ok(Response::error(404, "Unknown run")),
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, peer: SocketAddr, options: &ServerOptions) -> Result<()> {
+    let request = match read_request(&mut BufReader::new(&mut stream)).await {
+        Ok(request) => request,
+        Err(e) => {
+            let status = if matches!(e.downcast_ref::<ActionError>(), Some(ActionError::PolicyDenied(_))) { 413 } else { 400 };
+            let response = Response::error(status, format!("{:#}", e));
+            return write_response(&mut stream, &response).await;
+        }
+    };
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let response = if !authenticate(&request, &options.secret) {
+        Some(Response::error(401, "Missing or invalid credentials"))
+    } else {
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["tasks", name]) => match trigger_task(&mut stream, &request, name, options).await {
+                Ok(response) => response,
+                Err(e) => Some(Response::error(500, format!("{:#}", e))),
+            },
+            ("GET", ["runs", run_id, "log"]) => Some(run_log(run_id).await.unwrap_or_else(|e| Response::error(500, format!("{:#}", e)))),
+            (_, ["tasks", _]) | (_, ["runs", _, "log"]) => Some(Response::error(405, "Method not allowed")),
+            _ => Some(Response::error(404, "Not found")),
+        }
+    };
+    let status = response.as_ref().map_or(200, |response| response.status);
+    println!("{} {} {} -> {}", peer, request.method, request.path, status);
+    match response {
+        Some(response) => write_response(&mut stream, &response).await,
+        None => Ok(()),
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: &Response) -> Result<()> {
+    write_head(stream, response.status, response.content_type, Some(response.body.len()), None).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    Ok(())
+}
+
+/// Serves the webhooks triggering the tasks of the project: `POST /tasks/<name>` starts the task and returns the id
+/// of the run (or streams its log with `?stream`), `GET /runs/<id>/log` returns the log of the run
+pub async fn serve(options: ServerOptions) -> Result<()> {
+    let listener = TcpListener::bind(options.listen).await
+        .with_action_context(|| format!("Failed to listen on {}", options.listen))?;
+    println!("Listening for webhooks on http://{}", listener.local_addr()?);
+    let options = Arc::new(options);
+    loop {
+        let (stream, peer) = listener.accept().await.with_action_context(|| "Failed to accept a connection".to_string())?;
+        let options = options.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer, &options).await {
+                eprintln!("Warning: Request from {} failed: {:#}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request_and_params() -> Result<()> {
+        let body = r#"{"params": {"branch": "main", "pr": 42}}"#;
+        let raw = format!("POST /tasks/review?stream&target=a%20b HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let request = read_request(&mut BufReader::new(raw.as_bytes())).await?;
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/tasks/review"));
+        assert_eq!(request.headers.get("host").map(String::as_str), Some("localhost"));
+        assert_eq!(task_params(&request), HashMap::from([
+            ("target".to_string(), "a b".to_string()),
+            ("branch".to_string(), "main".to_string()),
+            ("pr".to_string(), "42".to_string()),
+        ]));
+        assert!(read_request(&mut BufReader::new("POST / HTTP/1.1\r\nContent-Length: 9999999\r\n\r\n".as_bytes())).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let body = br#"{"ref": "refs/heads/main"}"#.to_vec();
+        let signature = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, b"secret"), &body);
+        let signature: String = signature.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let request = |header: &str, value: String| Request {
+            method: "POST".to_string(),
+            path: "/tasks/review".to_string(),
+            query: HashMap::new(),
+            headers: HashMap::from([(header.to_string(), value)]),
+            body: body.clone(),
+        };
+
+        assert!(authenticate(&request("authorization", "Bearer secret".to_string()), "secret"));
+        assert!(!authenticate(&request("authorization", "Bearer guess".to_string()), "secret"));
+        assert!(authenticate(&request("x-hub-signature-256", format!("sha256={}", signature)), "secret"));
+        assert!(!authenticate(&request("x-hub-signature-256", format!("sha256={}", signature)), "other"));
+        assert!(!authenticate(&request("host", "localhost".to_string()), "secret"));
+    }
+}
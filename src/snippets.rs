@@ -0,0 +1,44 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use crate::templates;
+
+const INPUT_VARIABLE: &str = "input";
+
+// Expands a prompt of the form `@snippet-name rest of the prompt` into the stored snippet,
+// the rest of the prompt is available in the snippet as the `{{input}}` placeholder
+pub(crate) fn expand(prompt: &str, snippets: &HashMap<String, String>, variables: &HashMap<String, String>) -> Result<String> {
+    let Some(snippet_reference) = prompt.trim_start().strip_prefix('@') else {
+        return Ok(prompt.to_string());
+    };
+    let (name, input) = snippet_reference.split_once(char::is_whitespace).unwrap_or((snippet_reference, ""));
+    render(name, input.trim(), snippets, variables)
+}
+
+pub(crate) fn render(name: &str, input: &str, snippets: &HashMap<String, String>, variables: &HashMap<String, String>) -> Result<String> {
+    let snippet = snippets
+        .get(name)
+        .ok_or(anyhow::anyhow!("Snippet '{}' not found. Use 'cliff snippets list' to see the available snippets.", name))?;
+    let mut variables = variables.clone();
+    variables.entry(INPUT_VARIABLE.to_string()).or_insert_with(|| input.to_string());
+    templates::render(snippet, &variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() -> Result<()> {
+        let snippets = HashMap::from([
+            ("review".to_string(), "Review the code in the context for {{focus}}.".to_string()),
+            ("explain-error".to_string(), "Explain the error and suggest a fix: {{input}}".to_string()),
+        ]);
+        let variables = HashMap::from([("focus".to_string(), "error handling".to_string())]);
+
+        assert_eq!(expand("@review", &snippets, &variables)?, "Review the code in the context for error handling.");
+        assert_eq!(expand("@explain-error cannot borrow `x` as mutable", &snippets, &HashMap::new())?, "Explain the error and suggest a fix: cannot borrow `x` as mutable");
+        assert_eq!(expand("What is @review?", &snippets, &HashMap::new())?, "What is @review?");
+        assert!(expand("@unknown", &snippets, &HashMap::new()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use crate::chunking::{chunk, line_chunks, Chunk};
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+const MAX_SIGNATURE_CHARS: usize = 120;
+
+// Names of the languages parsed with tree-sitter and the extensions of their files
+const LANGUAGES: [(&str, &[&str]); 5] = [
+    ("rust", &["rs"]),
+    ("python", &["py"]),
+    ("javascript", &["js", "jsx", "mjs", "cjs"]),
+    ("typescript", &["ts", "tsx"]),
+    ("go", &["go"]),
+];
+
+// Suffixes of the kinds of the nodes defining their names, e.g. `function_item` or `class_definition`, unlike the keyword arguments of Python
+const DEFINITION_SUFFIXES: [&str; 7] = ["_item", "_definition", "_declaration", "_declarator", "_spec", "_signature", "_variant"];
+
+pub(crate) fn supports_language(name: &str) -> bool {
+    LANGUAGES.iter().any(|(language, _)| language.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn language_name(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    LANGUAGES.iter().find(|(_, extensions)| extensions.contains(&extension)).map(|(language, _)| *language)
+}
+
+fn language(path: &Path) -> Option<Language> {
+    let language = match path.extension()?.to_str()? {
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "py" => tree_sitter_python::LANGUAGE,
+        "js" | "jsx" | "mjs" | "cjs" => tree_sitter_javascript::LANGUAGE,
+        "ts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        "go" => tree_sitter_go::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+fn parse(path: &Path, content: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&language(path)?).ok()?;
+    parser.parse(content, None)
+}
+
+pub(crate) fn chunk_definitions<'a>(path: &Path, content: &'a str, max_bytes: usize) -> Option<Vec<Chunk<'a>>> {
+    let tree = parse(path, content)?;
+    let mut chunks = Vec::new();
+    chunk_nodes(content, &named_children(tree.root_node()), 0, content.len(), &[], max_bytes, &mut chunks);
+    Some(chunks)
+}
+
+// Definition of a symbol, the signature is qualified by the definitions enclosing it, e.g. `impl Config > pub fn save(&self) -> Result<()>`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SymbolDefinition {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub signature: String,
+}
+
+pub(crate) fn find_definitions(path: &Path, content: &str, name: &str) -> Vec<SymbolDefinition> {
+    let mut definitions = Vec::new();
+    if let Some(tree) = parse(path, content) {
+        collect_definitions(content, tree.root_node(), name, &mut Vec::new(), &mut definitions);
+    }
+    definitions
+}
+
+fn collect_definitions(content: &str, node: Node, name: &str, parents: &mut Vec<String>, definitions: &mut Vec<SymbolDefinition>) {
+    for child in named_children(node) {
+        let defines_name = DEFINITION_SUFFIXES.iter().any(|suffix| child.kind().ends_with(suffix))
+            && child.child_by_field_name("name").is_some_and(|name_node| &content[name_node.byte_range()] == name);
+        // The wrappers like the decorators share the signature of their definition
+        let child_signature = signature(content, child).filter(|_| definition(child) == child);
+        if let (true, Some(signature)) = (defines_name, &child_signature) {
+            definitions.push(SymbolDefinition {
+                start_line: child.start_position().row + 1,
+                end_line: child.end_position().row + 1,
+                signature: parents.iter().chain([signature]).cloned().collect::<Vec<_>>().join(" > "),
+            });
+        }
+        match child_signature {
+            Some(signature) => {
+                parents.push(signature);
+                collect_definitions(content, child, name, parents, definitions);
+                parents.pop();
+            }
+            None => collect_definitions(content, child, name, parents, definitions),
+        }
+    }
+}
+
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor).collect()
+}
+
+// The definition itself when wrapped, e.g. by the decorators in Python or by `export` in JavaScript
+fn definition(node: Node) -> Node {
+    node.child_by_field_name("definition")
+        .or_else(|| node.child_by_field_name("declaration"))
+        .unwrap_or(node)
+}
+
+// The text of a definition before its body, e.g. `pub fn save(&self) -> Result<()>`, None for the nodes which define nothing
+fn signature(content: &str, node: Node) -> Option<String> {
+    let definition = definition(node);
+    let body = definition.child_by_field_name("body");
+    if body.is_none() && definition.child_by_field_name("name").is_none() {
+        return None;
+    }
+    let end = body.map_or_else(|| definition.end_byte(), |body| body.start_byte());
+    let text = &content[definition.start_byte()..end];
+    let text = if body.is_some() { text } else { text.lines().next().unwrap_or_default() };
+    let signature = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let signature = signature.trim_end_matches([':', '{', '=']).trim_end().to_string();
+    Some(signature.chars().take(MAX_SIGNATURE_CHARS).collect())
+}
+
+fn line_start(content: &str, offset: usize) -> usize {
+    content[..offset].rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+// Groups the sibling nodes between `start` and `end` into chunks, the comments stay with the definitions following them
+fn chunk_nodes<'a>(content: &'a str, nodes: &[Node], start: usize, end: usize, parents: &[String], max_bytes: usize, chunks: &mut Vec<Chunk<'a>>) {
+    let mut segments: Vec<(usize, Vec<Node>)> = vec![(start, Vec::new())];
+    for (node_idx, node) in nodes.iter().enumerate() {
+        let follows_comment = node_idx > 0 && nodes[node_idx - 1].kind().contains("comment");
+        let node_start = line_start(content, node.start_byte()).max(start);
+        if node_idx > 0 && !follows_comment && node_start > segments.last().map_or(start, |(segment_start, _)| *segment_start) {
+            segments.push((node_start, Vec::new()));
+        }
+        if let Some((_, segment_nodes)) = segments.last_mut() {
+            segment_nodes.push(*node);
+        }
+    }
+
+    let qualified = |signature: String| match parents.is_empty() {
+        true => signature,
+        false => format!("{} > {}", parents.join(" > "), signature),
+    };
+    let mut current: Option<(usize, usize, Vec<String>)> = None;
+    for (segment_idx, (segment_start, segment_nodes)) in segments.iter().enumerate() {
+        let segment_end = segments.get(segment_idx + 1).map_or(end, |(next_start, _)| *next_start);
+        let signatures: Vec<String> = segment_nodes.iter().filter_map(|node| signature(content, *node)).map(&qualified).collect();
+        if segment_end - segment_start > max_bytes {
+            if let Some((chunk_start, chunk_end, chunk_signatures)) = current.take() {
+                chunks.push(chunk(content, chunk_start, chunk_end, chunk_signatures));
+            }
+            let definitions: Vec<Node> = segment_nodes.iter().copied().filter(|node| signature(content, *node).is_some()).collect();
+            let nested = match definitions.as_slice() {
+                [definition_node] => definition(*definition_node).child_by_field_name("body").map(named_children).filter(|nested| !nested.is_empty()),
+                _ => None,
+            };
+            match (nested, definitions.first().and_then(|node| signature(content, *node))) {
+                (Some(nested), Some(parent)) => {
+                    let parents = [parents, &[parent]].concat();
+                    chunk_nodes(content, &nested, *segment_start, segment_end, &parents, max_bytes, chunks);
+                }
+                _ => chunks.extend(line_chunks(content, *segment_start, segment_end, &signatures, max_bytes)),
+            }
+            continue;
+        }
+        current = match current.take() {
+            Some((chunk_start, _, mut chunk_signatures)) if segment_end - chunk_start <= max_bytes => {
+                chunk_signatures.extend(signatures);
+                Some((chunk_start, segment_end, chunk_signatures))
+            }
+            Some((chunk_start, chunk_end, chunk_signatures)) => {
+                chunks.push(chunk(content, chunk_start, chunk_end, chunk_signatures));
+                Some((*segment_start, segment_end, signatures))
+            }
+            None => Some((*segment_start, segment_end, signatures)),
+        };
+    }
+    if let Some((chunk_start, chunk_end, chunk_signatures)) = current {
+        chunks.push(chunk(content, chunk_start, chunk_end, chunk_signatures));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_definitions() {
+        let content = "struct Config {\n    name: String,\n}\n\nimpl Config {\n    pub fn save(&self) -> bool {\n        save(&self.name)\n    }\n}\n\nfn save(name: &str) -> bool {\n    !name.is_empty()\n}\n";
+        let definitions = find_definitions(Path::new("src/config.rs"), content, "save");
+        assert_eq!(definitions, vec![
+            SymbolDefinition { start_line: 6, end_line: 8, signature: "impl Config > pub fn save(&self) -> bool".to_string() },
+            SymbolDefinition { start_line: 11, end_line: 13, signature: "fn save(name: &str) -> bool".to_string() },
+        ]);
+        assert_eq!(language_name(Path::new("web/app.tsx")), Some("typescript"));
+        assert!(supports_language("Python"));
+    }
+}
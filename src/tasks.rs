@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use crate::actions::target::ExecutionTarget;
+use crate::config::{Model, ShellConfig, Task};
+use crate::error::{ActionError, IoResultExt};
+use crate::executor::{self, ExecutionSettings, MAIN_PLAN_LABEL};
+use crate::llm::ask_llm_for_plan;
+use crate::templates;
+use colored::*;
+use reqwest::Client;
+use std::collections::HashMap;
+
+// Only the end of a long verification output is sent to the LLM, the failures are usually reported last
+const MAX_VERIFY_OUTPUT_CHARS: usize = 8000;
+
+// Runs the verification command, returns its output when it fails
+async fn verify(command: &str, shell_config: ShellConfig, target: &dyn ExecutionTarget) -> Result<Option<String>> {
+    let shell = shell_config.resolve();
+    println!("Verifying with `{}`...", command);
+    let output = target.command(shell.program(), &shell.args(command, shell_config.login))
+        .output()
+        .await
+        .with_action_context(|| format!("Failed to run the verification command: {}", command))?;
+    if output.status.success() {
+        return Ok(None);
+    }
+    let output = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    Ok(Some(output))
+}
+
+fn retry_instruction(task: &Task, verify_command: &str, output: &str) -> String {
+    let skipped = output.chars().count().saturating_sub(MAX_VERIFY_OUTPUT_CHARS);
+    let output: String = output.chars().skip(skipped).collect();
+    format!(
+        "{}\n\nThe previous actions were executed but the verification command `{}` still fails with the output{}:\n{}\nMake the verification command succeed.",
+        task.instruction, verify_command, if skipped > 0 { " (the beginning is omitted)" } else { "" }, output.trim_end()
+    )
+}
+
+/// Task with the `{{name}}` placeholders of its instruction replaced with the parameters. The verify command is never
+/// parameterized as the parameters may come from webhooks and it is run without asking
+pub fn with_params(task: &Task, params: &HashMap<String, String>) -> Result<Task> {
+    let instruction = templates::render(&task.instruction, params).context("Failed to fill in the parameters of the task")?;
+    Ok(Task { instruction, ..task.clone() })
+}
+
+/// Plans and executes the instruction of the task, while the verification command of the task fails and attempts are
+/// left a new plan is made taking the failure and the already executed actions into account
+pub async fn run_task(task: &Task, model: &Model, context: &[String], settings: ExecutionSettings, client: &Client) -> Result<()> {
+    let mut execution_history = Vec::new();
+    let mut instruction = task.instruction.clone();
+    let attempts = task.max_attempts.max(1);
+    for attempt in 1..=attempts {
+        let plan = ask_llm_for_plan(model, &instruction, context, &execution_history, &settings, client).await.context("Error during LLM call")?;
+        plan.display();
+        executor::execute_plan(&plan, model, client, &mut execution_history, settings.clone(), &[MAIN_PLAN_LABEL.to_string()]).await?;
+        let Some(verify_command) = &task.verify else {
+            return Ok(());
+        };
+        match verify(verify_command, settings.shell, settings.target.as_ref()).await? {
+            None => {
+                println!("{}", format!("Verification `{}` succeeded.", verify_command).green());
+                return Ok(());
+            }
+            Some(output) => {
+                println!("{}", format!("Verification `{}` failed (attempt {} of {}).", verify_command, attempt, attempts).red());
+                instruction = retry_instruction(task, verify_command, &output);
+            }
+        }
+    }
+    Err(ActionError::CommandFailed {
+        message: format!("The verification `{}` still fails after {} attempts", task.verify.as_deref().unwrap_or_default(), attempts),
+        exit_code: None,
+    }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectConfig;
+
+    #[test]
+    fn test_project_tasks() -> Result<()> {
+        let project_config: ProjectConfig = toml::from_str(r#"
+            [tasks.test-fix]
+            instruction = "Fix the failing tests"
+            verify = "cargo test"
+
+            [tasks.docs]
+            instruction = "Document the public functions"
+            context = ["src/lib.rs"]
+        "#)?;
+        let task = project_config.task("test-fix")?;
+        assert_eq!(task.max_attempts, 3);
+        assert_eq!(project_config.task("docs")?.context, vec!["src/lib.rs".to_string()]);
+        assert!(project_config.task("deploy").unwrap_err().to_string().contains("the defined tasks: docs, test-fix"));
+
+        let review = Task { instruction: "Review the branch {{branch}}".to_string(), ..task.clone() };
+        assert_eq!(with_params(&review, &HashMap::from([("branch".to_string(), "main".to_string())]))?.instruction, "Review the branch main");
+        assert!(with_params(&review, &HashMap::new()).is_err());
+
+        let instruction = retry_instruction(task, "cargo test", &format!("{}test result: FAILED\n", "x".repeat(MAX_VERIFY_OUTPUT_CHARS)));
+        assert!(instruction.starts_with("Fix the failing tests\n\n"));
+        assert!(instruction.contains("`cargo test` still fails with the output (the beginning is omitted):\n"));
+        assert!(instruction.ends_with("test result: FAILED\nMake the verification command succeed."));
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use crate::config::Config;
+use crate::fs::walk_dir;
+
+const TEMPLATES_DIR_NAME: &str = "templates";
+
+pub(crate) fn templates_dir() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join(TEMPLATES_DIR_NAME))
+}
+
+pub(crate) fn template_path(templates_dir: &Path, name: &str) -> Result<PathBuf> {
+    let relative_path = Path::new(name);
+    if !relative_path.components().all(|component| matches!(component, Component::Normal(_))) {
+        anyhow::bail!("Invalid template name '{}', expected a path relative to the templates directory", name);
+    }
+    let path = templates_dir.join(relative_path);
+    if !path.exists() {
+        anyhow::bail!("Template '{}' not found in {}", name, templates_dir.display());
+    }
+    Ok(path)
+}
+
+pub(crate) fn load_template(templates_dir: &Path, name: &str) -> Result<String> {
+    let path = template_path(templates_dir, name)?;
+    fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read template: {}", path.display()))
+}
+
+// Replaces every `{{variable}}` placeholder in `template` with its value, failing on placeholders without a value
+pub(crate) fn render(template: &str, variables: &HashMap<String, String>) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut missing_variables = Vec::new();
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        match variables.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => missing_variables.push(name.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    if !missing_variables.is_empty() {
+        anyhow::bail!("Missing values for template variables: {}", missing_variables.join(", "));
+    }
+    Ok(rendered)
+}
+
+// Copies the project template directory `name` into `target_dir`, rendering both the file paths and the file contents.
+// Nothing is written when a rendered path leaves `target_dir` or, unless `force` is set, a file already exists
+pub(crate) fn instantiate_project(templates_dir: &Path, name: &str, target_dir: &Path, variables: &HashMap<String, String>, force: bool) -> Result<Vec<PathBuf>> {
+    let project_dir = template_path(templates_dir, name)?;
+    if !project_dir.is_dir() {
+        anyhow::bail!("Template '{}' is not a project template directory", name);
+    }
+    let mut files = Vec::new();
+    for entry in walk_dir(&project_dir, None) {
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(&project_dir)?.to_string_lossy().to_string();
+        let rendered_path = render(&relative_path, variables)?;
+        // The variables may contain e.g. `../../.bashrc`
+        if !Path::new(&rendered_path).components().all(|component| matches!(component, Component::Normal(_))) {
+            anyhow::bail!("The path '{}' of template '{}' renders to '{}' outside of the target directory", relative_path, name, rendered_path);
+        }
+        files.push((entry.into_path(), target_dir.join(rendered_path)));
+    }
+    let existing_files: Vec<String> = files.iter().filter(|(_, target_path)| target_path.exists()).map(|(_, target_path)| target_path.display().to_string()).collect();
+    if !force && !existing_files.is_empty() {
+        anyhow::bail!("The files already exist, use --force to overwrite them: {}", existing_files.join(", "));
+    }
+    let mut created_files = Vec::new();
+    for (template_path, target_path) in files {
+        let template_content = fs::read_to_string(&template_path)
+            .with_context(|| format!("Failed to read template: {}", template_path.display()))?;
+        if let Some(parent_dir) = target_path.parent() {
+            fs::create_dir_all(parent_dir)
+                .with_context(|| format!("Failed to create parent directories for '{}'", target_path.display()))?;
+        }
+        fs::write(&target_path, render(&template_content, variables)?)
+            .with_context(|| format!("Failed to write file: {}", target_path.display()))?;
+        created_files.push(target_path);
+    }
+    Ok(created_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let variables = HashMap::from([
+            ("name".to_string(), "cliff".to_string()),
+            ("version".to_string(), "0.1.0".to_string()),
+        ]);
+        assert_eq!(render("[package]\nname = \"{{name}}\"\nversion = \"{{ version }}\"", &variables)?, "[package]\nname = \"cliff\"\nversion = \"0.1.0\"");
+        assert_eq!(render("no placeholders {{", &variables)?, "no placeholders {{");
+
+        let error = render("{{name}} by {{author}}", &variables).unwrap_err();
+        assert_eq!(error.to_string(), "Missing values for template variables: author");
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_template() -> Result<()> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join("rust"))?;
+        fs::write(dir.path().join("rust").join("main.rs"), "fn main() {}")?;
+
+        assert_eq!(load_template(dir.path(), "rust/main.rs")?, "fn main() {}");
+        assert!(load_template(dir.path(), "rust/lib.rs").is_err());
+        assert!(load_template(dir.path(), "../main.rs").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_instantiate_project() -> Result<()> {
+        let templates = tempdir()?;
+        let target = tempdir()?;
+        fs::create_dir_all(templates.path().join("rust-cli").join("src"))?;
+        fs::write(templates.path().join("rust-cli").join("Cargo.toml"), "[package]\nname = \"{{name}}\"")?;
+        fs::write(templates.path().join("rust-cli").join("src").join("{{name}}.rs"), "// {{name}}")?;
+        let variables = HashMap::from([("name".to_string(), "hello".to_string())]);
+
+        let created_files = instantiate_project(templates.path(), "rust-cli", target.path(), &variables, false)?;
+
+        assert_eq!(created_files, vec![target.path().join("Cargo.toml"), target.path().join("src").join("hello.rs")]);
+        assert_eq!(fs::read_to_string(target.path().join("Cargo.toml"))?, "[package]\nname = \"hello\"");
+        assert_eq!(fs::read_to_string(target.path().join("src").join("hello.rs"))?, "// hello");
+
+        fs::write(target.path().join("Cargo.toml"), "[package]\nname = \"edited\"")?;
+        assert!(instantiate_project(templates.path(), "rust-cli", target.path(), &variables, false).is_err());
+        assert_eq!(fs::read_to_string(target.path().join("Cargo.toml"))?, "[package]\nname = \"edited\"");
+        instantiate_project(templates.path(), "rust-cli", target.path(), &variables, true)?;
+        assert_eq!(fs::read_to_string(target.path().join("Cargo.toml"))?, "[package]\nname = \"hello\"");
+
+        let escaping = HashMap::from([("name".to_string(), "../../escaped".to_string())]);
+        assert!(instantiate_project(templates.path(), "rust-cli", target.path(), &escaping, true).is_err());
+        assert!(!target.path().join("../escaped.rs").exists());
+        Ok(())
+    }
+}
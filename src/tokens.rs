@@ -0,0 +1,74 @@
+// Estimation of the number of tokens of a text without the vocabulary of the model: the text is split the way
+// BPE tokenizers pre-tokenize it and the pieces are counted by their typical token lengths
+
+// Short words are usually a single token, longer ones are split into pieces of about 4 characters
+const MAX_SINGLE_TOKEN_WORD_CHARS: usize = 6;
+const CHARS_PER_WORD_PIECE: usize = 4;
+// Digits are grouped by up to 3 into a token
+const DIGITS_PER_TOKEN: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Whitespace,
+    Newline,
+    // Punctuation, symbols and the characters of the scripts without spaces between words, a token each
+    Other,
+}
+
+fn classify(c: char) -> CharClass {
+    if c == '\n' {
+        CharClass::Newline
+    } else if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if c.is_ascii_digit() {
+        CharClass::Digit
+    } else if c.is_alphabetic() && (c.is_ascii() || !is_ideographic(c)) {
+        CharClass::Letter
+    } else {
+        CharClass::Other
+    }
+}
+
+// CJK, Hiragana, Katakana and Hangul are mostly tokenized character by character
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut run_class = None;
+    let mut run_length = 0;
+    for class in text.chars().map(classify).chain(std::iter::once(CharClass::Whitespace)) {
+        if Some(class) == run_class && class != CharClass::Other {
+            run_length += 1;
+            continue;
+        }
+        tokens += match run_class {
+            Some(CharClass::Letter) if run_length <= MAX_SINGLE_TOKEN_WORD_CHARS => 1,
+            Some(CharClass::Letter) => run_length.div_ceil(CHARS_PER_WORD_PIECE),
+            Some(CharClass::Digit) => run_length.div_ceil(DIGITS_PER_TOKEN),
+            // Spaces are merged into the following word, consecutive newlines are a single token
+            Some(CharClass::Whitespace) | None => 0,
+            Some(CharClass::Newline) | Some(CharClass::Other) => 1,
+        };
+        run_class = Some(class);
+        run_length = 1;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello, world!"), 4);
+        assert_eq!(estimate_tokens("fn main() {\n    println!(\"2024\");\n}\n"), 19);
+        assert_eq!(estimate_tokens("internationalization"), 5);
+        assert_eq!(estimate_tokens("你好"), 2);
+    }
+}
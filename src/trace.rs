@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use crate::error::ActionError;
+use crate::profiles;
+use crate::runs::{RunMetadata, RunStatus};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TRACES_DIR_NAME: &str = "traces";
+// The traces of the older runs are deleted
+const MAX_TRACES: usize = 100;
+
+// Spans recorded since the last run finished
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+
+/// Timed part of a run: the run itself, a plan, an action or an LLM call
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Span {
+    pub name: String,
+    pub category: String,
+    // Microseconds since the Unix epoch
+    pub start_us: u64,
+    pub duration_us: u64,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// Chrome trace-event JSON, opened by chrome://tracing, Perfetto or speedscope
+    Chrome,
+    /// OpenTelemetry OTLP/JSON, accepted by the OTLP HTTP endpoints of the collectors and the tracing backends
+    Otlp,
+}
+
+fn micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_micros() as u64).unwrap_or_default()
+}
+
+/// Records a span which started at `started` and ends now
+pub fn record(name: impl Into<String>, category: &str, started: SystemTime, attributes: &[(&str, String)]) {
+    let start_us = micros(started);
+    let span = Span {
+        name: name.into(),
+        category: category.to_string(),
+        start_us,
+        duration_us: micros(SystemTime::now()).saturating_sub(start_us),
+        attributes: attributes.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+    };
+    SPANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(span);
+}
+
+fn traces_dir() -> Result<PathBuf> {
+    Ok(profiles::data_dir()?.join(TRACES_DIR_NAME))
+}
+
+/// Saves the spans recorded during the finished run together with the span of the run, the traces of the older runs
+/// beyond the latest ones are deleted
+pub fn save(run: &RunMetadata) -> Result<()> {
+    let mut spans = std::mem::take(&mut *SPANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    let start_us = spans.iter().map(|span| span.start_us).min().unwrap_or_default().min(run.started_at * 1_000_000);
+    let mut attributes = BTreeMap::from([
+        ("run.id".to_string(), run.id.clone()),
+        ("run.model".to_string(), run.model.clone()),
+        ("run.status".to_string(), format!("{:?}", run.status)),
+    ]);
+    if let Some(tokens) = &run.tokens {
+        attributes.insert("llm.prompt_tokens".to_string(), tokens.prompt_tokens.to_string());
+        attributes.insert("llm.completion_tokens".to_string(), tokens.completion_tokens.to_string());
+    }
+    if let Some(error) = &run.error {
+        attributes.insert("error".to_string(), error.clone());
+    }
+    spans.insert(0, Span {
+        name: format!("{}: {}", run.command, run.instruction),
+        category: "run".to_string(),
+        start_us,
+        duration_us: micros(SystemTime::now()).saturating_sub(start_us),
+        attributes,
+    });
+    let dir = traces_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create the traces directory: {:?}", dir))?;
+    let path = dir.join(format!("{}.json", run.id));
+    fs::write(&path, serde_json::to_string(&spans)?).with_context(|| format!("Failed to write the trace: {:?}", path))?;
+
+    let mut traces: Vec<_> = fs::read_dir(&dir)?.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    if traces.len() > MAX_TRACES {
+        traces.sort_by_key(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok());
+        for old in &traces[..traces.len() - MAX_TRACES] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(())
+}
+
+/// Spans of the saved trace of the run
+pub fn load(run_id: &str) -> Result<Vec<Span>> {
+    let path = traces_dir()?.join(format!("{}.json", run_id));
+    let content = fs::read_to_string(&path)
+        .map_err(|e| ActionError::from_io(format!("No trace of the run '{}' was found", run_id), e))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse the trace: {:?}", path))
+}
+
+/// The spans in the format, nested by their timing: the spans of a sub-plan are inside the span of its action
+pub fn export(spans: &[Span], run_id: &str, format: TraceFormat) -> Value {
+    match format {
+        TraceFormat::Chrome => chrome_trace(spans),
+        TraceFormat::Otlp => otlp_trace(spans, run_id),
+    }
+}
+
+fn chrome_trace(spans: &[Span]) -> Value {
+    let events: Vec<Value> = spans
+        .iter()
+        .map(|span| json!({
+            "name": span.name,
+            "cat": span.category,
+            "ph": "X",
+            "ts": span.start_us,
+            "dur": span.duration_us,
+            "pid": 1,
+            "tid": 1,
+            "args": span.attributes,
+        }))
+        .collect();
+    json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+}
+
+// Index of the parent of every span, the innermost span containing it
+fn parents(spans: &[Span]) -> Vec<Option<usize>> {
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| (spans[i].start_us, std::cmp::Reverse(spans[i].duration_us), i));
+    let mut parents = vec![None; spans.len()];
+    let mut open: Vec<usize> = Vec::new();
+    for i in order {
+        let end = |j: usize| spans[j].start_us + spans[j].duration_us;
+        while open.last().is_some_and(|&j| end(j) < end(i)) {
+            open.pop();
+        }
+        parents[i] = open.last().copied();
+        open.push(i);
+    }
+    parents
+}
+
+fn otlp_trace(spans: &[Span], run_id: &str) -> Value {
+    let hash = digest::digest(&digest::SHA256, run_id.as_bytes());
+    let trace_id: String = hash.as_ref()[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let span_id = |i: usize| format!("{:016x}", i + 1);
+    let parents = parents(spans);
+    let otlp_spans: Vec<Value> = spans
+        .iter()
+        .enumerate()
+        .map(|(i, span)| {
+            let attributes: Vec<Value> = std::iter::once(("cliff.category", &span.category))
+                .chain(span.attributes.iter().map(|(key, value)| (key.as_str(), value)))
+                .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+                .collect();
+            let failed = span.attributes.contains_key("error") || span.attributes.get("run.status").is_some_and(|status| status == &format!("{:?}", RunStatus::Failed));
+            json!({
+                "traceId": trace_id,
+                "spanId": span_id(i),
+                "parentSpanId": parents[i].map(span_id).unwrap_or_default(),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": (span.start_us * 1000).to_string(),
+                "endTimeUnixNano": ((span.start_us + span.duration_us) * 1000).to_string(),
+                "attributes": attributes,
+                "status": { "code": if failed { 2 } else { 1 } },
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": "cliff" } }] },
+            "scopeSpans": [{ "scope": { "name": "cliff", "version": env!("CARGO_PKG_VERSION") }, "spans": otlp_spans }],
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(name: &str, start_us: u64, duration_us: u64) -> Span {
+        Span { name: name.to_string(), category: "action".to_string(), start_us, duration_us, attributes: BTreeMap::new() }
+    }
+
+    #[test]
+    fn test_trace_export() {
+        let spans = [span("run", 0, 100), span("plan main", 5, 90), span("ask_llm_for_plan", 10, 50), span("plan sub", 20, 30), span("run_command", 70, 10)];
+        assert_eq!(parents(&spans), [None, Some(0), Some(1), Some(2), Some(1)]);
+
+        let otlp = export(&spans, "run-1", TraceFormat::Otlp);
+        let otlp_spans = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(otlp_spans[3]["parentSpanId"], "0000000000000003");
+        assert_eq!(otlp_spans[4]["endTimeUnixNano"], "80000");
+        assert_eq!(otlp_spans[0]["traceId"].as_str().map(str::len), Some(32));
+
+        let chrome = export(&spans, "run-1", TraceFormat::Chrome);
+        assert_eq!(chrome["traceEvents"][4], json!({ "name": "run_command", "cat": "action", "ph": "X", "ts": 70, "dur": 10, "pid": 1, "tid": 1, "args": {} }));
+    }
+}
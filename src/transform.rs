@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use crate::config::Model;
+use crate::llm::ask_llm_to_transform;
+use reqwest::Client;
+
+// Splits the content into chunks of whole lines of at most `max_bytes` bytes, a longer line is a chunk of its own
+pub fn chunk_lines(content: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk_end = 0;
+    for line in content.split_inclusive('\n') {
+        if chunk_end > chunk_start && chunk_end - chunk_start + line.len() > max_bytes {
+            chunks.push(&content[chunk_start..chunk_end]);
+            chunk_start = chunk_end;
+        }
+        chunk_end += line.len();
+    }
+    if chunk_end > chunk_start {
+        chunks.push(&content[chunk_start..chunk_end]);
+    }
+    chunks
+}
+
+// Removes the code fence the LLM may wrap the whole output in
+fn strip_code_fence(output: &str) -> &str {
+    let trimmed = output.trim();
+    match (trimmed.strip_prefix("```"), trimmed.strip_suffix("```")) {
+        (Some(_), Some(_)) if trimmed.len() > 6 => {
+            let inner = &trimmed[..trimmed.len() - 3];
+            inner.split_once('\n').map_or("", |(_, body)| body.trim_end_matches('\n'))
+        }
+        _ => trimmed,
+    }
+}
+
+/// Transforms the content according to the instruction chunk by chunk, the transformed chunks are concatenated
+pub async fn transform(model_config: &Model, instruction: &str, path: &str, content: &str, chunk_size: usize, client: &Client) -> Result<String> {
+    let chunks = chunk_lines(content, chunk_size);
+    let mut transformed = Vec::new();
+    for (chunk_idx, chunk) in chunks.iter().enumerate() {
+        if chunks.len() > 1 {
+            println!("Transforming part {} of {}...", chunk_idx + 1, chunks.len());
+        }
+        let output = ask_llm_to_transform(model_config, instruction, path, chunk, chunk_idx + 1, chunks.len(), client)
+            .await
+            .with_context(|| format!("Failed to transform part {} of {}", chunk_idx + 1, chunks.len()))?;
+        transformed.push(strip_code_fence(&output).to_string());
+    }
+    let mut result = transformed.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_lines() {
+        let content = "a,b\n1,2\n3,4\nlong line\n";
+        assert_eq!(chunk_lines(content, 8), vec!["a,b\n1,2\n", "3,4\n", "long line\n"]);
+        assert_eq!(chunk_lines(content, 1024), vec![content]);
+        assert!(chunk_lines("", 8).is_empty());
+    }
+
+    #[test]
+    fn test_strip_code_fence() {
+        assert_eq!(strip_code_fence("```json\n[{\"a\": 1}]\n```\n"), "[{\"a\": 1}]");
+        assert_eq!(strip_code_fence("[{\"a\": 1}]\n"), "[{\"a\": 1}]");
+    }
+}
@@ -0,0 +1,102 @@
+use crate::actions::ActionOutput;
+use crate::actions::run_command::strip_ansi;
+use crate::config::TrimmingConfig;
+use crate::executor::ExecutedAction;
+use std::sync::OnceLock;
+
+static RULES: OnceLock<TrimmingConfig> = OnceLock::new();
+
+/// Rules trimming the outputs in the history sent to the LLM, the default rules are used when not set
+pub fn use_rules(rules: TrimmingConfig) {
+    let _ = RULES.set(rules);
+}
+
+fn rules() -> TrimmingConfig {
+    RULES.get().copied().unwrap_or_default()
+}
+
+/// Copy of the history with the outputs trimmed for a prompt, the history itself keeps the full outputs
+pub fn for_prompt(history: &[ExecutedAction]) -> Vec<ExecutedAction> {
+    history
+        .iter()
+        .map(|executed| ExecutedAction { output: trim_output(&executed.output, &rules()), ..executed.clone() })
+        .collect()
+}
+
+pub fn trim_output(output: &ActionOutput, rules: &TrimmingConfig) -> ActionOutput {
+    match output {
+        ActionOutput::Text { text } => ActionOutput::text(trim_text(text, rules)),
+        ActionOutput::Command { exit_code, stdout, stderr } => ActionOutput::Command {
+            exit_code: *exit_code,
+            stdout: trim_text(stdout, rules),
+            stderr: trim_text(stderr, rules),
+        },
+        ActionOutput::Listing { entries } => ActionOutput::Listing { entries: keep_head_and_tail(entries.clone(), rules) },
+        ActionOutput::Error { kind, message } => ActionOutput::Error { kind: kind.clone(), message: trim_text(message, rules) },
+        output => output.clone(),
+    }
+}
+
+/// The text without the ANSI codes and the overwritten progress, with the repeated lines collapsed, the long lines
+/// shortened and only the head and the tail of the many lines kept
+pub fn trim_text(text: &str, rules: &TrimmingConfig) -> String {
+    let text = if rules.strip_ansi { strip_ansi(text) } else { text.to_string() };
+    let mut lines: Vec<String> = Vec::new();
+    let mut repeats = 0;
+    for line in text.lines() {
+        // Only the last state of a line redrawn with carriage returns, e.g. of a progress bar, is visible
+        let line = line.trim_end_matches('\r').rsplit('\r').next().unwrap_or_default();
+        let line = match line.char_indices().nth(rules.max_line_chars) {
+            Some((end, _)) if rules.max_line_chars > 0 => format!("{}[... {} characters omitted]", &line[..end], line[end..].chars().count()),
+            _ => line.to_string(),
+        };
+        if rules.collapse_repeated && lines.last() == Some(&line) {
+            repeats += 1;
+            continue;
+        }
+        if repeats > 0 {
+            lines.push(format!("[... previous line repeated {} more times]", repeats));
+            repeats = 0;
+        }
+        lines.push(line);
+    }
+    if repeats > 0 {
+        lines.push(format!("[... previous line repeated {} more times]", repeats));
+    }
+    let trimmed = keep_head_and_tail(lines, rules).join("\n");
+    if text.ends_with('\n') && !trimmed.is_empty() { trimmed + "\n" } else { trimmed }
+}
+
+fn keep_head_and_tail(mut lines: Vec<String>, rules: &TrimmingConfig) -> Vec<String> {
+    let kept = rules.head_lines + rules.tail_lines;
+    if kept == 0 || lines.len() <= kept + 1 {
+        return lines;
+    }
+    let omitted = lines.len() - kept;
+    lines.splice(rules.head_lines..rules.head_lines + omitted, [format!("[... {} lines omitted ...]", omitted)]);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_text() {
+        let rules = TrimmingConfig { head_lines: 2, tail_lines: 1, max_line_chars: 10, ..TrimmingConfig::default() };
+        let log = "\x1b[1m\x1b[32m   Compiling\x1b[0m cliff\nwarning\nwarning\nwarning\n 10%\r 50%\r100%\nerror: a very long message\nline 7\nline 8\n";
+        assert_eq!(
+            trim_text(log, &rules),
+            "   Compili[... 8 characters omitted]\nwarning\n[... 4 lines omitted ...]\nline 8\n"
+        );
+        assert_eq!(trim_text("a\na\na\nb", &rules), "a\n[... previous line repeated 2 more times]\nb");
+        assert_eq!(trim_text("a\nb\nc\nd", &rules), "a\nb\nc\nd");
+
+        let unlimited = TrimmingConfig { head_lines: 0, tail_lines: 0, max_line_chars: 0, strip_ansi: false, collapse_repeated: false };
+        assert_eq!(trim_text("\x1b[31mred\x1b[0m\nx\nx", &unlimited), "\x1b[31mred\x1b[0m\nx\nx");
+        assert_eq!(
+            trim_output(&ActionOutput::Listing { entries: (1..=5).map(|i| i.to_string()).collect() }, &rules),
+            ActionOutput::Listing { entries: vec!["1".to_string(), "2".to_string(), "[... 2 lines omitted ...]".to_string(), "5".to_string()] }
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use crate::error::ActionError;
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+/// Scheme of the API URLs of the models served on a Unix socket, `unix:///path/to/socket[:/http/path]`
+pub const SCHEME: &str = "unix://";
+
+pub fn is_unix_socket(api_url: &str) -> bool {
+    api_url.starts_with(SCHEME)
+}
+
+// Socket path and HTTP path of the API URL, the HTTP path follows the socket path after ':' and is `/` by default
+fn split(api_url: &str) -> Option<(&str, &str)> {
+    let rest = api_url.strip_prefix(SCHEME)?;
+    match rest.split_once(":/") {
+        Some((socket, _)) => Some((socket, &rest[socket.len() + 1..])),
+        None => Some((rest, "/")),
+    }
+}
+
+/// URL the request of the API URL is built with, reqwest only accepts HTTP URLs: the HTTP path on localhost for a socket
+pub fn request_url(api_url: &str) -> String {
+    match split(api_url) {
+        Some((_, http_path)) => format!("http://localhost{}", http_path),
+        None => api_url.to_string(),
+    }
+}
+
+/// Sends the request built with the `request_url` of the `unix://` API URL over a new connection to the socket, reqwest
+/// only connects over TCP
+#[cfg(unix)]
+pub async fn send(api_url: &str, request: reqwest::Request) -> Result<reqwest::Response> {
+    use hyper_util::client::legacy::Client;
+    use hyper_util::rt::TokioExecutor;
+    use hyperlocal::UnixConnector;
+
+    static CLIENT: OnceLock<Client<UnixConnector, reqwest::Body>> = OnceLock::new();
+    let socket = socket_path(api_url)?;
+    let mut request = http::Request::try_from(request)?;
+    let http_path = request.uri().path_and_query().map_or("/", |path| path.as_str()).to_string();
+    *request.uri_mut() = hyperlocal::Uri::new(socket, &http_path).into();
+    let client = CLIENT.get_or_init(|| Client::builder(TokioExecutor::new()).build(UnixConnector));
+    let response = client.request(request).await
+        .map_err(|e| ActionError::Network(format!("Failed to send request to the socket {}: {}", socket, e)))?;
+    Ok(reqwest::Response::from(response.map(reqwest::Body::wrap)))
+}
+
+#[cfg(not(unix))]
+pub async fn send(api_url: &str, _request: reqwest::Request) -> Result<reqwest::Response> {
+    let socket = socket_path(api_url)?;
+    Err(ActionError::InvalidInput(format!("The socket {} can't be used, Unix sockets are not supported on this platform", socket)).into())
+}
+
+// Socket path of the API URL, failing when the socket is missing
+fn socket_path(api_url: &str) -> Result<&str> {
+    let (socket, _) = split(api_url).filter(|(socket, _)| !socket.is_empty())
+        .ok_or_else(|| ActionError::InvalidInput(format!("The API URL {} has no socket path, use unix:///path/to/socket", api_url)))?;
+    if !Path::new(socket).exists() {
+        return Err(ActionError::Network(format!("The socket {} does not exist, check that the local server is running", socket)).into());
+    }
+    Ok(socket)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_request_over_unix_socket() -> Result<()> {
+        assert_eq!(split("unix:///run/llm.sock:/v1/chat/completions"), Some(("/run/llm.sock", "/v1/chat/completions")));
+        assert_eq!(split("unix:///run/llm.sock"), Some(("/run/llm.sock", "/")));
+
+        let dir = tempfile::tempdir()?;
+        let socket = dir.path().join("llm.sock");
+        let listener = UnixListener::bind(&socket)?;
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = vec![0u8; 4096];
+            let read = stream.read(&mut request).await?;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 16\r\nConnection: close\r\n\r\n{\"answer\": \"OK\"}").await?;
+            Ok::<_, anyhow::Error>(String::from_utf8_lossy(&request[..read]).to_string())
+        });
+
+        let client = reqwest::Client::new();
+        let api_url = format!("unix://{}:/v1/chat", socket.display());
+        let body = send(&api_url, client.post(request_url(&api_url)).body("{}").build()?).await?.text().await?;
+
+        assert_eq!(body, "{\"answer\": \"OK\"}");
+        assert!(server.await??.starts_with("POST /v1/chat HTTP/1.1"));
+        assert!(send("unix:///missing/llm.sock", client.post(request_url("unix:///missing/llm.sock")).build()?).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Context, Result};
+use crate::error::{ActionError, IoResultExt};
+use crate::profiles;
+use ring::digest;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/amoilanen/cliff/releases/latest";
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const UPDATE_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+// Result of the last passive check for a new version, the check is done at most once a day
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+struct UpdateCheckState {
+    checked_at: u64,
+    latest_version: Option<String>,
+}
+
+impl UpdateCheckState {
+    const STATE_FILENAME: &'static str = "update_check.toml";
+
+    fn state_path() -> Result<PathBuf> {
+        Ok(profiles::data_dir()?.join(Self::STATE_FILENAME))
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(UpdateCheckState::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read update check state: {:?}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse update check state: {:?}", path))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .with_context(|| format!("Failed to create data directory: {:?}", parent))?;
+        }
+        let content = toml::to_string_pretty(self)
+            .with_context(|| "Failed to serialize update check state")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write update check state: {:?}", path))
+    }
+}
+
+// Name of the release artifact for the current platform, e.g. `cliff-x86_64-linux` or `cliff-x86_64-windows.exe`
+fn asset_name() -> String {
+    format!("cliff-{}-{}{}", std::env::consts::ARCH, std::env::consts::OS, std::env::consts::EXE_SUFFIX)
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.trim_start_matches('v').split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+// Checks the integrity of the artifact against the `<sha256 hex> [file name]` content of its checksum file. The checksum
+// is published with the same release, so it detects a corrupted or truncated download but does not authenticate it
+fn check_integrity(artifact: &[u8], checksum_file: &str) -> Result<()> {
+    let expected = checksum_file.split_whitespace().next().unwrap_or_default().to_lowercase();
+    let actual: String = digest::digest(&digest::SHA256, artifact)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if expected != actual {
+        return Err(ActionError::PolicyDenied(format!(
+            "The checksum of the downloaded release {} does not match the published checksum {}, the download is corrupted and the binary was not replaced", actual, expected
+        )).into());
+    }
+    Ok(())
+}
+
+async fn fetch_latest_release(client: &Client) -> Result<Release> {
+    let response = client.get(LATEST_RELEASE_URL)
+        .header("User-Agent", format!("cliff/{}", CURRENT_VERSION))
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| ActionError::from_reqwest(format!("Failed to fetch the latest release from {}", LATEST_RELEASE_URL), e))?;
+    if !response.status().is_success() {
+        return Err(ActionError::Network(format!("Failed to fetch the latest release, status: {}", response.status())).into());
+    }
+    response.json().await.with_context(|| "Failed to parse the latest release")
+}
+
+async fn download(client: &Client, asset: &ReleaseAsset) -> Result<Vec<u8>> {
+    let response = client.get(&asset.browser_download_url)
+        .header("User-Agent", format!("cliff/{}", CURRENT_VERSION))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| ActionError::from_reqwest(format!("Failed to download {}", asset.name), e))?;
+    Ok(response.bytes().await
+        .map_err(|e| ActionError::from_reqwest(format!("Failed to download {}", asset.name), e))?
+        .to_vec())
+}
+
+// Replaces the running binary: the new binary is written next to it and renamed over it, on Windows the running
+// binary cannot be overwritten and is moved aside first
+fn replace_current_binary(binary: &[u8]) -> Result<PathBuf> {
+    let current_path = std::env::current_exe().with_action_context(|| "Failed to locate the current binary".to_string())?;
+    let new_path = current_path.with_extension("update");
+    fs::write(&new_path, binary).with_action_context(|| format!("Failed to write {}", new_path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(0o755))
+            .with_action_context(|| format!("Failed to make {} executable", new_path.display()))?;
+    }
+    #[cfg(windows)]
+    {
+        let old_path = current_path.with_extension("old.exe");
+        let _ = fs::remove_file(&old_path);
+        fs::rename(&current_path, &old_path)
+            .with_action_context(|| format!("Failed to move aside {}", current_path.display()))?;
+    }
+    fs::rename(&new_path, &current_path)
+        .with_action_context(|| format!("Failed to replace {}", current_path.display()))?;
+    Ok(current_path)
+}
+
+/// Downloads the latest release for the current platform, checks its integrity and replaces the running binary
+pub async fn self_update(client: &Client, check_only: bool) -> Result<()> {
+    let release = fetch_latest_release(client).await?;
+    if !is_newer(&release.tag_name, CURRENT_VERSION) {
+        println!("cliff {} is up to date.", CURRENT_VERSION);
+        return Ok(());
+    }
+    println!("A new version is available: {} (current {}).", release.tag_name, CURRENT_VERSION);
+    if check_only {
+        return Ok(());
+    }
+    let asset_name = asset_name();
+    let checksum_name = format!("{}.sha256", asset_name);
+    let find_asset = |name: &str| release.assets.iter().find(|asset| asset.name == name)
+        .ok_or_else(|| ActionError::NotFound(format!("The release {} has no artifact '{}'", release.tag_name, name)));
+    let (asset, checksum_asset) = (find_asset(&asset_name)?, find_asset(&checksum_name)?);
+
+    println!("Downloading {}...", asset.name);
+    let binary = download(client, asset).await?;
+    let checksum = download(client, checksum_asset).await?;
+    check_integrity(&binary, &String::from_utf8_lossy(&checksum))?;
+    let path = replace_current_binary(&binary)?;
+    println!("Updated {} to {}.", path.display(), release.tag_name);
+    Ok(())
+}
+
+/// Prints a notice when a newer release exists, the releases are checked at most once a day and failures are ignored
+pub async fn notify_if_outdated(client: &Client) {
+    let Ok(path) = UpdateCheckState::state_path() else { return };
+    let mut state = UpdateCheckState::load_from(&path).unwrap_or_default();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    if now.saturating_sub(state.checked_at) >= UPDATE_CHECK_INTERVAL_SECS {
+        if let Ok(Ok(release)) = tokio::time::timeout(UPDATE_CHECK_TIMEOUT, fetch_latest_release(client)).await {
+            state.latest_version = Some(release.tag_name);
+        }
+        state.checked_at = now;
+        let _ = state.save_to(&path);
+    }
+    if let Some(latest_version) = state.latest_version.filter(|latest| is_newer(latest, CURRENT_VERSION)) {
+        eprintln!("A new version of cliff is available: {} (current {}), run `cliff self-update` to install it.", latest_version, CURRENT_VERSION);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_version_and_checksum() {
+        assert!(is_newer("v0.10.0", "0.9.3"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+
+        let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  cliff-x86_64-linux\n";
+        assert!(check_integrity(b"hello", checksum).is_ok());
+        assert!(check_integrity(b"tampered", checksum).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use crate::config::ShellConfig;
+use crate::error::IoResultExt;
+use crate::json;
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+// Check of the answer of `ask`, the answers failing it are asked to be corrected
+#[derive(Debug, Clone)]
+pub enum Validator {
+    // The answer has to match the regular expression
+    Regex(Regex),
+    // The answer has to be JSON conforming to the schema read from the file
+    Schema { path: String, schema: Value },
+    // The command receiving the answer on its standard input has to succeed, e.g. `jq .`
+    Command(String),
+}
+
+impl fmt::Display for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Validator::Regex(regex) => write!(f, "regex `{}`", regex),
+            Validator::Schema { path, .. } => write!(f, "JSON schema {}", path),
+            Validator::Command(command) => write!(f, "command `{}`", command),
+        }
+    }
+}
+
+/// Parses `regex:<pattern>`, `schema:<file>` or `cmd:<command>`, any other value is the command
+pub fn parse_validator(value: &str) -> Result<Validator> {
+    if let Some(pattern) = value.strip_prefix("regex:") {
+        return Ok(Validator::Regex(Regex::new(pattern)?));
+    }
+    if let Some(path) = value.strip_prefix("schema:") {
+        let content = std::fs::read_to_string(path)
+            .with_action_context(|| format!("Failed to read the JSON schema: {}", path))?;
+        let schema = serde_json::from_str(&content).map_err(|e| anyhow!("Invalid JSON schema {}: {}", path, e))?;
+        return Ok(Validator::Schema { path: path.to_string(), schema });
+    }
+    let command = value.strip_prefix("cmd:").unwrap_or(value).trim();
+    if command.is_empty() {
+        return Err(anyhow!("Empty validator command"));
+    }
+    Ok(Validator::Command(command.to_string()))
+}
+
+impl Validator {
+    /// The reason why the answer fails the validation, None when it passes, the failures to run the command are errors
+    pub async fn check(&self, answer: &str, shell_config: ShellConfig) -> Result<Option<String>> {
+        match self {
+            Validator::Regex(regex) => Ok((!regex.is_match(answer)).then(|| format!("it does not match the regex `{}`", regex))),
+            Validator::Schema { schema, .. } => {
+                let value: Value = match serde_json::from_str(json::strip_json_fence(answer)) {
+                    Ok(value) => value,
+                    Err(e) => return Ok(Some(format!("it is not valid JSON: {}", e))),
+                };
+                let violations = json::schema_violations(&value, schema);
+                Ok((!violations.is_empty()).then(|| format!("it does not conform to the JSON schema: {}", violations.join("; "))))
+            }
+            Validator::Command(command) => check_with_command(answer, command, shell_config).await,
+        }
+    }
+}
+
+/// The reason why the answer fails the first of the validators failing it
+pub async fn first_violation(answer: &str, validators: &[Validator], shell_config: ShellConfig) -> Result<Option<String>> {
+    for validator in validators {
+        if let Some(violation) = validator.check(answer, shell_config).await? {
+            return Ok(Some(violation));
+        }
+    }
+    Ok(None)
+}
+
+async fn check_with_command(answer: &str, command: &str, shell_config: ShellConfig) -> Result<Option<String>> {
+    let shell = shell_config.resolve();
+    let mut child = tokio::process::Command::new(shell.program())
+        .args(shell.args(command, shell_config.login))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_action_context(|| format!("Failed to execute the validator: {}", command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = answer.to_string();
+        // Written while the output is read so that a validator echoing its input does not block, it may exit without reading it all
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
+    let output = child.wait_with_output().await
+        .with_action_context(|| format!("Failed to wait for the validator: {}", command))?;
+    if output.status.success() {
+        return Ok(None);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(Some(match stderr.trim() {
+        "" => format!("`{}` failed with {}", command, output.status),
+        stderr => format!("`{}` failed with {}: {}", command, output.status, stderr),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_regex_and_schema_validators() -> Result<()> {
+        let shell = ShellConfig::default();
+        let regex = parse_validator(r"regex:^\d+$")?;
+        assert_eq!(regex.check("42", shell).await?, None);
+        assert_eq!(regex.check("forty two", shell).await?.as_deref(), Some(r"it does not match the regex `^\d+$`"));
+
+        let dir = tempfile::tempdir()?;
+        let schema_path = dir.path().join("schema.json");
+        std::fs::write(&schema_path, r#"{"type": "object", "required": ["name"]}"#)?;
+        let schema = parse_validator(&format!("schema:{}", schema_path.display()))?;
+        assert_eq!(schema.check("```json\n{\"name\": \"cliff\"}\n```", shell).await?, None);
+        assert_eq!(schema.check("{}", shell).await?.as_deref(), Some("it does not conform to the JSON schema: /: missing required property 'name'"));
+        assert!(schema.check("not json", shell).await?.is_some_and(|violation| violation.starts_with("it is not valid JSON")));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_validator() -> Result<()> {
+        let shell = ShellConfig::default();
+        let validator = parse_validator("grep -q cliff")?;
+        assert!(matches!(&validator, Validator::Command(command) if command == "grep -q cliff"));
+        assert_eq!(validator.check("cliff answers", shell).await?, None);
+        let failing = parse_validator("cmd:echo 'no JSON' >&2; exit 3")?;
+        let violation = failing.check("{", shell).await?.unwrap_or_default();
+        assert!(violation.contains("no JSON"), "{}", violation);
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use crate::error::{ActionError, IoResultExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// Fixtures the responses of the web requests are recorded to or replayed from, the requests go to the web as they are
+// without `--record-web` and `--replay-web`
+static FIXTURES: OnceLock<Fixtures> = OnceLock::new();
+
+/// Response of a web page, the web search or a context URL, kept in the fixtures file under its URL, the body of an image
+/// is its data URL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl WebResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+// The JSON file of the responses by URL, sorted so that the changes of the recorded fixtures are easy to review
+struct Fixtures {
+    path: PathBuf,
+    replay: bool,
+    responses: Mutex<BTreeMap<String, WebResponse>>,
+}
+
+impl Fixtures {
+    fn load(path: &Path, replay: bool) -> Result<Fixtures> {
+        let responses = if replay || path.exists() {
+            let content = std::fs::read_to_string(path).with_action_context(|| format!("Failed to read the web fixtures: {}", path.display()))?;
+            serde_json::from_str(&content)
+                .map_err(|e| ActionError::InvalidInput(format!("Failed to parse the web fixtures {}: {}", path.display(), e)))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Fixtures { path: path.to_path_buf(), replay, responses: Mutex::new(responses) })
+    }
+
+    async fn get(&self, client: &Client, url: &str, image_type: Option<&str>) -> Result<WebResponse> {
+        if self.replay {
+            let responses = self.responses.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            return responses.get(url).cloned().ok_or_else(|| {
+                ActionError::NotFound(format!("No response of {} is recorded in the web fixtures {}", url, self.path.display())).into()
+            });
+        }
+        let response = fetch(client, url, image_type).await?;
+        // Saved after every response so that the responses of a failed run are kept too
+        let mut responses = self.responses.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        responses.insert(url.to_string(), response.clone());
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*responses)?)
+            .with_action_context(|| format!("Failed to save the web fixtures: {}", self.path.display()))?;
+        Ok(response)
+    }
+}
+
+/// Records the responses of the web requests in the JSON file, the responses already recorded in it are kept
+pub fn record(path: &Path) -> Result<()> {
+    let _ = FIXTURES.set(Fixtures::load(path, false)?);
+    Ok(())
+}
+
+/// Answers the web requests with the responses recorded in the JSON file instead of sending them, e.g. to replay a run
+/// with a mock model offline
+pub fn replay(path: &Path) -> Result<()> {
+    let _ = FIXTURES.set(Fixtures::load(path, true)?);
+    Ok(())
+}
+
+/// Gets the URL, recording its response or answering with the recorded one when the fixtures are used
+pub async fn get(client: &Client, url: &str) -> Result<WebResponse> {
+    get_as(client, url, None).await
+}
+
+/// Gets the image at the URL like `get`, the body of the response is the data URL of the image of the MIME type
+pub async fn get_image(client: &Client, url: &str, image_type: &str) -> Result<WebResponse> {
+    get_as(client, url, Some(image_type)).await
+}
+
+async fn get_as(client: &Client, url: &str, image_type: Option<&str>) -> Result<WebResponse> {
+    match FIXTURES.get() {
+        Some(fixtures) => fixtures.get(client, url, image_type).await,
+        None => fetch(client, url, image_type).await,
+    }
+}
+
+async fn fetch(client: &Client, url: &str, image_type: Option<&str>) -> Result<WebResponse> {
+    let response = async {
+        let response = client.get(url).send().await?;
+        let status = response.status().as_u16();
+        let body = match image_type {
+            Some(image_type) => data_url(image_type, &response.bytes().await?),
+            None => response.text().await?,
+        };
+        Ok::<_, reqwest::Error>(WebResponse { status, body })
+    }.await;
+    response.map_err(|e| ActionError::from_reqwest(format!("Failed to fetch {}", url), e).into())
+}
+
+/// The bytes of an image as a data URL with the base64 encoded content
+pub fn data_url(image_type: &str, bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |triple, (i, byte)| triple | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    format!("data:{};base64,{}", image_type, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::error_kind;
+    use httpmock::prelude::*;
+
+    #[tokio::test]
+    async fn test_replay_recorded_responses() -> Result<()> {
+        let server = MockServer::start();
+        let page = server.mock(|when, then| {
+            when.method(GET).path("/page");
+            then.status(200).body("Recorded page");
+        });
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("web.json");
+        let url = server.url("/page");
+        let client = Client::new();
+
+        let recorded = Fixtures::load(&path, false)?.get(&client, &url, None).await?;
+        assert_eq!(recorded, WebResponse { status: 200, body: "Recorded page".to_string() });
+
+        let fixtures = Fixtures::load(&path, true)?;
+        assert_eq!(fixtures.get(&client, &url, None).await?, recorded);
+        page.assert_hits(1);
+        let missing = fixtures.get(&client, &server.url("/other"), None).await.unwrap_err();
+        assert_eq!(error_kind(&missing), "not_found");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_recorded_images() -> Result<()> {
+        let server = MockServer::start();
+        let image = server.mock(|when, then| {
+            when.method(GET).path("/screenshot.gif");
+            then.status(200).body(b"GIF89a\xff");
+        });
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("web.json");
+        let url = server.url("/screenshot.gif");
+        let client = Client::new();
+
+        let recorded = Fixtures::load(&path, false)?.get(&client, &url, Some("image/gif")).await?;
+        assert_eq!(recorded.body, "data:image/gif;base64,R0lGODlh/w==");
+        assert_eq!(Fixtures::load(&path, true)?.get(&client, &url, Some("image/gif")).await?, recorded);
+        image.assert_hits(1);
+        Ok(())
+    }
+}